@@ -0,0 +1,109 @@
+//! Batch imports described in a TOML file:
+//!
+//! ```toml
+//! [[asset]]
+//! source = "textures/brick.png"
+//! type = "texture:RGBA8UnormSrgb"  # Optional, same as --type
+//! importer = "ObjImporter"         # Optional, same as --importer
+//! name = "brick"                   # Optional, defaults to the file name
+//! output = "brick.asset"           # Optional
+//! compress = 3                     # Optional
+//! lz4 = true                       # Optional, compress with lz4 instead of zstd
+//! settings = { key = "value" }     # Optional importer settings
+//! tags = ["ui", "level1"]          # Optional
+//! ```
+//!
+//! Source paths are relative to the batch file.
+
+use std::path::Path;
+
+use crate::{CliError, ImportRequest};
+
+#[derive(serde::Deserialize, Debug)]
+struct BatchFile {
+    #[serde(default, rename = "asset")]
+    assets: Vec<BatchEntry>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct BatchEntry {
+    source: String,
+    #[serde(rename = "type")]
+    asset_type: Option<String>,
+    importer: Option<String>,
+    settings: Option<toml::Table>,
+    name: Option<String>,
+    output: Option<String>,
+    compress: Option<u32>,
+    #[serde(default)]
+    lz4: bool,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+pub fn read_batch(path: &Path) -> Result<Vec<ImportRequest>, CliError> {
+    let contents = std::fs::read_to_string(path).map_err(CliError::BatchRead)?;
+
+    let base_dir = path.parent().unwrap_or(Path::new("."));
+
+    parse_batch(&contents, base_dir)
+}
+
+fn parse_batch(contents: &str, base_dir: &Path) -> Result<Vec<ImportRequest>, CliError> {
+    let batch: BatchFile = toml::from_str(contents)?;
+
+    Ok(batch
+        .assets
+        .into_iter()
+        .map(|entry| ImportRequest {
+            source: base_dir.join(&entry.source),
+            asset_type: entry.asset_type,
+            importer: entry.importer,
+            settings: entry.settings.map(toml::Value::Table),
+            name: entry.name,
+            output: entry.output,
+            compress: entry.compress,
+            lz4: entry.lz4,
+            tags: entry.tags,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_batch_entries() {
+        let requests = parse_batch(
+            r#"
+            [[asset]]
+            source = "brick.png"
+            type = "texture:RGBA8UnormSrgb"
+            compress = 3
+
+            [[asset]]
+            source = "models/cube.obj"
+            name = "cube"
+            tags = ["props"]
+            settings = { normals = "flat" }
+            "#,
+            Path::new("art"),
+        )
+        .unwrap();
+
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].source, Path::new("art/brick.png"));
+        assert_eq!(requests[0].asset_type.as_deref(), Some("texture:RGBA8UnormSrgb"));
+        assert_eq!(requests[0].compress, Some(3));
+        assert_eq!(requests[1].name.as_deref(), Some("cube"));
+        assert_eq!(requests[1].tags, ["props"]);
+        assert!(requests[0].tags.is_empty());
+        assert_eq!(
+            requests[1].settings.as_ref().and_then(|settings| settings["normals"].as_str()),
+            Some("flat")
+        );
+
+        assert!(parse_batch("[[asset]]\nname = \"no source\"", Path::new(".")).is_err());
+    }
+}
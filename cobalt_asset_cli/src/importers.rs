@@ -0,0 +1,125 @@
+use std::path::Path;
+
+use cobalt_core::assets::{
+    asset::AssetFileSystemType,
+    importer::{ImporterRegistry, RegisteredImporter},
+    server::AssetServer,
+};
+
+use crate::CliError;
+
+/// An asset server with the engine's importers registered, the registry also knows the asset types.
+pub fn asset_server() -> AssetServer {
+    let mut asset_server = AssetServer::new();
+    cobalt_core::asset_types::register_asset_types(&mut asset_server);
+    cobalt_core::importers::register_importers(&mut asset_server);
+
+    asset_server
+}
+
+/// Picks the importer for a source.
+/// `importer` is an exact importer name. `asset_type` is an asset type name, where `kind:Param` is short for `Kind<Param>`,
+/// e.g. `texture:RGBA8UnormSrgb` or `mesh`. Without either, the importer is picked by the source's extension.
+pub fn resolve_importer<'a>(
+    registry: &'a ImporterRegistry,
+    source: &'a Path,
+    asset_type: Option<&str>,
+    importer: Option<&str>,
+) -> Result<&'a RegisteredImporter, CliError> {
+    if let Some(importer) = importer {
+        return registry
+            .get(importer)
+            .ok_or_else(|| CliError::UnknownImporter(importer.to_string()));
+    }
+
+    let candidates = match asset_type {
+        Some(asset_type) => {
+            let asset_type = expand_asset_type(asset_type);
+
+            let candidates = registry
+                .iter()
+                .filter(|importer| importer.asset_type().eq_ignore_ascii_case(&asset_type))
+                .collect::<Vec<_>>();
+
+            if candidates.is_empty() {
+                return Err(CliError::UnknownAssetType(asset_type));
+            }
+
+            // Several importers can produce the same type, the extension decides between them
+            if candidates.len() > 1 {
+                candidates
+                    .into_iter()
+                    .filter(|importer| importer.accepts(source))
+                    .collect()
+            } else {
+                candidates
+            }
+        }
+        None => registry.for_path(source).collect::<Vec<_>>(),
+    };
+
+    match candidates.as_slice() {
+        [importer] => Ok(*importer),
+        [] => Err(CliError::NoImporter(source.display().to_string())),
+        _ => Err(CliError::AmbiguousImporter(
+            source.display().to_string(),
+            candidates
+                .iter()
+                .map(|importer| importer.name().to_string())
+                .collect(),
+        )),
+    }
+}
+
+/// `kind:Param` -> `kind<Param>`
+fn expand_asset_type(asset_type: &str) -> String {
+    match asset_type.split_once(':') {
+        Some((kind, param)) => format!("{}<{}>", kind, param),
+        None => asset_type.to_string(),
+    }
+}
+
+/// Packed path used when no output path is given, the same one the asset manager suggests.
+pub fn default_relative_output(name: &str, fs_type: AssetFileSystemType) -> String {
+    match fs_type {
+        AssetFileSystemType::File => format!("{}.asset", name),
+        AssetFileSystemType::Directory => format!("{}/", name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_engine_importers() {
+        let asset_server = asset_server();
+        let registry = asset_server.importers();
+
+        let texture = resolve_importer(
+            registry,
+            Path::new("brick.png"),
+            Some("texture:RGBA8UnormSrgb"),
+            None,
+        )
+        .unwrap();
+        assert_eq!(texture.name(), "TextureImporter<RGBA8UnormSrgb>");
+
+        let mesh = resolve_importer(registry, Path::new("cube.obj"), Some("mesh"), None).unwrap();
+        assert_eq!(mesh.name(), "ObjImporter");
+
+        // Models can come from several formats, the extension picks the importer
+        let model = resolve_importer(registry, Path::new("cube.obj"), Some("model"), None).unwrap();
+        assert_eq!(model.name(), "ObjModelImporter");
+
+        let model = resolve_importer(registry, Path::new("sponza.glb"), None, None).unwrap();
+        assert_eq!(model.name(), "GltfImporter");
+
+        // Both OBJ importers accept the file
+        assert!(resolve_importer(registry, Path::new("cube.obj"), None, None).is_err());
+
+        // Every texture importer accepts PNGs
+        assert!(resolve_importer(registry, Path::new("brick.png"), None, None).is_err());
+        assert!(resolve_importer(registry, Path::new("brick.png"), Some("nope"), None).is_err());
+    }
+}
@@ -0,0 +1,668 @@
+use std::{
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use clap::{Parser, Subcommand};
+use cobalt_core::assets::{
+    asset::AssetID,
+    importer::ImporterRegistry,
+    manifest::{
+        delete_asset, force_delete_asset, set_asset_tags, stale_assets, AssetDeleteError,
+        AssetInfo, AssetPackError, AssetTagError, Manifest, ManifestLayout, ManifestReadError,
+        ManifestWriteError, PackInfo,
+    },
+    packed::PackCodec,
+    query::AssetQuery,
+    sidecar::{convert_layout, ManifestConvertError},
+    verify::{repair_manifest, ManifestRepairError, RepairOptions},
+};
+use importers::{default_relative_output, resolve_importer};
+use serde::Serialize;
+use simple_logger::SimpleLogger;
+
+pub mod batch;
+pub mod importers;
+
+/// Headless asset pipeline, the command line counterpart of the asset manager.
+#[derive(Parser, Debug)]
+#[command(name = "cobalt-assets", version)]
+struct Cli {
+    /// Directory containing manifest.toml.
+    #[arg(short, long, global = true, default_value = ".")]
+    assets_dir: PathBuf,
+
+    /// Print results as JSON instead of text.
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Creates an empty manifest in the assets directory.
+    Init {
+        /// `single` keeps every asset in manifest.toml, `sidecar` gives each asset a `.meta` file.
+        #[arg(long, default_value = "single")]
+        layout: ManifestLayout,
+    },
+    /// Imports and packs a source file or directory.
+    Import {
+        source: PathBuf,
+        /// Asset type to import as, e.g. `texture:RGBA8UnormSrgb` or `mesh`.
+        /// Picked from the source's extension if not set.
+        #[arg(short = 't', long = "type")]
+        asset_type: Option<String>,
+        /// Name of the importer to use, overrides `--type`.
+        #[arg(short, long)]
+        importer: Option<String>,
+        /// Importer settings as TOML, e.g. `--settings 'key = "value"'`. Unset keys use the defaults.
+        #[arg(short, long)]
+        settings: Option<String>,
+        /// Defaults to the source's file name without extension.
+        #[arg(short, long)]
+        name: Option<String>,
+        /// Packed path relative to the assets directory.
+        #[arg(short, long)]
+        output: Option<String>,
+        /// zstd compression level, the asset is stored uncompressed if not set.
+        #[arg(short, long)]
+        compress: Option<u32>,
+        /// Compress with lz4 instead of zstd, faster to load but larger. Ignores the level.
+        #[arg(long, requires = "compress")]
+        lz4: bool,
+        /// Tag to give the asset, can be repeated.
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+    },
+    /// Imports every asset described in a TOML file.
+    Batch { file: PathBuf },
+    /// Lists the registered importers with their extensions and default settings.
+    Importers,
+    /// Lists the assets in the manifest.
+    List {
+        /// Only list assets with this type name.
+        #[arg(short = 't', long = "type")]
+        type_name: Option<String>,
+        /// Only list assets with this tag, can be repeated to require several.
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        /// Only list assets at or under this path, relative to the assets directory.
+        #[arg(long)]
+        under: Option<PathBuf>,
+        /// Case insensitive text to find in the name, type, path or tags.
+        #[arg(long)]
+        search: Option<String>,
+    },
+    /// Adds tags to an asset, or removes them.
+    Tag {
+        asset: String,
+        #[arg(required = true)]
+        tags: Vec<String>,
+        /// Remove the tags instead of adding them.
+        #[arg(short, long)]
+        remove: bool,
+    },
+    /// Deletes an asset by ID or name.
+    Delete {
+        asset: String,
+        /// Delete even if other assets depend on it.
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Checks the manifest against the files on disk. Exits with an error if problems are found.
+    Verify {
+        /// Remove entries whose packed files are missing.
+        #[arg(long)]
+        prune: bool,
+    },
+    /// Reimports assets from their recorded sources, keeping their IDs.
+    Reimport {
+        /// IDs or names of the assets to reimport.
+        assets: Vec<String>,
+        /// Reimport every asset whose source changed since it was imported.
+        #[arg(long, conflicts_with = "assets")]
+        stale: bool,
+    },
+    /// Rewrites packed files written in older format versions in place.
+    /// Assets that fail to migrate are left as they were and need to be reimported.
+    Upgrade,
+    /// Moves the asset infos between manifest.toml and per-asset `.meta` files.
+    Convert {
+        /// `single` or `sidecar`.
+        layout: ManifestLayout,
+    },
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum CliError {
+    #[error("Failed to read manifest")]
+    ManifestRead(#[from] ManifestReadError),
+    #[error("Failed to write manifest")]
+    ManifestWrite(#[from] ManifestWriteError),
+    #[error("Failed to convert manifest")]
+    ManifestConvert(#[from] ManifestConvertError),
+    #[error("Failed to repair manifest")]
+    ManifestRepair(#[from] ManifestRepairError),
+    #[error("Failed to import asset")]
+    Pack(#[from] AssetPackError),
+    #[error("Failed to delete asset")]
+    Delete(#[from] AssetDeleteError),
+    #[error("Failed to tag asset")]
+    Tag(#[from] AssetTagError),
+    #[error("Failed to read batch file")]
+    BatchRead(#[source] std::io::Error),
+    #[error("Failed to parse batch file")]
+    BatchParse(#[from] toml::de::Error),
+    #[error("Asset not found: {0}")]
+    AssetNotFound(String),
+    #[error("Several assets are named {0}, use the ID instead")]
+    AmbiguousAssetName(String),
+    #[error("No importer registered with the name {0}")]
+    UnknownImporter(String),
+    #[error("No importer registered for asset type {0}")]
+    UnknownAssetType(String),
+    #[error("No importer found for {0}, pass --type or --importer")]
+    NoImporter(String),
+    #[error("Several importers can import {0}, pass --type or --importer: {1:?}")]
+    AmbiguousImporter(String, Vec<String>),
+    #[error("Invalid importer settings")]
+    InvalidSettings(#[source] toml::de::Error),
+    #[error("Compression level must be between {} and {}", PackInfo::MIN_COMPRESSION_LEVEL, PackInfo::MAX_COMPRESSION_LEVEL)]
+    InvalidCompressionLevel,
+    #[error("Invalid assets directory {0:?}")]
+    AssetsDir(PathBuf, #[source] std::io::Error),
+    #[error("A manifest already exists in {0:?}")]
+    AlreadyInitialised(PathBuf),
+    #[error("{0} failed")]
+    Failed(String),
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    SimpleLogger::new()
+        .with_colors(!cli.json)
+        .with_level(log::LevelFilter::Warn)
+        .without_timestamps()
+        .init()
+        .unwrap();
+
+    match run(&cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            if cli.json {
+                print_json(&serde_json::json!({ "error": error_chain(&e) }));
+            } else {
+                eprintln!("Error: {}", error_chain(&e));
+            }
+
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(cli: &Cli) -> Result<(), CliError> {
+    let assets_dir = resolve_path(&cli.assets_dir)
+        .map_err(|e| CliError::AssetsDir(cli.assets_dir.clone(), e))?;
+    let assets_dir = assets_dir.as_path();
+    let asset_server = importers::asset_server();
+    let registry = asset_server.importers();
+
+    match &cli.command {
+        Command::Init { layout } => {
+            if assets_dir.join("manifest.toml").exists() {
+                return Err(CliError::AlreadyInitialised(assets_dir.to_path_buf()));
+            }
+
+            std::fs::create_dir_all(assets_dir)
+                .map_err(|e| CliError::ManifestWrite(ManifestWriteError::Io(e)))?;
+            let mut manifest = Manifest::new();
+            manifest.layout = *layout;
+            manifest.save(assets_dir)?;
+
+            if cli.json {
+                print_json(&serde_json::json!({ "initialised": assets_dir }));
+            } else {
+                println!("Initialised assets directory {:?}", assets_dir);
+            }
+        }
+        Command::Import {
+            source,
+            asset_type,
+            importer,
+            settings,
+            name,
+            output,
+            compress,
+            lz4,
+            tags,
+        } => {
+            let settings = settings
+                .as_deref()
+                .map(|settings| toml::from_str::<toml::Table>(settings).map(toml::Value::Table))
+                .transpose()
+                .map_err(CliError::InvalidSettings)?;
+
+            let imported = import(
+                registry,
+                assets_dir,
+                &ImportRequest {
+                    source: source.clone(),
+                    asset_type: asset_type.clone(),
+                    importer: importer.clone(),
+                    settings,
+                    name: name.clone(),
+                    output: output.clone(),
+                    compress: *compress,
+                    lz4: *lz4,
+                    tags: tags.clone(),
+                },
+            )?;
+
+            print_imported(cli.json, &[imported]);
+        }
+        Command::Batch { file } => {
+            let requests = batch::read_batch(file)?;
+
+            // Keep going after a failure so one bad file doesn't hold up the rest
+            let mut imported = Vec::new();
+            let mut failed = Vec::new();
+
+            for request in &requests {
+                match import(registry, assets_dir, request) {
+                    Ok(asset) => imported.push(asset),
+                    Err(e) => failed.push(Failure {
+                        asset: request.source.display().to_string(),
+                        error: error_chain(&e),
+                    }),
+                }
+            }
+
+            if cli.json {
+                print_json(&serde_json::json!({ "imported": imported, "failed": failed }));
+            } else {
+                print_imported(false, &imported);
+                print_failures(&failed);
+            }
+
+            if !failed.is_empty() {
+                return Err(CliError::Failed(format!("{} of {} imports", failed.len(), requests.len())));
+            }
+        }
+        Command::Importers => {
+            if cli.json {
+                let importers = registry
+                    .iter()
+                    .map(|importer| {
+                        serde_json::json!({
+                            "name": importer.name(),
+                            "asset_type": importer.asset_type(),
+                            "extensions": importer.extensions(),
+                            "note": importer.note(),
+                            "default_settings": importer.default_settings(),
+                        })
+                    })
+                    .collect::<Vec<_>>();
+
+                print_json(&importers);
+            } else {
+                for importer in registry.iter() {
+                    println!(
+                        "{:<36} {:<24} {}",
+                        importer.name(),
+                        importer.asset_type(),
+                        importer.extensions().join(", ")
+                    );
+                }
+            }
+        }
+        Command::List {
+            type_name,
+            tags,
+            under,
+            search,
+        } => {
+            let manifest = Manifest::load(assets_dir)?;
+
+            let assets = manifest.query(&AssetQuery {
+                type_name: type_name.clone(),
+                tags: tags.clone(),
+                path_prefix: under.clone(),
+                search: search.clone(),
+                ..Default::default()
+            });
+
+            if cli.json {
+                print_json(&assets);
+            } else {
+                for asset_info in assets {
+                    println!(
+                        "{}  {:<24} {:<32} {:<32} {}",
+                        asset_info.asset_id.uuid(),
+                        asset_info.type_name,
+                        asset_info.name,
+                        asset_info.relative_path.display(),
+                        asset_info.tags.join(", ")
+                    );
+                }
+            }
+        }
+        Command::Tag {
+            asset,
+            tags,
+            remove,
+        } => {
+            let manifest = Manifest::load(assets_dir)?;
+            let asset_info = find_asset(&manifest, asset)?;
+
+            let mut new_tags = asset_info.tags.clone();
+
+            if *remove {
+                new_tags.retain(|tag| !tags.contains(tag));
+            } else {
+                new_tags.extend(tags.iter().cloned());
+            }
+
+            set_asset_tags(assets_dir, asset_info.asset_id, new_tags)?;
+
+            // Read back, the tags are cleaned up when set
+            let manifest = Manifest::load(assets_dir)?;
+            let tags = manifest
+                .asset(asset_info.asset_id)
+                .map(|asset_info| asset_info.tags.clone())
+                .unwrap_or_default();
+
+            if cli.json {
+                print_json(&serde_json::json!({ "asset_id": asset_info.asset_id, "tags": tags }));
+            } else {
+                println!("{} [{}]: {}", asset_info.name, asset_info.asset_id.uuid(), tags.join(", "));
+            }
+        }
+        Command::Delete { asset, force } => {
+            let manifest = Manifest::load(assets_dir)?;
+            let asset_info = find_asset(&manifest, asset)?;
+
+            if *force {
+                force_delete_asset(assets_dir, asset_info.asset_id)?;
+            } else {
+                delete_asset(assets_dir, asset_info.asset_id)?;
+            }
+
+            if cli.json {
+                print_json(&serde_json::json!({ "deleted": asset_info.asset_id }));
+            } else {
+                println!("Deleted {} [{}]", asset_info.name, asset_info.asset_id.uuid());
+            }
+        }
+        Command::Verify { prune } => {
+            let known_types = asset_server.registered_asset_types();
+
+            let (report, pruned) = if *prune {
+                let options = RepairOptions {
+                    prune_missing: true,
+                    ..Default::default()
+                };
+
+                let (report, summary) = repair_manifest(assets_dir, &known_types, &options)?;
+
+                (report, summary.pruned)
+            } else {
+                let manifest = Manifest::load(assets_dir)?;
+
+                (manifest.verify(assets_dir, &known_types), Vec::new())
+            };
+
+            if cli.json {
+                print_json(&serde_json::json!({
+                    "ok": report.is_ok(),
+                    "report": report,
+                    "pruned": pruned,
+                }));
+            } else {
+                print!("{}", report);
+
+                if !pruned.is_empty() {
+                    println!("Removed {} entries with missing files.", pruned.len());
+                }
+            }
+
+            if !report.is_ok() {
+                return Err(CliError::Failed("Verification".to_string()));
+            }
+        }
+        Command::Reimport { assets, stale } => {
+            let asset_ids = if *stale {
+                stale_assets(assets_dir)?
+            } else {
+                let manifest = Manifest::load(assets_dir)?;
+
+                assets
+                    .iter()
+                    .map(|asset| find_asset(&manifest, asset).map(|asset_info| asset_info.asset_id))
+                    .collect::<Result<Vec<_>, _>>()?
+            };
+
+            let mut reimported = Vec::new();
+            let mut failed = Vec::new();
+
+            for asset_id in &asset_ids {
+                match registry.reimport(assets_dir, *asset_id) {
+                    Ok(()) => reimported.push(*asset_id),
+                    Err(e) => failed.push(Failure {
+                        asset: asset_id.uuid().to_string(),
+                        error: error_chain(&e),
+                    }),
+                }
+            }
+
+            if cli.json {
+                print_json(&serde_json::json!({ "reimported": reimported, "failed": failed }));
+            } else {
+                for asset_id in &reimported {
+                    println!("Reimported [{}]", asset_id.uuid());
+                }
+                print_failures(&failed);
+            }
+
+            if !failed.is_empty() {
+                return Err(CliError::Failed(format!("{} of {} reimports", failed.len(), asset_ids.len())));
+            }
+        }
+        Command::Upgrade => {
+            let report = asset_server.upgrade_assets(assets_dir)?;
+
+            let failed = report
+                .failed
+                .iter()
+                .map(|(asset_id, error)| Failure {
+                    asset: asset_id.uuid().to_string(),
+                    error: error.clone(),
+                })
+                .collect::<Vec<_>>();
+
+            if cli.json {
+                print_json(&report);
+            } else {
+                for asset_id in &report.upgraded {
+                    println!("Upgraded [{}]", asset_id.uuid());
+                }
+                for asset_id in &report.skipped {
+                    println!("Skipped [{}], its type isn't registered", asset_id.uuid());
+                }
+                println!("{} upgraded, {} already current.", report.upgraded.len(), report.current.len());
+                print_failures(&failed);
+            }
+
+            if !failed.is_empty() {
+                return Err(CliError::Failed(format!("{} upgrades", failed.len())));
+            }
+        }
+        Command::Convert { layout } => {
+            convert_layout(assets_dir, *layout)?;
+
+            if cli.json {
+                print_json(&serde_json::json!({ "layout": layout }));
+            } else {
+                println!("Converted {:?} to the {:?} layout", assets_dir, layout);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// One asset to import, from the command line or a batch file.
+#[derive(Debug, Clone)]
+pub struct ImportRequest {
+    pub source: PathBuf,
+    pub asset_type: Option<String>,
+    pub importer: Option<String>,
+    pub settings: Option<toml::Value>,
+    pub name: Option<String>,
+    pub output: Option<String>,
+    pub compress: Option<u32>,
+    pub lz4: bool,
+    pub tags: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct Imported {
+    asset_id: AssetID,
+    name: String,
+    relative_path: String,
+}
+
+#[derive(Serialize)]
+struct Failure {
+    asset: String,
+    error: String,
+}
+
+fn import(
+    registry: &ImporterRegistry,
+    assets_dir: &Path,
+    request: &ImportRequest,
+) -> Result<Imported, CliError> {
+    if let Some(level) = request.compress {
+        if !(PackInfo::MIN_COMPRESSION_LEVEL..=PackInfo::MAX_COMPRESSION_LEVEL).contains(&level) {
+            return Err(CliError::InvalidCompressionLevel);
+        }
+    }
+
+    let abs_input = resolve_path(&request.source)
+        .map_err(|e| CliError::Pack(AssetPackError::SourceCouldNotOpen(e)))?;
+
+    let name = match &request.name {
+        Some(name) => name.clone(),
+        None => abs_input
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default(),
+    };
+
+    let importer = resolve_importer(
+        registry,
+        &abs_input,
+        request.asset_type.as_deref(),
+        request.importer.as_deref(),
+    )?;
+
+    let relative_path = request
+        .output
+        .clone()
+        .unwrap_or_else(|| default_relative_output(&name, importer.imported_fs_type()));
+
+    let asset_id = importer.import(
+        assets_dir,
+        &abs_input,
+        Path::new(&relative_path),
+        name.clone(),
+        PackInfo {
+            compression: request.compress,
+            codec: if request.lz4 {
+                PackCodec::Lz4
+            } else {
+                PackCodec::Zstd
+            },
+        },
+        request.settings.as_ref(),
+    )?;
+
+    if !request.tags.is_empty() {
+        set_asset_tags(assets_dir, asset_id, request.tags.clone())?;
+    }
+
+    Ok(Imported {
+        asset_id,
+        name,
+        relative_path,
+    })
+}
+
+/// Finds an asset by its ID or, failing that, its name.
+fn find_asset<'a>(manifest: &'a Manifest, asset: &str) -> Result<&'a AssetInfo, CliError> {
+    if let Some(asset_info) = manifest
+        .all_assets()
+        .find(|asset_info| asset_info.asset_id.uuid().to_string() == asset)
+    {
+        return Ok(asset_info);
+    }
+
+    match manifest.assets_named(asset).as_slice() {
+        [] => Err(CliError::AssetNotFound(asset.to_string())),
+        [asset_info] => Ok(*asset_info),
+        _ => Err(CliError::AmbiguousAssetName(asset.to_string())),
+    }
+}
+
+/// Absolute path with `..` and symlinks resolved. Sources are recorded relative to the assets
+/// directory when they are under it, which needs both resolved the same way.
+/// Paths that don't exist yet, like the directory given to `init`, are only made absolute.
+fn resolve_path(path: &Path) -> std::io::Result<PathBuf> {
+    std::fs::canonicalize(path).or_else(|_| std::path::absolute(path))
+}
+
+fn print_imported(json: bool, imported: &[Imported]) {
+    if json {
+        print_json(&serde_json::json!({ "imported": imported }));
+    } else {
+        for asset in imported {
+            println!(
+                "Imported {} [{}] -> {}",
+                asset.name,
+                asset.asset_id.uuid(),
+                asset.relative_path
+            );
+        }
+    }
+}
+
+fn print_failures(failed: &[Failure]) {
+    for failure in failed {
+        eprintln!("Failed {}: {}", failure.asset, failure.error);
+    }
+}
+
+/// The error and all of its sources, most of the library errors only describe the outermost step.
+fn error_chain(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+
+    while let Some(e) = source {
+        message.push_str(": ");
+        message.push_str(&e.to_string());
+        source = e.source();
+    }
+
+    message
+}
+
+fn print_json<T: Serialize + ?Sized>(value: &T) {
+    match serde_json::to_string_pretty(value) {
+        Ok(json) => println!("{}", json),
+        Err(e) => eprintln!("Failed to serialise output: {}", e),
+    }
+}
@@ -1,4 +1,7 @@
-use cobalt_core::assets::manifest::AssetInfo;
+use cobalt_core::assets::{
+    asset::AssetID,
+    manifest::{AssetInfo, Manifest},
+};
 use iced::{
     widget::{Button, Column, Row, Text},
     Element, Font,
@@ -9,13 +12,29 @@ use crate::Message;
 pub struct Asset {}
 
 impl Asset {
-    pub fn view(asset_info: &AssetInfo) -> Element<Message> {
+    pub fn view<'a>(asset_info: &'a AssetInfo, manifest: &'a Manifest) -> Element<'a, Message> {
         let created = humantime::format_rfc3339(asset_info.timestamp).to_string();
 
-        let actions_row = Row::new().push(Text::new("Actions:")).push(
+        let dependents = manifest.dependents(asset_info.asset_id);
+
+        let mut actions_row = Row::new().push(Text::new("Actions:")).push(
             Button::new(Text::new("Delete")).on_press(Message::DeleteAsset(asset_info.asset_id)),
         );
 
+        if asset_info.source.is_some() {
+            actions_row = actions_row.push(
+                Button::new(Text::new("Reimport"))
+                    .on_press(Message::ReimportAsset(asset_info.asset_id)),
+            );
+        }
+
+        if !dependents.is_empty() {
+            actions_row = actions_row.push(
+                Button::new(Text::new("Force Delete"))
+                    .on_press(Message::ForceDeleteAsset(asset_info.asset_id)),
+            );
+        }
+
         Column::new()
             .push(
                 Text::new(format!(
@@ -28,12 +47,50 @@ impl Asset {
                 .font(Font::MONOSPACE),
             )
             .push(Text::new(format!("Type: {}", asset_info.type_name)))
+            .push(Text::new(format!(
+                "Tags: {}",
+                if asset_info.tags.is_empty() {
+                    "None".to_string()
+                } else {
+                    asset_info.tags.join(", ")
+                }
+            )))
             .push(Text::new(format!("Created: {}", created)))
+            .push(Text::new(format!(
+                "Source: {}",
+                asset_info
+                    .source
+                    .as_ref()
+                    .map_or("Unknown".to_string(), |source| format!("{:?}", source.path))
+            )))
             .push(Text::new(format!(
                 "Relative Path: {:?}",
                 asset_info.relative_path
             )))
+            .push(Text::new(format!(
+                "Depends on: {}",
+                asset_names(manifest, &asset_info.dependencies)
+            )))
+            .push(Text::new(format!(
+                "Used by: {}",
+                asset_names(manifest, &dependents)
+            )))
             .push(actions_row)
             .into()
     }
 }
+
+fn asset_names(manifest: &Manifest, asset_ids: &[AssetID]) -> String {
+    if asset_ids.is_empty() {
+        return "None".to_string();
+    }
+
+    asset_ids
+        .iter()
+        .map(|asset_id| match manifest.find_asset(*asset_id) {
+            Some(asset_info) => asset_info.name.clone(),
+            None => format!("Missing [{}]", asset_id.uuid()),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
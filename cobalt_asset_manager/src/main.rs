@@ -1,7 +1,9 @@
 use cobalt_core::{
     assets::{
         asset::AssetID,
-        manifest::{delete_asset, Manifest},
+        manifest::{delete_asset, force_delete_asset, stale_assets, Manifest},
+        pack::{write_archive, PACK_EXTENSION},
+        verify::{repair_manifest, RepairOptions},
     },
     exports::assets::AssetServer,
 };
@@ -9,7 +11,10 @@ use iced::{
     widget::{self, row, Text},
     Settings, Subscription,
 };
-use pages::import_assets::{ImportAssets, ImportAssetsMessage};
+use pages::{
+    import_assets::{ImportAssets, ImportAssetsMessage},
+    view_assets::{ViewAssets, ViewAssetsMessage},
+};
 use simple_logger::SimpleLogger;
 
 pub mod components;
@@ -75,24 +80,42 @@ pub enum Message {
     DoNothing,
     SelectAssetDir,
     DeleteAsset(AssetID),
+    ForceDeleteAsset(AssetID),
     TabSelected(Tabs),
     ImportAssetsMessage(ImportAssetsMessage),
+    ViewAssetsMessage(ViewAssetsMessage),
     InitialiseNewAssetsDir,
     RefreshAssets,
+    BuildPack,
+    VerifyManifest,
+    RepairManifest,
+    ReimportAsset(AssetID),
+    ReimportStale,
 }
 
 pub struct App {
     asset_server: AssetServer,
     current_tab: Tabs,
     import_assets_page: ImportAssets,
+    view_assets_page: ViewAssets,
+    /// Result of the last manifest verification, shown on the view assets page.
+    verify_report: Option<String>,
 }
 
 impl Default for App {
     fn default() -> Self {
+        let mut asset_server = AssetServer::new();
+        cobalt_core::asset_types::register_asset_types(&mut asset_server);
+        cobalt_core::importers::register_importers(&mut asset_server);
+
+        let import_assets_page = ImportAssets::new(&asset_server);
+
         Self {
-            asset_server: AssetServer::new(),
+            asset_server,
             current_tab: Tabs::ViewAssets,
-            import_assets_page: ImportAssets::new(),
+            import_assets_page,
+            view_assets_page: ViewAssets::new(),
+            verify_report: None,
         }
     }
 }
@@ -120,17 +143,22 @@ impl App {
                     };
                 }
             }
-            Message::DeleteAsset(asset_id) => {
+            Message::DeleteAsset(asset_id) | Message::ForceDeleteAsset(asset_id) => {
                 if self.asset_server.get_manifest().is_err() {
                     log::error!("No manifest found, cannot delete asset");
                     return;
                 }
 
-                delete_asset(self.asset_server.assets_dir(), asset_id)
-                    .map_err(|e| {
-                        eprintln!("Error deleting asset: {}", e);
-                    })
-                    .expect("Error deleting asset");
+                let result = if let Message::ForceDeleteAsset(_) = event {
+                    force_delete_asset(self.asset_server.assets_dir(), asset_id)
+                } else {
+                    delete_asset(self.asset_server.assets_dir(), asset_id)
+                };
+
+                if let Err(e) = result {
+                    eprintln!("Error deleting asset: {}", e);
+                    return;
+                }
 
                 self.asset_server
                     .refresh_manifest()
@@ -145,6 +173,9 @@ impl App {
             Message::ImportAssetsMessage(message) => {
                 self.import_assets_page.update(message, &self.asset_server);
             }
+            Message::ViewAssetsMessage(message) => {
+                self.view_assets_page.update(message);
+            }
             Message::RefreshAssets => {
                 self.asset_server
                     .refresh_manifest()
@@ -153,6 +184,76 @@ impl App {
                     })
                     .expect("Error refreshing assets");
             }
+            Message::VerifyManifest => match self.asset_server.get_manifest() {
+                Ok(manifest) => {
+                    let report = manifest.verify(
+                        self.asset_server.assets_dir(),
+                        &self.asset_server.registered_asset_types(),
+                    );
+
+                    self.verify_report = Some(report.to_string());
+                }
+                Err(e) => eprintln!("Error verifying manifest: {}", e),
+            },
+            Message::RepairManifest => {
+                let options = RepairOptions {
+                    prune_missing: true,
+                    ..Default::default()
+                };
+
+                match repair_manifest(
+                    self.asset_server.assets_dir(),
+                    &self.asset_server.registered_asset_types(),
+                    &options,
+                ) {
+                    Ok((_, summary)) => {
+                        self.verify_report = Some(format!(
+                            "Removed {} entries with missing files.",
+                            summary.pruned.len()
+                        ));
+                    }
+                    Err(e) => eprintln!("Error repairing manifest: {}", e),
+                }
+
+                if let Err(e) = self.asset_server.refresh_manifest() {
+                    eprintln!("Error refreshing assets: {}", e);
+                }
+            }
+            Message::ReimportAsset(asset_id) => {
+                self.reimport(&[asset_id]);
+            }
+            Message::ReimportStale => match stale_assets(self.asset_server.assets_dir()) {
+                Ok(stale) => {
+                    log::info!("Reimporting {} stale assets", stale.len());
+                    self.reimport(&stale);
+                }
+                Err(e) => eprintln!("Error finding stale assets: {}", e),
+            },
+            Message::BuildPack => {
+                let assets_dir = self.asset_server.assets_dir().clone();
+
+                if !assets_dir.is_dir() {
+                    log::error!("A pack is mounted, select an assets directory to build from");
+                    return;
+                }
+
+                let output = rfd::FileDialog::new()
+                    .set_title("Save Asset Pack")
+                    .add_filter("Cobalt Asset Pack", &[PACK_EXTENSION])
+                    .set_file_name(format!("assets.{}", PACK_EXTENSION))
+                    .save_file();
+
+                if let Some(output) = output {
+                    match write_archive(&assets_dir, &output, Some(3)) {
+                        Ok(toc) => log::info!(
+                            "Built asset pack {:?} with {} files",
+                            output,
+                            toc.entries.len()
+                        ),
+                        Err(e) => eprintln!("Error building asset pack: {}", e),
+                    }
+                }
+            }
             Message::InitialiseNewAssetsDir => {
                 let asset_dir = rfd::FileDialog::new()
                     .set_title("Select Asset Directory")
@@ -188,6 +289,22 @@ impl App {
         }
     }
 
+    fn reimport(&mut self, asset_ids: &[AssetID]) {
+        for asset_id in asset_ids {
+            if let Err(e) = self
+                .asset_server
+                .importers()
+                .reimport(self.asset_server.assets_dir(), *asset_id)
+            {
+                eprintln!("Error reimporting asset {:?}: {}", asset_id, e);
+            }
+        }
+
+        if let Err(e) = self.asset_server.refresh_manifest() {
+            eprintln!("Error refreshing assets: {}", e);
+        }
+    }
+
     fn view(&self) -> iced::Element<Message> {
         let top_tab_select = Tabs::variants()
             .iter()
@@ -203,6 +320,7 @@ impl App {
             widget::button(Text::new("Select Asset Directory")).on_press(Message::SelectAssetDir),
             widget::Button::new(Text::new("Initialise New Assets Directory"))
                 .on_press(Message::InitialiseNewAssetsDir),
+            widget::Button::new(Text::new("Build Pack")).on_press(Message::BuildPack),
             widget::Text::new(format!(
                 "Asset Directory: {:?}",
                 self.asset_server.assets_dir().as_path()
@@ -213,7 +331,9 @@ impl App {
         .align_y(iced::Alignment::Center);
 
         let content = match self.current_tab {
-            Tabs::ViewAssets => pages::view_assets::ViewAssets::view(&self.asset_server),
+            Tabs::ViewAssets => self
+                .view_assets_page
+                .view(&self.asset_server, self.verify_report.as_deref()),
             Tabs::ImportAsset => self.import_assets_page.view(&self.asset_server),
         };
 
@@ -1,148 +1,28 @@
 use std::path::PathBuf;
 
-use cobalt_core::{
-    assets::{
-        asset::{AssetFileSystemType, AssetImporter, AssetTrait},
-        manifest::{pack_asset, AssetPackError, PackInfo},
-        server::AssetServer,
-    },
-    exports::asset_types::TextureAsset,
-    graphics::texture::TextureType,
-    importers::{gltf::GltfImporter, obj::ObjImporter, texture::TextureImporter},
-    renderer::mesh::Mesh,
+use cobalt_core::assets::{
+    asset::AssetFileSystemType,
+    importer::RegisteredImporter,
+    manifest::PackInfo,
+    packed::PackCodec,
+    server::AssetServer,
 };
-use iced::widget::{self, button, combo_box, row, Column, Text};
+use iced::widget::{self, button, combo_box, row, text_editor, Text};
 use iced_aw::style::colors;
 
 use crate::Message;
 
-#[derive(Debug, Clone)]
-pub enum AssetType {
-    Texture {
-        texture_type: Option<TextureType>,
-        texture_type_combo_box: combo_box::State<TextureType>,
-    },
-    Gltf,
-    Obj,
-}
-
-impl PartialEq for AssetType {
-    fn eq(&self, other: &Self) -> bool {
-        match (self, other) {
-            (AssetType::Texture { .. }, AssetType::Texture { .. }) => true,
-            (AssetType::Gltf, AssetType::Gltf) => true,
-            (AssetType::Obj, AssetType::Obj) => true,
-            _ => false,
-        }
-    }
-}
-
-#[derive(Debug, Clone)]
-pub enum ExtraConfigMessage {
-    SetTextureType(TextureType),
-}
-
-impl AssetType {
-    pub fn to_string(&self) -> String {
-        match self {
-            AssetType::Texture { .. } => "Texture".to_string(),
-            AssetType::Gltf => "Gltf".to_string(),
-            AssetType::Obj => "Obj".to_string(),
-        }
-    }
-
-    pub fn variants() -> Vec<Self> {
-        vec![
-            AssetType::Texture {
-                texture_type: None,
-                texture_type_combo_box: combo_box::State::new(TextureType::variants()),
-            },
-            AssetType::Gltf,
-            AssetType::Obj,
-        ]
-    }
-
-    pub fn unimported_fs_type(&self) -> AssetFileSystemType {
-        match self {
-            AssetType::Texture { .. } => {
-                TextureImporter::<{ TextureType::RGBA8Unorm }>::unimported_fs_type()
-            }
-            AssetType::Gltf => GltfImporter::unimported_fs_type(),
-            AssetType::Obj => ObjImporter::unimported_fs_type(),
-        }
-    }
-
-    pub fn note(&self) -> Option<String> {
-        match self {
-            AssetType::Texture { .. } => TextureImporter::<{ TextureType::RGBA8Unorm }>::note(),
-            AssetType::Gltf => GltfImporter::note(),
-            AssetType::Obj => ObjImporter::note(),
-        }
-    }
-
-    pub fn imported_fs_type(&self) -> AssetFileSystemType {
-        match self {
-            AssetType::Texture { .. } => {
-                TextureAsset::<{ TextureType::RGBA8Unorm }>::imported_fs_type()
-            }
-            AssetType::Gltf => GltfImporter::imported_fs_type(),
-            AssetType::Obj => Mesh::imported_fs_type(),
-        }
-    }
-
-    pub fn extra_config_update(&mut self, message: ExtraConfigMessage) {
-        match self {
-            AssetType::Texture {
-                texture_type: texture_type_ref,
-                ..
-            } => match message {
-                ExtraConfigMessage::SetTextureType(texture_type) => {
-                    *texture_type_ref = Some(texture_type);
-                }
-            },
-            AssetType::Gltf => {}
-            AssetType::Obj => {}
-        }
-    }
-
-    pub fn extra_config_view(&self) -> Column<Message> {
-        match self {
-            AssetType::Texture {
-                texture_type,
-                texture_type_combo_box,
-            } => {
-                let texture_type_combo_box = combo_box(
-                    texture_type_combo_box,
-                    "Texture Type...",
-                    texture_type.as_ref(),
-                    |selected| {
-                        Message::ImportAssetsMessage(ImportAssetsMessage::ExtraConfigMessage(
-                            ExtraConfigMessage::SetTextureType(selected),
-                        ))
-                    },
-                );
-
-                Column::new().push(texture_type_combo_box)
-            }
-            AssetType::Gltf => Column::new(),
-            AssetType::Obj => Column::new(),
-        }
-    }
-}
-
-impl std::fmt::Display for AssetType {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.to_string())
-    }
-}
-
 pub struct ImportAssets {
     abs_input: PathBuf,
     relative_output: String,
     name: String,
     pack: PackInfo,
-    asset_type: AssetType,
-    asset_types_combo_box: combo_box::State<AssetType>,
+    /// Name of the selected importer in the asset server's importer registry.
+    importer: Option<String>,
+    importers_combo_box: combo_box::State<String>,
+    /// Importer settings as TOML, starts out as the importer's defaults.
+    settings: text_editor::Content,
+    settings_error: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -153,125 +33,132 @@ pub enum ImportAssetsMessage {
     SelectInputPath { directory: bool },
     SetName(String),
     SetPackInfo(PackInfo),
-    SetAssetType(AssetType),
-    ExtraConfigMessage(ExtraConfigMessage),
+    SetImporter(String),
+    EditSettings(text_editor::Action),
 }
 
 impl ImportAssets {
-    pub fn new() -> Self {
+    pub fn new(asset_server: &AssetServer) -> Self {
+        let importer_names = asset_server
+            .importers()
+            .iter()
+            .map(|importer| importer.name().to_string())
+            .collect();
+
         Self {
             abs_input: PathBuf::new(),
             relative_output: "./".to_string(),
             name: "".to_string(),
-            pack: PackInfo { compression: None },
-            asset_type: AssetType::Texture {
-                texture_type: None,
-                texture_type_combo_box: combo_box::State::new(TextureType::variants()),
-            },
-            asset_types_combo_box: combo_box::State::new(AssetType::variants()),
+            pack: PackInfo::default(),
+            importer: None,
+            importers_combo_box: combo_box::State::new(importer_names),
+            settings: text_editor::Content::new(),
+            settings_error: None,
         }
     }
 
+    fn selected_importer<'a>(&self, asset_server: &'a AssetServer) -> Option<&'a RegisteredImporter> {
+        self.importer
+            .as_ref()
+            .and_then(|name| asset_server.importers().get(name))
+    }
+
     pub fn update(&mut self, message: ImportAssetsMessage, asset_server: &AssetServer) {
-        fn update_relative_path(s: &mut ImportAssets) {
+        fn update_relative_path(s: &mut ImportAssets, asset_server: &AssetServer) {
+            let imported_fs_type = s
+                .selected_importer(asset_server)
+                .map(|importer| importer.imported_fs_type());
+
             if !s.abs_input.exists() {
                 s.relative_output = "!ASSET INPUT NOT FOUND ON DISK!".to_string();
-            } else if s.asset_type.imported_fs_type() == AssetFileSystemType::File {
-                s.relative_output = format!("{}.asset", s.name);
-            } else {
+            } else if imported_fs_type == Some(AssetFileSystemType::Directory) {
                 s.relative_output = format!("{}/", s.name);
+            } else {
+                s.relative_output = format!("{}.asset", s.name);
             }
         }
 
         match message {
             ImportAssetsMessage::SetInputPath(path) => {
                 self.abs_input = path;
-                update_relative_path(self);
+                update_relative_path(self, asset_server);
             }
             ImportAssetsMessage::SelectInputPath { directory } => {
-                if directory {
-                    rfd::FileDialog::new()
-                        .set_title("Select Input Directory")
-                        .pick_folder()
-                        .map(|path| {
-                            self.abs_input = path;
-                            update_relative_path(self);
-                        });
+                let mut dialog = rfd::FileDialog::new();
+
+                if let Some(importer) = self.selected_importer(asset_server) {
+                    if !importer.extensions().is_empty() {
+                        dialog = dialog.add_filter(importer.name(), importer.extensions());
+                    }
+                }
+
+                let path = if directory {
+                    dialog.set_title("Select Input Directory").pick_folder()
                 } else {
-                    rfd::FileDialog::new()
-                        .set_title("Select Input File")
-                        .pick_file()
-                        .map(|path| {
-                            self.abs_input = path;
-                            update_relative_path(self);
-                        });
+                    dialog.set_title("Select Input File").pick_file()
+                };
+
+                if let Some(path) = path {
+                    self.abs_input = path;
+                    update_relative_path(self, asset_server);
                 }
             }
             ImportAssetsMessage::SetName(name) => {
                 self.name = name;
-                update_relative_path(self);
+                update_relative_path(self, asset_server);
             }
             ImportAssetsMessage::ImportAsset => {
-                match self.asset_type {
-                    AssetType::Texture {
-                        texture_type: Some(texture_type),
-                        ..
-                    } => {
-                        handle_texture_type(
-                            asset_server.assets_dir(),
-                            &self.abs_input,
-                            &PathBuf::from(&self.relative_output),
-                            self.name.clone(),
-                            self.pack.clone(),
-                            texture_type,
-                        )
-                        .map_err(|e| {
-                            eprintln!("Failed to import texture: {:?}", e);
-                            panic!("Failed to import texture: {:?}", e);
-                        })
-                        .unwrap();
-                    }
-                    AssetType::Gltf => {
-                        // add_or_pack_asset(
-                        //     asset_server.assets_dir(),
-                        //     &self.abs_input,
-                        //     &PathBuf::from(&self.relative_output),
-                        //     self.name.clone(),
-                        //     self.packed.clone(),
-                        // );
-                        todo!()
+                let importer = match self.selected_importer(asset_server) {
+                    Some(importer) => importer,
+                    None => {
+                        log::error!("No importer selected");
+                        return;
                     }
-                    AssetType::Obj => {
-                        pack_asset::<Mesh, ObjImporter>(
-                            asset_server.assets_dir(),
-                            &self.abs_input,
-                            &PathBuf::from(&self.relative_output),
-                            self.name.clone(),
-                            self.pack.clone(),
-                        )
-                        .map_err(|e| {
-                            eprintln!("Failed to import mesh: {:?}", e);
-                            panic!("Failed to import mesh: {:?}", e);
-                        })
-                        .unwrap();
+                };
+
+                let settings = match toml::from_str::<toml::Table>(&self.settings.text()) {
+                    Ok(settings) => settings,
+                    Err(e) => {
+                        self.settings_error = Some(e.to_string());
+                        return;
                     }
-                    _ => {}
+                };
+
+                if let Err(e) = importer.import(
+                    asset_server.assets_dir(),
+                    &self.abs_input,
+                    &PathBuf::from(&self.relative_output),
+                    self.name.clone(),
+                    self.pack.clone(),
+                    Some(&toml::Value::Table(settings)),
+                ) {
+                    eprintln!("Failed to import asset with {}: {:?}", importer.name(), e);
                 }
             }
             ImportAssetsMessage::SetPackInfo(pack_info) => {
                 self.pack = pack_info;
-                update_relative_path(self);
+                update_relative_path(self, asset_server);
             }
-            ImportAssetsMessage::SetAssetType(asset_type) => {
-                // Reset the input path if the asset type has changed
-                if self.asset_type != asset_type {
+            ImportAssetsMessage::SetImporter(name) => {
+                // Reset the input path and settings if the importer has changed
+                if self.importer.as_ref() != Some(&name) {
                     self.abs_input = PathBuf::new();
+
+                    let default_settings = asset_server
+                        .importers()
+                        .get(&name)
+                        .and_then(|importer| toml::to_string_pretty(importer.default_settings()).ok())
+                        .unwrap_or_default();
+
+                    self.settings = text_editor::Content::with_text(&default_settings);
+                    self.settings_error = None;
                 }
 
-                self.asset_type = asset_type;
+                self.importer = Some(name);
             }
-            ImportAssetsMessage::ExtraConfigMessage(message) => {
-                self.asset_type.extra_config_update(message);
+            ImportAssetsMessage::EditSettings(action) => {
+                self.settings.perform(action);
+                self.settings_error = None;
             }
             ImportAssetsMessage::SetRelativeOutput(relative_output) => {
                 self.relative_output = relative_output;
@@ -279,31 +166,44 @@ impl ImportAssets {
         }
     }
 
-    pub fn view(&self, _asset_server: &AssetServer) -> iced::Element<Message> {
-        // let manifest = if let Ok(manifest) = asset_server.get_manifest() {
-        //     manifest
-        // } else {
-        //     return widget::Text::new("Failed to load assets, manifest not found.").into();
-        // };
+    pub fn view<'a>(&'a self, asset_server: &'a AssetServer) -> iced::Element<'a, Message> {
+        let importer = self.selected_importer(asset_server);
 
-        let asset_type_combo_box = widget::row![
-            widget::Text::new("Asset Type: ").size(16),
+        let importer_combo_box = widget::row![
+            widget::Text::new("Importer: ").size(16),
             widget::combo_box(
-                &self.asset_types_combo_box,
-                "Asset type...",
-                Some(&self.asset_type),
-                |selected| Message::ImportAssetsMessage(ImportAssetsMessage::SetAssetType(
+                &self.importers_combo_box,
+                "Importer...",
+                self.importer.as_ref(),
+                |selected| Message::ImportAssetsMessage(ImportAssetsMessage::SetImporter(
                     selected
                 )),
             )
         ];
 
-        let asset_type_note: iced::Element<Message> = if let Some(note) = self.asset_type.note() {
-            widget::rich_text![
-                widget::span(note.to_string()).size(16).underline(true).color(colors::GRAY)
-            ].into()
+        let importer_note: iced::Element<Message> =
+            if let Some(note) = importer.and_then(|importer| importer.note()) {
+                widget::rich_text![
+                    widget::span(note.to_string()).size(16).underline(true).color(colors::GRAY)
+                ].into()
+            } else {
+                widget::rich_text![].into()
+            };
+
+        let importer_info: iced::Element<Message> = if let Some(importer) = importer {
+            widget::Text::new(format!(
+                "Imports {} from: {}",
+                importer.asset_type(),
+                if importer.extensions().is_empty() {
+                    "directories".to_string()
+                } else {
+                    importer.extensions().join(", ")
+                }
+            ))
+            .size(14)
+            .into()
         } else {
-            widget::rich_text![].into()
+            widget::Text::new("").into()
         };
 
         let name_input = widget::TextInput::new("Asset Name/Handle", &self.name)
@@ -325,9 +225,9 @@ impl ImportAssets {
             Message::ImportAssetsMessage(ImportAssetsMessage::SelectInputPath { directory: true }),
         );
 
-        let input_picker = match self.asset_type.unimported_fs_type() {
-            AssetFileSystemType::File => input_file_picker,
-            AssetFileSystemType::Directory => input_dir_picker,
+        let input_picker = match importer.map(|importer| importer.unimported_fs_type()) {
+            Some(AssetFileSystemType::Directory) => input_dir_picker,
+            _ => input_file_picker,
         };
 
         let input_path_row = row![input_path, input_picker].spacing(10);
@@ -337,6 +237,19 @@ impl ImportAssets {
                 |path| Message::ImportAssetsMessage(ImportAssetsMessage::SetRelativeOutput(path)),
             );
 
+        let settings_editor = widget::column![
+            widget::Text::new("Importer Settings (TOML)").size(16),
+            text_editor(&self.settings)
+                .height(120)
+                .on_action(|action| Message::ImportAssetsMessage(
+                    ImportAssetsMessage::EditSettings(action)
+                )),
+            widget::Text::new(self.settings_error.clone().unwrap_or_default())
+                .size(14)
+                .color(iced::Color::from_rgb(0.9, 0.3, 0.3)),
+        ]
+        .spacing(5);
+
         let import_button = button::Button::new(Text::new("Import Asset")).on_press(
             Message::ImportAssetsMessage(ImportAssetsMessage::ImportAsset),
         );
@@ -370,8 +283,19 @@ impl ImportAssets {
                 },
             );
 
+            let lz4_toggle = widget::Checkbox::new(
+                "Use lz4 (faster loading, larger files)",
+                self.pack.codec == PackCodec::Lz4,
+            )
+            .on_toggle(|lz4| {
+                let mut new_pack_info = self.pack.clone();
+                new_pack_info.codec = if lz4 { PackCodec::Lz4 } else { PackCodec::Zstd };
+                Message::ImportAssetsMessage(ImportAssetsMessage::SetPackInfo(new_pack_info))
+            });
+
             widget::column![
                 compression_toggle,
+                lz4_toggle,
                 compression_level_label,
                 compression_input
             ]
@@ -380,12 +304,13 @@ impl ImportAssets {
         };
 
         let content = widget::column![
-            asset_type_combo_box,
-            asset_type_note,
+            importer_combo_box,
+            importer_note,
+            importer_info,
             name_input,
             input_path_row,
             rel_out_path_input,
-            self.asset_type.extra_config_view(),
+            settings_editor,
             widget::row![pack_settings, widget::horizontal_space(), import_button].spacing(10),
         ]
         .spacing(10);
@@ -393,43 +318,3 @@ impl ImportAssets {
         content.into()
     }
 }
-
-macro_rules! generate_texture_asset_code {
-    ($($variant:ident),*) => {
-    /// Helper function to help faciltate texture imports as there can be multiple types of textures
-        fn handle_texture_type(
-            assets_dir: &std::path::Path,
-            abs_input_file: &std::path::Path,
-            relative_output_dir: &std::path::Path,
-            name: String,
-            packed: PackInfo,
-            texture_type: TextureType,
-        ) -> Result<(), AssetPackError> {
-            match texture_type {
-                $(
-                    TextureType::$variant => {
-                        pack_asset::<TextureAsset<{ TextureType::$variant }>, TextureImporter<{ TextureType::$variant }>>(
-                            assets_dir,
-                            abs_input_file,
-                            relative_output_dir,
-                            name,
-                            packed,
-                        )
-                    }
-                ),*
-            }
-        }
-    };
-}
-
-generate_texture_asset_code!(
-    RGBA32Float,
-    RGBA16Float,
-    RGBA8Unorm,
-    RGBA8UnormSrgb,
-    R32Float,
-    R16Float,
-    R8Unorm,
-    R8Uint,
-    R8Snorm
-);
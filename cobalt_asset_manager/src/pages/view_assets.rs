@@ -1,22 +1,109 @@
-use cobalt_core::assets::server::AssetServer;
+use cobalt_core::assets::{query::AssetQuery, server::AssetServer};
 use iced::widget::{self, Text};
 
 use crate::{components, Message};
 
-pub struct ViewAssets {}
+pub struct ViewAssets {
+    search: String,
+    type_filter: Option<String>,
+    tag_filter: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub enum ViewAssetsMessage {
+    SetSearch(String),
+    SetTypeFilter(String),
+    SetTagFilter(String),
+    ClearFilters,
+}
 
 impl ViewAssets {
-    pub fn view(asset_server: &AssetServer) -> iced::Element<Message> {
+    pub fn new() -> Self {
+        Self {
+            search: String::new(),
+            type_filter: None,
+            tag_filter: None,
+        }
+    }
+
+    pub fn update(&mut self, message: ViewAssetsMessage) {
+        match message {
+            ViewAssetsMessage::SetSearch(search) => self.search = search,
+            ViewAssetsMessage::SetTypeFilter(type_name) => self.type_filter = Some(type_name),
+            ViewAssetsMessage::SetTagFilter(tag) => self.tag_filter = Some(tag),
+            ViewAssetsMessage::ClearFilters => *self = Self::new(),
+        }
+    }
+
+    fn query(&self) -> AssetQuery {
+        AssetQuery {
+            type_name: self.type_filter.clone(),
+            tags: self.tag_filter.iter().cloned().collect(),
+            search: Some(self.search.trim().to_string()).filter(|search| !search.is_empty()),
+            ..Default::default()
+        }
+    }
+
+    pub fn view<'a>(
+        &'a self,
+        asset_server: &'a AssetServer,
+        verify_report: Option<&'a str>,
+    ) -> iced::Element<'a, Message> {
         let refresh_button = widget::button(Text::new("Refresh asset server").size(20))
             .on_press(Message::RefreshAssets);
 
+        let verify_row = widget::row![
+            widget::button(Text::new("Verify manifest")).on_press(Message::VerifyManifest),
+            widget::button(Text::new("Prune missing assets")).on_press(Message::RepairManifest),
+            widget::button(Text::new("Reimport stale assets")).on_press(Message::ReimportStale),
+        ]
+        .spacing(10);
+
         let assets_table = asset_server.get_manifest().map(|manifest| {
-            let assets = &manifest.assets;
+            let mut type_names = Vec::new();
+            let mut tags = Vec::new();
+
+            for asset_info in manifest.all_assets() {
+                type_names.push(asset_info.type_name.clone());
+                tags.extend(asset_info.tags.iter().cloned());
+            }
+
+            type_names.sort();
+            type_names.dedup();
+            tags.sort();
+            tags.dedup();
+
+            let filter_row = widget::row![
+                widget::TextInput::new("Search name, type, path or tag", &self.search)
+                    .on_input(|search| {
+                        Message::ViewAssetsMessage(ViewAssetsMessage::SetSearch(search))
+                    })
+                    .width(iced::Length::FillPortion(2)),
+                widget::pick_list(type_names, self.type_filter.as_ref(), |type_name| {
+                    Message::ViewAssetsMessage(ViewAssetsMessage::SetTypeFilter(type_name))
+                })
+                .placeholder("All types"),
+                widget::pick_list(tags, self.tag_filter.as_ref(), |tag| {
+                    Message::ViewAssetsMessage(ViewAssetsMessage::SetTagFilter(tag))
+                })
+                .placeholder("All tags"),
+                widget::button(Text::new("Clear filters"))
+                    .on_press(Message::ViewAssetsMessage(ViewAssetsMessage::ClearFilters)),
+            ]
+            .spacing(10)
+            .align_y(iced::Alignment::Center);
+
+            let assets = manifest.query(&self.query());
+            let shown = format!(
+                "Showing {} of {} assets",
+                assets.len(),
+                manifest.all_assets().count()
+            );
 
             let mut table_column = widget::column![].spacing(15);
 
             for asset_info in assets {
-                let asset = components::asset::Asset::view(asset_info);
+                let asset = components::asset::Asset::view(asset_info, manifest);
 
                 table_column = table_column.push(asset);
             }
@@ -25,11 +112,17 @@ impl ViewAssets {
                 .width(iced::Length::Fill)
                 .height(iced::Length::Fill);
 
-            assets_table
+            widget::column![filter_row, Text::new(shown), assets_table].spacing(10)
         });
 
         if let Ok(assets_table) = assets_table {
-            widget::column![refresh_button, assets_table].spacing(10).into()
+            let mut column = widget::column![refresh_button, verify_row].spacing(10);
+
+            if let Some(verify_report) = verify_report {
+                column = column.push(Text::new(verify_report));
+            }
+
+            column.push(assets_table).into()
         } else {
             widget::Text::new("Failed to load assets, manifest not found.").into()
         }
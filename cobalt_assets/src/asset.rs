@@ -5,7 +5,7 @@ use serde::{de::DeserializeSeed, Serialize};
 use std::{
     any::Any,
     fmt::{Debug, Formatter},
-    path::Path,
+    path::{Path, PathBuf},
     sync::Arc,
 };
 
@@ -28,7 +28,11 @@ impl AssetID {
 
 use cobalt_graphics::context::Graphics;
 
-use crate::manifest::{AssetInfo, ExtraAssetInfo};
+use crate::{
+    manifest::{AssetInfo, ImportedAsset},
+    packed::PackedAssetError,
+    source::AssetSource,
+};
 
 use super::server::AssetServer;
 
@@ -53,12 +57,60 @@ pub enum AssetReadError {
     MissingExtraAssetInfo(String),
     #[error("Failed to create asset: {0}")]
     CreateError(Box<dyn std::error::Error>),
+    #[error("Dependency {0:?} is not listed in the asset info")]
+    MissingDependency(AssetID),
+    #[error("Dependency {0:?} is not a {1}")]
+    DependencyTypeMismatch(AssetID, String),
+    #[error("Failed to read packed asset")]
+    Packed(#[from] PackedAssetError),
+    #[error("{0} format version {1} is newer than this build supports")]
+    UnsupportedVersion(String, u32),
+}
+
+/// Everything `AssetTrait::read` can use besides the packed data.
+pub struct AssetReadContext<'a> {
+    pub graphics: &'a Arc<RwLock<Graphics>>,
+    asset_server_ref: std::sync::Weak<RwLock<AssetServer>>,
+    /// The asset's dependencies, already loaded.
+    dependencies: &'a [(AssetID, Arc<dyn Any + Send + Sync + 'static>)],
+}
+
+impl<'a> AssetReadContext<'a> {
+    pub(crate) fn new(
+        graphics: &'a Arc<RwLock<Graphics>>,
+        asset_server_ref: std::sync::Weak<RwLock<AssetServer>>,
+        dependencies: &'a [(AssetID, Arc<dyn Any + Send + Sync + 'static>)],
+    ) -> Self {
+        Self {
+            graphics,
+            asset_server_ref,
+            dependencies,
+        }
+    }
+
+    /// A handle to one of the dependencies listed in the asset info.
+    /// They are loaded before the asset is read.
+    pub fn dependency<T: AssetTrait>(&self, asset_id: AssetID) -> Result<Asset<T>, AssetReadError> {
+        let data = self
+            .dependencies
+            .iter()
+            .find(|(id, _)| *id == asset_id)
+            .map(|(_, data)| data.clone())
+            .ok_or(AssetReadError::MissingDependency(asset_id))?;
+
+        if !data.is::<RwLock<T>>() {
+            return Err(AssetReadError::DependencyTypeMismatch(asset_id, T::type_name()));
+        }
+
+        Ok(Asset::new(self.asset_server_ref.clone(), Some(asset_id), data))
+    }
 }
 
 /// Assets are anything that can be loaded from disk.
 /// Types implementing this trait must be Send + Sync + 'static.
 /// NOTE: When loading, asset server will already type check the asset.
 /// NOTE: The graphics context is required for loading assets that need to be uploaded to the GPU.
+/// It is not locked while reading, lock it only for as long as needed.
 pub trait AssetTrait: Sized + Send + Sync + 'static {
     /// The name of the asset type.
     /// NOTE: MAKE SURE THIS IS UNIQUE
@@ -67,7 +119,66 @@ pub trait AssetTrait: Sized + Send + Sync + 'static {
     /// Whether the asset is to be stored as a directory or a file.
     fn imported_fs_type() -> AssetFileSystemType;
     
-    fn read(asset_info: &AssetInfo, assets_dir: &Path, graphics: &Graphics) -> Result<Self, AssetReadError>;
+    /// Reads the packed asset. Files must be read through the source, never from disk directly,
+    /// so assets can come from directories, archives or memory.
+    fn read(asset_info: &AssetInfo, source: &dyn AssetSource, context: &AssetReadContext) -> Result<Self, AssetReadError>;
+
+    /// Version of the packed format, written in the header of every packed file.
+    /// Bump it whenever the packed data changes and handle the older version in `migrate`.
+    fn format_version() -> u32 {
+        1
+    }
+
+    /// Converts the payload of a packed file from an older format version to the current one.
+    /// Version 0 is any file packed before the header held a format version.
+    fn migrate(version: u32, payload: Vec<u8>, asset_info: &AssetInfo) -> Result<Vec<u8>, AssetReadError> {
+        let _ = (payload, asset_info);
+        Err(AssetReadError::UnsupportedVersion(Self::type_name(), version))
+    }
+
+    /// Packed files of the asset, relative to the assets directory. Used to upgrade them in place.
+    fn packed_files(asset_info: &AssetInfo) -> Vec<PathBuf> {
+        vec![asset_info.relative_path.clone()]
+    }
+
+    /// Estimate of the memory the loaded asset uses, for the asset cache budget and memory stats.
+    /// Dependencies are counted as their own assets and should be left out.
+    fn memory_size(&self) -> AssetMemory {
+        AssetMemory {
+            cpu: std::mem::size_of::<Self>(),
+            gpu: 0,
+        }
+    }
+}
+
+/// Memory used by loaded assets in bytes, see `AssetTrait::memory_size`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+pub struct AssetMemory {
+    pub cpu: usize,
+    pub gpu: usize,
+}
+
+impl AssetMemory {
+    pub fn total(&self) -> usize {
+        self.cpu + self.gpu
+    }
+}
+
+impl std::ops::Add for AssetMemory {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            cpu: self.cpu + other.cpu,
+            gpu: self.gpu + other.gpu,
+        }
+    }
+}
+
+impl std::ops::AddAssign for AssetMemory {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -103,24 +214,43 @@ pub enum AssetImportError {
 }
 
 
+/// Settings for importers that don't have any.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default)]
+pub struct NoSettings {}
+
 /// T: The target asset type to import to.
 pub trait AssetImporter<T: AssetTrait> {
+    /// Options for the import. They are stored in the manifest so reimports use the same ones.
+    /// Must serialise to a TOML table, use `NoSettings` if there are none.
+    type Settings: Serialize + serde::de::DeserializeOwned + Default + Clone;
+
+    /// The name of the importer, stored in the manifest to find it again when reimporting.
+    /// NOTE: MAKE SURE THIS IS UNIQUE
+    fn name() -> String;
+
     fn unimported_fs_type() -> AssetFileSystemType;
 
+    /// Source file extensions the importer accepts, lower case without the dot.
+    /// Leave empty for importers of directories.
+    fn extensions() -> Vec<String> {
+        Vec::new()
+    }
+
     fn note() -> Option<String> {
         None
     }
 
     fn verify_source(abs_path: &Path) -> Result<(), AssetVerifyError>;
 
-    fn import(abs_input_path: &Path, asset_info: &AssetInfo, assets_dir: &Path) -> Result<ExtraAssetInfo, AssetImportError>;
+    /// `ExtraAssetInfo` converts into an `ImportedAsset` without dependencies or sub-assets.
+    fn import(abs_input_path: &Path, asset_info: &AssetInfo, assets_dir: &Path, settings: &Self::Settings) -> Result<ImportedAsset, AssetImportError>;
 }
 
 /// Handle to an asset.
 /// This is a wrapper around an `Arc<RwLock<T>>` that also contains the path.
 /// The handle can be serialized and deserialized.
-/// When the handle is serialized, it will serialize the path.
-/// When the handle is deserialized, it will load the asset into the global asset server.
+/// When the handle is serialized, it will serialize its id and type name.
+/// When the handle is deserialized, it will be loaded through the asset server in `AssetDeContext`.
 /// This can be converted into a Resource<T> by calling `.into()`.
 pub struct Asset<T: AssetTrait> {
     pub(crate) asset_id: AssetID,
@@ -135,29 +265,106 @@ impl<T: AssetTrait> PartialEq for Asset<T> {
     }
 }
 
-impl<T: AssetTrait> Component for Asset<T> {
-    type DeContext<'a> = ();
-    type SerContext<'a> = ();
+/// Context needed to deserialize asset handles.
+/// Handles are resolved through the asset server, reusing the loaded instance or loading it on demand.
+#[derive(Clone, Copy)]
+pub struct AssetDeContext<'a> {
+    pub asset_server: &'a Arc<RwLock<AssetServer>>,
+    pub graphics: &'a Arc<RwLock<Graphics>>,
+}
 
-    fn deserialise<'de, D>(context: Self::DeContext<'de>, deserializer: D) -> Result<Self, D::Error>
+impl<'a> AssetDeContext<'a> {
+    pub fn new(
+        asset_server: &'a Arc<RwLock<AssetServer>>,
+        graphics: &'a Arc<RwLock<Graphics>>,
+    ) -> Self {
+        Self {
+            asset_server,
+            graphics,
+        }
+    }
+
+    /// Seed for deserializing an `Asset<T>` inside another type's serde impl.
+    pub fn seed<T: AssetTrait>(self) -> AssetSeed<'a, T> {
+        AssetSeed {
+            context: self,
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+/// What a serialized handle looks like. The type name is only used for validation.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename = "Asset")]
+struct AssetRef {
+    id: AssetID,
+    type_name: String,
+}
+
+/// Deserializes an `Asset<T>` from its `AssetID`, see `AssetDeContext`.
+pub struct AssetSeed<'a, T: AssetTrait> {
+    context: AssetDeContext<'a>,
+    _marker: std::marker::PhantomData<T>,
+}
+
+impl<'de, 'a, T: AssetTrait> DeserializeSeed<'de> for AssetSeed<'a, T> {
+    type Value = Asset<T>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
     where
         D: serde::Deserializer<'de> {
-        todo!()
+        use serde::de::Error;
+
+        let asset_ref = <AssetRef as serde::Deserialize>::deserialize(deserializer)?;
+
+        if asset_ref.type_name != T::type_name() {
+            return Err(D::Error::custom(format!(
+                "Asset {:?} is a {}, expected {}",
+                asset_ref.id,
+                asset_ref.type_name,
+                T::type_name()
+            )));
+        }
+
+        self.context
+            .asset_server
+            .write()
+            .load::<T>(
+                Arc::downgrade(self.context.asset_server),
+                self.context.graphics,
+                asset_ref.id,
+            )
+            .map_err(|e| D::Error::custom(format!("Failed to load asset {:?}: {}", asset_ref.id, e)))
     }
+}
 
-    fn serialize<'se, S>(&self, context: Self::DeContext<'se>, serializer: S) -> Result<S::Ok, S::Error>
+/// Handles serialize as their `AssetID` and type name.
+impl<T: AssetTrait> Serialize for Asset<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer {
-        todo!()
+        AssetRef {
+            id: self.asset_id,
+            type_name: T::type_name(),
+        }
+        .serialize(serializer)
     }
 }
 
+impl<T: AssetTrait> Component for Asset<T> {
+    type DeContext<'a> = AssetDeContext<'a>;
+    type SerContext<'a> = ();
 
-impl<'de, T: AssetTrait> serde::Deserialize<'de> for Asset<T> {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    fn deserialise<'de, D>(context: Self::DeContext<'de>, deserializer: D) -> Result<Self, D::Error>
     where
         D: serde::Deserializer<'de> {
-        todo!()
+        context.seed::<T>().deserialize(deserializer)
+    }
+
+    fn serialize<'se, S>(&self, _context: Self::SerContext<'se>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer {
+        Serialize::serialize(self, serializer)
     }
 }
 
@@ -206,6 +413,10 @@ impl<T: AssetTrait> Asset<T> {
     pub fn unwrap_data(self) -> Arc<RwLock<T>> {
         self.data.clone()
     }
+
+    pub fn id(&self) -> AssetID {
+        self.asset_id
+    }
 }
 
 impl<T: AssetTrait> Clone for Asset<T> {
@@ -249,16 +460,16 @@ impl<T: AssetTrait> Drop for Asset<T> {
         // If it can't be upgraded, the asset server has been dropped.
         // This means the entire engine is shutting down or already shut down.
         // In this case, we don't need to remove the asset from the server.
-        self.asset_server_ref.upgrade().map(|server| {
-            let mut server = server.write();
-            
-            if let Some((_asset, count)) = server.loaded_assets.get_mut(&self.asset_id) {
-                if *count == 1 {
-                    server.loaded_assets.remove(&self.asset_id);
-                } else {
-                    *count -= 1;
-                }
-            }
-        });
+        // Handles and resources created from them share the same `Arc`, so the
+        // asset is only unused once this is the last strong reference.
+        // Checked before locking, the server may drop handles while it is locked.
+        // `unload` checks again under the lock, the asset may be loaded again in between.
+        if Arc::strong_count(&self.data) != 1 {
+            return;
+        }
+
+        if let Some(server) = self.asset_server_ref.upgrade() {
+            server.write().unload(self.asset_id);
+        }
     }
 }
@@ -0,0 +1,259 @@
+//! Keeps recently unreferenced assets loaded within a memory budget, so assets that are dropped and
+//! loaded again soon after, e.g. when switching levels, don't have to be read again.
+
+use std::{
+    any::Any,
+    fmt::{Display, Formatter},
+    sync::Arc,
+};
+
+use hashbrown::HashMap;
+
+use crate::{
+    asset::{AssetID, AssetMemory},
+    server::AssetServer,
+};
+
+/// Cache settings and the clock used to order assets by last use.
+#[derive(Debug, Default)]
+pub(crate) struct AssetCache {
+    /// Bytes of unreferenced assets to keep loaded, 0 disables the cache.
+    pub(crate) budget: usize,
+    tick: u64,
+}
+
+impl AssetCache {
+    pub(crate) fn enabled(&self) -> bool {
+        self.budget > 0
+    }
+
+    /// Advances the clock, loaded assets store the tick they were last used at.
+    pub(crate) fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+}
+
+/// Assets removed from the cache.
+/// Dropping it frees them, do that after releasing the asset server's lock: assets can hold handles
+/// to other assets, and dropping the last handle of an asset locks the server.
+#[must_use = "evicted assets should be dropped after the asset server is unlocked"]
+#[derive(Default)]
+pub struct EvictedAssets {
+    pub asset_ids: Vec<AssetID>,
+    /// Only held to be dropped.
+    #[allow(dead_code)]
+    data: Vec<Arc<dyn Any + Send + Sync + 'static>>,
+}
+
+impl EvictedAssets {
+    pub fn is_empty(&self) -> bool {
+        self.asset_ids.is_empty()
+    }
+}
+
+/// Memory used by the loaded assets of one type, see `AssetServer::memory_stats`.
+#[derive(Debug, Clone, Copy, Default, serde::Serialize)]
+pub struct AssetTypeMemory {
+    /// Loaded assets of the type, cached ones included.
+    pub loaded: usize,
+    /// Assets only kept loaded by the cache.
+    pub cached: usize,
+    pub memory: AssetMemory,
+    pub cached_memory: AssetMemory,
+}
+
+impl Display for AssetTypeMemory {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} loaded ({} cached), {} CPU, {} GPU",
+            self.loaded,
+            self.cached,
+            format_bytes(self.memory.cpu),
+            format_bytes(self.memory.gpu)
+        )
+    }
+}
+
+/// Formats a byte count with a binary unit, e.g. `1.5 MiB`.
+pub fn format_bytes(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Picks which of the unreferenced assets to evict, least recently used first,
+/// until the rest fit in the budget. Takes `(asset, last used tick, bytes)`.
+fn least_recently_used(
+    mut unreferenced: Vec<(AssetID, u64, usize)>,
+    budget: usize,
+) -> Vec<AssetID> {
+    let mut total = unreferenced.iter().map(|(_, _, size)| size).sum::<usize>();
+
+    unreferenced.sort_by_key(|(_, last_used, _)| *last_used);
+
+    let mut evicted = Vec::new();
+
+    for (asset_id, _, size) in unreferenced {
+        if total <= budget {
+            break;
+        }
+
+        total -= size;
+        evicted.push(asset_id);
+    }
+
+    evicted
+}
+
+impl AssetServer {
+    /// Sets how many bytes of unreferenced assets are kept loaded, see `AssetTrait::memory_size`.
+    /// With a budget, the server holds on to every loaded asset and `trim_cache` evicts the least
+    /// recently used of those without handles once they exceed it. 0 disables the cache, assets are
+    /// then unloaded as soon as their last handle drops.
+    pub fn set_cache_budget(&mut self, budget: usize) -> EvictedAssets {
+        self.cache.budget = budget;
+
+        if !self.cache.enabled() {
+            // Unreferenced assets go now, the rest once their handles drop
+            let mut evicted = self.trim_cache();
+
+            for loaded in self.loaded_assets.values_mut() {
+                evicted.data.extend(loaded.retained.take());
+            }
+
+            return evicted;
+        }
+
+        for loaded in self.loaded_assets.values_mut() {
+            if loaded.retained.is_none() {
+                loaded.retained = loaded.data.upgrade();
+            }
+        }
+
+        self.trim_cache()
+    }
+
+    pub fn cache_budget(&self) -> usize {
+        self.cache.budget
+    }
+
+    /// Evicts the least recently used unreferenced assets until the rest fit in the cache budget,
+    /// queuing `Removed` events for them. The engine calls this every frame.
+    /// NOTE: Dependencies of evicted assets stay referenced until the evicted assets are dropped,
+    /// they are considered on the next call.
+    pub fn trim_cache(&mut self) -> EvictedAssets {
+        // Assets released by a previous eviction or by disabling the cache
+        self.remove_dropped();
+
+        let tick = self.cache.next_tick();
+        let mut unreferenced = Vec::new();
+
+        for (asset_id, loaded) in self.loaded_assets.iter_mut() {
+            let Some(retained) = &loaded.retained else {
+                continue;
+            };
+
+            if Arc::strong_count(retained) > 1 {
+                loaded.last_used = tick;
+            } else {
+                unreferenced.push((*asset_id, loaded.last_used, loaded.memory.total()));
+            }
+        }
+
+        let mut evicted = EvictedAssets::default();
+
+        for asset_id in least_recently_used(unreferenced, self.cache.budget) {
+            if let Some(loaded) = self.remove_loaded(asset_id) {
+                evicted.asset_ids.push(asset_id);
+                evicted.data.extend(loaded.retained);
+                evicted
+                    .data
+                    .extend(loaded.dependencies.into_iter().map(|(_, data)| data));
+            }
+        }
+
+        evicted
+    }
+
+    /// Evicts every unreferenced asset, keeping the budget.
+    pub fn clear_cache(&mut self) -> EvictedAssets {
+        let budget = std::mem::replace(&mut self.cache.budget, 0);
+        let evicted = self.trim_cache();
+        self.cache.budget = budget;
+
+        evicted
+    }
+
+    /// Memory used by the loaded assets, by type name.
+    pub fn memory_stats(&self) -> HashMap<String, AssetTypeMemory> {
+        let mut stats = HashMap::<String, AssetTypeMemory>::new();
+
+        for loaded in self.loaded_assets.values() {
+            if loaded.data.strong_count() == 0 {
+                continue;
+            }
+
+            let type_stats = stats.entry(loaded.type_name.clone()).or_default();
+            type_stats.loaded += 1;
+            type_stats.memory += loaded.memory;
+
+            if loaded
+                .retained
+                .as_ref()
+                .is_some_and(|retained| Arc::strong_count(retained) == 1)
+            {
+                type_stats.cached += 1;
+                type_stats.cached_memory += loaded.memory;
+            }
+        }
+
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_least_recently_used_first() {
+        let ids = [
+            AssetID::generate(),
+            AssetID::generate(),
+            AssetID::generate(),
+        ];
+        let unreferenced = vec![(ids[0], 5, 100), (ids[1], 2, 100), (ids[2], 9, 100)];
+
+        assert!(least_recently_used(unreferenced.clone(), 300).is_empty());
+        assert_eq!(least_recently_used(unreferenced.clone(), 250), [ids[1]]);
+        assert_eq!(
+            least_recently_used(unreferenced.clone(), 100),
+            [ids[1], ids[0]]
+        );
+        assert_eq!(
+            least_recently_used(unreferenced, 0),
+            [ids[1], ids[0], ids[2]]
+        );
+    }
+
+    #[test]
+    fn formats_bytes() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(64 * 1024 * 1024), "64.0 MiB");
+    }
+}
@@ -0,0 +1,288 @@
+//! Type erased importers, so tools can list and run importers for asset types they don't know at compile time.
+
+use std::path::Path;
+
+use crate::{
+    asset::{AssetFileSystemType, AssetID, AssetImporter, AssetTrait, AssetVerifyError},
+    manifest::{pack_asset, reimport_asset, AssetPackError, Manifest, PackInfo},
+};
+
+/// Type erased `pack_asset`, the settings are deserialised into the importer's settings type.
+type ImportFn = fn(
+    &Path,
+    &Path,
+    &Path,
+    String,
+    PackInfo,
+    &toml::Value,
+) -> Result<AssetID, AssetPackError>;
+
+/// Type erased `reimport_asset`.
+type ReimportFn = fn(&Path, AssetID) -> Result<(), AssetPackError>;
+
+/// An importer registered in an `ImporterRegistry`.
+pub struct RegisteredImporter {
+    name: String,
+    asset_type: String,
+    extensions: Vec<String>,
+    note: Option<String>,
+    unimported_fs_type: AssetFileSystemType,
+    imported_fs_type: AssetFileSystemType,
+    default_settings: toml::Value,
+    verify: fn(&Path) -> Result<(), AssetVerifyError>,
+    import: ImportFn,
+    reimport: ReimportFn,
+}
+
+impl RegisteredImporter {
+    fn new<A: AssetTrait, T: AssetImporter<A>>() -> Self {
+        let default_settings = toml::Value::try_from(T::Settings::default()).unwrap_or_else(|e| {
+            log::error!("Default settings of importer {} don't serialise: {}", T::name(), e);
+            toml::Value::Table(toml::Table::new())
+        });
+
+        Self {
+            name: T::name(),
+            asset_type: A::type_name(),
+            extensions: T::extensions()
+                .into_iter()
+                .map(|extension| extension.to_lowercase())
+                .collect(),
+            note: T::note(),
+            unimported_fs_type: T::unimported_fs_type(),
+            imported_fs_type: A::imported_fs_type(),
+            default_settings,
+            verify: T::verify_source,
+            import: import_erased::<A, T>,
+            reimport: reimport_asset::<A, T>,
+        }
+    }
+
+    /// The unique name of the importer, stored in the manifest of assets it imported.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Type name of the asset type the importer produces.
+    pub fn asset_type(&self) -> &str {
+        &self.asset_type
+    }
+
+    /// Accepted source file extensions, lower case without the dot.
+    pub fn extensions(&self) -> &[String] {
+        &self.extensions
+    }
+
+    pub fn note(&self) -> Option<&str> {
+        self.note.as_deref()
+    }
+
+    pub fn unimported_fs_type(&self) -> AssetFileSystemType {
+        self.unimported_fs_type
+    }
+
+    pub fn imported_fs_type(&self) -> AssetFileSystemType {
+        self.imported_fs_type
+    }
+
+    /// The default settings as a TOML table. Doubles as the settings schema, every key that can be set is in here.
+    pub fn default_settings(&self) -> &toml::Value {
+        &self.default_settings
+    }
+
+    /// Whether the importer accepts the path based on its extension.
+    /// Importers without extensions, like those of directories, accept paths without one.
+    pub fn accepts(&self, path: &Path) -> bool {
+        if self.extensions.is_empty() {
+            return path.extension().is_none();
+        }
+
+        path.extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .is_some_and(|extension| self.extensions.contains(&extension))
+    }
+
+    pub fn verify_source(&self, abs_path: &Path) -> Result<(), AssetVerifyError> {
+        (self.verify)(abs_path)
+    }
+
+    /// Imports and packs an asset, see `pack_asset`.
+    /// Settings missing from `settings` are taken from the defaults.
+    pub fn import(
+        &self,
+        assets_dir: &Path,
+        abs_input: &Path,
+        relative_output: &Path,
+        name: String,
+        pack: PackInfo,
+        settings: Option<&toml::Value>,
+    ) -> Result<AssetID, AssetPackError> {
+        let settings = match settings {
+            Some(settings) => merge_settings(&self.default_settings, settings),
+            None => self.default_settings.clone(),
+        };
+
+        (self.import)(assets_dir, abs_input, relative_output, name, pack, &settings)
+    }
+
+    /// Reimports an asset, see `reimport_asset`.
+    pub fn reimport(&self, assets_dir: &Path, asset_id: AssetID) -> Result<(), AssetPackError> {
+        (self.reimport)(assets_dir, asset_id)
+    }
+}
+
+impl std::fmt::Debug for RegisteredImporter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RegisteredImporter")
+            .field("name", &self.name)
+            .field("asset_type", &self.asset_type)
+            .field("extensions", &self.extensions)
+            .finish()
+    }
+}
+
+fn import_erased<A: AssetTrait, T: AssetImporter<A>>(
+    assets_dir: &Path,
+    abs_input: &Path,
+    relative_output: &Path,
+    name: String,
+    pack: PackInfo,
+    settings: &toml::Value,
+) -> Result<AssetID, AssetPackError> {
+    let settings: T::Settings = settings
+        .clone()
+        .try_into()
+        .map_err(AssetPackError::SettingsDeserialize)?;
+
+    pack_asset::<A, T>(assets_dir, abs_input, relative_output, name, pack, &settings)
+}
+
+/// Overlays `overrides` on top of `defaults`, merging tables key by key.
+fn merge_settings(defaults: &toml::Value, overrides: &toml::Value) -> toml::Value {
+    match (defaults, overrides) {
+        (toml::Value::Table(defaults), toml::Value::Table(overrides)) => {
+            let mut merged = defaults.clone();
+
+            for (key, value) in overrides {
+                let value = match defaults.get(key) {
+                    Some(default) => merge_settings(default, value),
+                    None => value.clone(),
+                };
+
+                merged.insert(key.clone(), value);
+            }
+
+            toml::Value::Table(merged)
+        }
+        (_, overrides) => overrides.clone(),
+    }
+}
+
+/// All importers known to a tool or the engine, looked up by name or source extension.
+/// Plugins can register importers for their own asset types.
+#[derive(Debug, Default)]
+pub struct ImporterRegistry {
+    /// In registration order, so tools list them in a stable order.
+    importers: Vec<RegisteredImporter>,
+}
+
+impl ImporterRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers an importer, replacing any importer with the same name.
+    pub fn register<A: AssetTrait, T: AssetImporter<A>>(&mut self) {
+        let importer = RegisteredImporter::new::<A, T>();
+
+        match self.importers.iter_mut().find(|registered| registered.name == importer.name) {
+            Some(registered) => *registered = importer,
+            None => self.importers.push(importer),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&RegisteredImporter> {
+        self.importers.iter().find(|importer| importer.name == name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &RegisteredImporter> {
+        self.importers.iter()
+    }
+
+    /// Importers that accept the path based on its extension.
+    pub fn for_path<'a>(&'a self, path: &'a Path) -> impl Iterator<Item = &'a RegisteredImporter> {
+        self.importers
+            .iter()
+            .filter(move |importer| importer.accepts(path))
+    }
+
+    /// Imports with the importer called `importer`, see `RegisteredImporter::import`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn import(
+        &self,
+        importer: &str,
+        assets_dir: &Path,
+        abs_input: &Path,
+        relative_output: &Path,
+        name: String,
+        pack: PackInfo,
+        settings: Option<&toml::Value>,
+    ) -> Result<AssetID, AssetPackError> {
+        self.get(importer)
+            .ok_or_else(|| AssetPackError::UnknownImporter(importer.to_string()))?
+            .import(assets_dir, abs_input, relative_output, name, pack, settings)
+    }
+
+    /// Reimports an asset with the importer recorded in its source information.
+    pub fn reimport(&self, assets_dir: &Path, asset_id: AssetID) -> Result<(), AssetPackError> {
+        let manifest = Manifest::load(assets_dir)?;
+
+        let importer = manifest
+            .find_asset(asset_id)
+            .ok_or(AssetPackError::AssetNotFound)?
+            .source
+            .as_ref()
+            .ok_or(AssetPackError::NoSourceInfo)?
+            .importer
+            .clone();
+
+        self.get(&importer)
+            .ok_or(AssetPackError::UnknownImporter(importer))?
+            .reimport(assets_dir, asset_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_merge_over_defaults() {
+        let defaults: toml::Value = toml::from_str(
+            r#"
+            flip = false
+            quality = 5
+            [filter]
+            mode = "linear"
+            radius = 2
+            "#,
+        )
+        .unwrap();
+
+        let overrides: toml::Value = toml::from_str(
+            r#"
+            quality = 9
+            [filter]
+            radius = 3
+            "#,
+        )
+        .unwrap();
+
+        let merged = merge_settings(&defaults, &overrides);
+
+        assert_eq!(merged["flip"].as_bool(), Some(false));
+        assert_eq!(merged["quality"].as_integer(), Some(9));
+        assert_eq!(merged["filter"]["mode"].as_str(), Some("linear"));
+        assert_eq!(merged["filter"]["radius"].as_integer(), Some(3));
+    }
+}
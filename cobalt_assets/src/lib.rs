@@ -1,10 +1,24 @@
 pub mod server;
 pub mod asset;
+pub mod cache;
+pub mod importer;
 pub mod tests;
 pub mod manifest;
+pub mod pack;
+pub mod packed;
+pub mod query;
+pub mod sidecar;
+pub mod source;
+pub mod verify;
 pub mod exports {
     pub use super::asset::Asset;
+    pub use super::server::AssetEvent;
+    pub use super::server::AssetEventKind;
+    pub use super::server::UntypedAssetEvent;
     pub use super::server::AssetLoadError;
     pub use super::server::AssetServer;
+    pub use super::source::AssetSource;
     pub use super::asset::AssetTrait;
+    pub use super::asset::AssetDeContext;
+    pub use super::importer::ImporterRegistry;
 }
\ No newline at end of file
@@ -1,7 +1,12 @@
 use hashbrown::HashMap;
 use path_clean::PathClean;
 
-use crate::asset::{AssetImportError, AssetImporter};
+use crate::{
+    asset::{AssetImportError, AssetImporter},
+    packed::PackCodec,
+    query::ManifestIndex,
+    sidecar,
+};
 
 use super::{
     asset::{AssetFileSystemType, AssetID},
@@ -9,15 +14,18 @@ use super::{
 };
 use std::{
     io::{self},
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
-#[derive(serde::Deserialize, serde::Serialize, Clone, Debug)]
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, Default)]
 pub struct PackInfo {
     /// If this is `None`, the asset will not be compressed
-    /// If this is `Some`, the asset will be compressed
-    /// The value is the compression level from 0 to 9
+    /// If this is `Some`, the asset will be compressed with `codec`
+    /// The value is the compression level from 0 to 22, lz4 ignores it
     pub compression: Option<u32>,
+    /// Assets packed before lz4 was supported are zstd compressed.
+    #[serde(default)]
+    pub codec: PackCodec,
 }
 
 impl PackInfo {
@@ -44,6 +52,83 @@ pub struct AssetInfo {
     pub type_name: String,
 
     pub extra: ExtraAssetInfo,
+
+    /// Assets that must be loaded for this asset to be usable, e.g. the textures of a material.
+    /// They are loaded and kept alive along with this asset.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dependencies: Vec<AssetID>,
+
+    /// Where the asset was imported from. `None` for assets that weren't imported,
+    /// e.g. ones created by hand or adopted by `Manifest::repair`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<SourceInfo>,
+
+    /// User labels, e.g. `ui` or `level1`. See `Manifest::query`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+/// Information needed to reimport an asset from its source.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug)]
+pub struct SourceInfo {
+    /// Path to the source file or directory.
+    /// Relative to the assets directory if the source is inside it, otherwise absolute.
+    pub path: PathBuf,
+    /// Hash of the source's contents when it was imported, see `hash_source`.
+    pub hash: String,
+    /// `AssetImporter::name` of the importer used.
+    pub importer: String,
+    /// The importer's settings, serialised.
+    pub settings: toml::Value,
+}
+
+impl SourceInfo {
+    /// The absolute path of the source.
+    pub fn abs_path(&self, assets_dir: &Path) -> PathBuf {
+        // Joining an absolute path replaces the base
+        assets_dir.join(&self.path)
+    }
+}
+
+/// Hashes the contents of a file, or of every file in a directory, to detect changed sources.
+/// Uses 64 bit FNV-1a, the output is prefixed with the algorithm name so it can be changed later.
+pub fn hash_source(path: &Path) -> io::Result<String> {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    fn hash_bytes(hash: &mut u64, bytes: &[u8]) {
+        for byte in bytes {
+            *hash ^= *byte as u64;
+            *hash = hash.wrapping_mul(PRIME);
+        }
+    }
+
+    fn hash_path(hash: &mut u64, root: &Path, path: &Path) -> io::Result<()> {
+        if path.is_dir() {
+            let mut entries = std::fs::read_dir(path)?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<io::Result<Vec<_>>>()?;
+
+            // Directory iteration order isn't stable
+            entries.sort();
+
+            for entry in entries {
+                hash_path(hash, root, &entry)?;
+            }
+        } else {
+            // Include the path so renames count as changes
+            let relative_path = path.strip_prefix(root).unwrap_or(path);
+            hash_bytes(hash, relative_path.to_string_lossy().as_bytes());
+            hash_bytes(hash, &std::fs::read(path)?);
+        }
+
+        Ok(())
+    }
+
+    let mut hash = OFFSET_BASIS;
+    hash_path(&mut hash, path, path)?;
+
+    Ok(format!("fnv1a64:{:016x}", hash))
 }
 
 #[derive(serde::Deserialize, serde::Serialize, Clone, Debug)]
@@ -55,33 +140,200 @@ impl ExtraAssetInfo {
     }
 }
 
-#[derive(serde::Deserialize, serde::Serialize)]
+/// Result of importing an asset.
+pub struct ImportedAsset {
+    pub extra: ExtraAssetInfo,
+    /// See `AssetInfo::dependencies`.
+    pub dependencies: Vec<AssetID>,
+    /// Assets created inside the imported asset's directory, e.g. the meshes of a glTF scene.
+    /// Their paths must be relative to that directory. They are written to a sub-manifest.
+    /// Only allowed for assets stored as directories.
+    pub sub_assets: Vec<AssetInfo>,
+}
+
+impl From<ExtraAssetInfo> for ImportedAsset {
+    fn from(extra: ExtraAssetInfo) -> Self {
+        Self {
+            extra,
+            dependencies: Vec::new(),
+            sub_assets: Vec::new(),
+        }
+    }
+}
+
+/// A manifest stored inside the directory of a composite asset, listing the assets it contains.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug)]
 pub struct SubManifest {
     pub parent_asset: AssetID,
     /// Relative path to the sub-manifest's directory
     pub manifest_dir: PathBuf,
 }
 
+/// Where the asset infos of a manifest are stored.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ManifestLayout {
+    /// Every asset info in `manifest.toml`.
+    #[default]
+    Single,
+    /// Each asset info in a `.meta` file next to the asset, see the `sidecar` module.
+    Sidecar,
+}
+
+impl ManifestLayout {
+    fn is_single(&self) -> bool {
+        *self == Self::Single
+    }
+}
+
+impl std::str::FromStr for ManifestLayout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "single" => Ok(Self::Single),
+            "sidecar" => Ok(Self::Sidecar),
+            _ => Err(format!("Unknown manifest layout \"{}\", expected single or sidecar", s)),
+        }
+    }
+}
+
 #[derive(serde::Deserialize, serde::Serialize, Clone)]
 pub struct Manifest {
+    #[serde(default, skip_serializing_if = "ManifestLayout::is_single")]
+    pub layout: ManifestLayout,
+    /// With the sidecar layout these come from the meta files, `manifest.toml` lists none.
+    #[serde(default)]
     pub assets: Vec<AssetInfo>,
-    // pub sub_manifests: Vec<SubManifest>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sub_manifests: Vec<SubManifest>,
+    /// Assets from all sub-manifests, recursively, with paths relative to this manifest.
+    /// Filled in when loading, never written back.
+    #[serde(skip)]
+    pub sub_assets: Vec<AssetInfo>,
+    #[serde(skip)]
+    pub(crate) index: ManifestIndex,
 }
 
 impl Manifest {
+    /// Loads the manifest of an assets directory, in either layout.
     pub fn load(manifest_parent_dir: &std::path::Path) -> Result<Self, ManifestReadError> {
-        let manifest_path = manifest_parent_dir.join("manifest.toml");
-        let manifest = std::fs::read_to_string(manifest_path)?;
-        let manifest: Manifest = toml::from_str(&manifest)?;
+        let read = |relative_path: &Path| std::fs::read(manifest_parent_dir.join(relative_path));
+        let mut manifest = Self::load_with(&read)?;
+
+        if manifest.layout == ManifestLayout::Sidecar {
+            manifest.add_meta_files(sidecar::load_meta_files(manifest_parent_dir)?, &read)?;
+        }
+
         Ok(manifest)
     }
 
+    /// Loads `manifest.toml` and its sub-manifests using the given function to read files.
+    /// Paths passed to `read` are relative to the manifest's directory.
+    /// Meta files are not read, see `AssetSource::read_manifest` for the sidecar layout.
+    pub fn load_with(
+        read: &dyn Fn(&Path) -> io::Result<Vec<u8>>,
+    ) -> Result<Self, ManifestReadError> {
+        let mut manifest = Self::parse(&read(Path::new("manifest.toml"))?)?;
+        manifest.load_sub_manifests(read)?;
+        Ok(manifest)
+    }
+
+    fn parse(data: &[u8]) -> Result<Self, ManifestReadError> {
+        let manifest = std::str::from_utf8(data)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        Ok(toml::from_str(manifest)?)
+    }
+
+    /// Reads all sub-manifests into `sub_assets` and rebuilds the index.
+    pub fn load_sub_manifests(
+        &mut self,
+        read: &dyn Fn(&Path) -> io::Result<Vec<u8>>,
+    ) -> Result<(), ManifestReadError> {
+        self.sub_assets.clear();
+
+        for sub_manifest in &self.sub_manifests {
+            let dir = sub_manifest.manifest_dir.clone();
+
+            let sub = Self::load_with(&|relative_path: &Path| read(&dir.join(relative_path)))?;
+
+            for mut asset_info in sub.assets.into_iter().chain(sub.sub_assets) {
+                asset_info.relative_path = dir.join(&asset_info.relative_path);
+                self.sub_assets.push(asset_info);
+            }
+        }
+
+        self.rebuild_index();
+
+        Ok(())
+    }
+
+    /// Writes `manifest.toml` to the directory, and the meta files with the sidecar layout.
+    /// Sub-manifests are not written.
+    pub fn save(&self, manifest_parent_dir: &Path) -> Result<(), ManifestWriteError> {
+        if self.layout == ManifestLayout::Sidecar {
+            return self.save_meta_files(manifest_parent_dir);
+        }
+
+        let manifest = toml::to_string(self)?;
+        std::fs::write(manifest_parent_dir.join("manifest.toml"), manifest)?;
+        Ok(())
+    }
+
     pub fn new() -> Self {
         Self {
+            layout: ManifestLayout::Single,
             assets: Vec::new(),
-            // sub_manifests: Vec::new(),
+            sub_manifests: Vec::new(),
+            sub_assets: Vec::new(),
+            index: ManifestIndex::default(),
         }
     }
+
+    /// All assets, including the ones from sub-manifests.
+    pub fn all_assets(&self) -> impl Iterator<Item = &AssetInfo> {
+        self.assets.iter().chain(self.sub_assets.iter())
+    }
+
+    pub fn find_asset(&self, asset_id: AssetID) -> Option<&AssetInfo> {
+        self.all_assets()
+            .find(|asset_info| asset_info.asset_id == asset_id)
+    }
+
+    /// Assets that list `asset_id` as a dependency.
+    pub fn dependents(&self, asset_id: AssetID) -> Vec<AssetID> {
+        self.all_assets()
+            .filter(|asset_info| asset_info.dependencies.contains(&asset_id))
+            .map(|asset_info| asset_info.asset_id)
+            .collect()
+    }
+
+    /// Every asset mapped to the assets it directly depends on.
+    pub fn dependency_graph(&self) -> HashMap<AssetID, Vec<AssetID>> {
+        self.all_assets()
+            .map(|asset_info| (asset_info.asset_id, asset_info.dependencies.clone()))
+            .collect()
+    }
+
+    /// IDs of the assets in the sub-manifest(s) of the given asset.
+    pub fn sub_assets_of(&self, asset_id: AssetID) -> Vec<AssetID> {
+        let dirs = self
+            .sub_manifests
+            .iter()
+            .filter(|sub_manifest| sub_manifest.parent_asset == asset_id)
+            .map(|sub_manifest| sub_manifest.manifest_dir.clean())
+            .collect::<Vec<_>>();
+
+        self.sub_assets
+            .iter()
+            .filter(|asset_info| {
+                let path = asset_info.relative_path.clean();
+                dirs.iter().any(|dir| path.starts_with(dir))
+            })
+            .map(|asset_info| asset_info.asset_id)
+            .collect()
+    }
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -92,6 +344,14 @@ pub enum ManifestReadError {
     Toml(#[from] toml::de::Error),
 }
 
+#[derive(thiserror::Error, Debug)]
+pub enum ManifestWriteError {
+    #[error("Failed to serialise manifest")]
+    Serialize(#[from] toml::ser::Error),
+    #[error("Failed to write manifest file")]
+    Io(#[from] std::io::Error),
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum AssetPackError {
     #[error("Failed to read manifest file")]
@@ -126,6 +386,26 @@ pub enum AssetPackError {
 
     #[error("Directories that will act as assets must be empty")]
     AssetDirectoryNotEmpty,
+
+    #[error("Only assets stored as directories can contain sub-assets")]
+    SubAssetsNotInDirectory,
+    #[error("Dependency not found in manifest: {0:?}")]
+    MissingDependency(AssetID),
+
+    #[error("Asset not found in manifest")]
+    AssetNotFound,
+    #[error("Asset has no source information, it can't be reimported")]
+    NoSourceInfo,
+    #[error("Asset was imported with {found}, not {expected}")]
+    ImporterMismatch { expected: String, found: String },
+    #[error("No importer registered with the name {0}")]
+    UnknownImporter(String),
+    #[error("Failed to serialise importer settings")]
+    SettingsSerialize(toml::ser::Error),
+    #[error("Failed to deserialise importer settings")]
+    SettingsDeserialize(toml::de::Error),
+    #[error("Failed to save manifest")]
+    ManifestSave(ManifestWriteError),
 }
 
 pub fn pack_asset<A: AssetTrait, T: AssetImporter<A>>(
@@ -134,7 +414,8 @@ pub fn pack_asset<A: AssetTrait, T: AssetImporter<A>>(
     relative_output: &std::path::Path,
     name: String,
     packed: PackInfo,
-) -> Result<(), AssetPackError> {
+    settings: &T::Settings,
+) -> Result<AssetID, AssetPackError> {
     let mut manifest = Manifest::load(assets_dir)?;
 
     let mut asset_info = AssetInfo {
@@ -145,6 +426,9 @@ pub fn pack_asset<A: AssetTrait, T: AssetImporter<A>>(
         timestamp: std::time::SystemTime::now(),
         type_name: A::type_name(),
         extra: ExtraAssetInfo::new(),
+        dependencies: Vec::new(),
+        source: None,
+        tags: Vec::new(),
     };
 
     if !relative_output.is_relative() {
@@ -154,6 +438,13 @@ pub fn pack_asset<A: AssetTrait, T: AssetImporter<A>>(
         )));
     }
 
+    if manifest.layout == ManifestLayout::Sidecar && sidecar::is_meta_file(relative_output) {
+        return Err(AssetPackError::InvalidOutputPath(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Output path has the extension of meta files",
+        )));
+    }
+
     let abs_output = assets_dir.join(relative_output.clean());
 
     if manifest.assets.iter().any(|asset| {
@@ -202,17 +493,21 @@ pub fn pack_asset<A: AssetTrait, T: AssetImporter<A>>(
         }
     }
 
-    let extra = T::import(abs_input, &asset_info, assets_dir)
+    asset_info.source = Some(source_info::<A, T>(assets_dir, abs_input, settings)?);
+
+    let imported = T::import(abs_input, &asset_info, assets_dir, settings)
         .map_err(|e| AssetPackError::ImportError(e))?;
 
-    asset_info.extra = extra;
+    apply_import::<A>(&mut manifest, &mut asset_info, imported, &abs_output)?;
 
-    manifest.assets.push(asset_info);
+    let asset_id = asset_info.asset_id;
 
-    let new_manifest = toml::to_string(&manifest)?;
+    manifest.assets.push(asset_info);
+    manifest.rebuild_index();
 
-    std::fs::write(assets_dir.join("manifest.toml"), new_manifest)
-        .map_err(AssetPackError::ManifestWrite)
+    manifest
+        .save(assets_dir)
+        .map_err(AssetPackError::ManifestSave)
         .map_err(|e| {
             // If writing the packed file fails, remove the file
 
@@ -225,6 +520,291 @@ pub fn pack_asset<A: AssetTrait, T: AssetImporter<A>>(
             e
         })?;
 
+    Ok(asset_id)
+}
+
+fn source_info<A: AssetTrait, T: AssetImporter<A>>(
+    assets_dir: &Path,
+    abs_input: &Path,
+    settings: &T::Settings,
+) -> Result<SourceInfo, AssetPackError> {
+    let path = match abs_input.strip_prefix(assets_dir) {
+        Ok(relative_path) => relative_path.to_path_buf(),
+        Err(_) => abs_input.to_path_buf(),
+    };
+
+    Ok(SourceInfo {
+        path,
+        hash: hash_source(abs_input).map_err(AssetPackError::SourceCouldNotOpen)?,
+        importer: T::name(),
+        settings: toml::Value::try_from(settings).map_err(AssetPackError::SettingsSerialize)?,
+    })
+}
+
+/// Copies the results of an import into the asset info, and writes the sub-manifest if there is one.
+fn apply_import<A: AssetTrait>(
+    manifest: &mut Manifest,
+    asset_info: &mut AssetInfo,
+    imported: ImportedAsset,
+    abs_output: &Path,
+) -> Result<(), AssetPackError> {
+    for dependency in &imported.dependencies {
+        let is_sub_asset = imported
+            .sub_assets
+            .iter()
+            .any(|sub_asset| sub_asset.asset_id == *dependency);
+
+        if !is_sub_asset && manifest.find_asset(*dependency).is_none() {
+            return Err(AssetPackError::MissingDependency(*dependency));
+        }
+    }
+
+    asset_info.extra = imported.extra;
+    asset_info.dependencies = imported.dependencies;
+
+    if !imported.sub_assets.is_empty() {
+        if !matches!(A::imported_fs_type(), AssetFileSystemType::Directory) {
+            return Err(AssetPackError::SubAssetsNotInDirectory);
+        }
+
+        let mut sub_manifest = Manifest::new();
+        sub_manifest.assets = imported.sub_assets;
+
+        std::fs::write(
+            abs_output.join("manifest.toml"),
+            toml::to_string(&sub_manifest)?,
+        )
+        .map_err(AssetPackError::ManifestWrite)?;
+
+        manifest.sub_manifests.push(SubManifest {
+            parent_asset: asset_info.asset_id,
+            manifest_dir: asset_info.relative_path.clean(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Where the packed data of an asset is kept while it is reimported.
+fn backup_path(abs_output: &Path) -> PathBuf {
+    let mut backup = abs_output.as_os_str().to_os_string();
+    backup.push(".bak");
+    PathBuf::from(backup)
+}
+
+/// IDs and type names of the sub-assets of the asset's previous import, by path relative to the
+/// asset's directory. Only found while `reimport_asset` runs, importers reuse these IDs for
+/// sub-assets written to the same path so references to them survive the reimport.
+pub fn previous_sub_assets(
+    assets_dir: &Path,
+    asset_info: &AssetInfo,
+) -> HashMap<PathBuf, (AssetID, String)> {
+    let backup = backup_path(&assets_dir.join(asset_info.relative_path.clean()));
+
+    let data = match std::fs::read(backup.join("manifest.toml")) {
+        Ok(data) => data,
+        Err(_) => return HashMap::new(),
+    };
+
+    match Manifest::parse(&data) {
+        Ok(sub_manifest) => sub_manifest
+            .assets
+            .into_iter()
+            .map(|sub_asset| {
+                (
+                    sub_asset.relative_path.clean(),
+                    (sub_asset.asset_id, sub_asset.type_name),
+                )
+            })
+            .collect(),
+        Err(e) => {
+            log::warn!(
+                "Failed to read the previous sub-manifest of \"{}\", its sub-assets get new IDs: {}",
+                asset_info.name,
+                e
+            );
+            HashMap::new()
+        }
+    }
+}
+
+/// Runs the importer the asset was imported with again, keeping its ID.
+/// The source path and settings stored in the manifest are used.
+/// If the import fails, the previous packed data is restored.
+/// Sub-assets written to the same path as before keep their IDs, see `previous_sub_assets`.
+pub fn reimport_asset<A: AssetTrait, T: AssetImporter<A>>(
+    assets_dir: &Path,
+    asset_id: AssetID,
+) -> Result<(), AssetPackError> {
+    let mut manifest = Manifest::load(assets_dir)?;
+
+    let asset_index = manifest
+        .assets
+        .iter()
+        .position(|asset_info| asset_info.asset_id == asset_id)
+        .ok_or(AssetPackError::AssetNotFound)?;
+
+    let mut asset_info = manifest.assets[asset_index].clone();
+
+    let source = asset_info
+        .source
+        .clone()
+        .ok_or(AssetPackError::NoSourceInfo)?;
+
+    if source.importer != T::name() {
+        return Err(AssetPackError::ImporterMismatch {
+            expected: T::name(),
+            found: source.importer,
+        });
+    }
+
+    let settings: T::Settings = source
+        .settings
+        .clone()
+        .try_into()
+        .map_err(AssetPackError::SettingsDeserialize)?;
+
+    let abs_input = source.abs_path(assets_dir);
+    let abs_output = assets_dir.join(asset_info.relative_path.clean());
+
+    // Move the old data out of the way, so it can be restored if the import fails
+    let backup = backup_path(&abs_output);
+
+    if abs_output.exists() {
+        std::fs::rename(&abs_output, &backup).map_err(AssetPackError::WriteFile)?;
+    }
+
+    if matches!(A::imported_fs_type(), AssetFileSystemType::Directory) {
+        std::fs::create_dir_all(&abs_output).map_err(AssetPackError::WriteFile)?;
+    }
+
+    let restore = |e: AssetPackError| {
+        let _ = if abs_output.is_dir() {
+            std::fs::remove_dir_all(&abs_output)
+        } else {
+            std::fs::remove_file(&abs_output)
+        };
+
+        if let Err(e) = std::fs::rename(&backup, &abs_output) {
+            log::error!("Failed to restore {:?} after failed reimport: {}", abs_output, e);
+        }
+
+        e
+    };
+
+    let result = source_info::<A, T>(assets_dir, &abs_input, &settings).and_then(|source| {
+        asset_info.source = Some(source);
+
+        T::import(&abs_input, &asset_info, assets_dir, &settings)
+            .map_err(AssetPackError::ImportError)
+    });
+
+    let imported = result.map_err(restore)?;
+
+    // Old sub-assets are replaced by the new ones
+    manifest
+        .sub_manifests
+        .retain(|sub_manifest| sub_manifest.parent_asset != asset_id);
+
+    asset_info.timestamp = std::time::SystemTime::now();
+    apply_import::<A>(&mut manifest, &mut asset_info, imported, &abs_output).map_err(restore)?;
+
+    manifest.assets[asset_index] = asset_info;
+    manifest.rebuild_index();
+
+    manifest
+        .save(assets_dir)
+        .map_err(|e| restore(AssetPackError::ManifestSave(e)))?;
+
+    let removed = if backup.is_dir() {
+        std::fs::remove_dir_all(&backup)
+    } else {
+        std::fs::remove_file(&backup)
+    };
+
+    if let Err(e) = removed {
+        if backup.exists() {
+            log::warn!("Failed to remove backup {:?}: {}", backup, e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Assets whose source has changed since they were imported.
+/// Assets without source information, or whose source no longer exists, are skipped.
+pub fn stale_assets(assets_dir: &Path) -> Result<Vec<AssetID>, ManifestReadError> {
+    let manifest = Manifest::load(assets_dir)?;
+
+    Ok(manifest
+        .assets
+        .iter()
+        .filter(|asset_info| match &asset_info.source {
+            Some(source) => match hash_source(&source.abs_path(assets_dir)) {
+                Ok(hash) => hash != source.hash,
+                Err(e) => {
+                    log::warn!(
+                        "Source of \"{}\" could not be read: {}",
+                        asset_info.name,
+                        e
+                    );
+                    false
+                }
+            },
+            None => false,
+        })
+        .map(|asset_info| asset_info.asset_id)
+        .collect())
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum AssetTagError {
+    #[error("Failed to read manifest file")]
+    ManifestRead(#[from] ManifestReadError),
+    #[error("Failed to save manifest")]
+    ManifestSave(#[from] ManifestWriteError),
+    #[error("Asset not found in manifest")]
+    AssetNotFound,
+    #[error("Asset is part of another asset, its tags can't be changed")]
+    IsSubAsset,
+}
+
+/// Replaces the tags of an asset. Tags are trimmed, empty and repeated ones are dropped.
+pub fn set_asset_tags(
+    assets_dir: &Path,
+    asset_id: AssetID,
+    tags: Vec<String>,
+) -> Result<(), AssetTagError> {
+    let mut manifest = Manifest::load(assets_dir)?;
+
+    if manifest
+        .sub_assets
+        .iter()
+        .any(|asset_info| asset_info.asset_id == asset_id)
+    {
+        return Err(AssetTagError::IsSubAsset);
+    }
+
+    let asset_info = manifest
+        .assets
+        .iter_mut()
+        .find(|asset_info| asset_info.asset_id == asset_id)
+        .ok_or(AssetTagError::AssetNotFound)?;
+
+    asset_info.tags.clear();
+
+    for tag in tags {
+        let tag = tag.trim();
+
+        if !tag.is_empty() && !asset_info.tags.iter().any(|existing| existing == tag) {
+            asset_info.tags.push(tag.to_string());
+        }
+    }
+
+    manifest.rebuild_index();
+
+    manifest.save(assets_dir)?;
+
     Ok(())
 }
 
@@ -238,12 +818,76 @@ pub enum AssetDeleteError {
     ManifestWrite(std::io::Error),
     #[error("Failed to delete asset")]
     DeleteAsset,
+    #[error("Failed to save manifest")]
+    ManifestSave(#[from] ManifestWriteError),
+    #[error("Other assets depend on this asset: {0:?}")]
+    HasDependents(Vec<AssetID>),
+    #[error("Asset is part of another asset and can't be deleted on its own")]
+    IsSubAsset,
 }
 
+/// Deletes an asset and its packed file or directory.
+/// Refuses to delete assets that other assets depend on, see `force_delete_asset`.
 pub fn delete_asset(asset_dir: &std::path::Path, asset_id: AssetID) -> Result<(), AssetDeleteError> {
+    delete_asset_inner(asset_dir, asset_id, false)
+}
+
+/// Deletes an asset even if other assets depend on it.
+/// The dependents are logged, they will fail to load until their dependencies are fixed.
+pub fn force_delete_asset(
+    asset_dir: &std::path::Path,
+    asset_id: AssetID,
+) -> Result<(), AssetDeleteError> {
+    delete_asset_inner(asset_dir, asset_id, true)
+}
+
+fn delete_asset_inner(
+    asset_dir: &std::path::Path,
+    asset_id: AssetID,
+    force: bool,
+) -> Result<(), AssetDeleteError> {
     // Remove the asset from the manifest
     let mut manifest = Manifest::load(asset_dir)?;
 
+    if manifest
+        .sub_assets
+        .iter()
+        .any(|asset_info| asset_info.asset_id == asset_id)
+    {
+        return Err(AssetDeleteError::IsSubAsset);
+    }
+
+    // Sub-assets are deleted along with the asset, so only outside dependents count
+    let mut removed = manifest.sub_assets_of(asset_id);
+    removed.push(asset_id);
+
+    let mut dependents = Vec::new();
+
+    for dependent in removed
+        .iter()
+        .flat_map(|removed_id| manifest.dependents(*removed_id))
+    {
+        if !removed.contains(&dependent) && !dependents.contains(&dependent) {
+            dependents.push(dependent);
+        }
+    }
+
+    if !dependents.is_empty() {
+        if !force {
+            return Err(AssetDeleteError::HasDependents(dependents));
+        }
+
+        log::warn!(
+            "Deleting asset [{:?}] which other assets depend on: {:?}",
+            asset_id,
+            dependents
+        );
+    }
+
+    manifest
+        .sub_manifests
+        .retain(|sub_manifest| sub_manifest.parent_asset != asset_id);
+
     let asset_index = manifest
         .assets
         .iter()
@@ -251,11 +895,10 @@ pub fn delete_asset(asset_dir: &std::path::Path, asset_id: AssetID) -> Result<()
         .ok_or(AssetDeleteError::DeleteAsset)?;
 
     let asset_info = manifest.assets.remove(asset_index);
+    manifest.rebuild_index();
 
     let abs_path = asset_dir.join(&asset_info.relative_path);
 
-    let new_manifest = toml::to_string(&manifest)?;
-    
     // Remove the asset file or directory
     if abs_path.is_file() {
         std::fs::remove_file(&abs_path).map_err(|_| AssetDeleteError::DeleteAsset)?;
@@ -263,8 +906,7 @@ pub fn delete_asset(asset_dir: &std::path::Path, asset_id: AssetID) -> Result<()
         std::fs::remove_dir_all(&abs_path).map_err(|_| AssetDeleteError::DeleteAsset)?;
     }
 
-    std::fs::write(asset_dir.join("manifest.toml"), new_manifest)
-        .map_err(AssetDeleteError::ManifestWrite)?;
+    manifest.save(asset_dir)?;
 
     Ok(())
 }
@@ -285,9 +927,7 @@ pub fn remove_packed_asset(
     asset_dir: &std::path::Path,
     handle: &str,
 ) -> Result<(), AssetPackRemoveError> {
-    let manifest_path = asset_dir.join("manifest.toml");
-
-    let mut manifest = Manifest::load(&manifest_path)?;
+    let mut manifest = Manifest::load(asset_dir)?;
 
     let asset_index = manifest
         .assets
@@ -296,12 +936,17 @@ pub fn remove_packed_asset(
         .ok_or(AssetPackRemoveError::HandleNotFound)?;
 
     let asset_info = manifest.assets.remove(asset_index);
+    manifest.rebuild_index();
 
-    std::fs::remove_file(&asset_info.relative_path).map_err(AssetPackRemoveError::RemovePacked)?;
+    let abs_path = asset_dir.join(&asset_info.relative_path);
 
-    let manifest = toml::to_string(&manifest)?;
+    if abs_path.is_dir() {
+        std::fs::remove_dir_all(&abs_path).map_err(AssetPackRemoveError::RemovePacked)?;
+    } else {
+        std::fs::remove_file(&abs_path).map_err(AssetPackRemoveError::RemovePacked)?;
+    }
 
-    std::fs::write(manifest_path, manifest).map_err(AssetPackRemoveError::ManifestWrite)?;
+    manifest.save(asset_dir)?;
 
     Ok(())
 }
@@ -314,8 +959,200 @@ pub enum AssetPackRemoveError {
     ManifestSerialize(#[from] toml::ser::Error),
     #[error("Failed to write manifest file")]
     ManifestWrite(std::io::Error),
+    #[error("Failed to save manifest")]
+    ManifestSave(#[from] ManifestWriteError),
     #[error("Handle not found in manifest")]
     HandleNotFound,
     #[error("Failed to remove packed asset")]
     RemovePacked(std::io::Error),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::helpers::{asset_info, TempDir};
+
+    #[test]
+    fn sub_manifests_are_flattened() {
+        let mesh = asset_info("mesh");
+        let scene = asset_info("scene");
+        let material = AssetInfo {
+            dependencies: vec![mesh.asset_id],
+            ..asset_info("material")
+        };
+
+        let mut sub = Manifest::new();
+        sub.assets.push(mesh.clone());
+
+        let mut main = Manifest::new();
+        main.assets.push(scene.clone());
+        main.assets.push(material.clone());
+        main.sub_manifests.push(SubManifest {
+            parent_asset: scene.asset_id,
+            manifest_dir: PathBuf::from("scene"),
+        });
+
+        let mut files = HashMap::new();
+        files.insert(PathBuf::from("manifest.toml"), toml::to_string(&main).unwrap());
+        files.insert(PathBuf::from("scene/manifest.toml"), toml::to_string(&sub).unwrap());
+
+        let manifest = Manifest::load_with(&|relative_path: &Path| {
+            files
+                .get(relative_path)
+                .map(|data| data.clone().into_bytes())
+                .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+        })
+        .unwrap();
+
+        let loaded_mesh = manifest.find_asset(mesh.asset_id).unwrap();
+        assert_eq!(loaded_mesh.relative_path, PathBuf::from("scene/mesh"));
+
+        assert_eq!(manifest.sub_assets_of(scene.asset_id), vec![mesh.asset_id]);
+        assert_eq!(manifest.dependents(mesh.asset_id), vec![material.asset_id]);
+    }
+
+    /// A directory asset with one sub-asset for every line of its source file.
+    struct Parts;
+
+    impl AssetTrait for Parts {
+        fn type_name() -> String {
+            "Parts".to_string()
+        }
+
+        fn imported_fs_type() -> AssetFileSystemType {
+            AssetFileSystemType::Directory
+        }
+
+        fn read(
+            _: &AssetInfo,
+            _: &dyn crate::source::AssetSource,
+            _: &crate::asset::AssetReadContext,
+        ) -> Result<Self, crate::asset::AssetReadError> {
+            Err(crate::asset::AssetReadError::FileNotFound)
+        }
+    }
+
+    struct PartsImporter;
+
+    impl AssetImporter<Parts> for PartsImporter {
+        type Settings = crate::asset::NoSettings;
+
+        fn name() -> String {
+            "PartsImporter".to_string()
+        }
+
+        fn unimported_fs_type() -> AssetFileSystemType {
+            AssetFileSystemType::File
+        }
+
+        fn verify_source(_: &Path) -> Result<(), crate::asset::AssetVerifyError> {
+            Ok(())
+        }
+
+        fn import(
+            abs_input_path: &Path,
+            parent: &AssetInfo,
+            assets_dir: &Path,
+            _: &Self::Settings,
+        ) -> Result<ImportedAsset, AssetImportError> {
+            let previous = previous_sub_assets(assets_dir, parent);
+            let output_dir = assets_dir.join(&parent.relative_path);
+            std::fs::create_dir_all(&output_dir)?;
+            let mut imported = ImportedAsset::from(ExtraAssetInfo::new());
+
+            for part in std::fs::read_to_string(abs_input_path)?.lines() {
+                std::fs::write(output_dir.join(part), part)?;
+
+                let mut sub_asset = asset_info(part);
+                if let Some((asset_id, _)) = previous.get(Path::new(part)) {
+                    sub_asset.asset_id = *asset_id;
+                }
+
+                imported.dependencies.push(sub_asset.asset_id);
+                imported.sub_assets.push(sub_asset);
+            }
+
+            Ok(imported)
+        }
+    }
+
+    fn sub_asset_ids(manifest: &Manifest) -> HashMap<PathBuf, AssetID> {
+        manifest
+            .sub_assets
+            .iter()
+            .map(|asset_info| (asset_info.relative_path.clone(), asset_info.asset_id))
+            .collect()
+    }
+
+    #[test]
+    fn reimport_keeps_ids() {
+        let dir = TempDir::new("reimport");
+        let source = dir.join("parts.txt");
+        std::fs::write(&source, "a\nb").unwrap();
+        Manifest::new().save(&dir).unwrap();
+
+        let asset_id = pack_asset::<Parts, PartsImporter>(
+            &dir,
+            &source,
+            Path::new("parts"),
+            "parts".to_string(),
+            PackInfo::default(),
+            &crate::asset::NoSettings {},
+        )
+        .unwrap();
+
+        let before = sub_asset_ids(&Manifest::load(&dir).unwrap());
+        assert_eq!(before.len(), 2);
+        assert!(stale_assets(&dir).unwrap().is_empty());
+
+        // "a" is gone, "c" is new
+        std::fs::write(&source, "b\nc").unwrap();
+        assert_eq!(stale_assets(&dir).unwrap(), [asset_id]);
+
+        reimport_asset::<Parts, PartsImporter>(&dir, asset_id).unwrap();
+
+        let manifest = Manifest::load(&dir).unwrap();
+        let after = sub_asset_ids(&manifest);
+
+        assert_eq!(manifest.assets.len(), 1);
+        assert_eq!(manifest.assets[0].asset_id, asset_id);
+        assert_eq!(manifest.sub_manifests.len(), 1);
+        assert_eq!(after.len(), 2);
+        assert_eq!(after[Path::new("parts/b")], before[Path::new("parts/b")]);
+        assert!(!before.values().any(|id| *id == after[Path::new("parts/c")]));
+        assert_eq!(manifest.assets[0].dependencies.len(), 2);
+        assert!(!dir.join("parts").join("a").exists());
+        assert!(!dir.join("parts.bak").exists());
+        assert!(stale_assets(&dir).unwrap().is_empty());
+    }
+
+    #[test]
+    fn delete_refuses_dependents() {
+        let dir = TempDir::new("delete");
+        std::fs::write(dir.join("texture"), [0]).unwrap();
+        std::fs::write(dir.join("material"), [0]).unwrap();
+
+        let texture = asset_info("texture");
+        let material = AssetInfo {
+            dependencies: vec![texture.asset_id],
+            ..asset_info("material")
+        };
+
+        let mut manifest = Manifest::new();
+        manifest.assets = vec![texture.clone(), material.clone()];
+        manifest.save(&dir).unwrap();
+
+        assert!(matches!(
+            delete_asset(&dir, texture.asset_id),
+            Err(AssetDeleteError::HasDependents(dependents)) if dependents == [material.asset_id]
+        ));
+        assert!(dir.join("texture").exists());
+        assert_eq!(Manifest::load(&dir).unwrap().assets.len(), 2);
+
+        delete_asset(&dir, material.asset_id).unwrap();
+        delete_asset(&dir, texture.asset_id).unwrap();
+
+        assert!(Manifest::load(&dir).unwrap().assets.is_empty());
+        assert!(!dir.join("texture").exists() && !dir.join("material").exists());
+    }
+}
@@ -0,0 +1,442 @@
+//! Single file `.cpak` asset archives.
+//!
+//! Layout of an archive:
+//! ```text
+//! [magic: "CPAK"] [version: u32] [toc offset: u64] [toc length: u64]
+//! [entry data...]
+//! [table of contents, bincode]
+//! ```
+//! All integers are little endian. The table of contents holds the manifest and the
+//! offset, size and compression of every file, keyed by its path relative to the assets directory.
+
+use std::{
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use hashbrown::HashMap;
+use parking_lot::Mutex;
+use path_clean::PathClean;
+
+use crate::manifest::{Manifest, ManifestLayout, ManifestReadError};
+
+pub const PACK_MAGIC: [u8; 4] = *b"CPAK";
+pub const PACK_VERSION: u32 = 1;
+pub const PACK_EXTENSION: &str = "cpak";
+
+/// Size of the magic, version, toc offset and toc length.
+const HEADER_SIZE: u64 = 4 + 4 + 8 + 8;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PackEntryCompression {
+    None,
+    Zstd,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct PackEntry {
+    /// Offset of the data from the start of the archive.
+    pub offset: u64,
+    /// Size of the data stored in the archive.
+    pub size: u64,
+    /// Size of the data after decompression.
+    pub uncompressed_size: u64,
+    pub compression: PackEntryCompression,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct PackToc {
+    /// The manifest, serialized as TOML exactly as it would be on disk.
+    pub manifest: String,
+    /// Key is the path relative to the assets directory, see `entry_key`.
+    pub entries: HashMap<String, PackEntry>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum PackArchiveError {
+    #[error("File IO error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Failed to read manifest file")]
+    ManifestRead(#[from] ManifestReadError),
+    #[error("Failed to serialise manifest")]
+    ManifestSerialize(#[from] toml::ser::Error),
+    #[error("Failed to parse manifest in archive")]
+    ManifestParse(#[from] toml::de::Error),
+    #[error("Failed to (de)serialise table of contents")]
+    Toc(#[from] bincode::Error),
+    #[error("File is not a .cpak archive")]
+    InvalidMagic,
+    #[error("Unsupported archive version {0}")]
+    UnsupportedVersion(u32),
+    #[error(
+        "Table of contents at {offset} with length {len} is outside the {file_len} byte archive"
+    )]
+    InvalidToc {
+        offset: u64,
+        len: u64,
+        file_len: u64,
+    },
+    #[error("Packed asset file not found on disk: {0:?}")]
+    AssetFileNotFound(PathBuf),
+}
+
+/// Turns a path relative to the assets directory into the key used in the table of contents.
+/// Paths are cleaned and always use `/` as the separator so archives are portable.
+pub fn entry_key(relative_path: &Path) -> String {
+    relative_path
+        .clean()
+        .components()
+        .filter_map(|c| match c {
+            std::path::Component::Normal(s) => Some(s.to_string_lossy().into_owned()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Bundles the manifest and every packed asset file in `assets_dir` into a single archive.
+/// If `compression` is `Some`, entries are compressed with zstd at the given level, unless that
+/// doesn't make them smaller (most packed assets are compressed already).
+/// The archive is written next to `output` first and renamed once complete, so a failed write
+/// never leaves a partial archive behind.
+pub fn write_archive(
+    assets_dir: &Path,
+    output: &Path,
+    compression: Option<u32>,
+) -> Result<PackToc, PackArchiveError> {
+    let mut manifest = Manifest::load(assets_dir)?;
+
+    // Archives hold a single manifest, whatever the layout of the directory
+    manifest.layout = ManifestLayout::Single;
+
+    let mut files = Vec::new();
+
+    for asset_info in &manifest.assets {
+        let abs_path = assets_dir.join(&asset_info.relative_path);
+
+        if abs_path.is_file() {
+            files.push(asset_info.relative_path.clean());
+        } else if abs_path.is_dir() {
+            collect_files(assets_dir, &abs_path, &mut files)?;
+        } else {
+            return Err(PackArchiveError::AssetFileNotFound(abs_path));
+        }
+    }
+
+    let mut temp_name = output.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(".tmp");
+    let temp_output = output.with_file_name(temp_name);
+
+    let written = write_archive_file(assets_dir, &temp_output, manifest, files, compression);
+
+    let toc = written.and_then(|toc| {
+        std::fs::rename(&temp_output, output)?;
+        Ok(toc)
+    });
+
+    if toc.is_err() {
+        let _ = std::fs::remove_file(&temp_output);
+    }
+
+    toc
+}
+
+fn write_archive_file(
+    assets_dir: &Path,
+    output: &Path,
+    manifest: Manifest,
+    files: Vec<PathBuf>,
+    compression: Option<u32>,
+) -> Result<PackToc, PackArchiveError> {
+    let mut writer = io::BufWriter::new(std::fs::File::create(output)?);
+
+    // Placeholder header, the toc location is filled in at the end
+    writer.write_all(&[0; HEADER_SIZE as usize])?;
+
+    let mut entries = HashMap::new();
+    let mut offset = HEADER_SIZE;
+
+    for relative_path in files {
+        let data = std::fs::read(assets_dir.join(&relative_path))?;
+        let uncompressed_size = data.len() as u64;
+
+        let (data, compression) = match compression {
+            Some(level) => {
+                let compressed = zstd::encode_all(data.as_slice(), level as i32)?;
+
+                if compressed.len() < data.len() {
+                    (compressed, PackEntryCompression::Zstd)
+                } else {
+                    (data, PackEntryCompression::None)
+                }
+            }
+            None => (data, PackEntryCompression::None),
+        };
+
+        writer.write_all(&data)?;
+
+        entries.insert(
+            entry_key(&relative_path),
+            PackEntry {
+                offset,
+                size: data.len() as u64,
+                uncompressed_size,
+                compression,
+            },
+        );
+
+        offset += data.len() as u64;
+    }
+
+    let toc = PackToc {
+        manifest: toml::to_string(&manifest)?,
+        entries,
+    };
+
+    let toc_bytes = bincode::serialize(&toc)?;
+    writer.write_all(&toc_bytes)?;
+
+    writer.seek(SeekFrom::Start(0))?;
+    writer.write_all(&PACK_MAGIC)?;
+    writer.write_all(&PACK_VERSION.to_le_bytes())?;
+    writer.write_all(&offset.to_le_bytes())?;
+    writer.write_all(&(toc_bytes.len() as u64).to_le_bytes())?;
+    writer.flush()?;
+
+    Ok(toc)
+}
+
+fn collect_files(
+    assets_dir: &Path,
+    dir: &Path,
+    files: &mut Vec<PathBuf>,
+) -> Result<(), PackArchiveError> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            collect_files(assets_dir, &path, files)?;
+        } else {
+            // Can't fail, `dir` is always inside `assets_dir`
+            files.push(path.strip_prefix(assets_dir).unwrap().to_path_buf());
+        }
+    }
+
+    Ok(())
+}
+
+/// An opened `.cpak` archive. Entries are read on demand.
+pub struct PackReader {
+    path: PathBuf,
+    file: Mutex<io::BufReader<std::fs::File>>,
+    toc: PackToc,
+    manifest: Manifest,
+    modified: Option<SystemTime>,
+}
+
+impl std::fmt::Debug for PackReader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PackReader")
+            .field("path", &self.path)
+            .field("entries", &self.toc.entries.len())
+            .finish()
+    }
+}
+
+impl PackReader {
+    pub fn open(path: &Path) -> Result<Self, PackArchiveError> {
+        let file = std::fs::File::open(path)?;
+        let metadata = file.metadata()?;
+        let modified = metadata.modified().ok();
+        let file_len = metadata.len();
+        let mut reader = io::BufReader::new(file);
+
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+
+        if magic != PACK_MAGIC {
+            return Err(PackArchiveError::InvalidMagic);
+        }
+
+        let version = read_u32(&mut reader)?;
+
+        if version != PACK_VERSION {
+            return Err(PackArchiveError::UnsupportedVersion(version));
+        }
+
+        let toc_offset = read_u64(&mut reader)?;
+        let toc_len = read_u64(&mut reader)?;
+
+        // Checked before allocating, a corrupted header could ask for any length
+        if toc_offset < HEADER_SIZE
+            || toc_offset
+                .checked_add(toc_len)
+                .is_none_or(|toc_end| toc_end > file_len)
+        {
+            return Err(PackArchiveError::InvalidToc {
+                offset: toc_offset,
+                len: toc_len,
+                file_len,
+            });
+        }
+
+        reader.seek(SeekFrom::Start(toc_offset))?;
+
+        let mut toc_bytes = vec![0; toc_len as usize];
+        reader.read_exact(&mut toc_bytes)?;
+
+        let toc: PackToc = bincode::deserialize(&toc_bytes)?;
+        let mut manifest: Manifest = toml::from_str(&toc.manifest)?;
+
+        let mut pack = Self {
+            path: path.to_path_buf(),
+            file: Mutex::new(reader),
+            toc,
+            manifest: Manifest::new(),
+            modified,
+        };
+
+        // Sub-manifests are stored as regular entries
+        manifest.load_sub_manifests(&|relative_path: &Path| pack.read(relative_path))?;
+        pack.manifest = manifest;
+
+        Ok(pack)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn manifest(&self) -> &Manifest {
+        &self.manifest
+    }
+
+    pub fn toc(&self) -> &PackToc {
+        &self.toc
+    }
+
+    /// Modification time of the archive file when it was opened.
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.modified
+    }
+
+    pub fn contains(&self, relative_path: &Path) -> bool {
+        self.toc.entries.contains_key(&entry_key(relative_path))
+    }
+
+    /// Reads and decompresses the file at a path relative to the assets directory.
+    pub fn read(&self, relative_path: &Path) -> io::Result<Vec<u8>> {
+        let entry = self
+            .toc
+            .entries
+            .get(&entry_key(relative_path))
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("{:?} not found in archive {:?}", relative_path, self.path),
+                )
+            })?;
+
+        let mut data = vec![0; entry.size as usize];
+
+        {
+            let mut file = self.file.lock();
+            file.seek(SeekFrom::Start(entry.offset))?;
+            file.read_exact(&mut data)?;
+        }
+
+        match entry.compression {
+            PackEntryCompression::None => Ok(data),
+            PackEntryCompression::Zstd => {
+                let mut decompressed = Vec::with_capacity(entry.uncompressed_size as usize);
+                zstd::Decoder::new(data.as_slice())?.read_to_end(&mut decompressed)?;
+                Ok(decompressed)
+            }
+        }
+    }
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::helpers::{asset_info, TempDir};
+
+    /// An assets directory with a file asset and a directory asset, archived to `assets.cpak`.
+    fn write_test_archive(dir: &Path) -> (PathBuf, Vec<u8>) {
+        let assets_dir = dir.join("assets");
+        std::fs::create_dir_all(assets_dir.join("model")).unwrap();
+
+        let compressible = b"cobalt ".repeat(512);
+        std::fs::write(assets_dir.join("brick.asset"), &compressible).unwrap();
+        std::fs::write(assets_dir.join("model/mesh.asset"), [1, 2, 3]).unwrap();
+
+        let mut manifest = Manifest::new();
+        manifest.assets.push(asset_info("brick.asset"));
+        manifest.assets.push(asset_info("model"));
+        manifest.save(&assets_dir).unwrap();
+
+        let output = dir.join("assets.cpak");
+        write_archive(&assets_dir, &output, Some(3)).unwrap();
+
+        (output, compressible)
+    }
+
+    #[test]
+    fn archive_roundtrip() {
+        let dir = TempDir::new("roundtrip");
+        let (output, compressible) = write_test_archive(&dir);
+
+        assert!(!dir.join("assets.cpak.tmp").exists());
+
+        let pack = PackReader::open(&output).unwrap();
+
+        assert_eq!(pack.manifest().assets.len(), 2);
+        assert_eq!(
+            pack.toc().entries["brick.asset"].compression,
+            PackEntryCompression::Zstd
+        );
+        assert_eq!(pack.read(Path::new("brick.asset")).unwrap(), compressible);
+        assert_eq!(
+            pack.read(Path::new("./model/mesh.asset")).unwrap(),
+            [1, 2, 3]
+        );
+        assert!(pack.read(Path::new("missing.asset")).is_err());
+    }
+
+    #[test]
+    fn corrupted_headers_are_rejected() {
+        let dir = TempDir::new("corrupted");
+        let (output, _) = write_test_archive(&dir);
+        let archive = std::fs::read(&output).unwrap();
+
+        // Table of contents length far past the end of the file
+        let mut corrupted = archive.clone();
+        corrupted[16..24].copy_from_slice(&u64::MAX.to_le_bytes());
+        std::fs::write(&output, &corrupted).unwrap();
+        assert!(matches!(
+            PackReader::open(&output),
+            Err(PackArchiveError::InvalidToc { .. })
+        ));
+
+        let mut corrupted = archive;
+        corrupted[..4].copy_from_slice(b"NOPE");
+        std::fs::write(&output, &corrupted).unwrap();
+        assert!(matches!(
+            PackReader::open(&output),
+            Err(PackArchiveError::InvalidMagic)
+        ));
+    }
+}
@@ -0,0 +1,482 @@
+//! Container every packed asset file is written in, whatever the asset type.
+//!
+//! Layout of a packed asset file:
+//! ```text
+//! [magic: "CBAF"] [version: u32] [codec: u8] [reserved: 3 bytes] [uncompressed size: u64]
+//! [format version: u32] [type name length: u16] [type name: UTF-8]
+//! [payload, compressed with the codec]
+//! ```
+//! All integers are little endian. The payload is streamed through the decoder on read.
+//! The type name and format version are those of the `AssetTrait` that wrote the payload,
+//! older format versions are upgraded with `AssetTrait::migrate` on read.
+//! Version 1 containers have no format version or type name.
+//! Files written before the container existed have no header at all, see `read_packed_file`.
+
+use std::{
+    io::{self, Read, Write},
+    path::Path,
+};
+
+use crate::{
+    asset::{AssetID, AssetReadError, AssetTrait},
+    manifest::{AssetInfo, PackInfo},
+    source::{AssetSource, DirectorySource},
+};
+
+pub const PACKED_MAGIC: [u8; 4] = *b"CBAF";
+pub const PACKED_VERSION: u32 = 2;
+
+/// Size of the magic, version, codec, reserved bytes and uncompressed size.
+const HEADER_SIZE: usize = 4 + 4 + 1 + 3 + 8;
+/// Magic number of zstd frames, legacy compressed files start with it.
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+
+const CODEC_NONE: u8 = 0;
+const CODEC_ZSTD: u8 = 1;
+const CODEC_LZ4: u8 = 2;
+
+/// Compression algorithm of packed assets, used when `PackInfo::compression` is set.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PackCodec {
+    /// Smallest files, the compression level applies.
+    #[default]
+    Zstd,
+    /// Faster to decompress than zstd but larger, the compression level is ignored.
+    Lz4,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum PackedAssetError {
+    #[error("File IO error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Unsupported packed asset version {0}")]
+    UnsupportedVersion(u32),
+    #[error("Unknown packed asset codec {0}")]
+    UnknownCodec(u8),
+    #[error("Packed asset has {0} bytes, the header says {1}")]
+    SizeMismatch(usize, u64),
+    #[error("Packed asset is a {0}, expected a {1}")]
+    TypeMismatch(String, String),
+}
+
+/// The contents of a packed asset file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackedFile {
+    /// `None` for files packed before the header held the asset type.
+    pub type_name: Option<String>,
+    /// 0 for files packed before the header held a format version.
+    pub format_version: u32,
+    pub payload: Vec<u8>,
+}
+
+impl PackedFile {
+    /// Whether the file was written by `A` in its current format.
+    pub fn is_current<A: AssetTrait>(&self) -> bool {
+        self.type_name.as_deref() == Some(A::type_name().as_str())
+            && self.format_version == A::format_version()
+    }
+
+    /// Checks the file holds an `A` and returns its payload in the current format version,
+    /// migrating it if it is older.
+    pub fn into_current<A: AssetTrait>(
+        self,
+        asset_info: &AssetInfo,
+    ) -> Result<Vec<u8>, AssetReadError> {
+        if let Some(type_name) = self.type_name {
+            if type_name != A::type_name() {
+                return Err(PackedAssetError::TypeMismatch(type_name, A::type_name()).into());
+            }
+        }
+
+        let current = A::format_version();
+
+        if self.format_version == current {
+            Ok(self.payload)
+        } else if self.format_version < current {
+            A::migrate(self.format_version, self.payload, asset_info)
+        } else {
+            Err(AssetReadError::UnsupportedVersion(
+                A::type_name(),
+                self.format_version,
+            ))
+        }
+    }
+}
+
+/// Compresses `payload` as `pack` says and prepends the header.
+pub fn encode_packed(
+    pack: &PackInfo,
+    type_name: &str,
+    format_version: u32,
+    payload: &[u8],
+) -> io::Result<Vec<u8>> {
+    let (codec, compressed) = match (pack.compression, pack.codec) {
+        (None, _) => (CODEC_NONE, None),
+        (Some(level), PackCodec::Zstd) => {
+            (CODEC_ZSTD, Some(zstd::encode_all(payload, level as i32)?))
+        }
+        (Some(_), PackCodec::Lz4) => {
+            let mut encoder = lz4_flex::frame::FrameEncoder::new(Vec::new());
+            encoder.write_all(payload)?;
+
+            (CODEC_LZ4, Some(encoder.finish().map_err(io::Error::other)?))
+        }
+    };
+
+    let body = compressed.as_deref().unwrap_or(payload);
+    let type_name_length = u16::try_from(type_name.len())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    let mut data = Vec::with_capacity(HEADER_SIZE + 6 + type_name.len() + body.len());
+    data.extend_from_slice(&PACKED_MAGIC);
+    data.extend_from_slice(&PACKED_VERSION.to_le_bytes());
+    data.extend_from_slice(&[codec, 0, 0, 0]);
+    data.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    data.extend_from_slice(&format_version.to_le_bytes());
+    data.extend_from_slice(&type_name_length.to_le_bytes());
+    data.extend_from_slice(type_name.as_bytes());
+    data.extend_from_slice(body);
+
+    Ok(data)
+}
+
+/// Writes `payload` to `abs_path` in the packed asset container.
+pub fn write_packed(
+    abs_path: &Path,
+    pack: &PackInfo,
+    type_name: &str,
+    format_version: u32,
+    payload: &[u8],
+) -> io::Result<()> {
+    std::fs::write(
+        abs_path,
+        encode_packed(pack, type_name, format_version, payload)?,
+    )
+}
+
+/// Writes the payload of an `A` in its current format version.
+pub fn write_packed_asset<A: AssetTrait>(
+    abs_path: &Path,
+    pack: &PackInfo,
+    payload: &[u8],
+) -> io::Result<()> {
+    write_packed(
+        abs_path,
+        pack,
+        &A::type_name(),
+        A::format_version(),
+        payload,
+    )
+}
+
+/// Whether `data` starts with the packed asset header.
+pub fn is_packed(data: &[u8]) -> bool {
+    data.starts_with(&PACKED_MAGIC)
+}
+
+/// Reads the header and streams the payload through its decoder.
+pub fn decode_packed(mut reader: impl Read) -> Result<PackedFile, PackedAssetError> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+
+    if magic != PACKED_MAGIC {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Not a packed asset").into());
+    }
+
+    decode_after_magic(reader)
+}
+
+fn decode_after_magic(mut reader: impl Read) -> Result<PackedFile, PackedAssetError> {
+    let mut header = [0u8; HEADER_SIZE - 4];
+    reader.read_exact(&mut header)?;
+
+    let version = u32::from_le_bytes(header[0..4].try_into().unwrap());
+    let codec = header[4];
+    let uncompressed_size = u64::from_le_bytes(header[8..16].try_into().unwrap());
+
+    let (type_name, format_version) = match version {
+        1 => (None, 0),
+        PACKED_VERSION => {
+            let mut versioning = [0u8; 6];
+            reader.read_exact(&mut versioning)?;
+
+            let format_version = u32::from_le_bytes(versioning[0..4].try_into().unwrap());
+            let mut type_name =
+                vec![0; u16::from_le_bytes([versioning[4], versioning[5]]) as usize];
+            reader.read_exact(&mut type_name)?;
+
+            let type_name = String::from_utf8(type_name)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+            (Some(type_name), format_version)
+        }
+        version => return Err(PackedAssetError::UnsupportedVersion(version)),
+    };
+
+    // Don't trust the header with huge allocations, the vector grows if needed
+    let mut payload = Vec::with_capacity(uncompressed_size.min(1 << 28) as usize);
+
+    match codec {
+        CODEC_NONE => reader.read_to_end(&mut payload)?,
+        CODEC_ZSTD => zstd::Decoder::new(reader)?.read_to_end(&mut payload)?,
+        CODEC_LZ4 => lz4_flex::frame::FrameDecoder::new(reader).read_to_end(&mut payload)?,
+        codec => return Err(PackedAssetError::UnknownCodec(codec)),
+    };
+
+    if payload.len() as u64 != uncompressed_size {
+        return Err(PackedAssetError::SizeMismatch(
+            payload.len(),
+            uncompressed_size,
+        ));
+    }
+
+    Ok(PackedFile {
+        type_name,
+        format_version,
+        payload,
+    })
+}
+
+/// Reads the packed asset file at `relative_path`.
+/// Files without the header were written before the container existed. They are read as
+/// format version 0, decompressed if they are a zstd stream and returned as they are otherwise.
+pub fn read_packed_file(
+    source: &dyn AssetSource,
+    relative_path: &Path,
+) -> Result<PackedFile, PackedAssetError> {
+    let mut reader = source.open(relative_path)?;
+
+    // Files shorter than the magic can only be legacy ones
+    let mut prefix = Vec::with_capacity(PACKED_MAGIC.len());
+    (&mut reader)
+        .take(PACKED_MAGIC.len() as u64)
+        .read_to_end(&mut prefix)?;
+
+    if prefix == PACKED_MAGIC {
+        return decode_after_magic(reader);
+    }
+
+    let is_zstd = prefix == ZSTD_MAGIC;
+    let mut legacy = io::Cursor::new(prefix).chain(reader);
+    let mut payload = Vec::new();
+
+    if is_zstd {
+        zstd::Decoder::new(legacy)?.read_to_end(&mut payload)?;
+    } else {
+        legacy.read_to_end(&mut payload)?;
+    }
+
+    Ok(PackedFile {
+        type_name: None,
+        format_version: 0,
+        payload,
+    })
+}
+
+/// Reads the payload of an `A` from the packed file at `relative_path`, in the current format version.
+pub fn read_packed_asset<A: AssetTrait>(
+    source: &dyn AssetSource,
+    relative_path: &Path,
+    asset_info: &AssetInfo,
+) -> Result<Vec<u8>, AssetReadError> {
+    read_packed_file(source, relative_path)?.into_current::<A>(asset_info)
+}
+
+/// Outcome of `AssetServer::upgrade_assets`.
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct UpgradeReport {
+    /// Assets with at least one file rewritten in the current format version.
+    pub upgraded: Vec<AssetID>,
+    /// Assets already in the current format version.
+    pub current: Vec<AssetID>,
+    /// Assets that couldn't be read or migrated, with the error. Their files are left as they were.
+    pub failed: Vec<(AssetID, String)>,
+    /// Assets of types that aren't registered on the server.
+    pub skipped: Vec<AssetID>,
+}
+
+/// Rewrites the packed files of an asset in the assets directory that are in an older format version.
+/// Returns whether any file was rewritten.
+pub fn upgrade_packed_asset<A: AssetTrait>(
+    assets_dir: &Path,
+    asset_info: &AssetInfo,
+) -> Result<bool, AssetReadError> {
+    let source = DirectorySource::new(assets_dir);
+    let mut upgraded = false;
+
+    for relative_path in A::packed_files(asset_info) {
+        let file = read_packed_file(&source, &relative_path)?;
+
+        if file.is_current::<A>() {
+            continue;
+        }
+
+        let payload = file.into_current::<A>(asset_info)?;
+
+        // Written next to the file and moved over it, so a failed write leaves the old version
+        let abs_path = assets_dir.join(&relative_path);
+        let mut temp_name = abs_path.file_name().unwrap_or_default().to_os_string();
+        temp_name.push(".tmp");
+        let temp_path = abs_path.with_file_name(temp_name);
+
+        let written = write_packed_asset::<A>(&temp_path, &asset_info.pack, &payload)
+            .and_then(|()| std::fs::rename(&temp_path, &abs_path));
+
+        if written.is_err() {
+            let _ = std::fs::remove_file(&temp_path);
+        }
+
+        written?;
+        upgraded = true;
+    }
+
+    Ok(upgraded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        asset::{AssetFileSystemType, AssetReadContext},
+        source::MemorySource,
+        tests::helpers,
+    };
+
+    /// Format 2 doubles every byte of format 1.
+    struct Doubled;
+
+    impl AssetTrait for Doubled {
+        fn type_name() -> String {
+            "Doubled".to_string()
+        }
+
+        fn imported_fs_type() -> AssetFileSystemType {
+            AssetFileSystemType::File
+        }
+
+        fn read(
+            asset_info: &AssetInfo,
+            source: &dyn AssetSource,
+            _: &AssetReadContext,
+        ) -> Result<Self, AssetReadError> {
+            read_packed_asset::<Self>(source, &asset_info.relative_path, asset_info)?;
+
+            Ok(Doubled)
+        }
+
+        fn format_version() -> u32 {
+            2
+        }
+
+        fn migrate(
+            version: u32,
+            payload: Vec<u8>,
+            _: &AssetInfo,
+        ) -> Result<Vec<u8>, AssetReadError> {
+            match version {
+                0 | 1 => Ok(payload.iter().map(|byte| byte * 2).collect()),
+                _ => Err(AssetReadError::UnsupportedVersion(
+                    Self::type_name(),
+                    version,
+                )),
+            }
+        }
+    }
+
+    fn asset_info() -> AssetInfo {
+        AssetInfo {
+            type_name: Doubled::type_name(),
+            ..helpers::asset_info("doubled")
+        }
+    }
+
+    fn pack(compression: Option<u32>, codec: PackCodec) -> PackInfo {
+        PackInfo { compression, codec }
+    }
+
+    #[test]
+    fn roundtrip_every_codec() {
+        let payload = (0..4096).map(|i| (i % 7) as u8).collect::<Vec<_>>();
+
+        for pack in [
+            pack(None, PackCodec::Zstd),
+            pack(Some(3), PackCodec::Zstd),
+            pack(Some(0), PackCodec::Lz4),
+        ] {
+            let data = encode_packed(&pack, "Mesh", 3, &payload).unwrap();
+
+            assert!(is_packed(&data));
+            assert_eq!(
+                decode_packed(data.as_slice()).unwrap(),
+                PackedFile {
+                    type_name: Some("Mesh".to_string()),
+                    format_version: 3,
+                    payload: payload.clone(),
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn reads_legacy_files() {
+        let payload = b"legacy payload".to_vec();
+
+        let source = MemorySource::new("test");
+        source.insert("raw", payload.clone());
+        source.insert("zstd", zstd::encode_all(payload.as_slice(), 3).unwrap());
+
+        // A version 1 container, without a format version or type name
+        let mut v1 = encode_packed(&pack(Some(3), PackCodec::Lz4), "", 0, &payload).unwrap();
+        v1[4..8].copy_from_slice(&1u32.to_le_bytes());
+        v1.drain(HEADER_SIZE..HEADER_SIZE + 6);
+        source.insert("v1", v1);
+
+        for path in ["raw", "zstd", "v1"] {
+            let file = read_packed_file(&source, Path::new(path)).unwrap();
+
+            assert_eq!(file.type_name, None);
+            assert_eq!(file.format_version, 0);
+            assert_eq!(file.payload, payload);
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_payloads() {
+        let mut data = encode_packed(&pack(None, PackCodec::Zstd), "Mesh", 1, b"payload").unwrap();
+        data.pop();
+
+        assert!(matches!(
+            decode_packed(data.as_slice()),
+            Err(PackedAssetError::SizeMismatch(6, 7))
+        ));
+    }
+
+    #[test]
+    fn migrates_older_versions() {
+        let info = asset_info();
+        let file = |type_name: Option<&str>, format_version: u32| PackedFile {
+            type_name: type_name.map(str::to_string),
+            format_version,
+            payload: vec![1, 2, 3],
+        };
+
+        let current = file(Some("Doubled"), 2);
+        assert!(current.is_current::<Doubled>());
+        assert_eq!(current.into_current::<Doubled>(&info).unwrap(), [1, 2, 3]);
+
+        for older in [file(Some("Doubled"), 1), file(None, 0)] {
+            assert!(!older.is_current::<Doubled>());
+            assert_eq!(older.into_current::<Doubled>(&info).unwrap(), [2, 4, 6]);
+        }
+
+        assert!(matches!(
+            file(Some("Doubled"), 3).into_current::<Doubled>(&info),
+            Err(AssetReadError::UnsupportedVersion(_, 3))
+        ));
+        assert!(matches!(
+            file(Some("Mesh"), 2).into_current::<Doubled>(&info),
+            Err(AssetReadError::Packed(PackedAssetError::TypeMismatch(..)))
+        ));
+    }
+}
@@ -0,0 +1,348 @@
+//! Indexed lookups of the assets in a manifest, by ID, name, type, tag and path.
+
+use std::{
+    borrow::Cow,
+    path::{Path, PathBuf},
+};
+
+use hashbrown::HashMap;
+use path_clean::PathClean;
+
+use crate::{
+    asset::{AssetID, AssetTrait},
+    manifest::{AssetInfo, Manifest},
+};
+
+/// Positions of the assets of a manifest, in the order of `Manifest::all_assets`.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ManifestIndex {
+    /// Number of assets indexed, used to detect an outdated index.
+    len: usize,
+    ids: HashMap<AssetID, usize>,
+    names: HashMap<String, Vec<usize>>,
+    types: HashMap<String, Vec<usize>>,
+    tags: HashMap<String, Vec<usize>>,
+    /// Sorted by path. Paths are compared by component, so every path under a prefix is in one run.
+    paths: Vec<(PathBuf, usize)>,
+}
+
+impl ManifestIndex {
+    pub fn new(manifest: &Manifest) -> Self {
+        let mut index = Self::default();
+
+        for (position, asset_info) in manifest.all_assets().enumerate() {
+            index.ids.insert(asset_info.asset_id, position);
+            index
+                .names
+                .entry(asset_info.name.clone())
+                .or_default()
+                .push(position);
+            index
+                .types
+                .entry(asset_info.type_name.clone())
+                .or_default()
+                .push(position);
+
+            for tag in &asset_info.tags {
+                index.tags.entry(tag.clone()).or_default().push(position);
+            }
+
+            index
+                .paths
+                .push((asset_info.relative_path.clean(), position));
+            index.len += 1;
+        }
+
+        index.paths.sort();
+        index
+    }
+
+    fn positions_under(&self, prefix: &Path) -> Vec<usize> {
+        let prefix = prefix.clean();
+
+        if is_root(&prefix) {
+            return (0..self.len).collect();
+        }
+
+        let start = self
+            .paths
+            .partition_point(|(path, _)| path.as_path() < prefix.as_path());
+
+        self.paths[start..]
+            .iter()
+            .take_while(|(path, _)| path.starts_with(&prefix))
+            .map(|(_, position)| *position)
+            .collect()
+    }
+}
+
+/// Cleaning an empty path gives `.`, both mean the whole assets directory.
+fn is_root(path: &Path) -> bool {
+    path == Path::new(".")
+}
+
+/// Filters for `Manifest::query`. Every filter that is set must match.
+///
+/// ```ignore
+/// let ui_textures = manifest.query(
+///     &AssetQuery::new()
+///         .of_type::<TextureAsset<{ TextureType::RGBA8UnormSrgb }>>()
+///         .tag("ui"),
+/// );
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct AssetQuery {
+    pub name: Option<String>,
+    pub type_name: Option<String>,
+    /// The asset must have all of these tags.
+    pub tags: Vec<String>,
+    /// Only assets at or under this path, relative to the assets directory.
+    pub path_prefix: Option<PathBuf>,
+    /// Case insensitive text to find in the name, type, path or tags.
+    pub search: Option<String>,
+}
+
+impl AssetQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn named(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn of_type<T: AssetTrait>(self) -> Self {
+        self.type_name(T::type_name())
+    }
+
+    pub fn type_name(mut self, type_name: impl Into<String>) -> Self {
+        self.type_name = Some(type_name.into());
+        self
+    }
+
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tags.push(tag.into());
+        self
+    }
+
+    pub fn under(mut self, path_prefix: impl Into<PathBuf>) -> Self {
+        self.path_prefix = Some(path_prefix.into());
+        self
+    }
+
+    pub fn search(mut self, text: impl Into<String>) -> Self {
+        self.search = Some(text.into());
+        self
+    }
+
+    pub fn matches(&self, asset_info: &AssetInfo) -> bool {
+        self.name
+            .as_ref()
+            .is_none_or(|name| asset_info.name == *name)
+            && self
+                .type_name
+                .as_ref()
+                .is_none_or(|type_name| asset_info.type_name == *type_name)
+            && self.tags.iter().all(|tag| asset_info.tags.contains(tag))
+            && self.path_prefix.as_ref().is_none_or(|prefix| {
+                let prefix = prefix.clean();
+                is_root(&prefix) || asset_info.relative_path.clean().starts_with(prefix)
+            })
+            && self.search.as_ref().is_none_or(|text| {
+                let text = text.to_lowercase();
+
+                asset_info.name.to_lowercase().contains(&text)
+                    || asset_info.type_name.to_lowercase().contains(&text)
+                    || asset_info
+                        .relative_path
+                        .to_string_lossy()
+                        .to_lowercase()
+                        .contains(&text)
+                    || asset_info
+                        .tags
+                        .iter()
+                        .any(|tag| tag.to_lowercase().contains(&text))
+            })
+    }
+}
+
+impl Manifest {
+    /// Rebuilds the index used by the lookups below.
+    /// Loading and every function of this crate that changes a manifest do this already.
+    /// Call it after changing `assets` or `sub_assets` by hand. Lookups check what the index finds,
+    /// but miss assets edited in place or replaced by another until it is rebuilt.
+    pub fn rebuild_index(&mut self) {
+        self.index = ManifestIndex::new(self);
+    }
+
+    /// The index, or a temporary one if the number of assets changed since it was built.
+    fn index(&self) -> Cow<'_, ManifestIndex> {
+        if self.index.len == self.assets.len() + self.sub_assets.len() {
+            Cow::Borrowed(&self.index)
+        } else {
+            Cow::Owned(ManifestIndex::new(self))
+        }
+    }
+
+    fn asset_at(&self, position: usize) -> Option<&AssetInfo> {
+        match position.checked_sub(self.assets.len()) {
+            None => self.assets.get(position),
+            Some(sub_position) => self.sub_assets.get(sub_position),
+        }
+    }
+
+    /// Same as `find_asset`, without a scan.
+    pub fn asset(&self, asset_id: AssetID) -> Option<&AssetInfo> {
+        match self.asset_at(*self.index().ids.get(&asset_id)?) {
+            Some(asset_info) if asset_info.asset_id == asset_id => Some(asset_info),
+            // Another asset took its place since the index was built
+            _ => self.asset_at(*ManifestIndex::new(self).ids.get(&asset_id)?),
+        }
+    }
+
+    pub fn assets_named(&self, name: &str) -> Vec<&AssetInfo> {
+        self.query(&AssetQuery::new().named(name))
+    }
+
+    pub fn assets_of_type(&self, type_name: &str) -> Vec<&AssetInfo> {
+        self.query(&AssetQuery::new().type_name(type_name))
+    }
+
+    pub fn assets_tagged(&self, tag: &str) -> Vec<&AssetInfo> {
+        self.query(&AssetQuery::new().tag(tag))
+    }
+
+    /// Assets at or under the path, relative to the assets directory.
+    pub fn assets_under(&self, path_prefix: &Path) -> Vec<&AssetInfo> {
+        self.query(&AssetQuery::new().under(path_prefix))
+    }
+
+    /// Assets matching every filter of the query, in manifest order.
+    /// The most selective indexed filter picks the candidates, every filter is checked on each of
+    /// them, so assets edited in place since the index was built are never returned by mistake.
+    pub fn query(&self, query: &AssetQuery) -> Vec<&AssetInfo> {
+        let index = self.index();
+
+        fn positions_of(positions: Option<&Vec<usize>>) -> Cow<'_, [usize]> {
+            Cow::Borrowed(positions.map_or(&[][..], Vec::as_slice))
+        }
+
+        let mut candidates: Vec<Cow<'_, [usize]>> = Vec::new();
+
+        if let Some(name) = &query.name {
+            candidates.push(positions_of(index.names.get(name)));
+        }
+        if let Some(type_name) = &query.type_name {
+            candidates.push(positions_of(index.types.get(type_name)));
+        }
+        for tag in &query.tags {
+            candidates.push(positions_of(index.tags.get(tag)));
+        }
+        if let Some(path_prefix) = &query.path_prefix {
+            candidates.push(Cow::Owned(index.positions_under(path_prefix)));
+        }
+
+        let mut positions = match candidates
+            .into_iter()
+            .min_by_key(|positions| positions.len())
+        {
+            Some(positions) => positions.into_owned(),
+            None => (0..index.len).collect(),
+        };
+        positions.sort_unstable();
+
+        positions
+            .into_iter()
+            .filter_map(|position| self.asset_at(position))
+            .filter(|asset_info| query.matches(asset_info))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::helpers;
+
+    fn asset_info(path: &str, type_name: &str, tags: &[&str]) -> AssetInfo {
+        AssetInfo {
+            name: Path::new(path)
+                .file_stem()
+                .unwrap()
+                .to_string_lossy()
+                .into_owned(),
+            type_name: type_name.to_string(),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            ..helpers::asset_info(path)
+        }
+    }
+
+    fn manifest() -> Manifest {
+        let mut manifest = Manifest::new();
+        manifest.assets = vec![
+            asset_info("ui/button.asset", "Texture<RGBA8UnormSrgb>", &["ui"]),
+            asset_info("ui/font.asset", "Texture<R8Unorm>", &["ui"]),
+            asset_info("world/grass.asset", "Texture<RGBA8UnormSrgb>", &["terrain"]),
+            asset_info("world/tree.asset", "Mesh", &["terrain", "foliage"]),
+        ];
+        manifest.rebuild_index();
+        manifest
+    }
+
+    fn names(assets: Vec<&AssetInfo>) -> Vec<&str> {
+        assets
+            .into_iter()
+            .map(|asset_info| asset_info.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn queries_combine_filters() {
+        let manifest = manifest();
+
+        let query = AssetQuery::new()
+            .type_name("Texture<RGBA8UnormSrgb>")
+            .tag("ui");
+        assert_eq!(names(manifest.query(&query)), ["button"]);
+
+        let query = AssetQuery::new().tag("terrain").under("world");
+        assert_eq!(names(manifest.query(&query)), ["grass", "tree"]);
+
+        let query = AssetQuery::new().search("TREE");
+        assert_eq!(names(manifest.query(&query)), ["tree"]);
+
+        assert!(manifest.query(&AssetQuery::new().tag("missing")).is_empty());
+        assert_eq!(manifest.query(&AssetQuery::new()).len(), 4);
+    }
+
+    #[test]
+    fn lookups_use_the_index() {
+        let mut manifest = manifest();
+        let tree = manifest.assets[3].asset_id;
+
+        assert_eq!(manifest.asset(tree).unwrap().name, "tree");
+        assert_eq!(names(manifest.assets_of_type("Mesh")), ["tree"]);
+        assert_eq!(names(manifest.assets_tagged("ui")), ["button", "font"]);
+        assert_eq!(
+            names(manifest.assets_under(Path::new("./ui/"))),
+            ["button", "font"]
+        );
+        assert!(manifest.assets_under(Path::new("u")).is_empty());
+
+        // Assets added without rebuilding the index are still found
+        manifest.assets.push(asset_info(
+            "ui/icon.asset",
+            "Texture<RGBA8UnormSrgb>",
+            &["ui"],
+        ));
+        assert_eq!(names(manifest.assets_named("icon")), ["icon"]);
+        manifest.rebuild_index();
+
+        // Or replaced by one of the same count
+        let grass = manifest.assets[2].asset_id;
+        manifest.assets[2] = asset_info("world/rock.asset", "Mesh", &[]);
+        assert!(manifest.asset(grass).is_none());
+        assert!(manifest.assets_named("grass").is_empty());
+    }
+}
@@ -2,14 +2,26 @@ use hashbrown::HashMap;
 use parking_lot::RwLock;
 use std::{
     any::Any,
+    collections::VecDeque,
     error::Error,
-    path::PathBuf,
+    marker::PhantomData,
+    path::{Path, PathBuf},
     sync::{Arc, Weak},
+    time::{Duration, Instant, SystemTime},
 };
 
 use cobalt_graphics::context::Graphics;
 
-use crate::asset::AssetReadError;
+use crate::{
+    asset::{AssetImporter, AssetMemory, AssetReadContext, AssetReadError},
+    cache::AssetCache,
+    importer::ImporterRegistry,
+    manifest::ManifestReadError,
+    pack::PACK_EXTENSION,
+    packed::{upgrade_packed_asset, UpgradeReport},
+    query::AssetQuery,
+    source::{AssetSource, DirectorySource, PackSource},
+};
 
 use super::{
     asset::AssetID,
@@ -19,14 +31,197 @@ use super::{
 
 pub struct AssetServer {
     /// This is a map of the assets that are currently loaded.
-    /// Will only contain Weak<RwLock<dyn Any + Send + Sync + 'static>>.
-    /// Not stored as such because of the dynamic size of the type.
-    pub(crate) loaded_assets: HashMap<AssetID, (Weak<dyn Any + Send + Sync + 'static>, usize)>,
+    pub(crate) loaded_assets: HashMap<AssetID, LoadedAsset>,
     /// NOTE: Do not edit this directly. Use the set_assets_dir method.
     /// It canonicalizes the path.
+    /// If the base source is a pack, this is the path of the archive.
     pub(crate) assets_dir: PathBuf,
-    /// The currently loaded main manifest file.
+    /// Mounted sources in priority order, the last one has the highest priority.
+    pub(crate) sources: Vec<MountedSource>,
+    /// The manifests of all sources merged together.
+    /// If several sources contain the same asset ID, the highest priority one wins.
     pub(crate) manifest: Option<Manifest>,
+    /// Index into `sources` of the source each asset in the merged manifest comes from.
+    pub(crate) asset_sources: HashMap<AssetID, usize>,
+    /// If this is `None`, hot reloading is disabled.
+    pub(crate) hot_reload: Option<HotReloadState>,
+    /// Events that have not been drained yet, at most `MAX_PENDING_EVENTS`.
+    pub(crate) pending_events: VecDeque<UntypedAssetEvent>,
+    /// Events dropped from the full queue since it was last drained.
+    pub(crate) dropped_events: usize,
+    /// Loaders for asset types by type name, used to load dependencies.
+    pub(crate) asset_types: HashMap<String, LoadFn>,
+    /// Rewrites the packed files of an asset in the current format version, by type name.
+    pub(crate) upgraders: HashMap<String, UpgradeFn>,
+    /// Importers for tools, registering one also registers its asset type.
+    pub(crate) importers: ImporterRegistry,
+    /// Assets whose dependencies are being loaded, to detect dependency cycles.
+    pub(crate) loading: Vec<AssetID>,
+    pub(crate) cache: AssetCache,
+}
+
+/// Type erased `load`, returns the `Arc<RwLock<T>>` of the asset.
+type LoadFn = fn(
+    &mut AssetServer,
+    Weak<RwLock<AssetServer>>,
+    &Arc<RwLock<Graphics>>,
+    AssetID,
+) -> Result<Arc<dyn Any + Send + Sync + 'static>, AssetLoadError>;
+
+/// `upgrade_packed_asset` for an asset type.
+type UpgradeFn = fn(&Path, &AssetInfo) -> Result<bool, AssetReadError>;
+
+/// Type erased function that re-reads an asset and swaps the new data into the existing allocation.
+/// The `Arc` must contain a `RwLock<T>` where `T` is the type the function was created for.
+type ReloadFn = fn(
+    &Arc<dyn Any + Send + Sync + 'static>,
+    &AssetInfo,
+    &dyn AssetSource,
+    &AssetReadContext,
+) -> Result<Box<dyn Any + Send + Sync + 'static>, AssetReadError>;
+
+/// Type erased `AssetTrait::memory_size`, the `Arc` must contain a `RwLock<T>`.
+type MemoryFn = fn(&Arc<dyn Any + Send + Sync + 'static>) -> AssetMemory;
+
+/// Bookkeeping for an asset that has been loaded by the server.
+pub(crate) struct LoadedAsset {
+    /// Will only contain Weak<RwLock<dyn Any + Send + Sync + 'static>>.
+    /// Not stored as such because of the dynamic size of the type.
+    pub(crate) data: Weak<dyn Any + Send + Sync + 'static>,
+    /// The manifest timestamp of the asset when it was last read.
+    pub(crate) timestamp: SystemTime,
+    /// The modification time of the packed file or directory when it was last read.
+    pub(crate) modified: Option<SystemTime>,
+    /// The source the asset was last read from.
+    pub(crate) source: Arc<dyn AssetSource>,
+    pub(crate) reload: ReloadFn,
+    /// Keeps the asset's dependencies loaded for as long as the asset is.
+    pub(crate) dependencies: Vec<(AssetID, Arc<dyn Any + Send + Sync + 'static>)>,
+    pub(crate) type_name: String,
+    /// Measured when the asset is read.
+    pub(crate) memory: AssetMemory,
+    pub(crate) memory_size: MemoryFn,
+    /// Keeps the asset loaded after its handles drop while the cache is enabled, see `trim_cache`.
+    pub(crate) retained: Option<Arc<dyn Any + Send + Sync + 'static>>,
+    /// `AssetCache` tick of the last time the asset was loaded or seen referenced.
+    pub(crate) last_used: u64,
+}
+
+/// The old data of the assets reloaded by `AssetServer::poll_changes`.
+/// Dropping it frees the data, do that after releasing the asset server's lock: the old data can
+/// hold the last handles to other assets, and dropping the last handle of an asset locks the server.
+#[must_use = "replaced asset data should be dropped after the asset server is unlocked"]
+#[derive(Default)]
+pub struct ReplacedAssets {
+    pub asset_ids: Vec<AssetID>,
+    /// Only held to be dropped.
+    #[allow(dead_code)]
+    data: Vec<Box<dyn Any + Send + Sync + 'static>>,
+}
+
+impl ReplacedAssets {
+    pub fn is_empty(&self) -> bool {
+        self.asset_ids.is_empty()
+    }
+}
+
+/// A source mounted on the asset server along with its manifest.
+pub(crate) struct MountedSource {
+    pub(crate) source: Arc<dyn AssetSource>,
+    pub(crate) manifest: Manifest,
+    /// The modification time of the manifest when it was last read.
+    pub(crate) manifest_modified: Option<SystemTime>,
+}
+
+impl MountedSource {
+    fn new(source: Arc<dyn AssetSource>) -> Result<Self, ManifestReadError> {
+        let manifest_modified = source.manifest_modified();
+        let manifest = source.read_manifest()?;
+
+        Ok(Self {
+            source,
+            manifest,
+            manifest_modified,
+        })
+    }
+}
+
+pub(crate) struct HotReloadState {
+    pub(crate) poll_interval: Duration,
+    pub(crate) last_poll: Instant,
+}
+
+/// Events beyond this are dropped, oldest first, if nothing drains them.
+const MAX_PENDING_EVENTS: usize = 4096;
+
+/// What happened to an asset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AssetEventKind {
+    /// The asset was read and is now loaded.
+    Loaded,
+    /// The asset was changed on disk and the new data has been swapped into all existing handles.
+    Modified,
+    /// The asset was unloaded, either after its last handle dropped or when evicted from the cache.
+    Removed,
+    /// Loading or reloading the asset failed. A failed reload keeps the old data.
+    Failed(String),
+}
+
+/// Notification about an asset of any type, retrieved with `AssetServer::drain_events`.
+/// The engine drains these every frame and passes them to the `App` and plugins.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UntypedAssetEvent {
+    pub asset_id: AssetID,
+    pub type_name: String,
+    pub kind: AssetEventKind,
+}
+
+impl UntypedAssetEvent {
+    pub fn is<T: AssetTrait>(&self) -> bool {
+        self.type_name == T::type_name()
+    }
+
+    /// The event as an `AssetEvent<T>`, if it is about an asset of type `T`.
+    ///
+    /// ```ignore
+    /// if let Some(event) = event.downcast::<Mesh>() {
+    ///     if event.kind == AssetEventKind::Modified { ... }
+    /// }
+    /// ```
+    pub fn downcast<T: AssetTrait>(&self) -> Option<AssetEvent<T>> {
+        self.is::<T>().then(|| AssetEvent {
+            asset_id: self.asset_id,
+            kind: self.kind.clone(),
+            _marker: PhantomData,
+        })
+    }
+}
+
+/// Notification about an asset of type `T`, see `UntypedAssetEvent::downcast`.
+pub struct AssetEvent<T: AssetTrait> {
+    pub asset_id: AssetID,
+    pub kind: AssetEventKind,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: AssetTrait> Clone for AssetEvent<T> {
+    fn clone(&self) -> Self {
+        Self {
+            asset_id: self.asset_id,
+            kind: self.kind.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: AssetTrait> std::fmt::Debug for AssetEvent<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AssetEvent")
+            .field("type", &T::type_name())
+            .field("asset_id", &self.asset_id)
+            .field("kind", &self.kind)
+            .finish()
+    }
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -52,6 +247,15 @@ pub enum AssetLoadError {
     GraphicsContextDoesNotExist,
     #[error("Failed to read asset")]
     ReadError(#[from] AssetReadError),
+    #[error("Asset type {0} is not registered, it can't be loaded as a dependency")]
+    UnregisteredAssetType(String),
+    #[error("Failed to load dependency {dependency:?}: {error}")]
+    DependencyFailed {
+        dependency: AssetID,
+        error: Box<AssetLoadError>,
+    },
+    #[error("Asset {0:?} depends on itself")]
+    DependencyCycle(AssetID),
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -73,8 +277,71 @@ impl AssetServer {
         Self {
             loaded_assets: HashMap::new(),
             assets_dir: PathBuf::from("./"),
+            sources: Vec::new(),
             manifest: None,
+            asset_sources: HashMap::new(),
+            hot_reload: None,
+            pending_events: VecDeque::new(),
+            dropped_events: 0,
+            asset_types: HashMap::new(),
+            upgraders: HashMap::new(),
+            importers: ImporterRegistry::new(),
+            loading: Vec::new(),
+            cache: AssetCache::default(),
+        }
+    }
+
+    /// Registers an asset type so assets of that type can be loaded as dependencies of other assets.
+    /// Types are also registered automatically the first time they are loaded with `load`.
+    pub fn register_asset_type<T: AssetTrait>(&mut self) {
+        self.asset_types.insert(T::type_name(), load_erased::<T>);
+        self.upgraders.insert(T::type_name(), upgrade_packed_asset::<T>);
+    }
+
+    /// Rewrites every packed file in `assets_dir` written in an older format version,
+    /// migrating it with `AssetTrait::migrate`. Assets of unregistered types are skipped.
+    pub fn upgrade_assets(&self, assets_dir: &Path) -> Result<UpgradeReport, ManifestReadError> {
+        let manifest = Manifest::load(assets_dir)?;
+        let mut report = UpgradeReport::default();
+
+        for asset_info in manifest.all_assets() {
+            let Some(upgrade) = self.upgraders.get(&asset_info.type_name) else {
+                report.skipped.push(asset_info.asset_id);
+                continue;
+            };
+
+            match upgrade(assets_dir, asset_info) {
+                Ok(true) => report.upgraded.push(asset_info.asset_id),
+                Ok(false) => report.current.push(asset_info.asset_id),
+                Err(e) => {
+                    log::error!(
+                        "Failed to upgrade asset \"{}\" [{:?}]: {}",
+                        asset_info.name,
+                        asset_info.asset_id,
+                        e
+                    );
+
+                    report.failed.push((asset_info.asset_id, e.to_string()));
+                }
+            }
         }
+
+        Ok(report)
+    }
+
+    /// Type names of all registered asset types.
+    pub fn registered_asset_types(&self) -> Vec<String> {
+        self.asset_types.keys().cloned().collect()
+    }
+
+    /// Registers an importer along with the asset type it produces.
+    pub fn register_importer<A: AssetTrait, T: AssetImporter<A>>(&mut self) {
+        self.register_asset_type::<A>();
+        self.importers.register::<A, T>();
+    }
+
+    pub fn importers(&self) -> &ImporterRegistry {
+        &self.importers
     }
 
     pub fn get_manifest(&self) -> Result<&Manifest, ManifestNotLoaded> {
@@ -89,50 +356,132 @@ impl AssetServer {
         &self.assets_dir
     }
 
+    /// The mounted sources in priority order, the last one has the highest priority.
+    pub fn sources(&self) -> impl Iterator<Item = &Arc<dyn AssetSource>> {
+        self.sources.iter().map(|mounted| &mounted.source)
+    }
+
+    /// The source an asset in the merged manifest will be read from.
+    pub fn asset_source(&self, asset_id: AssetID) -> Option<&Arc<dyn AssetSource>> {
+        self.asset_sources
+            .get(&asset_id)
+            .map(|index| &self.sources[*index].source)
+    }
+
+    /// Sets the assets directory and loads its manifest.
+    /// This unmounts all other sources, the directory becomes the only (base) source.
+    /// If the path is a `.cpak` archive, the archive is mounted instead.
     pub fn set_assets_dir(&mut self, assets_dir: &str) -> Result<(), Box<dyn Error>> {
-        let assets_dir_path = PathBuf::from(assets_dir);
+        let assets_dir = absolute_path(Path::new(assets_dir))?;
 
-        let absolute_assets_dir_path = if assets_dir_path.is_absolute() {
-            assets_dir_path
-        } else {
-            std::env::current_dir().unwrap().join(assets_dir_path)
+        log::info!("Setting assets directory to: {:?}", assets_dir);
+
+        let mounted = match MountedSource::new(open_source(&assets_dir)?) {
+            Ok(mounted) => mounted,
+            Err(err) => {
+                log::warn!("Failed to load manifest file: {}", err);
+                return Err("Failed to load manifest file".into());
+            }
         };
 
-        log::info!(
-            "Setting assets directory to: {:?}",
-            absolute_assets_dir_path
-        );
+        self.sources = vec![mounted];
+        self.assets_dir = assets_dir;
+        self.merge_manifests();
+
+        Ok(())
+    }
 
-        let assets_dir = absolute_assets_dir_path
-            .canonicalize()
-            .map_err(|e| format!("Failed to canonicalize assets directory: {}", e))?;
+    /// Mounts a source on top of the existing ones, giving it the highest priority.
+    /// Assets in its manifest override assets with the same ID in lower priority sources,
+    /// which is how mods and patches replace base content.
+    pub fn mount_source(&mut self, source: Arc<dyn AssetSource>) -> Result<(), ManifestReadError> {
+        log::info!("Mounting asset source: {}", source.name());
 
-        let manifest_load_res = Manifest::load(&assets_dir);
+        self.sources.push(MountedSource::new(source)?);
+        self.merge_manifests();
 
-        if let Ok(manifest) = manifest_load_res {
-            self.manifest = Some(manifest);
-            self.assets_dir = assets_dir;
-            return Ok(());
-        } else if let Err(err) = manifest_load_res {
-            log::warn!("Failed to load manifest file: {}", err);
-            return Err("Failed to load manifest file".into());
-        }
+        Ok(())
+    }
 
+    /// Mounts a directory or `.cpak` archive with the highest priority, see `mount_source`.
+    pub fn mount_path(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let path = absolute_path(path)?;
+        self.mount_source(open_source(&path)?)?;
         Ok(())
     }
 
+    /// Removes a previously mounted source.
+    /// Already loaded assets keep their data, they are re-read from the new winning source on the
+    /// next hot reload poll.
+    pub fn unmount_source(&mut self, source: &Arc<dyn AssetSource>) -> bool {
+        let len = self.sources.len();
+
+        self.sources
+            .retain(|mounted| !Arc::ptr_eq(&mounted.source, source));
+
+        if self.sources.len() == len {
+            return false;
+        }
+
+        self.merge_manifests();
+        true
+    }
+
+    /// Re-reads the manifest of every mounted source.
+    /// If a manifest fails to load, the previous version of it is kept.
     pub fn refresh_manifest(&mut self) -> Result<(), Box<dyn Error>> {
-        let manifest_load_res = Manifest::load(&self.assets_dir);
-
-        if let Ok(manifest) = manifest_load_res {
-            self.manifest = Some(manifest);
-            return Ok(());
-        } else if let Err(err) = manifest_load_res {
-            log::warn!("Failed to load manifest file: {}", err);
-            return Err("Failed to load manifest file".into());
+        let mut result = Ok(());
+
+        for mounted in &mut self.sources {
+            mounted.manifest_modified = mounted.source.manifest_modified();
+
+            match mounted.source.read_manifest() {
+                Ok(manifest) => mounted.manifest = manifest,
+                Err(err) => {
+                    log::warn!(
+                        "Failed to load manifest file of {}: {}",
+                        mounted.source.name(),
+                        err
+                    );
+                    result = Err("Failed to load manifest file".into());
+                }
+            }
         }
 
-        Ok(())
+        self.merge_manifests();
+
+        result
+    }
+
+    /// Rebuilds the merged manifest from the manifests of all sources.
+    /// Assets from sub-manifests are flattened into `assets` of the merged manifest.
+    fn merge_manifests(&mut self) {
+        self.asset_sources.clear();
+
+        if self.sources.is_empty() {
+            self.manifest = None;
+            return;
+        }
+
+        let mut merged = Manifest::new();
+        let mut indices = HashMap::new();
+
+        for (source_index, mounted) in self.sources.iter().enumerate() {
+            for asset_info in mounted.manifest.all_assets() {
+                match indices.get(&asset_info.asset_id) {
+                    Some(index) => merged.assets[*index] = asset_info.clone(),
+                    None => {
+                        indices.insert(asset_info.asset_id, merged.assets.len());
+                        merged.assets.push(asset_info.clone());
+                    }
+                }
+
+                self.asset_sources.insert(asset_info.asset_id, source_index);
+            }
+        }
+
+        merged.rebuild_index();
+        self.manifest = Some(merged);
     }
 
     pub fn list_loaded_assets(&self) -> Result<Vec<&AssetInfo>, ManifestNotLoaded> {
@@ -140,14 +489,15 @@ impl AssetServer {
 
         let mut loaded_assets = Vec::new();
 
-        for (asset_id, (_, _)) in &self.loaded_assets {
-            let asset_info = manifest
-                .assets
-                .iter()
-                .find(|asset_info| asset_info.asset_id == *asset_id)
-                .unwrap();
+        for (asset_id, loaded) in &self.loaded_assets {
+            if loaded.data.strong_count() == 0 {
+                continue;
+            }
 
-            loaded_assets.push(asset_info);
+            // The asset might have been removed from the manifest after it was loaded
+            if let Some(asset_info) = manifest.asset(*asset_id) {
+                loaded_assets.push(asset_info);
+            }
         }
 
         Ok(loaded_assets)
@@ -155,25 +505,73 @@ impl AssetServer {
 
     /// Load an asset from disk.
     /// The asset must be present in the manifest file.
+    /// If the asset is already loaded, a new handle to the existing data is returned.
+    /// Dependencies listed in the manifest are loaded too and stay loaded while the asset is.
     pub fn load<T: AssetTrait>(
-        &self,
+        &mut self,
         self_weak_ref: Weak<RwLock<AssetServer>>,
-        graphics: &Graphics,
+        graphics: &Arc<RwLock<Graphics>>,
         asset_id: AssetID,
     ) -> Result<Asset<T>, AssetLoadError> {
+        let data = self.load_data::<T>(self_weak_ref.clone(), graphics, asset_id)?;
+
+        Ok(Asset::new(self_weak_ref, Some(asset_id), data))
+    }
+
+    /// Loads an asset and returns its `Arc<RwLock<T>>` without creating a handle.
+    /// Queues a `Failed` event if it can't be loaded.
+    fn load_data<T: AssetTrait>(
+        &mut self,
+        self_weak_ref: Weak<RwLock<AssetServer>>,
+        graphics: &Arc<RwLock<Graphics>>,
+        asset_id: AssetID,
+    ) -> Result<Arc<dyn Any + Send + Sync + 'static>, AssetLoadError> {
+        let result = self.read_data::<T>(self_weak_ref, graphics, asset_id);
+
+        if let Err(e) = &result {
+            self.push_event(
+                asset_id,
+                T::type_name(),
+                AssetEventKind::Failed(e.to_string()),
+            );
+        }
+
+        result
+    }
+
+    fn read_data<T: AssetTrait>(
+        &mut self,
+        self_weak_ref: Weak<RwLock<AssetServer>>,
+        graphics: &Arc<RwLock<Graphics>>,
+        asset_id: AssetID,
+    ) -> Result<Arc<dyn Any + Send + Sync + 'static>, AssetLoadError> {
         // Check if the asset is already loaded
-        if let Some(_) = self.loaded_assets.get(&asset_id) {
-            return Err(AssetLoadError::AssetAlreadyLoaded);
+        if let Some(data) = self.loaded_data(asset_id) {
+            if !data.is::<RwLock<T>>() {
+                return Err(AssetLoadError::TypeMismatch {
+                    load_type: T::type_name(),
+                    asset_type: self
+                        .get_manifest()?
+                        .asset(asset_id)
+                        .map_or("Unknown".to_string(), |asset_info| {
+                            asset_info.type_name.clone()
+                        }),
+                });
+            }
+
+            return Ok(data);
         }
 
+        self.register_asset_type::<T>();
+
         let manifest = self.get_manifest()?;
 
         let asset_info = manifest
-            .assets
-            .iter()
-            .find(|asset_info| asset_info.asset_id == asset_id)
+            .asset(asset_id)
             .ok_or(AssetLoadError::AssetNotFound)?;
 
+        let source = self.sources[self.asset_sources[&asset_id]].source.clone();
+
         // Type check
         if asset_info.type_name != T::type_name() {
             return Err(AssetLoadError::TypeMismatch {
@@ -182,8 +580,38 @@ impl AssetServer {
             });
         }
 
-        let asset = T::read(asset_info, &self.assets_dir, graphics)?;
-        
+        if self.loading.contains(&asset_id) {
+            return Err(AssetLoadError::DependencyCycle(asset_id));
+        }
+
+        let asset_info = asset_info.clone();
+        let modified = source.modified(&asset_info.relative_path);
+
+        // Dependencies are loaded first so `read` can create handles to them
+        self.loading.push(asset_id);
+
+        let mut dependencies = Vec::with_capacity(asset_info.dependencies.len());
+
+        for dependency in &asset_info.dependencies {
+            match self.load_dependency(self_weak_ref.clone(), graphics, *dependency) {
+                Ok(data) => dependencies.push((*dependency, data)),
+                Err(error) => {
+                    self.loading.retain(|id| *id != asset_id);
+
+                    return Err(AssetLoadError::DependencyFailed {
+                        dependency: *dependency,
+                        error: Box::new(error),
+                    });
+                }
+            }
+        }
+
+        self.loading.retain(|id| *id != asset_id);
+
+        let context = AssetReadContext::new(graphics, self_weak_ref, &dependencies);
+
+        let asset = T::read(&asset_info, source.as_ref(), &context)?;
+
         let asset_arc = Arc::new(RwLock::new(asset));
 
         // For adding to the loaded assets map
@@ -191,7 +619,306 @@ impl AssetServer {
             Arc::from_raw(Arc::into_raw(asset_arc) as *const (dyn Any + Send + Sync + 'static))
         };
 
-        Ok(Asset::new(self_weak_ref, Some(asset_id), asset_any))
+        self.loaded_assets.insert(
+            asset_id,
+            LoadedAsset {
+                data: Arc::downgrade(&asset_any),
+                timestamp: asset_info.timestamp,
+                modified,
+                source,
+                reload: reload_erased::<T>,
+                dependencies,
+                type_name: T::type_name(),
+                memory: memory_size_erased::<T>(&asset_any),
+                memory_size: memory_size_erased::<T>,
+                retained: self.cache.enabled().then(|| asset_any.clone()),
+                last_used: self.cache.next_tick(),
+            },
+        );
+
+        self.push_event(asset_id, T::type_name(), AssetEventKind::Loaded);
+
+        Ok(asset_any)
+    }
+
+    /// The data of an asset if it is loaded, marking it as used for the cache.
+    fn loaded_data(&mut self, asset_id: AssetID) -> Option<Arc<dyn Any + Send + Sync + 'static>> {
+        let loaded = self.loaded_assets.get_mut(&asset_id)?;
+        let data = loaded.data.upgrade()?;
+        loaded.last_used = self.cache.next_tick();
+
+        Some(data)
+    }
+
+    /// Loads an asset whose type is only known from the manifest.
+    fn load_dependency(
+        &mut self,
+        self_weak_ref: Weak<RwLock<AssetServer>>,
+        graphics: &Arc<RwLock<Graphics>>,
+        asset_id: AssetID,
+    ) -> Result<Arc<dyn Any + Send + Sync + 'static>, AssetLoadError> {
+        if let Some(data) = self.loaded_data(asset_id) {
+            return Ok(data);
+        }
+
+        let type_name = self
+            .get_manifest()?
+            .asset(asset_id)
+            .ok_or(AssetLoadError::AssetNotFound)?
+            .type_name
+            .clone();
+
+        let load = *self
+            .asset_types
+            .get(&type_name)
+            .ok_or(AssetLoadError::UnregisteredAssetType(type_name))?;
+
+        load(self, self_weak_ref, graphics, asset_id)
+    }
+
+    /// Enables or disables hot reloading.
+    /// When enabled, `poll_changes` will watch the manifests of all sources and the packed files of
+    /// all loaded assets, re-reading any that changed.
+    /// For a mounted pack, the whole archive is reopened when it changes.
+    pub fn set_hot_reload(&mut self, enabled: bool, poll_interval: Duration) {
+        if enabled {
+            self.hot_reload = Some(HotReloadState {
+                poll_interval,
+                last_poll: Instant::now(),
+            });
+
+            // Anything that changed while disabled will be picked up on the first poll
+            for mounted in &mut self.sources {
+                mounted.manifest_modified = mounted.source.manifest_modified();
+            }
+        } else {
+            self.hot_reload = None;
+        }
+    }
+
+    pub fn hot_reload_enabled(&self) -> bool {
+        self.hot_reload.is_some()
+    }
+
+    /// Checks the manifest and loaded assets for changes by comparing modification times.
+    /// Changed assets are re-read and swapped into their existing `Arc<RwLock<T>>`, so every
+    /// `Asset<T>` handle sees the new data. If reading fails, the old data is kept.
+    /// Does nothing if hot reloading is disabled or the poll interval has not elapsed yet.
+    /// Reloads and failed reloads are queued as events for `drain_events`.
+    /// Returns the old data of the reloaded assets, drop it after releasing the server's lock.
+    /// NOTE: Reloaded assets can only use the dependencies they had when first loaded.
+    pub fn poll_changes(
+        &mut self,
+        self_weak_ref: Weak<RwLock<AssetServer>>,
+        graphics: &Arc<RwLock<Graphics>>,
+    ) -> ReplacedAssets {
+        let mut replaced = ReplacedAssets::default();
+
+        match &mut self.hot_reload {
+            Some(state) => {
+                if state.last_poll.elapsed() < state.poll_interval {
+                    return replaced;
+                }
+
+                state.last_poll = Instant::now();
+            }
+            None => return replaced,
+        }
+
+        let mut manifest_changed = false;
+
+        for mounted in &mut self.sources {
+            let manifest_modified = mounted.source.manifest_modified();
+
+            if manifest_modified == mounted.manifest_modified {
+                continue;
+            }
+
+            log::info!("Manifest of {} changed, reloading it.", mounted.source.name());
+
+            mounted.manifest_modified = manifest_modified;
+            manifest_changed = true;
+
+            match mounted.source.read_manifest() {
+                Ok(manifest) => mounted.manifest = manifest,
+                Err(e) => log::warn!(
+                    "Failed to reload changed manifest, keeping the old one: {}",
+                    e
+                ),
+            }
+        }
+
+        if manifest_changed {
+            self.merge_manifests();
+        }
+
+        self.remove_dropped();
+
+        let manifest = match &self.manifest {
+            Some(manifest) => manifest,
+            None => return replaced,
+        };
+
+        let mut events = Vec::new();
+
+        for (asset_id, loaded) in self.loaded_assets.iter_mut() {
+            let asset_info = match manifest.asset(*asset_id) {
+                Some(asset_info) => asset_info,
+                None => {
+                    if manifest_changed {
+                        log::warn!(
+                            "Loaded asset [{:?}] was removed from the manifest, it will not be reloaded.",
+                            asset_id
+                        );
+                    }
+                    continue;
+                }
+            };
+
+            // Always present for assets in the merged manifest
+            let source = &self.sources[self.asset_sources[asset_id]].source;
+            let modified = source.modified(&asset_info.relative_path);
+
+            // A different source may now provide the asset, e.g. after mounting a patch
+            if modified == loaded.modified
+                && asset_info.timestamp == loaded.timestamp
+                && Arc::ptr_eq(source, &loaded.source)
+            {
+                continue;
+            }
+
+            let data = match loaded.data.upgrade() {
+                Some(data) => data,
+                None => continue,
+            };
+
+            // Update these even if reading fails, so a broken file isn't re-read every poll.
+            loaded.modified = modified;
+            loaded.timestamp = asset_info.timestamp;
+            loaded.source = source.clone();
+
+            let context =
+                AssetReadContext::new(graphics, self_weak_ref.clone(), &loaded.dependencies);
+
+            match (loaded.reload)(&data, asset_info, source.as_ref(), &context) {
+                Ok(old_data) => {
+                    loaded.memory = (loaded.memory_size)(&data);
+                    replaced.asset_ids.push(*asset_id);
+                    replaced.data.push(old_data);
+
+                    log::info!("Reloaded asset \"{}\" [{:?}]", asset_info.name, asset_id);
+                    events.push((
+                        *asset_id,
+                        loaded.type_name.clone(),
+                        AssetEventKind::Modified,
+                    ));
+                }
+                Err(e) => {
+                    log::error!(
+                        "Failed to reload asset \"{}\" [{:?}], keeping old data: {}",
+                        asset_info.name,
+                        asset_id,
+                        e
+                    );
+                    events.push((
+                        *asset_id,
+                        loaded.type_name.clone(),
+                        AssetEventKind::Failed(e.to_string()),
+                    ));
+                }
+            }
+        }
+
+        for (asset_id, type_name, kind) in events {
+            self.push_event(asset_id, type_name, kind);
+        }
+
+        replaced
+    }
+
+    /// Takes all the asset events that have been queued since the last call.
+    /// When running in the engine, it drains them every frame, use `App::on_asset_event` instead.
+    pub fn drain_events(&mut self) -> Vec<UntypedAssetEvent> {
+        if self.dropped_events > 0 {
+            log::warn!(
+                "{} asset events were dropped because the queue was full.",
+                self.dropped_events
+            );
+            self.dropped_events = 0;
+        }
+
+        self.pending_events.drain(..).collect()
+    }
+
+    pub(crate) fn push_event(
+        &mut self,
+        asset_id: AssetID,
+        type_name: String,
+        kind: AssetEventKind,
+    ) {
+        if self.pending_events.len() >= MAX_PENDING_EVENTS {
+            if self.dropped_events == 0 {
+                log::warn!(
+                    "Asset event queue is full, dropping the oldest events. Call AssetServer::drain_events regularly."
+                );
+            }
+
+            self.pending_events.pop_front();
+            self.dropped_events += 1;
+        }
+
+        self.pending_events.push_back(UntypedAssetEvent {
+            asset_id,
+            type_name,
+            kind,
+        });
+    }
+
+    /// Removes a loaded asset whose last handle is being dropped, queuing a `Removed` event.
+    /// Does nothing if the asset was loaded again since the handle checked, the dropping handle
+    /// must then not be the only strong reference left.
+    pub(crate) fn unload(&mut self, asset_id: AssetID) {
+        let unused = self
+            .loaded_assets
+            .get(&asset_id)
+            .is_some_and(|loaded| loaded.data.strong_count() <= 1);
+
+        if !unused {
+            return;
+        }
+
+        self.remove_loaded(asset_id);
+
+        // Dependencies that were only kept alive by this asset
+        self.remove_dropped();
+    }
+
+    /// Removes the assets whose data has been dropped, queuing `Removed` events.
+    pub(crate) fn remove_dropped(&mut self) {
+        loop {
+            let dropped = self
+                .loaded_assets
+                .iter()
+                .filter(|(_, loaded)| loaded.data.strong_count() == 0)
+                .map(|(asset_id, _)| *asset_id)
+                .collect::<Vec<_>>();
+
+            if dropped.is_empty() {
+                break;
+            }
+
+            // Removing an asset releases its dependencies, which may leave more dropped assets
+            for asset_id in dropped {
+                self.remove_loaded(asset_id);
+            }
+        }
+    }
+
+    pub(crate) fn remove_loaded(&mut self, asset_id: AssetID) -> Option<LoadedAsset> {
+        let loaded = self.loaded_assets.remove(&asset_id)?;
+        self.push_event(asset_id, loaded.type_name.clone(), AssetEventKind::Removed);
+
+        Some(loaded)
     }
 
     /// Get the asset ID from the asset's name.
@@ -199,60 +926,162 @@ impl AssetServer {
     pub fn find_asset_by_name(&self, name: &str) -> Result<AssetID, FindAssetByName> {
         let manifest = self.get_manifest()?;
 
-        let mut asset_id = None;
+        match manifest.assets_named(name).as_slice() {
+            [] => Err(FindAssetByName::AssetNotFound),
+            [asset_info] => Ok(asset_info.asset_id),
+            _ => Err(FindAssetByName::DuplicateAssetNames),
+        }
+    }
 
-        for asset_info in &manifest.assets {
-            if asset_info.name == name {
-                if asset_id.is_some() {
-                    return Err(FindAssetByName::DuplicateAssetNames);
-                }
+    /// Assets of the mounted sources matching the query, see `Manifest::query`.
+    pub fn query(&self, query: &AssetQuery) -> Result<Vec<&AssetInfo>, ManifestNotLoaded> {
+        Ok(self.get_manifest()?.query(query))
+    }
+}
+
+fn load_erased<T: AssetTrait>(
+    server: &mut AssetServer,
+    self_weak_ref: Weak<RwLock<AssetServer>>,
+    graphics: &Arc<RwLock<Graphics>>,
+    asset_id: AssetID,
+) -> Result<Arc<dyn Any + Send + Sync + 'static>, AssetLoadError> {
+    server.load_data::<T>(self_weak_ref, graphics, asset_id)
+}
+
+fn memory_size_erased<T: AssetTrait>(data: &Arc<dyn Any + Send + Sync + 'static>) -> AssetMemory {
+    data.downcast_ref::<RwLock<T>>()
+        .map_or(AssetMemory::default(), |lock| lock.read().memory_size())
+}
+
+fn reload_erased<T: AssetTrait>(
+    data: &Arc<dyn Any + Send + Sync + 'static>,
+    asset_info: &AssetInfo,
+    source: &dyn AssetSource,
+    context: &AssetReadContext,
+) -> Result<Box<dyn Any + Send + Sync + 'static>, AssetReadError> {
+    let lock = data.downcast_ref::<RwLock<T>>().unwrap_or_else(|| {
+        panic!(
+            "Failed to downcast loaded asset to {:?}",
+            std::any::type_name::<RwLock<T>>()
+        )
+    });
+
+    // Read before locking so handles aren't blocked while the file is being parsed
+    let new_data = T::read(asset_info, source, context)?;
+
+    // Not dropped here, the old data can hold the last handles of other assets
+    let old_data = std::mem::replace(&mut *lock.write(), new_data);
 
-                asset_id = Some(asset_info.asset_id);
+    Ok(Box::new(old_data))
+}
+
+fn absolute_path(path: &Path) -> Result<PathBuf, Box<dyn Error>> {
+    let absolute_path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()?.join(path)
+    };
+
+    Ok(absolute_path
+        .canonicalize()
+        .map_err(|e| format!("Failed to canonicalize assets directory: {}", e))?)
+}
+
+/// Opens a `.cpak` archive if the path is one, otherwise treats it as a directory.
+fn open_source(path: &Path) -> Result<Arc<dyn AssetSource>, Box<dyn Error>> {
+    if path.is_file() && path.extension().is_some_and(|ext| ext == PACK_EXTENSION) {
+        Ok(Arc::new(PackSource::open(path)?))
+    } else {
+        Ok(Arc::new(DirectorySource::new(path)))
+    }
+}
+
+/// Returns the modification time of a file.
+/// For directories, the latest modification time of any file inside is returned.
+pub(crate) fn last_modified(path: &Path) -> Option<SystemTime> {
+    let metadata = std::fs::metadata(path).ok()?;
+
+    if metadata.is_dir() {
+        let mut latest = metadata.modified().ok();
+
+        for entry in std::fs::read_dir(path).ok()?.flatten() {
+            let entry_modified = last_modified(&entry.path());
+
+            if entry_modified > latest {
+                latest = entry_modified;
             }
         }
 
-        asset_id.ok_or(FindAssetByName::AssetNotFound)
+        latest
+    } else {
+        metadata.modified().ok()
     }
+}
 
-    // /// Load an asset from disk.
-    // /// If the asset is already loaded, it will not load it again.
-    // /// The path is relative to the assets directory.
-    // pub fn load<T: AssetTrait>(&mut self, path: &Path) -> Result<Asset<T>, AssetLoadError> {
-    //     let absolute_path = self.assets_dir.join(path);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{source::MemorySource, tests::helpers};
 
-    //     let relative_path_string = extract_relative_path(&absolute_path, &self.assets_dir);
+    fn asset_info(asset_id: AssetID, name: &str) -> AssetInfo {
+        AssetInfo {
+            asset_id,
+            ..helpers::asset_info(name)
+        }
+    }
 
-    //     // Check if the asset is already loaded
-    //     if let Some((asset, count)) = self.loaded_assets.get_mut(relative_path_string.as_str()) {
-    //         // If the asset is loaded, increment the count
-    //         *count += 1;
+    #[test]
+    fn higher_priority_source_overrides() {
+        let shared = AssetID::generate();
+        let base_only = AssetID::generate();
 
-    //         if let Some(asset) = asset.upgrade() {
-    //             return Ok(Asset::new(
-    //                 ImString::from_str(relative_path_string.as_str()).unwrap(),
-    //                 asset,
-    //             ));
-    //         }
-    //     }
+        let base = MemorySource::new("base");
+        let mut manifest = Manifest::new();
+        manifest.assets.push(asset_info(shared, "base"));
+        manifest.assets.push(asset_info(base_only, "base_only"));
+        base.insert_manifest(&manifest).unwrap();
 
-    //     let asset_handle_path = ImString::from_str(relative_path_string.as_str()).unwrap();
+        let patch = MemorySource::new("patch");
+        let mut manifest = Manifest::new();
+        manifest.assets.push(asset_info(shared, "patch"));
+        patch.insert_manifest(&manifest).unwrap();
 
-    //     let file = std::fs::File::open(&absolute_path)?;
+        let base: Arc<dyn AssetSource> = Arc::new(base);
+        let patch: Arc<dyn AssetSource> = Arc::new(patch);
 
-    //     let buf_reader = BufReader::new(file);
+        let mut server = AssetServer::new();
+        server.mount_source(base.clone()).unwrap();
+        server.mount_source(patch.clone()).unwrap();
 
-    //     let asset = Arc::new(RwLock::new(T::read_from_file_to_buffer(
-    //         buf_reader,
-    //         &absolute_path,
-    //     )?));
+        let manifest = server.get_manifest().unwrap();
+        assert_eq!(manifest.assets.len(), 2);
+        assert_eq!(server.find_asset_by_name("patch").unwrap(), shared);
+        assert!(server.find_asset_by_name("base").is_err());
+        assert!(Arc::ptr_eq(server.asset_source(shared).unwrap(), &patch));
+        assert!(Arc::ptr_eq(server.asset_source(base_only).unwrap(), &base));
+
+        assert!(server.unmount_source(&patch));
+        assert_eq!(server.find_asset_by_name("base").unwrap(), shared);
+    }
 
-    //     let asset_any = unsafe {
-    //         Arc::from_raw(Arc::into_raw(asset) as *const (dyn Any + Send + Sync + 'static))
-    //     };
+    #[test]
+    fn event_queue_drops_oldest() {
+        let mut server = AssetServer::new();
+        let first = AssetID::generate();
 
-    //     self.loaded_assets
-    //         .insert(asset_handle_path.clone(), (Arc::downgrade(&asset_any), 1));
+        server.push_event(first, "Test".to_string(), AssetEventKind::Loaded);
 
-    //     Ok(Asset::new(asset_handle_path, asset_any))
-    // }
+        for _ in 0..MAX_PENDING_EVENTS {
+            server.push_event(
+                AssetID::generate(),
+                "Test".to_string(),
+                AssetEventKind::Removed,
+            );
+        }
+
+        let events = server.drain_events();
+        assert_eq!(events.len(), MAX_PENDING_EVENTS);
+        assert!(events.iter().all(|event| event.asset_id != first));
+        assert!(server.drain_events().is_empty());
+    }
 }
@@ -0,0 +1,468 @@
+//! The sidecar manifest layout, where each asset's `AssetInfo` lives in a `.meta` file next to its
+//! packed file or directory instead of in `manifest.toml`. Importing or deleting an asset only
+//! touches its own meta file, so several people can import assets without conflicts.
+//!
+//! `manifest.toml` then only records the layout. Directories keep a cached index of the meta files
+//! in `manifest.index`, only meta files that changed since it was written are parsed again.
+//! The index can be deleted at any time and should not be committed.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use hashbrown::{HashMap, HashSet};
+use path_clean::PathClean;
+
+use crate::manifest::{
+    AssetInfo, Manifest, ManifestLayout, ManifestReadError, ManifestWriteError, SubManifest,
+};
+
+pub const META_EXTENSION: &str = "meta";
+/// Cached index of the meta files, relative to the assets directory.
+pub const INDEX_FILE: &str = "manifest.index";
+
+/// Contents of a `.meta` file.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug)]
+pub struct MetaFile {
+    /// Whether the asset's directory has a sub-manifest of its own.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub sub_manifest: bool,
+    pub asset: AssetInfo,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Default)]
+struct SidecarIndex {
+    #[serde(default)]
+    entries: Vec<IndexEntry>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+struct IndexEntry {
+    path: PathBuf,
+    modified: SystemTime,
+    size: u64,
+    meta: MetaFile,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ManifestConvertError {
+    #[error("Failed to read manifest")]
+    ManifestRead(#[from] ManifestReadError),
+    #[error("Failed to write manifest")]
+    ManifestWrite(#[from] ManifestWriteError),
+}
+
+/// Path of the meta file of an asset, e.g. `textures/grass.png.meta` for `textures/grass.png`.
+pub fn meta_path(relative_path: &Path) -> PathBuf {
+    let mut path = relative_path.clean().into_os_string();
+    path.push(".");
+    path.push(META_EXTENSION);
+    PathBuf::from(path)
+}
+
+pub fn is_meta_file(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == META_EXTENSION)
+}
+
+fn parse_meta(relative_path: &Path, data: &[u8]) -> Result<MetaFile, ManifestReadError> {
+    let text =
+        std::str::from_utf8(data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    toml::from_str(text).map_err(|e| {
+        log::error!("Failed to parse meta file {:?}: {}", relative_path, e);
+        ManifestReadError::Toml(e)
+    })
+}
+
+/// Reads every meta file listed by a source, without an index.
+pub(crate) fn read_meta_files(
+    read: &dyn Fn(&Path) -> io::Result<Vec<u8>>,
+    files: Vec<PathBuf>,
+) -> Result<Vec<MetaFile>, ManifestReadError> {
+    let mut files = files
+        .into_iter()
+        .filter(|path| is_meta_file(path))
+        .collect::<Vec<_>>();
+    files.sort();
+
+    files
+        .iter()
+        .map(|path| parse_meta(path, &read(path)?))
+        .collect()
+}
+
+/// Reads every meta file in the assets directory, taking the ones that haven't changed from the index.
+/// The index is rewritten if anything changed, failing to write it is only logged.
+pub(crate) fn load_meta_files(assets_dir: &Path) -> Result<Vec<MetaFile>, ManifestReadError> {
+    let index_path = assets_dir.join(INDEX_FILE);
+
+    let mut cached = read_index(&index_path)
+        .entries
+        .into_iter()
+        .map(|entry| (entry.path.clone(), entry))
+        .collect::<HashMap<_, _>>();
+
+    let mut entries = Vec::new();
+    let mut changed = false;
+
+    for (path, metadata) in find_meta_files(assets_dir)? {
+        let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+        let size = metadata.len();
+
+        match cached.remove(&path) {
+            Some(entry) if entry.modified == modified && entry.size == size => entries.push(entry),
+            _ => {
+                let meta = parse_meta(&path, &std::fs::read(assets_dir.join(&path))?)?;
+
+                entries.push(IndexEntry {
+                    path,
+                    modified,
+                    size,
+                    meta,
+                });
+                changed = true;
+            }
+        }
+    }
+
+    let index = SidecarIndex { entries };
+
+    // Entries left in the cache belong to deleted meta files
+    if changed || !cached.is_empty() {
+        write_index(&index_path, &index);
+    }
+
+    Ok(index.entries.into_iter().map(|entry| entry.meta).collect())
+}
+
+/// A missing or outdated index reads as empty, it is rebuilt from scratch.
+fn read_index(index_path: &Path) -> SidecarIndex {
+    std::fs::read_to_string(index_path)
+        .ok()
+        .and_then(|text| toml::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+/// Failing to write the index is only logged, it is a cache.
+fn write_index(index_path: &Path, index: &SidecarIndex) {
+    if let Err(e) = toml::to_string(index)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        .and_then(|text| std::fs::write(index_path, text))
+    {
+        log::warn!("Failed to write the manifest index {:?}: {}", index_path, e);
+    }
+}
+
+/// Latest modification time of the meta files, the index and the directories in the assets directory.
+/// Directories are included so that deleting a meta file counts as a change, packed files are not.
+pub(crate) fn meta_files_modified(assets_dir: &Path) -> Option<SystemTime> {
+    fn visit(dir: &Path, latest: &mut Option<SystemTime>) -> io::Result<()> {
+        *latest = (*latest).max(std::fs::metadata(dir)?.modified().ok());
+
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+
+            if entry.file_type()?.is_dir() {
+                visit(&path, latest)?;
+            } else if is_meta_file(&path) {
+                *latest = (*latest).max(entry.metadata()?.modified().ok());
+            }
+        }
+
+        Ok(())
+    }
+
+    let mut latest = std::fs::metadata(assets_dir.join(INDEX_FILE))
+        .and_then(|metadata| metadata.modified())
+        .ok();
+
+    visit(assets_dir, &mut latest).ok()?;
+    latest
+}
+
+/// Meta files in the assets directory with their metadata, sorted by path.
+fn find_meta_files(assets_dir: &Path) -> io::Result<Vec<(PathBuf, std::fs::Metadata)>> {
+    fn visit(
+        root: &Path,
+        dir: &Path,
+        files: &mut Vec<(PathBuf, std::fs::Metadata)>,
+    ) -> io::Result<()> {
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            let metadata = entry.metadata()?;
+
+            if metadata.is_dir() {
+                visit(root, &path, files)?;
+            } else if is_meta_file(&path) {
+                // Can't fail, `dir` is always inside `root`
+                files.push((path.strip_prefix(root).unwrap().to_path_buf(), metadata));
+            }
+        }
+
+        Ok(())
+    }
+
+    let mut files = Vec::new();
+    visit(assets_dir, assets_dir, &mut files)?;
+    files.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Ok(files)
+}
+
+impl Manifest {
+    /// Adds the assets of the meta files, and the sub-manifests they have.
+    pub(crate) fn add_meta_files(
+        &mut self,
+        meta_files: Vec<MetaFile>,
+        read: &dyn Fn(&Path) -> io::Result<Vec<u8>>,
+    ) -> Result<(), ManifestReadError> {
+        for meta in meta_files {
+            if meta.sub_manifest {
+                self.sub_manifests.push(SubManifest {
+                    parent_asset: meta.asset.asset_id,
+                    manifest_dir: meta.asset.relative_path.clean(),
+                });
+            }
+
+            self.assets.push(meta.asset);
+        }
+
+        self.load_sub_manifests(read)
+    }
+
+    /// Writes a meta file for every asset and a `manifest.toml` holding only the layout.
+    /// Meta files whose contents didn't change are left alone. Those of removed assets, meaning ones
+    /// listed in the index but not written now, are deleted. Meta files the index doesn't know about,
+    /// e.g. added by someone else since the manifest was loaded, are kept.
+    pub(crate) fn save_meta_files(&self, assets_dir: &Path) -> Result<(), ManifestWriteError> {
+        let mut header = Manifest::new();
+        header.layout = ManifestLayout::Sidecar;
+
+        write_if_changed(
+            &assets_dir.join("manifest.toml"),
+            &toml::to_string(&header)?,
+        )?;
+
+        let index_path = assets_dir.join(INDEX_FILE);
+        let previous = read_index(&index_path);
+
+        let mut written = HashSet::new();
+        let mut entries = Vec::new();
+
+        for asset_info in &self.assets {
+            let meta = MetaFile {
+                sub_manifest: self
+                    .sub_manifests
+                    .iter()
+                    .any(|sub_manifest| sub_manifest.parent_asset == asset_info.asset_id),
+                asset: asset_info.clone(),
+            };
+
+            // Going through a `toml::Value` sorts the keys, so unchanged assets give the same text
+            let text = toml::to_string(&toml::Value::try_from(&meta)?)?;
+
+            let relative_path = meta_path(&asset_info.relative_path);
+            let abs_path = assets_dir.join(&relative_path);
+
+            if let Some(parent) = abs_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            write_if_changed(&abs_path, &text)?;
+
+            let metadata = std::fs::metadata(&abs_path)?;
+
+            written.insert(relative_path.clone());
+            entries.push(IndexEntry {
+                path: relative_path,
+                modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                size: metadata.len(),
+                meta,
+            });
+        }
+
+        for entry in &previous.entries {
+            if written.contains(&entry.path) {
+                continue;
+            }
+
+            match std::fs::remove_file(assets_dir.join(&entry.path)) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+
+        // The index now lists what was written, so the next save knows what it may delete
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        write_index(&index_path, &SidecarIndex { entries });
+
+        Ok(())
+    }
+}
+
+/// Leaves the file alone if it already has the contents, so its modification time stays the same.
+fn write_if_changed(abs_path: &Path, text: &str) -> io::Result<()> {
+    if std::fs::read_to_string(abs_path).ok().as_deref() == Some(text) {
+        return Ok(());
+    }
+
+    std::fs::write(abs_path, text)
+}
+
+/// Moves the asset infos of an assets directory to the given layout.
+/// Converting to the single layout deletes the meta files and the index.
+pub fn convert_layout(
+    assets_dir: &Path,
+    layout: ManifestLayout,
+) -> Result<(), ManifestConvertError> {
+    let mut manifest = Manifest::load(assets_dir)?;
+
+    manifest.layout = layout;
+    manifest.save(assets_dir)?;
+
+    if layout == ManifestLayout::Single {
+        for (relative_path, _) in find_meta_files(assets_dir).map_err(ManifestWriteError::Io)? {
+            std::fs::remove_file(assets_dir.join(relative_path)).map_err(ManifestWriteError::Io)?;
+        }
+
+        let index_path = assets_dir.join(INDEX_FILE);
+
+        if index_path.exists() {
+            std::fs::remove_file(index_path).map_err(ManifestWriteError::Io)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        source::{AssetSource, DirectorySource},
+        tests::helpers::{asset_info, TempDir},
+    };
+
+    #[test]
+    fn meta_paths_sit_next_to_the_asset() {
+        assert_eq!(
+            meta_path(Path::new("./textures//grass.png")),
+            PathBuf::from("textures/grass.png.meta")
+        );
+        assert!(is_meta_file(Path::new("textures/grass.png.meta")));
+        assert!(!is_meta_file(Path::new("textures/grass.png")));
+    }
+
+    #[test]
+    fn layouts_convert_both_ways() {
+        let dir = TempDir::new("convert");
+
+        let mut manifest = Manifest::new();
+        manifest.assets.push(asset_info("textures/grass.png"));
+        manifest.assets.push(asset_info("model"));
+        manifest.sub_manifests.push(SubManifest {
+            parent_asset: manifest.assets[1].asset_id,
+            manifest_dir: PathBuf::from("model"),
+        });
+        manifest.save(&dir).unwrap();
+        std::fs::create_dir_all(dir.join("model")).unwrap();
+        std::fs::write(dir.join("model/manifest.toml"), "assets = []").unwrap();
+
+        convert_layout(&dir, ManifestLayout::Sidecar).unwrap();
+        assert!(dir.join("textures/grass.png.meta").is_file());
+        assert!(dir.join("model.meta").is_file());
+
+        // Loaded twice, the second time from the index
+        for _ in 0..2 {
+            let loaded = Manifest::load(&dir).unwrap();
+
+            assert_eq!(loaded.layout, ManifestLayout::Sidecar);
+            assert_eq!(loaded.assets.len(), 2);
+            assert_eq!(loaded.sub_manifests.len(), 1);
+            assert!(dir.join(INDEX_FILE).is_file());
+        }
+
+        // Removed assets lose their meta file
+        let mut loaded = Manifest::load(&dir).unwrap();
+        loaded
+            .assets
+            .retain(|asset_info| asset_info.relative_path != Path::new("model"));
+        loaded.sub_manifests.clear();
+        loaded.save(&dir).unwrap();
+        assert!(!dir.join("model.meta").exists());
+        assert_eq!(Manifest::load(&dir).unwrap().assets.len(), 1);
+
+        convert_layout(&dir, ManifestLayout::Single).unwrap();
+        assert!(!dir.join("textures/grass.png.meta").exists());
+        assert!(!dir.join(INDEX_FILE).exists());
+
+        let loaded = Manifest::load(&dir).unwrap();
+        assert_eq!(loaded.layout, ManifestLayout::Single);
+        assert_eq!(loaded.assets.len(), 1);
+    }
+
+    #[test]
+    fn saving_keeps_meta_files_added_since_loading() {
+        let dir = TempDir::new("added");
+
+        let mut manifest = Manifest::new();
+        manifest.layout = ManifestLayout::Sidecar;
+        manifest.assets.push(asset_info("grass.png"));
+        manifest.assets.push(asset_info("stone.png"));
+        manifest.save(&dir).unwrap();
+
+        let mut loaded = Manifest::load(&dir).unwrap();
+
+        // Imported by someone else after loading
+        let other = MetaFile {
+            sub_manifest: false,
+            asset: asset_info("dirt.png"),
+        };
+        std::fs::write(dir.join("dirt.png.meta"), toml::to_string(&other).unwrap()).unwrap();
+
+        loaded
+            .assets
+            .retain(|asset_info| asset_info.relative_path != Path::new("stone.png"));
+        loaded.save(&dir).unwrap();
+
+        assert!(!dir.join("stone.png.meta").exists());
+        assert!(dir.join("dirt.png.meta").is_file());
+        assert_eq!(Manifest::load(&dir).unwrap().assets.len(), 2);
+    }
+
+    #[test]
+    fn only_meta_files_change_the_manifest() {
+        let dir = TempDir::new("modified");
+
+        let mut manifest = Manifest::new();
+        manifest.layout = ManifestLayout::Sidecar;
+        manifest.assets.push(asset_info("textures/grass.png"));
+        manifest.save(&dir).unwrap();
+        std::fs::write(dir.join("textures/grass.png"), [1, 2, 3]).unwrap();
+
+        let source = DirectorySource::new(dir.to_path_buf());
+        let before = source.manifest_modified();
+        assert!(before.is_some());
+
+        let later = SystemTime::now() + std::time::Duration::from_secs(3600);
+        let set_modified = |relative_path: &str| {
+            std::fs::File::options()
+                .write(true)
+                .open(dir.join(relative_path))
+                .unwrap()
+                .set_modified(later)
+                .unwrap();
+        };
+
+        // Packed files changing doesn't touch the manifest
+        set_modified("textures/grass.png");
+        assert_eq!(source.manifest_modified(), before);
+
+        set_modified("textures/grass.png.meta");
+        assert_eq!(source.manifest_modified(), Some(later));
+    }
+}
@@ -0,0 +1,343 @@
+//! Asset sources are where the asset server reads packed asset files and manifests from.
+//! Several sources can be mounted at once, see `AssetServer::mount_source`.
+
+use std::{
+    fmt::Debug,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+
+use hashbrown::HashMap;
+use parking_lot::RwLock;
+
+use crate::{
+    manifest::{Manifest, ManifestLayout, ManifestReadError},
+    pack::{entry_key, PackArchiveError, PackReader},
+    server::last_modified,
+    sidecar::{self, INDEX_FILE},
+};
+
+/// A virtual filesystem that assets are read from.
+/// All paths are relative to the root of the source.
+pub trait AssetSource: Debug + Send + Sync + 'static {
+    /// Human readable description of the source, used for logging.
+    fn name(&self) -> String;
+
+    /// Reads the whole file at the relative path.
+    fn read(&self, relative_path: &Path) -> io::Result<Vec<u8>>;
+
+    /// Opens the file at the relative path for streaming reads.
+    fn open(&self, relative_path: &Path) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(io::Cursor::new(self.read(relative_path)?)))
+    }
+
+    fn exists(&self, relative_path: &Path) -> bool;
+
+    /// Lists every file under the relative directory, recursively.
+    /// Returned paths are relative to the root of the source.
+    fn list(&self, relative_dir: &Path) -> io::Result<Vec<PathBuf>>;
+
+    /// Modification time of a file. For directories, the latest modification time of any file inside.
+    fn modified(&self, relative_path: &Path) -> Option<SystemTime>;
+
+    /// Reads and parses `manifest.toml` at the root of the source, along with its sub-manifests.
+    /// With the sidecar layout, every meta file in the source is read as well.
+    fn read_manifest(&self) -> Result<Manifest, ManifestReadError> {
+        let read = |relative_path: &Path| self.read(relative_path);
+        let mut manifest = Manifest::load_with(&read)?;
+
+        if manifest.layout == ManifestLayout::Sidecar {
+            let meta_files = sidecar::read_meta_files(&read, self.list(Path::new(""))?)?;
+            manifest.add_meta_files(meta_files, &read)?;
+        }
+
+        Ok(manifest)
+    }
+
+    /// Modification time of the manifest, used to detect changes when hot reloading.
+    fn manifest_modified(&self) -> Option<SystemTime> {
+        self.modified(Path::new("manifest.toml"))
+    }
+}
+
+/// Loose files in a directory on disk, used during development.
+#[derive(Debug, Clone)]
+pub struct DirectorySource {
+    root: PathBuf,
+}
+
+impl DirectorySource {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+}
+
+impl AssetSource for DirectorySource {
+    fn name(&self) -> String {
+        format!("directory {:?}", self.root)
+    }
+
+    fn read(&self, relative_path: &Path) -> io::Result<Vec<u8>> {
+        std::fs::read(self.root.join(relative_path))
+    }
+
+    fn open(&self, relative_path: &Path) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(io::BufReader::new(std::fs::File::open(
+            self.root.join(relative_path),
+        )?)))
+    }
+
+    fn exists(&self, relative_path: &Path) -> bool {
+        self.root.join(relative_path).exists()
+    }
+
+    fn list(&self, relative_dir: &Path) -> io::Result<Vec<PathBuf>> {
+        fn visit(root: &Path, dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+            for entry in std::fs::read_dir(dir)? {
+                let path = entry?.path();
+
+                if path.is_dir() {
+                    visit(root, &path, files)?;
+                } else {
+                    // Can't fail, `dir` is always inside `root`
+                    files.push(path.strip_prefix(root).unwrap().to_path_buf());
+                }
+            }
+
+            Ok(())
+        }
+
+        let mut files = Vec::new();
+        visit(&self.root, &self.root.join(relative_dir), &mut files)?;
+        Ok(files)
+    }
+
+    fn modified(&self, relative_path: &Path) -> Option<SystemTime> {
+        last_modified(&self.root.join(relative_path))
+    }
+
+    /// Uses the cached index of the meta files with the sidecar layout.
+    fn read_manifest(&self) -> Result<Manifest, ManifestReadError> {
+        Manifest::load(&self.root)
+    }
+
+    /// With the sidecar layout any meta file can change the manifest, so they are checked along with
+    /// the index, see `sidecar::meta_files_modified`. The index only exists with that layout.
+    fn manifest_modified(&self) -> Option<SystemTime> {
+        let manifest_modified = self.modified(Path::new("manifest.toml"));
+
+        if self.root.join(INDEX_FILE).exists() {
+            manifest_modified.max(sidecar::meta_files_modified(&self.root))
+        } else {
+            manifest_modified
+        }
+    }
+}
+
+/// Contents and modification time of each file, by the path as returned by `entry_key`.
+type MemoryFiles = HashMap<String, (Arc<[u8]>, SystemTime)>;
+
+/// Files held in memory. Useful for tests and for assets generated at runtime.
+#[derive(Debug, Default)]
+pub struct MemorySource {
+    name: String,
+    files: RwLock<MemoryFiles>,
+}
+
+impl MemorySource {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            files: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Adds or replaces a file. Its modification time is set to now.
+    pub fn insert(&self, relative_path: impl AsRef<Path>, data: impl Into<Arc<[u8]>>) {
+        self.files.write().insert(
+            entry_key(relative_path.as_ref()),
+            (data.into(), SystemTime::now()),
+        );
+    }
+
+    /// Serialises the manifest to `manifest.toml`.
+    pub fn insert_manifest(&self, manifest: &Manifest) -> Result<(), toml::ser::Error> {
+        self.insert("manifest.toml", toml::to_string(manifest)?.into_bytes());
+        Ok(())
+    }
+
+    pub fn remove(&self, relative_path: impl AsRef<Path>) -> bool {
+        self.files
+            .write()
+            .remove(&entry_key(relative_path.as_ref()))
+            .is_some()
+    }
+}
+
+impl AssetSource for MemorySource {
+    fn name(&self) -> String {
+        format!("memory \"{}\"", self.name)
+    }
+
+    fn read(&self, relative_path: &Path) -> io::Result<Vec<u8>> {
+        self.files
+            .read()
+            .get(&entry_key(relative_path))
+            .map(|(data, _)| data.to_vec())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("{:?} not found in {}", relative_path, self.name()),
+                )
+            })
+    }
+
+    fn exists(&self, relative_path: &Path) -> bool {
+        let key = entry_key(relative_path);
+        let prefix = format!("{}/", key);
+
+        self.files
+            .read()
+            .keys()
+            .any(|k| *k == key || k.starts_with(&prefix))
+    }
+
+    fn list(&self, relative_dir: &Path) -> io::Result<Vec<PathBuf>> {
+        let key = entry_key(relative_dir);
+        let prefix = if key.is_empty() {
+            String::new()
+        } else {
+            format!("{}/", key)
+        };
+
+        Ok(self
+            .files
+            .read()
+            .keys()
+            .filter(|k| k.starts_with(&prefix))
+            .map(PathBuf::from)
+            .collect())
+    }
+
+    fn modified(&self, relative_path: &Path) -> Option<SystemTime> {
+        let key = entry_key(relative_path);
+        let prefix = format!("{}/", key);
+
+        self.files
+            .read()
+            .iter()
+            .filter(|(k, _)| **k == key || k.starts_with(&prefix))
+            .map(|(_, (_, modified))| *modified)
+            .max()
+    }
+}
+
+/// A `.cpak` archive, used when shipping.
+/// The archive is reopened whenever the manifest is re-read, so rebuilt packs are picked up.
+#[derive(Debug)]
+pub struct PackSource {
+    path: PathBuf,
+    reader: RwLock<Arc<PackReader>>,
+}
+
+impl PackSource {
+    pub fn open(path: &Path) -> Result<Self, PackArchiveError> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            reader: RwLock::new(Arc::new(PackReader::open(path)?)),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn reader(&self) -> Arc<PackReader> {
+        self.reader.read().clone()
+    }
+}
+
+impl AssetSource for PackSource {
+    fn name(&self) -> String {
+        format!("pack {:?}", self.path)
+    }
+
+    fn read(&self, relative_path: &Path) -> io::Result<Vec<u8>> {
+        self.reader().read(relative_path)
+    }
+
+    fn exists(&self, relative_path: &Path) -> bool {
+        !self.list(relative_path).map_or(true, |files| files.is_empty())
+    }
+
+    fn list(&self, relative_dir: &Path) -> io::Result<Vec<PathBuf>> {
+        let key = entry_key(relative_dir);
+        let prefix = format!("{}/", key);
+
+        Ok(self
+            .reader()
+            .toc()
+            .entries
+            .keys()
+            .filter(|k| key.is_empty() || **k == key || k.starts_with(&prefix))
+            .map(PathBuf::from)
+            .collect())
+    }
+
+    /// Archives are immutable once written, so this is the archive's own modification time.
+    fn modified(&self, _relative_path: &Path) -> Option<SystemTime> {
+        self.reader().modified()
+    }
+
+    fn read_manifest(&self) -> Result<Manifest, ManifestReadError> {
+        let reader = PackReader::open(&self.path).map_err(|e| match e {
+            PackArchiveError::Io(e) => ManifestReadError::Io(e),
+            PackArchiveError::ManifestParse(e) => ManifestReadError::Toml(e),
+            e => ManifestReadError::Io(io::Error::new(io::ErrorKind::InvalidData, e)),
+        })?;
+
+        let manifest = reader.manifest().clone();
+        *self.reader.write() = Arc::new(reader);
+
+        Ok(manifest)
+    }
+
+    fn manifest_modified(&self) -> Option<SystemTime> {
+        last_modified(&self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_source_read_list() {
+        let source = MemorySource::new("test");
+        source.insert("textures/a.png", vec![1, 2, 3]);
+        source.insert("./meshes//b.mesh", vec![4]);
+
+        assert_eq!(source.read(Path::new("textures/a.png")).unwrap(), vec![1, 2, 3]);
+        assert_eq!(source.read(Path::new("meshes/b.mesh")).unwrap(), vec![4]);
+        assert!(source.read(Path::new("missing")).is_err());
+
+        assert!(source.exists(Path::new("textures")));
+        assert!(!source.exists(Path::new("text")));
+
+        let mut all = source.list(Path::new("")).unwrap();
+        all.sort();
+        assert_eq!(
+            all,
+            vec![PathBuf::from("meshes/b.mesh"), PathBuf::from("textures/a.png")]
+        );
+
+        assert!(source.remove("textures/a.png"));
+        assert!(source.modified(Path::new("textures")).is_none());
+    }
+}
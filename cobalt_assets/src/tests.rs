@@ -109,3 +109,76 @@
 //         assert!(result.is_err());
 //     }
 // }
+
+/// Fixtures shared by the unit tests of the crate.
+#[cfg(test)]
+pub(crate) mod helpers {
+    use std::{
+        ops::Deref,
+        path::{Path, PathBuf},
+        sync::atomic::{AtomicUsize, Ordering},
+        time::SystemTime,
+    };
+
+    use crate::{
+        asset::AssetID,
+        manifest::{AssetInfo, ExtraAssetInfo, PackInfo},
+    };
+
+    static TEMP_DIR_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    /// An empty directory under the system temp directory, removed with its contents on drop so
+    /// failing tests don't leave it behind.
+    pub(crate) struct TempDir(PathBuf);
+
+    impl TempDir {
+        pub(crate) fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "cobalt_assets_{}_{}_{}",
+                name,
+                std::process::id(),
+                TEMP_DIR_COUNT.fetch_add(1, Ordering::Relaxed)
+            ));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+
+            Self(dir)
+        }
+    }
+
+    impl Deref for TempDir {
+        type Target = Path;
+
+        fn deref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl AsRef<Path> for TempDir {
+        fn as_ref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// A "Test" asset at `relative_path`, named after it, with a new ID.
+    pub(crate) fn asset_info(relative_path: &str) -> AssetInfo {
+        AssetInfo {
+            asset_id: AssetID::generate(),
+            relative_path: PathBuf::from(relative_path),
+            pack: PackInfo::default(),
+            name: relative_path.to_string(),
+            timestamp: SystemTime::now(),
+            type_name: "Test".to_string(),
+            extra: ExtraAssetInfo::new(),
+            dependencies: Vec::new(),
+            source: None,
+            tags: Vec::new(),
+        }
+    }
+}
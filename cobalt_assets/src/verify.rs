@@ -0,0 +1,398 @@
+//! Checking the manifest against the files on disk, and fixing what can be fixed automatically.
+
+use std::{
+    fmt::{self, Display, Formatter},
+    path::{Path, PathBuf},
+};
+
+use hashbrown::HashMap;
+use path_clean::PathClean;
+
+use crate::{
+    asset::AssetID,
+    manifest::{AssetInfo, ExtraAssetInfo, Manifest, ManifestReadError, PackInfo},
+    sidecar::{is_meta_file, INDEX_FILE},
+};
+
+/// Problems found by `Manifest::verify`.
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct ManifestReport {
+    /// Assets whose packed file or directory doesn't exist.
+    pub missing_files: Vec<AssetID>,
+    /// Files in the assets directory that no asset refers to, relative to the assets directory.
+    pub orphan_files: Vec<PathBuf>,
+    /// Names shared by several assets. `AssetServer::find_asset_by_name` rejects these.
+    pub duplicate_names: Vec<(String, Vec<AssetID>)>,
+    /// Packed paths shared by several assets.
+    pub duplicate_paths: Vec<(PathBuf, Vec<AssetID>)>,
+    /// Assets with a type name that isn't in the known types passed to `verify`.
+    pub unknown_types: Vec<(AssetID, String)>,
+    /// Pairs of (asset, dependency) where the dependency isn't in the manifest.
+    pub missing_dependencies: Vec<(AssetID, AssetID)>,
+}
+
+impl ManifestReport {
+    pub fn is_ok(&self) -> bool {
+        self.missing_files.is_empty()
+            && self.orphan_files.is_empty()
+            && self.duplicate_names.is_empty()
+            && self.duplicate_paths.is_empty()
+            && self.unknown_types.is_empty()
+            && self.missing_dependencies.is_empty()
+    }
+}
+
+impl Display for ManifestReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.is_ok() {
+            return writeln!(f, "No problems found.");
+        }
+
+        for asset_id in &self.missing_files {
+            writeln!(f, "Missing file: [{}]", asset_id.uuid())?;
+        }
+
+        for path in &self.orphan_files {
+            writeln!(f, "Orphan file: {:?}", path)?;
+        }
+
+        for (name, asset_ids) in &self.duplicate_names {
+            writeln!(f, "Duplicate name \"{}\": {} assets", name, asset_ids.len())?;
+        }
+
+        for (path, asset_ids) in &self.duplicate_paths {
+            writeln!(f, "Duplicate path {:?}: {} assets", path, asset_ids.len())?;
+        }
+
+        for (asset_id, type_name) in &self.unknown_types {
+            writeln!(f, "Unknown type \"{}\": [{}]", type_name, asset_id.uuid())?;
+        }
+
+        for (asset_id, dependency) in &self.missing_dependencies {
+            writeln!(
+                f,
+                "Missing dependency [{}] of [{}]",
+                dependency.uuid(),
+                asset_id.uuid()
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Given the path of an orphan file relative to the assets directory, returns the type name to
+/// register it as, or `None` to leave it alone.
+pub type AdoptFn<'a> = &'a dyn Fn(&Path) -> Option<String>;
+
+/// What `Manifest::repair` is allowed to change.
+#[derive(Default)]
+pub struct RepairOptions<'a> {
+    /// Remove entries whose packed file or directory is missing.
+    pub prune_missing: bool,
+    /// Add orphan files to the manifest, see `AdoptFn`.
+    pub adopt_orphans: Option<AdoptFn<'a>>,
+}
+
+/// Changes made by `Manifest::repair`.
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct RepairSummary {
+    pub pruned: Vec<AssetID>,
+    pub adopted: Vec<AssetID>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ManifestRepairError {
+    #[error("Failed to read manifest file")]
+    ManifestRead(#[from] ManifestReadError),
+    #[error("Failed to write manifest file")]
+    ManifestWrite(#[from] crate::manifest::ManifestWriteError),
+}
+
+impl Manifest {
+    /// Checks the manifest against the files in the assets directory.
+    /// `known_types` are the asset type names that can be loaded, if empty the check is skipped.
+    pub fn verify(&self, assets_dir: &Path, known_types: &[String]) -> ManifestReport {
+        let mut report = ManifestReport::default();
+
+        let mut names: HashMap<&str, Vec<AssetID>> = HashMap::new();
+        let mut paths: HashMap<PathBuf, Vec<AssetID>> = HashMap::new();
+
+        for asset_info in self.all_assets() {
+            if !assets_dir.join(&asset_info.relative_path).exists() {
+                report.missing_files.push(asset_info.asset_id);
+            }
+
+            names
+                .entry(asset_info.name.as_str())
+                .or_default()
+                .push(asset_info.asset_id);
+
+            paths
+                .entry(asset_info.relative_path.clean())
+                .or_default()
+                .push(asset_info.asset_id);
+
+            if !known_types.is_empty() && !known_types.contains(&asset_info.type_name) {
+                report
+                    .unknown_types
+                    .push((asset_info.asset_id, asset_info.type_name.clone()));
+            }
+
+            for dependency in &asset_info.dependencies {
+                if self.find_asset(*dependency).is_none() {
+                    report
+                        .missing_dependencies
+                        .push((asset_info.asset_id, *dependency));
+                }
+            }
+        }
+
+        report.duplicate_names = names
+            .into_iter()
+            .filter(|(_, asset_ids)| asset_ids.len() > 1)
+            .map(|(name, asset_ids)| (name.to_string(), asset_ids))
+            .collect();
+
+        let referenced = paths.keys().cloned().collect::<Vec<_>>();
+
+        report.duplicate_paths = paths
+            .into_iter()
+            .filter(|(_, asset_ids)| asset_ids.len() > 1)
+            .collect();
+
+        let mut files = Vec::new();
+        collect_files(assets_dir, assets_dir, &mut files);
+
+        report.orphan_files = files
+            .into_iter()
+            .filter(|file| {
+                // Directory assets own everything inside them
+                *file != Path::new("manifest.toml")
+                    && *file != Path::new(INDEX_FILE)
+                    && !is_meta_file(file)
+                    && !referenced.iter().any(|path| file.starts_with(path))
+            })
+            .collect();
+
+        report.orphan_files.sort();
+
+        report
+    }
+
+    /// Fixes the problems in the report that the options allow.
+    /// Only entries of the main manifest are pruned, sub-manifests belong to their parent asset
+    /// and are dropped along with it. The manifest is not saved, its index is rebuilt.
+    pub fn repair(&mut self, report: &ManifestReport, options: &RepairOptions) -> RepairSummary {
+        let mut summary = RepairSummary::default();
+
+        if options.prune_missing {
+            self.assets.retain(|asset_info| {
+                if report.missing_files.contains(&asset_info.asset_id) {
+                    summary.pruned.push(asset_info.asset_id);
+                    false
+                } else {
+                    true
+                }
+            });
+
+            let orphaned_sub_assets = summary
+                .pruned
+                .iter()
+                .flat_map(|asset_id| self.sub_assets_of(*asset_id))
+                .collect::<Vec<_>>();
+
+            self.sub_manifests
+                .retain(|sub_manifest| !summary.pruned.contains(&sub_manifest.parent_asset));
+            self.sub_assets
+                .retain(|asset_info| !orphaned_sub_assets.contains(&asset_info.asset_id));
+        }
+
+        if let Some(adopt) = options.adopt_orphans {
+            for path in &report.orphan_files {
+                let type_name = match adopt(path) {
+                    Some(type_name) => type_name,
+                    None => continue,
+                };
+
+                let asset_info = AssetInfo {
+                    asset_id: AssetID::generate(),
+                    relative_path: path.clone(),
+                    pack: PackInfo::default(),
+                    name: path.to_string_lossy().into_owned(),
+                    timestamp: std::time::SystemTime::now(),
+                    type_name,
+                    extra: ExtraAssetInfo::new(),
+                    dependencies: Vec::new(),
+                    source: None,
+                    tags: Vec::new(),
+                };
+
+                summary.adopted.push(asset_info.asset_id);
+                self.assets.push(asset_info);
+            }
+        }
+
+        self.rebuild_index();
+
+        summary
+    }
+}
+
+/// Verifies the manifest in the assets directory, repairs it and saves it if anything changed.
+pub fn repair_manifest(
+    assets_dir: &Path,
+    known_types: &[String],
+    options: &RepairOptions,
+) -> Result<(ManifestReport, RepairSummary), ManifestRepairError> {
+    let mut manifest = Manifest::load(assets_dir)?;
+
+    let report = manifest.verify(assets_dir, known_types);
+    let summary = manifest.repair(&report, options);
+
+    if !summary.pruned.is_empty() || !summary.adopted.is_empty() {
+        manifest.save(assets_dir)?;
+    }
+
+    Ok((report, summary))
+}
+
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<PathBuf>) {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+
+        if path.is_dir() {
+            collect_files(root, &path, files);
+        } else if let Ok(relative_path) = path.strip_prefix(root) {
+            files.push(relative_path.to_path_buf());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        manifest::SubManifest,
+        tests::helpers::{asset_info, TempDir},
+    };
+
+    #[test]
+    fn verify_and_repair() {
+        let dir = TempDir::new("repair");
+        std::fs::write(dir.join("present.asset"), [0]).unwrap();
+        std::fs::write(dir.join("stray.png"), [0]).unwrap();
+        std::fs::write(dir.join("manifest.toml"), "").unwrap();
+
+        let present = asset_info("present.asset");
+        let gone = asset_info("gone.asset");
+        // A model whose directory, and so its sub-manifest, was deleted
+        let model = asset_info("model");
+        let mesh = asset_info("model/mesh.asset");
+
+        let mut manifest = Manifest::new();
+        manifest.assets = vec![present.clone(), gone.clone(), model.clone()];
+        manifest.sub_manifests.push(SubManifest {
+            parent_asset: model.asset_id,
+            manifest_dir: PathBuf::from("model"),
+        });
+        manifest.sub_assets.push(mesh.clone());
+
+        let report = manifest.verify(&dir, &[]);
+        assert_eq!(
+            report.missing_files,
+            [gone.asset_id, model.asset_id, mesh.asset_id]
+        );
+        assert_eq!(report.orphan_files, [PathBuf::from("stray.png")]);
+        assert!(!report.is_ok());
+
+        // Nothing is changed without options
+        let summary = manifest.repair(&report, &RepairOptions::default());
+        assert!(summary.pruned.is_empty() && summary.adopted.is_empty());
+        assert_eq!(manifest.assets.len(), 3);
+
+        let adopt =
+            |path: &Path| (path.extension() == Some("png".as_ref())).then(|| "Texture".to_string());
+        let summary = manifest.repair(
+            &report,
+            &RepairOptions {
+                prune_missing: true,
+                adopt_orphans: Some(&adopt),
+            },
+        );
+
+        assert_eq!(summary.pruned, [gone.asset_id, model.asset_id]);
+        assert_eq!(summary.adopted.len(), 1);
+        assert!(manifest.sub_manifests.is_empty());
+        assert!(manifest.sub_assets.is_empty());
+
+        let adopted = manifest.asset(summary.adopted[0]).unwrap();
+        assert_eq!(adopted.relative_path, PathBuf::from("stray.png"));
+        assert_eq!(adopted.type_name, "Texture");
+        assert_eq!(manifest.asset(present.asset_id).unwrap().name, present.name);
+
+        assert!(manifest.verify(&dir, &[]).is_ok());
+    }
+
+    #[test]
+    fn repair_keeps_lookups_current() {
+        let dir = TempDir::new("lookups");
+        std::fs::write(dir.join("stray.png"), [0]).unwrap();
+        std::fs::write(dir.join("manifest.toml"), "").unwrap();
+
+        let gone = asset_info("gone.asset");
+
+        let mut manifest = Manifest::new();
+        manifest.assets = vec![gone.clone()];
+        manifest.rebuild_index();
+
+        let adopt = |_: &Path| Some("Texture".to_string());
+        let report = manifest.verify(&dir, &[]);
+        let summary = manifest.repair(
+            &report,
+            &RepairOptions {
+                prune_missing: true,
+                adopt_orphans: Some(&adopt),
+            },
+        );
+
+        // One asset replaced another, the number of assets is the same
+        assert_eq!(manifest.assets.len(), 1);
+        assert!(manifest.asset(gone.asset_id).is_none());
+        assert_eq!(
+            manifest.asset(summary.adopted[0]).unwrap().relative_path,
+            PathBuf::from("stray.png")
+        );
+        assert_eq!(manifest.assets_of_type("Texture").len(), 1);
+    }
+
+    #[test]
+    fn reports_duplicates_and_unknown_types() {
+        let dir = TempDir::new("duplicates");
+        std::fs::write(dir.join("a.asset"), [0]).unwrap();
+
+        let first = asset_info("a.asset");
+        let mut second = asset_info("./a.asset");
+        second.name = first.name.clone();
+        second.type_name = "Unknown".to_string();
+        second.dependencies.push(AssetID::generate());
+
+        let mut manifest = Manifest::new();
+        manifest.assets = vec![first, second.clone()];
+
+        let report = manifest.verify(&dir, &["Test".to_string()]);
+        assert_eq!(report.duplicate_names.len(), 1);
+        assert_eq!(report.duplicate_paths.len(), 1);
+        assert_eq!(
+            report.unknown_types,
+            [(second.asset_id, "Unknown".to_string())]
+        );
+        assert_eq!(report.missing_dependencies.len(), 1);
+        assert!(report.missing_files.is_empty());
+    }
+}
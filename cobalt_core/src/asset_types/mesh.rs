@@ -1,18 +1,291 @@
-use std::io::{BufReader, Read};
+use std::path::Path;
 
-use cobalt_assets::asset::{AssetReadError, AssetTrait};
-use cobalt_graphics::vertex::UvNormalVertex;
+use cobalt_assets::{
+    asset::{AssetImportError, AssetMemory, AssetReadContext, AssetReadError, AssetTrait},
+    manifest::{AssetInfo, ExtraAssetInfo, PackInfo},
+    packed::{read_packed_asset, write_packed_asset},
+    source::AssetSource,
+};
+use cobalt_graphics::vertex::{UvNormalTangentVertex, UvNormalVertex};
 use wgpu::util::DeviceExt;
 
-use crate::{renderer::mesh::Mesh, types::aabb::AABB};
+use crate::{
+    renderer::mesh::{Mesh, MeshLod},
+    types::aabb::AABB,
+};
+
+/// Index buffer of a mesh, `U16` is used when every vertex fits in it.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub enum MeshIndices {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+impl MeshIndices {
+    pub fn len(&self) -> usize {
+        match self {
+            MeshIndices::U16(indices) => indices.len(),
+            MeshIndices::U32(indices) => indices.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn to_u32(&self) -> Vec<u32> {
+        match self {
+            MeshIndices::U16(indices) => indices.iter().map(|index| *index as u32).collect(),
+            MeshIndices::U32(indices) => indices.clone(),
+        }
+    }
+
+    /// `U16` if every index fits, `U32` otherwise.
+    pub fn smallest(indices: Vec<u32>) -> Self {
+        if indices.iter().all(|index| *index <= u16::MAX as u32) {
+            MeshIndices::U16(indices.into_iter().map(|index| index as u16).collect())
+        } else {
+            MeshIndices::U32(indices)
+        }
+    }
+
+    pub fn format(&self) -> wgpu::IndexFormat {
+        match self {
+            MeshIndices::U16(_) => wgpu::IndexFormat::Uint16,
+            MeshIndices::U32(_) => wgpu::IndexFormat::Uint32,
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        match self {
+            MeshIndices::U16(indices) => bytemuck::cast_slice(indices),
+            MeshIndices::U32(indices) => bytemuck::cast_slice(indices),
+        }
+    }
+}
 
 #[derive(serde::Serialize, serde::Deserialize)]
 pub struct MeshAssetBuffer {
-    pub index_buffer: Vec<u32>,
-    pub vertex_buffer: Vec<UvNormalVertex>,
+    /// Indices of the base mesh followed by the indices of every LOD level.
+    pub index_buffer: MeshIndices,
+    pub vertex_buffer: Vec<UvNormalTangentVertex>,
+    /// Index count of the base mesh.
     pub num_indices: u32,
     pub local_aabb: AABB,
     pub has_uv: bool,
+    /// Simplified levels, from most to least detailed. They share the vertex buffer.
+    pub lods: Vec<MeshLod>,
+}
+
+/// Format 2, before LOD levels were added.
+#[derive(serde::Deserialize)]
+struct MeshAssetBufferV2 {
+    index_buffer: MeshIndices,
+    vertex_buffer: Vec<UvNormalTangentVertex>,
+    num_indices: u32,
+    local_aabb: AABB,
+    has_uv: bool,
+}
+
+impl From<MeshAssetBufferV2> for MeshAssetBuffer {
+    fn from(v2: MeshAssetBufferV2) -> Self {
+        Self {
+            index_buffer: v2.index_buffer,
+            vertex_buffer: v2.vertex_buffer,
+            num_indices: v2.num_indices,
+            local_aabb: v2.local_aabb,
+            has_uv: v2.has_uv,
+            lods: Vec::new(),
+        }
+    }
+}
+
+/// Meshes imported before tangents were added.
+#[derive(serde::Deserialize)]
+struct LegacyMeshAssetBuffer {
+    index_buffer: Vec<u32>,
+    vertex_buffer: Vec<UvNormalVertex>,
+    num_indices: u32,
+    local_aabb: AABB,
+    has_uv: bool,
+}
+
+impl From<LegacyMeshAssetBuffer> for MeshAssetBuffer {
+    fn from(legacy: LegacyMeshAssetBuffer) -> Self {
+        let mut vertex_buffer = legacy
+            .vertex_buffer
+            .into_iter()
+            .map(UvNormalTangentVertex::from)
+            .collect::<Vec<_>>();
+
+        generate_tangents(&mut vertex_buffer, &legacy.index_buffer);
+
+        Self {
+            index_buffer: MeshIndices::U32(legacy.index_buffer),
+            vertex_buffer,
+            num_indices: legacy.num_indices,
+            local_aabb: legacy.local_aabb,
+            has_uv: legacy.has_uv,
+            lods: Vec::new(),
+        }
+    }
+}
+
+impl MeshAssetBuffer {
+    /// Extra asset info key holding the format version of the packed mesh.
+    /// Only files packed before the header held a format version need it,
+    /// meshes without either are read as the legacy format and get tangents generated.
+    pub const FORMAT_KEY: &'static str = "mesh_format";
+    /// Tangents, 16 or 32 bit indices and LOD levels.
+    const FORMAT: u32 = 3;
+
+    /// Writes the packed mesh to `output_path`.
+    /// Returns the extra asset info needed to read it back.
+    pub fn write_packed(
+        &self,
+        pack: &PackInfo,
+        output_path: &Path,
+    ) -> Result<ExtraAssetInfo, AssetImportError> {
+        write_packed_asset::<Mesh>(output_path, pack, &bincode::serialize(self)?)
+            .map_err(AssetImportError::WriteError)?;
+
+        let mut extra_info = ExtraAssetInfo::new();
+        extra_info
+            .0
+            .insert(Self::FORMAT_KEY.to_string(), Self::FORMAT.to_string());
+
+        Ok(extra_info)
+    }
+
+    /// Reads a mesh packed before the header held a format version, the extra asset info says which one.
+    fn deserialize_unversioned(asset_info: &AssetInfo, data: &[u8]) -> Result<Self, AssetReadError> {
+        match asset_info.extra.0.get(Self::FORMAT_KEY) {
+            Some(format) if format.parse::<u32>() == Ok(Self::FORMAT) => {
+                bincode::deserialize(data).map_err(AssetReadError::DeserializeError)
+            }
+            Some(format) if format.parse::<u32>() == Ok(2) => {
+                bincode::deserialize::<MeshAssetBufferV2>(data)
+                    .map(Self::from)
+                    .map_err(AssetReadError::DeserializeError)
+            }
+            Some(format) => Err(AssetReadError::ParseError(
+                format!("Unsupported mesh format \"{}\", reimport the mesh", format).into(),
+            )),
+            None => bincode::deserialize::<LegacyMeshAssetBuffer>(data)
+                .map(Self::from)
+                .map_err(AssetReadError::DeserializeError),
+        }
+    }
+}
+
+/// Generates a tangent for every vertex from the triangle UVs, for normal mapping.
+/// Triangle tangents are accumulated on shared vertices and made perpendicular to the normal,
+/// the same approach MikkTSpace takes without splitting vertices.
+///
+/// UVs have their origin at the top left, so the bitangent points towards decreasing v
+/// (up the texture), matching glTF tangents. `tangent.w` holds its handedness.
+/// Vertices without a usable UV mapping get an arbitrary tangent perpendicular to the normal.
+pub(crate) fn generate_tangents(vertices: &mut [UvNormalTangentVertex], indices: &[u32]) {
+    let mut tangents = vec![[0.0f32; 3]; vertices.len()];
+    let mut bitangents = vec![[0.0f32; 3]; vertices.len()];
+
+    for triangle in indices.chunks_exact(3) {
+        let [i0, i1, i2] = [
+            triangle[0] as usize,
+            triangle[1] as usize,
+            triangle[2] as usize,
+        ];
+        let [v0, v1, v2] = [vertices[i0], vertices[i1], vertices[i2]];
+
+        let e1 = sub(v1.position, v0.position);
+        let e2 = sub(v2.position, v0.position);
+
+        let (du1, dv1) = (v1.uv[0] - v0.uv[0], v1.uv[1] - v0.uv[1]);
+        let (du2, dv2) = (v2.uv[0] - v0.uv[0], v2.uv[1] - v0.uv[1]);
+
+        let det = du1 * dv2 - du2 * dv1;
+
+        if det.abs() <= f32::EPSILON {
+            continue;
+        }
+
+        let r = 1.0 / det;
+
+        let tangent = scale(sub(scale(e1, dv2), scale(e2, dv1)), r);
+        // Negated to point towards decreasing v
+        let bitangent = scale(sub(scale(e1, du2), scale(e2, du1)), r);
+
+        for i in [i0, i1, i2] {
+            tangents[i] = add(tangents[i], tangent);
+            bitangents[i] = add(bitangents[i], bitangent);
+        }
+    }
+
+    for (i, vertex) in vertices.iter_mut().enumerate() {
+        let normal = vertex.normal;
+
+        // Gram-Schmidt orthogonalise
+        let tangent = sub(tangents[i], scale(normal, dot(normal, tangents[i])));
+
+        let tangent = match normalize(tangent) {
+            Some(tangent) => tangent,
+            None => perpendicular(normal),
+        };
+
+        let handedness = if dot(cross(normal, tangent), bitangents[i]) < 0.0 {
+            -1.0
+        } else {
+            1.0
+        };
+
+        vertex.tangent = [tangent[0], tangent[1], tangent[2], handedness];
+    }
+}
+
+/// Any unit vector perpendicular to `normal`.
+fn perpendicular(normal: [f32; 3]) -> [f32; 3] {
+    // Cross with the axis least aligned with the normal
+    let axis = if normal[0].abs() < 0.9 {
+        [1.0, 0.0, 0.0]
+    } else {
+        [0.0, 1.0, 0.0]
+    };
+
+    normalize(cross(normal, axis)).unwrap_or([1.0, 0.0, 0.0])
+}
+
+fn add(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn scale(a: [f32; 3], s: f32) -> [f32; 3] {
+    [a[0] * s, a[1] * s, a[2] * s]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn normalize(a: [f32; 3]) -> Option<[f32; 3]> {
+    let length = dot(a, a).sqrt();
+
+    if length <= f32::EPSILON {
+        None
+    } else {
+        Some(scale(a, 1.0 / length))
+    }
 }
 
 impl AssetTrait for Mesh {
@@ -24,36 +297,41 @@ impl AssetTrait for Mesh {
         cobalt_assets::asset::AssetFileSystemType::File
     }
 
-    fn read(
-        asset_info: &cobalt_assets::manifest::AssetInfo,
-        assets_dir: &std::path::Path,
-        graphics: &cobalt_graphics::context::Graphics,
-    ) -> Result<Self, AssetReadError> {
-        let abs_path = assets_dir.join(&asset_info.relative_path);
+    fn format_version() -> u32 {
+        MeshAssetBuffer::FORMAT
+    }
 
-        let data = if let Some(_) = asset_info.pack.compression {
-            let reader = BufReader::new(std::fs::File::open(&abs_path)?);
+    fn migrate(
+        version: u32,
+        payload: Vec<u8>,
+        asset_info: &AssetInfo,
+    ) -> Result<Vec<u8>, AssetReadError> {
+        match version {
+            0 => {
+                let mesh_buffer = MeshAssetBuffer::deserialize_unversioned(asset_info, &payload)?;
 
-            let mut decoder = zstd::Decoder::new(reader).map_err(|e| AssetReadError::Io(e))?;
+                Ok(bincode::serialize(&mesh_buffer)?)
+            }
+            _ => Err(AssetReadError::UnsupportedVersion(Self::type_name(), version)),
+        }
+    }
 
-            let mut decoded = Vec::new();
-            decoder
-                .read_to_end(&mut decoded)
-                .map_err(|e| AssetReadError::Io(e))?;
+    fn read(
+        asset_info: &cobalt_assets::manifest::AssetInfo,
+        source: &dyn AssetSource,
+        context: &AssetReadContext,
+    ) -> Result<Self, AssetReadError> {
+        let data = read_packed_asset::<Self>(source, &asset_info.relative_path, asset_info)?;
 
-            decoded
-        } else {
-            std::fs::read(&abs_path)?
-        };
+        let mesh_buffer: MeshAssetBuffer = bincode::deserialize(&data)?;
 
-        let mesh_buffer: MeshAssetBuffer =
-            bincode::deserialize(&data).map_err(|e| AssetReadError::DeserializeError(e))?;
+        let graphics = context.graphics.read();
 
         let index_buffer = graphics
             .device
             .create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: Some("Index Buffer"),
-                contents: bytemuck::cast_slice(&mesh_buffer.index_buffer),
+                contents: mesh_buffer.index_buffer.as_bytes(),
                 usage: wgpu::BufferUsages::INDEX,
             });
 
@@ -67,10 +345,80 @@ impl AssetTrait for Mesh {
 
         Ok(Mesh {
             index_buffer,
+            index_format: mesh_buffer.index_buffer.format(),
             vertex_buffer,
             num_indices: mesh_buffer.num_indices,
             local_aabb: mesh_buffer.local_aabb,
             has_uv: mesh_buffer.has_uv,
+            lods: mesh_buffer.lods,
         })
     }
+
+    fn memory_size(&self) -> AssetMemory {
+        AssetMemory {
+            cpu: std::mem::size_of::<Self>()
+                + self.lods.capacity() * std::mem::size_of::<MeshLod>(),
+            gpu: (self.vertex_buffer.size() + self.index_buffer.size()) as usize,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vertex(position: [f32; 3], uv: [f32; 2]) -> UvNormalTangentVertex {
+        UvNormalTangentVertex {
+            position,
+            uv,
+            normal: [0.0, 0.0, 1.0],
+            tangent: [0.0; 4],
+        }
+    }
+
+    #[test]
+    fn tangents_follow_uvs() {
+        // u along +x, v along -y (top left origin), so the bitangent is +y
+        let mut vertices = [
+            vertex([0.0, 0.0, 0.0], [0.0, 1.0]),
+            vertex([1.0, 0.0, 0.0], [1.0, 1.0]),
+            vertex([0.0, 1.0, 0.0], [0.0, 0.0]),
+        ];
+
+        generate_tangents(&mut vertices, &[0, 1, 2]);
+
+        for vertex in vertices {
+            assert_eq!(vertex.tangent, [1.0, 0.0, 0.0, 1.0]);
+        }
+    }
+
+    #[test]
+    fn mirrored_uvs_flip_handedness() {
+        let mut vertices = [
+            vertex([0.0, 0.0, 0.0], [0.0, 0.0]),
+            vertex([1.0, 0.0, 0.0], [1.0, 0.0]),
+            vertex([0.0, 1.0, 0.0], [0.0, 1.0]),
+        ];
+
+        generate_tangents(&mut vertices, &[0, 1, 2]);
+
+        assert_eq!(vertices[0].tangent, [1.0, 0.0, 0.0, -1.0]);
+    }
+
+    #[test]
+    fn missing_uvs_give_perpendicular_tangent() {
+        let mut vertices = [
+            vertex([0.0, 0.0, 0.0], [0.0, 0.0]),
+            vertex([1.0, 0.0, 0.0], [0.0, 0.0]),
+            vertex([0.0, 1.0, 0.0], [0.0, 0.0]),
+        ];
+
+        generate_tangents(&mut vertices, &[0, 1, 2]);
+
+        let [x, y, z, _] = vertices[0].tangent;
+        let tangent = [x, y, z];
+
+        assert_eq!(dot(tangent, [0.0, 0.0, 1.0]), 0.0);
+        assert!((dot(tangent, tangent) - 1.0).abs() < 1e-6);
+    }
 }
@@ -1,6 +1,64 @@
 pub mod texture;
 pub mod mesh;
+pub mod model;
+
+use std::path::Path;
+
+use cobalt_assets::{
+    asset::{AssetReadError, AssetTrait},
+    manifest::AssetInfo,
+    packed::read_packed_asset,
+    server::AssetServer,
+    source::AssetSource,
+};
+use cobalt_graphics::texture::TextureType;
+
+use crate::renderer::{deferred::exports::Material, mesh::Mesh};
+
+use self::{model::Model, texture::TextureAsset};
 
 pub mod exports {
     pub use super::texture::TextureAsset;
-}
\ No newline at end of file
+    pub use super::model::{Model, ModelLight, ModelNode, ModelPrimitive, ModelScene};
+}
+
+macro_rules! register_texture_types {
+    ($server:expr, $($variant:ident),*) => {
+        $(
+            $server.register_asset_type::<TextureAsset<{ TextureType::$variant }>>();
+        )*
+    };
+}
+
+/// Registers the asset types defined by the engine, so they can be loaded as dependencies.
+pub fn register_asset_types(server: &mut AssetServer) {
+    server.register_asset_type::<Mesh>();
+    server.register_asset_type::<Material>();
+    server.register_asset_type::<Model>();
+
+    register_texture_types!(
+        server,
+        RGBA32Float,
+        RGBA16Float,
+        RGBA8Unorm,
+        RGBA8UnormSrgb,
+        R32Float,
+        R16Float,
+        R8Unorm,
+        R8Uint,
+        R8Snorm
+    );
+}
+
+/// Reads a packed RON file of an `A`.
+pub(crate) fn read_ron<A: AssetTrait, T: serde::de::DeserializeOwned>(
+    source: &dyn AssetSource,
+    relative_path: &Path,
+    asset_info: &AssetInfo,
+) -> Result<T, AssetReadError> {
+    let data = read_packed_asset::<A>(source, relative_path, asset_info)?;
+
+    let text = String::from_utf8(data).map_err(|e| AssetReadError::ParseError(Box::new(e)))?;
+
+    ron::from_str(&text).map_err(|e| AssetReadError::ParseError(Box::new(e)))
+}
@@ -0,0 +1,424 @@
+use std::{path::PathBuf, sync::Arc};
+
+use cobalt_assets::{
+    asset::{
+        Asset, AssetFileSystemType, AssetID, AssetMemory, AssetReadContext, AssetReadError,
+        AssetTrait,
+    },
+    manifest::AssetInfo,
+    source::AssetSource,
+};
+use cobalt_ecs::{component::Component, entity::Entity, world::World};
+use cobalt_graphics::context::Graphics;
+use hashbrown::HashMap;
+use parking_lot::RwLock;
+use ultraviolet::{Rotor3, Vec3};
+
+use crate::{
+    components::{entity_name::EntityName, transform::Transform},
+    renderer::{
+        camera::{Camera, Projection},
+        deferred::exports::Material,
+        mesh::Mesh,
+        renderable::{mesh::MeshRenderable, Renderable},
+    },
+    types::resource::Resource,
+};
+
+use super::read_ron;
+
+/// Node hierarchy of an imported model, e.g. a glTF scene or the objects of an OBJ file.
+/// Meshes and materials are sub-assets of the model asset, referenced by `AssetID`.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct ModelScene {
+    pub nodes: Vec<ModelNode>,
+    /// Indices of the nodes without parents.
+    pub roots: Vec<usize>,
+}
+
+impl ModelScene {
+    /// Meshes and materials used by the nodes, without duplicates.
+    pub fn dependencies(&self) -> Vec<AssetID> {
+        let mut dependencies = Vec::new();
+
+        for primitive in self.nodes.iter().flat_map(|node| &node.primitives) {
+            for asset_id in std::iter::once(primitive.mesh).chain(primitive.material) {
+                if !dependencies.contains(&asset_id) {
+                    dependencies.push(asset_id);
+                }
+            }
+        }
+
+        dependencies
+    }
+
+    /// Checks that the node indices are in range and form trees, every node reachable from the
+    /// roots at most once, so spawning terminates and creates each node once.
+    pub fn validate(&self) -> Result<(), String> {
+        let mut visited = vec![false; self.nodes.len()];
+        let mut stack = self.roots.clone();
+
+        while let Some(index) = stack.pop() {
+            let Some(node) = self.nodes.get(index) else {
+                return Err(format!(
+                    "Node index {} out of range, the scene has {} nodes",
+                    index,
+                    self.nodes.len()
+                ));
+            };
+
+            if std::mem::replace(&mut visited[index], true) {
+                return Err(format!(
+                    "Node {} is reachable more than once, the nodes have a cycle or a shared child",
+                    index
+                ));
+            }
+
+            stack.extend(&node.children);
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct ModelNode {
+    pub name: Option<String>,
+    pub translation: [f32; 3],
+    /// Quaternion, `[x, y, z, w]`.
+    pub rotation: [f32; 4],
+    pub scale: [f32; 3],
+    /// Indices into `ModelScene::nodes`.
+    pub children: Vec<usize>,
+    /// One per primitive of the node's mesh.
+    pub primitives: Vec<ModelPrimitive>,
+    pub light: Option<ModelLight>,
+    /// Only perspective cameras are imported.
+    #[serde(default)]
+    pub camera: Option<Projection>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct ModelPrimitive {
+    pub mesh: AssetID,
+    /// `None` if the primitive uses the default material.
+    pub material: Option<AssetID>,
+}
+
+/// Lights, as defined by glTF's `KHR_lights_punctual` extension.
+/// Intensities are in the extension's units, candela for point and spot lights and lux for directional lights.
+/// Spawned as a component on the light's node, the renderer does not use them yet.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub enum ModelLight {
+    Directional {
+        color: [f32; 3],
+        intensity: f32,
+    },
+    Point {
+        color: [f32; 3],
+        intensity: f32,
+        range: Option<f32>,
+    },
+    Spot {
+        color: [f32; 3],
+        intensity: f32,
+        range: Option<f32>,
+        inner_cone_angle: f32,
+        outer_cone_angle: f32,
+    },
+}
+
+impl Component for ModelLight {
+    type DeContext<'a> = ();
+    type SerContext<'a> = ();
+
+    fn deserialise<'de, D>(_context: Self::DeContext<'de>, deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de> {
+        serde::Deserialize::deserialize(deserializer)
+    }
+
+    fn serialize<'se, S>(&self, _context: Self::SerContext<'se>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer {
+        serde::Serialize::serialize(self, serializer)
+    }
+}
+
+/// An imported model.
+/// The asset is a directory holding the scene description, with its meshes, materials and textures as sub-assets.
+pub struct Model {
+    name: String,
+    scene: ModelScene,
+    meshes: HashMap<AssetID, Asset<Mesh>>,
+    materials: HashMap<AssetID, Asset<Material>>,
+}
+
+impl Model {
+    /// Name of the scene description inside the asset's directory.
+    pub(crate) const SCENE_FILE: &'static str = "scene.ron";
+
+    pub fn scene(&self) -> &ModelScene {
+        &self.scene
+    }
+
+    pub fn mesh(&self, asset_id: AssetID) -> Option<&Asset<Mesh>> {
+        self.meshes.get(&asset_id)
+    }
+
+    pub fn material(&self, asset_id: AssetID) -> Option<&Asset<Material>> {
+        self.materials.get(&asset_id)
+    }
+
+    /// Creates an entity for every node of the scene and returns the root entity, named after the asset.
+    /// Nodes get a `Transform` and, if they have one, an `EntityName`, a `Camera` or a `ModelLight`.
+    /// Nodes with a single primitive are rendered directly, ones with several get an entity per primitive.
+    /// Primitives without a material use `Material::default`.
+    ///
+    /// NOTE: The ECS has no hierarchy yet, so node transforms are combined with their parents' and `root`,
+    /// moving the root entity later does not move the rest of the scene.
+    /// Cameras are spawned disabled.
+    pub fn spawn(
+        &self,
+        world: &mut World,
+        graphics: &Arc<RwLock<Graphics>>,
+        root: Transform,
+    ) -> Entity {
+        let root_trs = Trs {
+            translation: root.position(),
+            rotation: root.rotation(),
+            scale: root.scale(),
+        };
+
+        let root_entity = world.create_entity();
+        world.add_component(root_entity, root);
+        world.add_component(root_entity, EntityName(self.name.clone()));
+
+        // Only created if a primitive needs it, shared by all of them
+        let mut default_material = None;
+
+        let mut stack = self
+            .scene
+            .roots
+            .iter()
+            .map(|index| (*index, root_trs))
+            .collect::<Vec<_>>();
+
+        while let Some((index, parent_trs)) = stack.pop() {
+            let node = &self.scene.nodes[index];
+
+            let trs = parent_trs.then(&Trs {
+                translation: node.translation.into(),
+                rotation: Rotor3::from_quaternion_array(node.rotation),
+                scale: node.scale.into(),
+            });
+
+            let entity = world.create_entity();
+            world.add_component(entity, trs.transform());
+
+            if let Some(name) = &node.name {
+                world.add_component(entity, EntityName(name.clone()));
+            }
+
+            if let Some(light) = &node.light {
+                world.add_component(entity, light.clone());
+            }
+
+            if let Some(projection) = &node.camera {
+                world.add_component(entity, Camera::new(false, projection.clone()));
+            }
+
+            for (i, primitive) in node.primitives.iter().enumerate() {
+                let mesh = match self.meshes.get(&primitive.mesh) {
+                    Some(mesh) => mesh.clone(),
+                    None => {
+                        log::warn!("Mesh {:?} of node {} is not loaded, skipping it.", primitive.mesh, index);
+                        continue;
+                    }
+                };
+
+                let primitive_entity = if node.primitives.len() == 1 {
+                    entity
+                } else {
+                    let primitive_entity = world.create_entity();
+                    world.add_component(primitive_entity, trs.transform());
+
+                    if let Some(name) = &node.name {
+                        world.add_component(primitive_entity, EntityName(format!("{}/{}", name, i)));
+                    }
+
+                    primitive_entity
+                };
+
+                world.add_component(
+                    primitive_entity,
+                    Renderable::Mesh(MeshRenderable::new(mesh)),
+                );
+
+                match primitive.material.and_then(|material| self.materials.get(&material)) {
+                    Some(material) => {
+                        world.add_component(primitive_entity, material.clone());
+                    }
+                    None => {
+                        let material = default_material
+                            .get_or_insert_with(|| Resource::new(Material::default(graphics)));
+
+                        world.add_component(primitive_entity, material.clone());
+                    }
+                }
+            }
+
+            stack.extend(node.children.iter().map(|child| (*child, trs)));
+        }
+
+        root_entity
+    }
+}
+
+/// Translation, rotation and scale of a node in world space.
+#[derive(Clone, Copy)]
+struct Trs {
+    translation: Vec3,
+    rotation: Rotor3,
+    scale: Vec3,
+}
+
+impl Trs {
+    /// Applies a child's local transform. Exact for uniform scales,
+    /// non-uniform scales of rotated parents can't be represented by a single `Transform`.
+    fn then(&self, local: &Trs) -> Trs {
+        Trs {
+            translation: self.translation + self.rotation * (self.scale * local.translation),
+            rotation: self.rotation * local.rotation,
+            scale: self.scale * local.scale,
+        }
+    }
+
+    fn transform(&self) -> Transform {
+        Transform::with_position_rotation_scale(self.translation, self.rotation, self.scale)
+    }
+}
+
+impl std::fmt::Debug for Model {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Model")
+            .field("nodes", &self.scene.nodes.len())
+            .field("meshes", &self.meshes.len())
+            .field("materials", &self.materials.len())
+            .finish()
+    }
+}
+
+impl AssetTrait for Model {
+    fn type_name() -> String {
+        "Model".to_string()
+    }
+
+    fn imported_fs_type() -> AssetFileSystemType {
+        AssetFileSystemType::Directory
+    }
+
+    /// Unversioned scenes are the same RON.
+    fn migrate(
+        version: u32,
+        payload: Vec<u8>,
+        _asset_info: &AssetInfo,
+    ) -> Result<Vec<u8>, AssetReadError> {
+        match version {
+            0 => Ok(payload),
+            _ => Err(AssetReadError::UnsupportedVersion(Self::type_name(), version)),
+        }
+    }
+
+    fn packed_files(asset_info: &AssetInfo) -> Vec<PathBuf> {
+        vec![asset_info.relative_path.join(Self::SCENE_FILE)]
+    }
+
+    /// Meshes and materials are dependencies, so they are already loaded.
+    fn read(
+        asset_info: &AssetInfo,
+        source: &dyn AssetSource,
+        context: &AssetReadContext,
+    ) -> Result<Self, AssetReadError> {
+        let scene: ModelScene = read_ron::<Self, _>(
+            source,
+            &asset_info.relative_path.join(Self::SCENE_FILE),
+            asset_info,
+        )?;
+
+        scene
+            .validate()
+            .map_err(|e| AssetReadError::ParseError(e.into()))?;
+
+        let mut meshes = HashMap::new();
+        let mut materials = HashMap::new();
+
+        for primitive in scene.nodes.iter().flat_map(|node| &node.primitives) {
+            if !meshes.contains_key(&primitive.mesh) {
+                meshes.insert(primitive.mesh, context.dependency::<Mesh>(primitive.mesh)?);
+            }
+
+            if let Some(material) = primitive.material {
+                if !materials.contains_key(&material) {
+                    materials.insert(material, context.dependency::<Material>(material)?);
+                }
+            }
+        }
+
+        Ok(Self {
+            name: asset_info.name.clone(),
+            scene,
+            meshes,
+            materials,
+        })
+    }
+
+    /// The scene only, meshes and materials are counted as their own assets.
+    fn memory_size(&self) -> AssetMemory {
+        let nodes = self.scene.nodes.iter().map(|node| {
+            std::mem::size_of::<ModelNode>()
+                + node.children.len() * std::mem::size_of::<usize>()
+                + node.primitives.len() * std::mem::size_of::<ModelPrimitive>()
+        });
+
+        AssetMemory {
+            cpu: std::mem::size_of::<Self>() + nodes.sum::<usize>(),
+            gpu: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(children: Vec<usize>) -> ModelNode {
+        ModelNode {
+            name: None,
+            translation: [0.0; 3],
+            rotation: [0.0, 0.0, 0.0, 1.0],
+            scale: [1.0; 3],
+            children,
+            primitives: Vec::new(),
+            light: None,
+            camera: None,
+        }
+    }
+
+    #[test]
+    fn validates_node_hierarchy() {
+        let scene = |nodes, roots| ModelScene { nodes, roots };
+
+        assert!(scene(vec![node(vec![1, 2]), node(vec![]), node(vec![])], vec![0])
+            .validate()
+            .is_ok());
+        assert!(scene(vec![node(vec![1])], vec![0]).validate().is_err());
+        assert!(scene(vec![node(vec![])], vec![1]).validate().is_err());
+        assert!(scene(vec![node(vec![1]), node(vec![0])], vec![0])
+            .validate()
+            .is_err());
+        assert!(scene(vec![node(vec![1]), node(vec![])], vec![0, 1])
+            .validate()
+            .is_err());
+    }
+}
@@ -1,11 +1,15 @@
-use cobalt_assets::asset::{AssetFileSystemType, AssetReadError, AssetTrait};
-use cobalt_graphics::{
-    context::Graphics,
-    texture::{Texture, TextureType},
+use cobalt_assets::{
+    asset::{AssetFileSystemType, AssetMemory, AssetReadContext, AssetReadError, AssetTrait},
+    manifest::AssetInfo,
+    packed::read_packed_asset,
+    source::AssetSource,
 };
-use image::GenericImageView;
+use cobalt_graphics::texture::{Texture, TextureType};
 
-use crate::importers::texture::TextureAssetBuffer;
+use crate::importers::{
+    ktx2::Ktx2Texture,
+    texture::{create_texture_from_ktx2, PackedTexture},
+};
 
 #[derive(Debug)]
 pub struct TextureAsset<const T: TextureType>(Texture<T>);
@@ -27,60 +31,68 @@ impl<const T: TextureType> TextureAsset<T> {
         &mut self.0
     }
 }
+/// Name of the asset type of textures of type `ty`, `TextureAsset::<T>::type_name` for a runtime type.
+pub(crate) fn texture_type_name(ty: TextureType) -> String {
+    format!("Texture<{}>", ty.to_string())
+}
+
 impl<const T: TextureType> AssetTrait for TextureAsset<T> {
     fn type_name() -> String {
-        format!("Texture<{}>", T.to_string())
+        texture_type_name(T)
     }
 
     fn imported_fs_type() -> AssetFileSystemType {
         AssetFileSystemType::File
     }
 
+    fn format_version() -> u32 {
+        PackedTexture::VERSION
+    }
+
+    fn migrate(
+        version: u32,
+        payload: Vec<u8>,
+        asset_info: &AssetInfo,
+    ) -> Result<Vec<u8>, AssetReadError> {
+        match version {
+            0 => PackedTexture::migrate_unversioned(T, payload, asset_info),
+            _ => Err(AssetReadError::UnsupportedVersion(Self::type_name(), version)),
+        }
+    }
+
+    /// The payload is either KTX2 for block compressed textures or a `PackedTexture`.
     fn read(
-        asset_info: &cobalt_assets::manifest::AssetInfo,
-        assets_dir: &std::path::Path,
-        graphics: &Graphics,
+        asset_info: &AssetInfo,
+        source: &dyn AssetSource,
+        context: &AssetReadContext,
     ) -> Result<Self, AssetReadError> {
-        let abs_path = assets_dir.join(&asset_info.relative_path);
-
-        let tab: TextureAssetBuffer = if let Some(_) = asset_info.pack.compression {
-            let mime_type = asset_info
-                .extra
-                .0
-                .get("mime")
-                .ok_or(AssetReadError::MissingExtraAssetInfo("mime".to_string()))?;
-
-            // Read from PNG buffer
-            let dyn_image = image::load_from_memory_with_format(
-                &std::fs::read(&abs_path).map_err(|e| AssetReadError::Io(e))?,
-                image::ImageFormat::from_mime_type(mime_type).ok_or(AssetReadError::ParseError(
-                    "Unsupported image format".to_string().into(),
-                ))?,
-            )
-            .map_err(|e| AssetReadError::ParseError(Box::new(e)))?;
-
-            let (width, height) = dyn_image.dimensions();
-
-            TextureAssetBuffer {
-                ty: T,
-                image: dyn_image,
-                size: wgpu::Extent3d {
-                    width,
-                    height,
-                    depth_or_array_layers: 1,
-                },
-            }
-        } else {
-            // Deserialise from file assuming data is TextureAssetBuffer
-            bincode::deserialize_from(
-                std::fs::File::open(&abs_path).map_err(|e| AssetReadError::Io(e))?,
-            )
-            .map_err(|e| {
-                log::error!("{}", e);
-                AssetReadError::DeserializeError(e)
-            })?
-        };
-
-        Ok(TextureAsset::new(tab.create_texture(graphics)?))
+        let sampler_settings = PackedTexture::sampler_settings(asset_info)?;
+        let data = read_packed_asset::<Self>(source, &asset_info.relative_path, asset_info)?;
+
+        if Ktx2Texture::has_identifier(&data) {
+            let ktx2 =
+                Ktx2Texture::read(&data).map_err(|e| AssetReadError::ParseError(Box::new(e)))?;
+
+            return Ok(TextureAsset::new(create_texture_from_ktx2(
+                &context.graphics.read(),
+                &ktx2,
+                &sampler_settings,
+            )?));
+        }
+
+        let packed: PackedTexture = bincode::deserialize(&data)?;
+
+        Ok(TextureAsset::new(packed.create_texture(
+            &context.graphics.read(),
+            PackedTexture::kind(asset_info)?,
+            &sampler_settings,
+        )?))
+    }
+
+    fn memory_size(&self) -> AssetMemory {
+        AssetMemory {
+            cpu: std::mem::size_of::<Self>(),
+            gpu: self.0.gpu_memory(),
+        }
     }
 }
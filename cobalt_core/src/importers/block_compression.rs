@@ -0,0 +1,709 @@
+//! Encoders and decoders for the block compressed texture formats.
+//! Encoding fits each block's endpoints along the principal axis of its texels, BC7 only uses mode 6
+//! (one subset with RGBA endpoints). Decoding handles every BC7 mode, so files compressed by other
+//! tools can be read.
+
+use cobalt_graphics::texture::BlockCompression;
+
+/// RGBA texels of a 4x4 block, row by row.
+pub(crate) type Block = [[u8; 4]; 16];
+
+/// Appends the compressed block to `out`.
+/// BC4 compresses the red channel, BC5 the red and green channels.
+pub(crate) fn encode_block(compression: BlockCompression, texels: &Block, out: &mut Vec<u8>) {
+    match compression {
+        BlockCompression::Bc1 => out.extend(encode_color(texels, true)),
+        BlockCompression::Bc3 => {
+            out.extend(encode_channel(&texels.map(|texel| texel[3])));
+            out.extend(encode_color(texels, false));
+        }
+        BlockCompression::Bc4 => out.extend(encode_channel(&texels.map(|texel| texel[0]))),
+        BlockCompression::Bc5 => {
+            out.extend(encode_channel(&texels.map(|texel| texel[0])));
+            out.extend(encode_channel(&texels.map(|texel| texel[1])));
+        }
+        BlockCompression::Bc7 => out.extend(encode_bc7(texels)),
+    }
+}
+
+/// Decodes one block of `compression.bytes_per_block()` bytes.
+/// BC4 decodes to the red channel, BC5 to red and green, the missing channels are 0 and alpha 255.
+pub(crate) fn decode_block(compression: BlockCompression, data: &[u8]) -> Block {
+    match compression {
+        BlockCompression::Bc1 => decode_color(&data[..8], true),
+        BlockCompression::Bc3 => {
+            let alpha = decode_channel(&data[..8]);
+            let mut texels = decode_color(&data[8..16], false);
+
+            for (texel, alpha) in texels.iter_mut().zip(alpha) {
+                texel[3] = alpha;
+            }
+
+            texels
+        }
+        BlockCompression::Bc4 => decode_channel(&data[..8]).map(|red| [red, 0, 0, 255]),
+        BlockCompression::Bc5 => {
+            let red = decode_channel(&data[..8]);
+            let green = decode_channel(&data[8..16]);
+
+            std::array::from_fn(|i| [red[i], green[i], 0, 255])
+        }
+        BlockCompression::Bc7 => decode_bc7(data[..16].try_into().unwrap()),
+    }
+}
+
+/// Endpoints of the line through `points` along their principal axis, spanning all of them.
+fn principal_endpoints<const N: usize>(points: &[[f32; N]]) -> ([f32; N], [f32; N]) {
+    if points.is_empty() {
+        return ([0.0; N], [0.0; N]);
+    }
+
+    let mut mean = [0.0; N];
+    for point in points {
+        for c in 0..N {
+            mean[c] += point[c] / points.len() as f32;
+        }
+    }
+
+    let mut covariance = [[0.0; N]; N];
+    for point in points {
+        for i in 0..N {
+            for j in 0..N {
+                covariance[i][j] += (point[i] - mean[i]) * (point[j] - mean[j]);
+            }
+        }
+    }
+
+    // Power iteration, starting from the diagonal of the bounding box
+    let mut axis: [f32; N] = std::array::from_fn(|c| {
+        let min = points.iter().map(|point| point[c]).fold(f32::MAX, f32::min);
+        let max = points.iter().map(|point| point[c]).fold(f32::MIN, f32::max);
+        max - min
+    });
+
+    for _ in 0..8 {
+        let next: [f32; N] =
+            std::array::from_fn(|i| (0..N).map(|j| covariance[i][j] * axis[j]).sum());
+        let length = next.iter().map(|x| x * x).sum::<f32>().sqrt();
+
+        if length < f32::EPSILON {
+            break;
+        }
+
+        axis = next.map(|x| x / length);
+    }
+
+    let length = axis.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if length < f32::EPSILON {
+        // Every point is the same
+        return (mean, mean);
+    }
+    axis = axis.map(|x| x / length);
+
+    let project = |point: &[f32; N]| (0..N).map(|c| (point[c] - mean[c]) * axis[c]).sum::<f32>();
+    let min = points.iter().map(project).fold(f32::MAX, f32::min);
+    let max = points.iter().map(project).fold(f32::MIN, f32::max);
+
+    (
+        std::array::from_fn(|c| (mean[c] + axis[c] * min).clamp(0.0, 255.0)),
+        std::array::from_fn(|c| (mean[c] + axis[c] * max).clamp(0.0, 255.0)),
+    )
+}
+
+/// Index of the palette entry closest to `texel`, comparing the first `channels` channels.
+fn nearest(palette: &[[u8; 4]], texel: &[u8; 4], channels: usize) -> usize {
+    let distance = |entry: &[u8; 4]| {
+        (0..channels)
+            .map(|c| (entry[c] as i32 - texel[c] as i32).pow(2))
+            .sum::<i32>()
+    };
+
+    (0..palette.len())
+        .min_by_key(|&i| distance(&palette[i]))
+        .unwrap_or(0)
+}
+
+fn pack_565(color: [f32; 3]) -> u16 {
+    let r = (color[0] * 31.0 / 255.0).round() as u16;
+    let g = (color[1] * 63.0 / 255.0).round() as u16;
+    let b = (color[2] * 31.0 / 255.0).round() as u16;
+
+    (r << 11) | (g << 5) | b
+}
+
+fn unpack_565(color: u16) -> [u32; 3] {
+    let r = (color >> 11) as u32 & 31;
+    let g = (color >> 5) as u32 & 63;
+    let b = color as u32 & 31;
+
+    [
+        (r << 3) | (r >> 2),
+        (g << 2) | (g >> 4),
+        (b << 3) | (b >> 2),
+    ]
+}
+
+/// The four colours of a BC1 style block. With `four_color` false the third is the average and the
+/// fourth transparent black.
+fn color_palette(color0: u16, color1: u16, four_color: bool) -> [[u8; 4]; 4] {
+    let c0 = unpack_565(color0);
+    let c1 = unpack_565(color1);
+    let mix = |w0: u32, w1: u32| -> [u8; 4] {
+        let [r, g, b] = std::array::from_fn(|c| ((w0 * c0[c] + w1 * c1[c]) / (w0 + w1)) as u8);
+        [r, g, b, 255]
+    };
+
+    if four_color {
+        [mix(1, 0), mix(0, 1), mix(2, 1), mix(1, 2)]
+    } else {
+        [mix(1, 0), mix(0, 1), mix(1, 1), [0, 0, 0, 0]]
+    }
+}
+
+/// Encodes the colour half of a BC1 or BC3 block. With `alpha`, texels under half opacity are made
+/// transparent, as BC1 allows.
+fn encode_color(texels: &Block, alpha: bool) -> [u8; 8] {
+    let transparent = |texel: &[u8; 4]| alpha && texel[3] < 128;
+
+    let points = texels
+        .iter()
+        .filter(|texel| !transparent(texel))
+        .map(|texel| [texel[0] as f32, texel[1] as f32, texel[2] as f32])
+        .collect::<Vec<_>>();
+    let has_transparent = points.len() < texels.len();
+
+    let (start, end) = principal_endpoints(&points);
+    let (mut color0, mut color1) = (pack_565(end), pack_565(start));
+
+    // color0 > color1 selects four colours, otherwise three and transparent
+    if has_transparent == (color0 > color1) {
+        std::mem::swap(&mut color0, &mut color1);
+    }
+
+    let four_color = color0 > color1;
+    let palette = color_palette(color0, color1, four_color);
+    let opaque_entries = if four_color { 4 } else { 3 };
+
+    let mut indices = 0u32;
+    for (i, texel) in texels.iter().enumerate() {
+        let index = if transparent(texel) {
+            3
+        } else {
+            nearest(&palette[..opaque_entries], texel, 3)
+        };
+
+        indices |= (index as u32) << (i * 2);
+    }
+
+    let mut block = [0; 8];
+    block[..2].copy_from_slice(&color0.to_le_bytes());
+    block[2..4].copy_from_slice(&color1.to_le_bytes());
+    block[4..].copy_from_slice(&indices.to_le_bytes());
+    block
+}
+
+/// Decodes the colour half of a BC1 or BC3 block. BC3 always uses four colours.
+fn decode_color(data: &[u8], alpha: bool) -> Block {
+    let color0 = u16::from_le_bytes([data[0], data[1]]);
+    let color1 = u16::from_le_bytes([data[2], data[3]]);
+    let indices = u32::from_le_bytes(data[4..8].try_into().unwrap());
+
+    let palette = color_palette(color0, color1, !alpha || color0 > color1);
+
+    std::array::from_fn(|i| palette[(indices >> (i * 2)) as usize & 3])
+}
+
+/// The eight values of a BC4 style block. With `value0 <= value1` the last two are 0 and 255.
+fn channel_palette(value0: u8, value1: u8) -> [u8; 8] {
+    let (v0, v1) = (value0 as u32, value1 as u32);
+    let mut palette = [value0, value1, 0, 0, 0, 0, 0, 255];
+
+    if value0 > value1 {
+        for i in 1..7 {
+            palette[i + 1] = (((7 - i as u32) * v0 + i as u32 * v1 + 3) / 7) as u8;
+        }
+    } else {
+        for i in 1..5 {
+            palette[i + 1] = (((5 - i as u32) * v0 + i as u32 * v1 + 2) / 5) as u8;
+        }
+    }
+
+    palette
+}
+
+/// Encodes a single channel as a BC4 block, also the alpha of BC3 and each channel of BC5.
+fn encode_channel(values: &[u8; 16]) -> [u8; 8] {
+    let min = *values.iter().min().unwrap();
+    let max = *values.iter().max().unwrap();
+
+    let mut block = [max, min, 0, 0, 0, 0, 0, 0];
+
+    // Equal endpoints select the six value palette, whose first entry is still the endpoint
+    if max > min {
+        let palette = channel_palette(max, min);
+        let mut indices = 0u64;
+
+        for (i, value) in values.iter().enumerate() {
+            let index = (0..8)
+                .min_by_key(|&index| (palette[index] as i32 - *value as i32).abs())
+                .unwrap();
+
+            indices |= (index as u64) << (i * 3);
+        }
+
+        block[2..].copy_from_slice(&indices.to_le_bytes()[..6]);
+    }
+
+    block
+}
+
+fn decode_channel(data: &[u8]) -> [u8; 16] {
+    let palette = channel_palette(data[0], data[1]);
+
+    let mut index_bytes = [0; 8];
+    index_bytes[..6].copy_from_slice(&data[2..8]);
+    let indices = u64::from_le_bytes(index_bytes);
+
+    std::array::from_fn(|i| palette[(indices >> (i * 3)) as usize & 7])
+}
+
+/// Layout of a BC7 mode.
+struct Bc7Mode {
+    subsets: usize,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    /// A p-bit per endpoint.
+    endpoint_pbits: bool,
+    /// A p-bit per subset, shared by its endpoints.
+    shared_pbits: bool,
+    index_bits: u32,
+    secondary_index_bits: u32,
+}
+
+#[rustfmt::skip]
+const BC7_MODES: [Bc7Mode; 8] = [
+    Bc7Mode { subsets: 3, partition_bits: 4, rotation_bits: 0, index_selection_bits: 0, color_bits: 4, alpha_bits: 0, endpoint_pbits: true, shared_pbits: false, index_bits: 3, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 6, alpha_bits: 0, endpoint_pbits: false, shared_pbits: true, index_bits: 3, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 3, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 5, alpha_bits: 0, endpoint_pbits: false, shared_pbits: false, index_bits: 2, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 7, alpha_bits: 0, endpoint_pbits: true, shared_pbits: false, index_bits: 2, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 1, color_bits: 5, alpha_bits: 6, endpoint_pbits: false, shared_pbits: false, index_bits: 2, secondary_index_bits: 3 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 0, color_bits: 7, alpha_bits: 8, endpoint_pbits: false, shared_pbits: false, index_bits: 2, secondary_index_bits: 2 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 0, index_selection_bits: 0, color_bits: 7, alpha_bits: 7, endpoint_pbits: true, shared_pbits: false, index_bits: 4, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 5, alpha_bits: 5, endpoint_pbits: true, shared_pbits: false, index_bits: 2, secondary_index_bits: 0 },
+];
+
+/// Subset of each texel in the two subset partitions, bit `i` for texel `i`.
+#[rustfmt::skip]
+const BC7_PARTITIONS_2: [u16; 64] = [
+    0xCCCC, 0x8888, 0xEEEE, 0xECC8, 0xC880, 0xFEEC, 0xFEC8, 0xEC80,
+    0xC800, 0xFFEC, 0xFE80, 0xE800, 0xFFE8, 0xFF00, 0xFFF0, 0xF000,
+    0xF710, 0x008E, 0x7100, 0x08CE, 0x008C, 0x7310, 0x3100, 0x8CCE,
+    0x088C, 0x3110, 0x6666, 0x366C, 0x17E8, 0x0FF0, 0x718E, 0x399C,
+    0xAAAA, 0xF0F0, 0x5A5A, 0x33CC, 0x3C3C, 0x55AA, 0x9696, 0xA55A,
+    0x73CE, 0x13C8, 0x324C, 0x3BDC, 0x6996, 0xC33C, 0x9966, 0x0660,
+    0x0272, 0x04E4, 0x4E40, 0x2720, 0xC936, 0x936C, 0x39C6, 0x639C,
+    0x9336, 0x9CC6, 0x817E, 0xE718, 0xCCF0, 0x0FCC, 0x7744, 0xEE22,
+];
+
+/// Subset of each texel in the three subset partitions.
+#[rustfmt::skip]
+const BC7_PARTITIONS_3: [[u8; 16]; 64] = [
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 1, 2, 2, 2, 2], [0, 0, 0, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 2, 0, 0, 1, 2, 2, 1, 1, 2, 2, 1, 1], [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 1, 0, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2], [0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1], [0, 0, 1, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2], [0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2], [0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2],
+    [0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2], [0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2, 1, 2, 2, 2], [0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0, 2, 2, 2, 0],
+    [0, 0, 0, 1, 0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2], [0, 1, 1, 1, 0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0],
+    [0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2], [0, 0, 2, 2, 0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1],
+    [0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2, 0, 2, 2, 2], [0, 0, 0, 1, 0, 0, 0, 1, 2, 2, 2, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2], [0, 0, 0, 0, 1, 1, 0, 0, 2, 2, 1, 0, 2, 2, 1, 0],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1, 0, 0, 0, 0], [0, 0, 1, 2, 0, 0, 1, 2, 1, 1, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1, 0, 1, 1, 0], [0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1],
+    [0, 0, 2, 2, 1, 1, 0, 2, 1, 1, 0, 2, 0, 0, 2, 2], [0, 1, 1, 0, 0, 1, 1, 0, 2, 0, 0, 2, 2, 2, 2, 2],
+    [0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1], [0, 0, 0, 0, 2, 0, 0, 0, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 2, 2, 2], [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 2, 0, 0, 2, 2, 0, 2, 2, 2], [0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0], [0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0],
+    [0, 1, 2, 0, 2, 0, 1, 2, 1, 2, 0, 1, 0, 1, 2, 0], [0, 0, 1, 1, 2, 2, 0, 0, 1, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0, 1, 1], [0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1], [0, 0, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 1, 1], [0, 2, 2, 0, 1, 2, 2, 1, 0, 2, 2, 0, 1, 2, 2, 1],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 0, 1, 0, 1], [0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2], [0, 2, 2, 2, 0, 1, 1, 1, 0, 2, 2, 2, 0, 1, 1, 1],
+    [0, 0, 0, 2, 1, 1, 1, 2, 0, 0, 0, 2, 1, 1, 1, 2], [0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2], [0, 0, 0, 2, 1, 1, 1, 2, 1, 1, 1, 2, 0, 0, 0, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2], [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2, 2, 2, 2, 2], [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2],
+    [0, 0, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2], [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2],
+    [0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 1], [0, 2, 2, 2, 1, 2, 2, 2, 0, 2, 2, 2, 1, 2, 2, 2],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2], [0, 1, 1, 1, 2, 0, 1, 1, 2, 2, 0, 1, 2, 2, 2, 0],
+];
+
+/// Texel whose index has its top bit dropped, for the second subset of the two subset partitions.
+#[rustfmt::skip]
+const BC7_ANCHORS_2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15,
+    15,  2,  8,  2,  2,  8,  8, 15,  2,  8,  2,  2,  8,  8,  2,  2,
+    15, 15,  6,  8,  2,  8, 15, 15,  2,  8,  2,  2,  2, 15, 15,  6,
+     6,  2,  6,  8, 15, 15,  2,  2, 15, 15, 15, 15, 15,  2,  2, 15,
+];
+
+/// Anchor texels of the second and third subsets of the three subset partitions.
+#[rustfmt::skip]
+const BC7_ANCHORS_3: [[u8; 64]; 2] = [
+    [
+         3,  3, 15, 15,  8,  3, 15, 15,  8,  8,  6,  6,  6,  5,  3,  3,
+         3,  3,  8, 15,  3,  3,  6, 10,  5,  8,  8,  6,  8,  5, 15, 15,
+         8, 15,  3,  5,  6, 10,  8, 15, 15,  3, 15,  5, 15, 15, 15, 15,
+         3, 15,  5,  5,  5,  8,  5, 10,  5, 10,  8, 13, 15, 12,  3,  3,
+    ],
+    [
+        15,  8,  8,  3, 15, 15,  3,  8, 15, 15, 15, 15, 15, 15, 15,  8,
+        15,  8, 15,  3, 15,  8, 15,  8,  3, 15,  6, 10, 15, 15, 10,  8,
+        15,  3, 15, 10, 10,  8,  9, 10,  6, 15,  8, 15,  3,  6,  6,  8,
+        15,  3, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15,  3, 15, 15,  8,
+    ],
+];
+
+fn bc7_weights(index_bits: u32) -> &'static [u32] {
+    match index_bits {
+        2 => &[0, 21, 43, 64],
+        3 => &[0, 9, 18, 27, 37, 46, 55, 64],
+        _ => &[0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64],
+    }
+}
+
+fn bc7_interpolate(e0: u8, e1: u8, index: usize, index_bits: u32) -> u8 {
+    let weight = bc7_weights(index_bits)[index];
+
+    (((64 - weight) * e0 as u32 + weight * e1 as u32 + 32) >> 6) as u8
+}
+
+/// Reads a BC7 block from its lowest bit up.
+struct BitReader(u128);
+
+impl BitReader {
+    fn read(&mut self, count: u32) -> u8 {
+        let value = (self.0 & ((1 << count) - 1)) as u8;
+        self.0 >>= count;
+        value
+    }
+}
+
+/// Expands an endpoint channel of `bits` bits, its p-bit included, to 8 bits.
+fn bc7_unquantize(value: u8, bits: u32) -> u8 {
+    let value = (value as u32) << (8 - bits);
+
+    (value | (value >> bits)) as u8
+}
+
+fn decode_bc7(data: [u8; 16]) -> Block {
+    let mut reader = BitReader(u128::from_le_bytes(data));
+
+    let Some(mode_index) = (0..8).find(|_| reader.read(1) == 1) else {
+        // Reserved mode
+        return [[0; 4]; 16];
+    };
+    let mode = &BC7_MODES[mode_index];
+
+    let partition = reader.read(mode.partition_bits) as usize;
+    let rotation = reader.read(mode.rotation_bits);
+    let index_selection = reader.read(mode.index_selection_bits);
+
+    let endpoint_count = mode.subsets * 2;
+    let mut endpoints = [[0u8; 4]; 6];
+
+    for c in 0..3 {
+        for endpoint in &mut endpoints[..endpoint_count] {
+            endpoint[c] = reader.read(mode.color_bits);
+        }
+    }
+
+    for endpoint in &mut endpoints[..endpoint_count] {
+        endpoint[3] = reader.read(mode.alpha_bits);
+    }
+
+    let mut pbits = [None; 6];
+    if mode.endpoint_pbits {
+        for pbit in &mut pbits[..endpoint_count] {
+            *pbit = Some(reader.read(1));
+        }
+    } else if mode.shared_pbits {
+        for subset in 0..mode.subsets {
+            let pbit = reader.read(1);
+            pbits[subset * 2] = Some(pbit);
+            pbits[subset * 2 + 1] = Some(pbit);
+        }
+    }
+
+    for (endpoint, pbit) in endpoints[..endpoint_count].iter_mut().zip(pbits) {
+        let (color_bits, alpha_bits) = match pbit {
+            Some(pbit) => {
+                for value in endpoint.iter_mut() {
+                    *value = (*value << 1) | pbit;
+                }
+                (mode.color_bits + 1, mode.alpha_bits + 1)
+            }
+            None => (mode.color_bits, mode.alpha_bits),
+        };
+
+        for value in &mut endpoint[..3] {
+            *value = bc7_unquantize(*value, color_bits);
+        }
+
+        endpoint[3] = if mode.alpha_bits == 0 {
+            255
+        } else {
+            bc7_unquantize(endpoint[3], alpha_bits)
+        };
+    }
+
+    let subset = |texel: usize| match mode.subsets {
+        1 => 0,
+        2 => (BC7_PARTITIONS_2[partition] >> texel) as usize & 1,
+        _ => BC7_PARTITIONS_3[partition][texel] as usize,
+    };
+    let is_anchor = |texel: usize| match (mode.subsets, subset(texel)) {
+        (_, 0) => texel == 0,
+        (2, _) => texel == BC7_ANCHORS_2[partition] as usize,
+        (_, subset) => texel == BC7_ANCHORS_3[subset - 1][partition] as usize,
+    };
+
+    let mut indices = [0usize; 16];
+    for (texel, index) in indices.iter_mut().enumerate() {
+        *index = reader.read(mode.index_bits - is_anchor(texel) as u32) as usize;
+    }
+
+    let mut secondary_indices = [0usize; 16];
+    if mode.secondary_index_bits > 0 {
+        for (texel, index) in secondary_indices.iter_mut().enumerate() {
+            *index = reader.read(mode.secondary_index_bits - (texel == 0) as u32) as usize;
+        }
+    }
+
+    std::array::from_fn(|texel| {
+        let e0 = endpoints[subset(texel) * 2];
+        let e1 = endpoints[subset(texel) * 2 + 1];
+
+        let ((color_index, color_bits), (alpha_index, alpha_bits)) =
+            match (mode.secondary_index_bits, index_selection) {
+                (0, _) => (
+                    (indices[texel], mode.index_bits),
+                    (indices[texel], mode.index_bits),
+                ),
+                (_, 0) => (
+                    (indices[texel], mode.index_bits),
+                    (secondary_indices[texel], mode.secondary_index_bits),
+                ),
+                _ => (
+                    (secondary_indices[texel], mode.secondary_index_bits),
+                    (indices[texel], mode.index_bits),
+                ),
+            };
+
+        let mut texel = [0; 4];
+        for c in 0..3 {
+            texel[c] = bc7_interpolate(e0[c], e1[c], color_index, color_bits);
+        }
+        texel[3] = bc7_interpolate(e0[3], e1[3], alpha_index, alpha_bits);
+
+        match rotation {
+            1 => texel.swap(0, 3),
+            2 => texel.swap(1, 3),
+            3 => texel.swap(2, 3),
+            _ => {}
+        }
+
+        texel
+    })
+}
+
+/// Quantizes an endpoint to 7 bits per channel and a shared p-bit, picking the p-bit closest to it.
+fn bc7_quantize_mode6(endpoint: [f32; 4]) -> ([u8; 4], u8) {
+    (0..2)
+        .map(|pbit| {
+            let quantized =
+                endpoint.map(|value| ((value - pbit as f32) / 2.0).round().clamp(0.0, 127.0) as u8);
+            let error = (0..4)
+                .map(|c| (((quantized[c] << 1) | pbit) as f32 - endpoint[c]).powi(2))
+                .sum::<f32>();
+
+            (quantized, pbit, error)
+        })
+        .min_by(|a, b| a.2.total_cmp(&b.2))
+        .map(|(quantized, pbit, _)| (quantized, pbit))
+        .unwrap()
+}
+
+/// Encodes a BC7 block in mode 6.
+fn encode_bc7(texels: &Block) -> [u8; 16] {
+    let points = texels.map(|texel| texel.map(|value| value as f32));
+    let (start, end) = principal_endpoints(&points);
+
+    let (mut q0, mut p0) = bc7_quantize_mode6(start);
+    let (mut q1, mut p1) = bc7_quantize_mode6(end);
+
+    let e0 = q0.map(|value| (value << 1) | p0);
+    let e1 = q1.map(|value| (value << 1) | p1);
+    let palette: [[u8; 4]; 16] = std::array::from_fn(|index| {
+        std::array::from_fn(|c| bc7_interpolate(e0[c], e1[c], index, 4))
+    });
+
+    let mut indices = texels.map(|texel| nearest(&palette, &texel, 4));
+
+    // The first texel's index is stored without its top bit, the weights are symmetric
+    if indices[0] >= 8 {
+        std::mem::swap(&mut q0, &mut q1);
+        std::mem::swap(&mut p0, &mut p1);
+        indices = indices.map(|index| 15 - index);
+    }
+
+    let mut bits = 1u128 << 6;
+    let mut offset = 7;
+    let mut write = |value: u128, count: u32| {
+        bits |= value << offset;
+        offset += count;
+    };
+
+    for c in 0..4 {
+        write(q0[c] as u128, 7);
+        write(q1[c] as u128, 7);
+    }
+
+    write(p0 as u128, 1);
+    write(p1 as u128, 1);
+
+    for (texel, index) in indices.iter().enumerate() {
+        write(*index as u128, if texel == 0 { 3 } else { 4 });
+    }
+
+    bits.to_le_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A gradient along one direction with a little noise, as in most blocks of real textures.
+    fn gradient() -> Block {
+        std::array::from_fn(|i| {
+            let t = i as u8;
+            let noise = (i * 7 % 5) as u8;
+            [
+                20 + t * 12 + noise,
+                230 - t * 10,
+                60 + t * 5 + noise,
+                255 - t * 8,
+            ]
+        })
+    }
+
+    fn max_error(a: &Block, b: &Block, channels: usize) -> i32 {
+        a.iter()
+            .zip(b)
+            .flat_map(|(a, b)| (0..channels).map(|c| (a[c] as i32 - b[c] as i32).abs()))
+            .max()
+            .unwrap()
+    }
+
+    #[test]
+    fn encoded_blocks_decode_close_to_the_input() {
+        let texels = gradient();
+
+        for (compression, channels, tolerance) in [
+            (BlockCompression::Bc1, 3, 36),
+            (BlockCompression::Bc3, 4, 36),
+            (BlockCompression::Bc4, 1, 16),
+            (BlockCompression::Bc5, 2, 16),
+            (BlockCompression::Bc7, 4, 8),
+        ] {
+            let mut data = Vec::new();
+            encode_block(compression, &texels, &mut data);
+            assert_eq!(data.len(), compression.bytes_per_block());
+
+            let decoded = decode_block(compression, &data);
+            let error = max_error(&texels, &decoded, channels);
+            assert!(error <= tolerance, "{:?} error {}", compression, error);
+        }
+    }
+
+    #[test]
+    fn bc1_keeps_transparent_texels() {
+        let mut texels = gradient();
+        texels[5][3] = 0;
+
+        let mut data = Vec::new();
+        encode_block(BlockCompression::Bc1, &texels, &mut data);
+        let decoded = decode_block(BlockCompression::Bc1, &data);
+
+        assert_eq!(decoded[5][3], 0);
+        assert!(decoded
+            .iter()
+            .enumerate()
+            .all(|(i, texel)| i == 5 || texel[3] == 255));
+    }
+
+    #[test]
+    fn flat_blocks_are_exact() {
+        let texels = [[10, 120, 250, 255]; 16];
+
+        for compression in [
+            BlockCompression::Bc4,
+            BlockCompression::Bc5,
+            BlockCompression::Bc7,
+        ] {
+            let mut data = Vec::new();
+            encode_block(compression, &texels, &mut data);
+            let decoded = decode_block(compression, &data);
+
+            let channels = if compression == BlockCompression::Bc7 {
+                3
+            } else {
+                1
+            };
+            assert!(
+                max_error(&texels, &decoded, channels) <= 1,
+                "{:?}",
+                compression
+            );
+        }
+    }
+
+    #[test]
+    fn partition_anchors_are_in_their_subsets() {
+        for partition in 0..64 {
+            assert_eq!(BC7_PARTITIONS_2[partition] & 1, 0);
+            assert_eq!(
+                (BC7_PARTITIONS_2[partition] >> BC7_ANCHORS_2[partition]) & 1,
+                1
+            );
+
+            let subsets = BC7_PARTITIONS_3[partition];
+            assert_eq!(subsets[0], 0);
+            assert_eq!(subsets[BC7_ANCHORS_3[0][partition] as usize], 1);
+            assert_eq!(subsets[BC7_ANCHORS_3[1][partition] as usize], 2);
+        }
+    }
+
+    #[test]
+    fn decodes_bc7_mode_5() {
+        // Mode 5, no rotation, endpoints red 0 -> 127, green and blue 0, alpha 255.
+        // Texel 1 has colour index 3 and alpha index 0, the rest 0.
+        let mut bits = 1u128 << 5;
+        bits |= 127u128 << (6 + 2 + 7);
+        bits |= 255u128 << (6 + 2 + 42);
+        bits |= 255u128 << (6 + 2 + 42 + 8);
+        bits |= 3u128 << (6 + 2 + 42 + 16 + 1);
+
+        let decoded = decode_bc7(bits.to_le_bytes());
+
+        assert_eq!(decoded[0], [0, 0, 0, 255]);
+        assert_eq!(decoded[1], [255, 0, 0, 255]);
+    }
+}
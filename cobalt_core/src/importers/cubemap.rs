@@ -0,0 +1,173 @@
+use std::f32::consts::PI;
+
+use cobalt_assets::asset::AssetImportError;
+use image::{DynamicImage, GenericImageView};
+
+/// File names of each cube face, in layer order +X, -X, +Y, -Y, +Z, -Z.
+const FACE_NAMES: [[&str; 3]; 6] = [
+    ["px", "posx", "right"],
+    ["nx", "negx", "left"],
+    ["py", "posy", "top"],
+    ["ny", "negy", "bottom"],
+    ["pz", "posz", "front"],
+    ["nz", "negz", "back"],
+];
+
+/// Projects an equirectangular panorama onto the six faces of a cube map.
+/// Faces are `face_size` texels wide, in layer order +X, -X, +Y, -Y, +Z, -Z.
+pub(crate) fn equirect_to_cube(image: &DynamicImage, face_size: u32) -> Vec<DynamicImage> {
+    let (width, height) = image.dimensions();
+    let source = image.to_rgba32f();
+
+    // Bilinear sample, wrapping horizontally and clamping at the poles
+    let sample = |u: f32, v: f32| {
+        let x = u * width as f32 - 0.5;
+        let y = (v * height as f32 - 0.5).clamp(0.0, height as f32 - 1.0);
+
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+
+        let texel = |x: f32, y: f32| {
+            let x = (x as i64).rem_euclid(width as i64) as u32;
+            let y = (y as u32).min(height - 1);
+            source.get_pixel(x, y).0
+        };
+
+        let corners = [
+            (texel(x0, y0), (1.0 - fx) * (1.0 - fy)),
+            (texel(x0 + 1.0, y0), fx * (1.0 - fy)),
+            (texel(x0, y0 + 1.0), (1.0 - fx) * fy),
+            (texel(x0 + 1.0, y0 + 1.0), fx * fy),
+        ];
+
+        let mut result = [0.0; 4];
+        for (value, weight) in corners {
+            for channel in 0..4 {
+                result[channel] += value[channel] * weight;
+            }
+        }
+        result
+    };
+
+    (0..6)
+        .map(|face| {
+            DynamicImage::ImageRgba32F(image::Rgba32FImage::from_fn(
+                face_size,
+                face_size,
+                |x, y| {
+                    // Texel center on the face, -1 to 1 with v going down
+                    let u = (x as f32 + 0.5) / face_size as f32 * 2.0 - 1.0;
+                    let v = (y as f32 + 0.5) / face_size as f32 * 2.0 - 1.0;
+
+                    let [dx, dy, dz] = face_direction(face, u, v);
+                    let length = (dx * dx + dy * dy + dz * dz).sqrt();
+
+                    image::Rgba(sample(
+                        0.5 + dz.atan2(dx) / (2.0 * PI),
+                        0.5 - (dy / length).asin() / PI,
+                    ))
+                },
+            ))
+        })
+        .collect()
+}
+
+/// Direction through a point of a cube face, following the WebGPU cube map layout.
+fn face_direction(face: usize, u: f32, v: f32) -> [f32; 3] {
+    match face {
+        0 => [1.0, -v, -u],
+        1 => [-1.0, -v, u],
+        2 => [u, 1.0, v],
+        3 => [u, -1.0, -v],
+        4 => [u, -v, 1.0],
+        _ => [-u, -v, -1.0],
+    }
+}
+
+/// Splits a horizontal strip of square tiles, like a 6:1 cube map strip or a colour grading LUT.
+pub(crate) fn split_strip(image: &DynamicImage) -> Result<Vec<DynamicImage>, AssetImportError> {
+    let (width, height) = image.dimensions();
+
+    if height == 0 || width % height != 0 {
+        return Err(AssetImportError::ParseError(
+            format!(
+                "A {}x{} image isn't a horizontal strip of square tiles",
+                width, height
+            )
+            .into(),
+        ));
+    }
+
+    Ok((0..width / height)
+        .map(|tile| image.crop_imm(tile * height, 0, height, height))
+        .collect())
+}
+
+/// Whether `image` has the 2:1 aspect of an equirectangular panorama.
+pub(crate) fn is_equirect(image: &DynamicImage) -> bool {
+    let (width, height) = image.dimensions();
+    width == 2 * height
+}
+
+/// Orders the files of a cube map directory by face, using their names without extension,
+/// e.g. "px", "posx" or "right" for +X.
+pub(crate) fn sort_cube_faces(
+    paths: Vec<std::path::PathBuf>,
+) -> Result<Vec<std::path::PathBuf>, AssetImportError> {
+    FACE_NAMES
+        .iter()
+        .map(|names| {
+            paths
+                .iter()
+                .find(|path| {
+                    path.file_stem()
+                        .and_then(|stem| stem.to_str())
+                        .is_some_and(|stem| {
+                            names.iter().any(|name| stem.eq_ignore_ascii_case(name))
+                        })
+                })
+                .cloned()
+                .ok_or_else(|| {
+                    AssetImportError::LoadError(
+                        format!("Missing the cube face \"{}\"", names.join("\" / \"")).into(),
+                    )
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equirect_faces_sample_their_direction() {
+        // Left half red, right half blue: -X looks at the seam at u = 0, +X at the middle
+        let image = DynamicImage::ImageRgba32F(image::Rgba32FImage::from_fn(64, 32, |x, _| {
+            if x < 32 {
+                image::Rgba([1.0, 0.0, 0.0, 1.0])
+            } else {
+                image::Rgba([0.0, 0.0, 1.0, 1.0])
+            }
+        }));
+
+        let faces = equirect_to_cube(&image, 8);
+        assert_eq!(faces.len(), 6);
+
+        // +Z is at a quarter turn into the right half, -Z into the left half
+        let center = |face: usize| faces[face].to_rgba32f().get_pixel(4, 4).0;
+        assert_eq!(center(4), [0.0, 0.0, 1.0, 1.0]);
+        assert_eq!(center(5), [1.0, 0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn strips_split_into_square_tiles() {
+        let image = DynamicImage::ImageRgba8(image::RgbaImage::new(96, 16));
+
+        let tiles = split_strip(&image).unwrap();
+        assert_eq!(tiles.len(), 6);
+        assert!(tiles.iter().all(|tile| tile.dimensions() == (16, 16)));
+
+        assert!(split_strip(&DynamicImage::ImageRgba8(image::RgbaImage::new(90, 16))).is_err());
+    }
+}
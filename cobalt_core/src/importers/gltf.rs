@@ -1,85 +1,722 @@
-#![allow(unused)]
+use std::path::Path;
 
-use cobalt_assets::asset::{AssetFileSystemType, AssetImporter, AssetTrait};
-use cobalt_ecs::world;
-use cobalt_graphics::texture::GenericTexture;
+use cobalt_assets::{
+    asset::{
+        AssetFileSystemType, AssetID, AssetImportError, AssetImporter, AssetTrait,
+        AssetVerifyError,
+    },
+    manifest::{AssetInfo, ExtraAssetInfo, ImportedAsset},
+};
+use cobalt_graphics::{
+    texture::{SamplerSettings, TextureAddressMode, TextureFilter, TextureType},
+    vertex::UvNormalTangentVertex,
+};
+use hashbrown::HashMap;
 
-use crate::{renderer::mesh::Mesh, types::resource::Resource};
+use crate::{
+    asset_types::{
+        model::{Model, ModelLight, ModelNode, ModelPrimitive, ModelScene},
+        mesh::{generate_tangents, MeshAssetBuffer, MeshIndices},
+        texture::TextureAsset,
+    },
+    renderer::{
+        camera::{AspectRatio, Projection},
+        deferred::exports::{Material, MaterialDescriptor},
+        mesh::Mesh,
+    },
+    types::{aabb::AABB, either::Either},
+};
 
-#[derive(Debug)]
-pub struct GltfImporter {
-    meshes: Vec<Resource<Mesh>>,
-    textures: Vec<Resource<GenericTexture>>,
-    document: gltf::Document,
-}
+use super::{
+    mesh_optimizer::{optimize_mesh, MeshLodSettings, MeshOptimizeSettings},
+    obj::generate_normals,
+    texture::{material_block_compression, MipFilter, TextureAssetBuffer},
+    write_ron, SubAssets,
+};
+
+/// Maps a glTF sampler onto the texture sampler settings, unset filters are linear.
+fn sampler_settings(sampler: &gltf::texture::Sampler) -> SamplerSettings {
+    use gltf::texture::{MagFilter, MinFilter, WrappingMode};
+
+    let address_mode = |mode: WrappingMode| match mode {
+        WrappingMode::ClampToEdge => TextureAddressMode::ClampToEdge,
+        WrappingMode::MirroredRepeat => TextureAddressMode::MirrorRepeat,
+        WrappingMode::Repeat => TextureAddressMode::Repeat,
+    };
+
+    let (min_filter, mipmap_filter) = match sampler.min_filter() {
+        Some(MinFilter::Nearest) | Some(MinFilter::NearestMipmapNearest) => {
+            (TextureFilter::Nearest, TextureFilter::Nearest)
+        }
+        Some(MinFilter::NearestMipmapLinear) => (TextureFilter::Nearest, TextureFilter::Linear),
+        Some(MinFilter::LinearMipmapNearest) => (TextureFilter::Linear, TextureFilter::Nearest),
+        Some(MinFilter::Linear) | Some(MinFilter::LinearMipmapLinear) | None => {
+            (TextureFilter::Linear, TextureFilter::Linear)
+        }
+    };
 
-impl GltfImporter {
-    pub fn spawn(&self, world: &mut world::World) {
-        // for node in self.scene_desc.nodes() {
-        //     let mut entity = world.create_entity();
-        //     entity.add_component(node);
-        // }
+    SamplerSettings {
+        mag_filter: match sampler.mag_filter() {
+            Some(MagFilter::Nearest) => TextureFilter::Nearest,
+            Some(MagFilter::Linear) | None => TextureFilter::Linear,
+        },
+        min_filter,
+        mipmap_filter,
+        address_mode_u: address_mode(sampler.wrap_s()),
+        address_mode_v: address_mode(sampler.wrap_t()),
+        ..Default::default()
     }
 }
 
-impl AssetTrait for GltfImporter {
-    fn type_name() -> String {
-        "Gltf".to_string()
+/// Imports glTF 2.0 files (`.gltf` with external or embedded buffers, and `.glb`).
+/// Every mesh primitive, material and used texture becomes a sub-asset of the imported directory,
+/// the node hierarchy is kept as a `ModelScene`.
+pub struct GltfImporter;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct GltfImportSettings {
+    pub optimize: MeshOptimizeSettings,
+    pub lod: MeshLodSettings,
+    /// Block compress textures: BC7 for albedo, BC5 for normal maps, BC4 for metallic and roughness.
+    pub compress_textures: bool,
+}
+
+/// How a glTF texture is used, textures are converted to the type the material expects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum TextureRole {
+    Albedo,
+    Normal,
+    /// Blue channel of a metallic-roughness texture.
+    Metallic,
+    /// Green channel of a metallic-roughness texture.
+    Roughness,
+}
+
+impl TextureRole {
+    fn texture_type(&self) -> TextureType {
+        match self {
+            TextureRole::Albedo => Material::ALBEDO_TEXTURE_TYPE,
+            TextureRole::Normal => Material::NORMAL_TEXTURE_TYPE,
+            TextureRole::Metallic => Material::METALLIC_TEXTURE_TYPE,
+            TextureRole::Roughness => Material::ROUGHNESS_TEXTURE_TYPE,
+        }
     }
 
-    fn imported_fs_type() -> AssetFileSystemType {
-        AssetFileSystemType::Directory
+    fn type_name(&self) -> String {
+        match self {
+            TextureRole::Albedo => TextureAsset::<{ Material::ALBEDO_TEXTURE_TYPE }>::type_name(),
+            TextureRole::Normal => TextureAsset::<{ Material::NORMAL_TEXTURE_TYPE }>::type_name(),
+            TextureRole::Metallic => {
+                TextureAsset::<{ Material::METALLIC_TEXTURE_TYPE }>::type_name()
+            }
+            TextureRole::Roughness => {
+                TextureAsset::<{ Material::ROUGHNESS_TEXTURE_TYPE }>::type_name()
+            }
+        }
     }
+}
+
+/// State of a single import.
+struct GltfImport<'a> {
+    sub_assets: SubAssets<'a>,
+    settings: &'a GltfImportSettings,
+    buffers: Vec<gltf::buffer::Data>,
+    images: Vec<gltf::image::Data>,
+    /// Keyed by texture index, role and the factor baked into the texture.
+    textures: HashMap<(usize, TextureRole, u32), AssetID>,
+}
+
+impl<'a> GltfImport<'a> {
+    /// Packs a texture for the given role, each combination is only packed once.
+    fn texture(
+        &mut self,
+        texture: gltf::Texture,
+        role: TextureRole,
+        factor: f32,
+    ) -> Result<AssetID, AssetImportError> {
+        let key = (texture.index(), role, factor.to_bits());
+
+        if let Some(asset_id) = self.textures.get(&key) {
+            return Ok(*asset_id);
+        }
 
-    fn read(
-        asset_info: &cobalt_assets::manifest::AssetInfo,
-        assets_dir: &std::path::Path,
-        graphics: &cobalt_graphics::context::Graphics,
-    ) -> Result<Self, cobalt_assets::asset::AssetReadError> {
-        todo!()
+        let image = to_dynamic_image(&self.images[texture.source().index()])?;
+
+        let image = match role {
+            TextureRole::Albedo | TextureRole::Normal => image,
+            TextureRole::Metallic => extract_channel(&image, 2, factor),
+            TextureRole::Roughness => extract_channel(&image, 1, factor),
+        };
+
+        // Materials can bake different factors into the same texture, each gets its own file
+        let variant = self
+            .textures
+            .keys()
+            .filter(|(index, other_role, _)| *index == texture.index() && *other_role == role)
+            .count();
+
+        let file_name = match variant {
+            0 => format!("texture_{}_{:?}.asset", texture.index(), role),
+            _ => format!("texture_{}_{:?}_{}.asset", texture.index(), role, variant),
+        }
+        .to_lowercase();
+
+        let mut texture_buffer = TextureAssetBuffer::from_image(image, role.texture_type());
+        texture_buffer.generate_mips(MipFilter::Box);
+
+        let extra = texture_buffer.write_packed(
+            self.sub_assets.pack(),
+            &self.sub_assets.abs_path(&file_name),
+            &sampler_settings(&texture.sampler()),
+            self.settings
+                .compress_textures
+                .then(|| material_block_compression(role.texture_type()))
+                .flatten(),
+        )?;
+
+        let name = match texture.name().or(texture.source().name()) {
+            Some(name) => format!("{} ({:?})", name, role),
+            None => format!("texture {} ({:?})", texture.index(), role),
+        };
+
+        let asset_id = self
+            .sub_assets
+            .add(file_name, name, role.type_name(), extra, Vec::new());
+
+        self.textures.insert(key, asset_id);
+
+        Ok(asset_id)
     }
 
-    // fn read_source(
-    //     abs_path: &std::path::Path,
-    //     graphics: &cobalt_graphics::context::Graphics,
-    // ) -> Result<Self, cobalt_assets::server::AssetLoadError> {
-    //     let file = std::fs::File::open(abs_path)?;
+    fn material(&mut self, material: gltf::Material) -> Result<AssetID, AssetImportError> {
+        let index = material.index().unwrap_or(0);
+        let pbr = material.pbr_metallic_roughness();
+
+        let albedo_texture = pbr
+            .base_color_texture()
+            .map(|info| self.texture(info.texture(), TextureRole::Albedo, 1.0))
+            .transpose()?;
+
+        let base_color = pbr.base_color_factor();
+
+        // The texture is multiplied by the color, which is redundant if it's white
+        let albedo_color = if albedo_texture.is_some() && base_color == [1.0; 4] {
+            None
+        } else {
+            Some(base_color)
+        };
+
+        let normal = material
+            .normal_texture()
+            .map(|info| self.texture(info.texture(), TextureRole::Normal, 1.0))
+            .transpose()?;
+
+        // Factors are baked into the textures, the material only takes one or the other
+        let (metallic, roughness) = match pbr.metallic_roughness_texture() {
+            Some(info) => (
+                Either::Right(self.texture(
+                    info.texture(),
+                    TextureRole::Metallic,
+                    pbr.metallic_factor(),
+                )?),
+                Either::Right(self.texture(
+                    info.texture(),
+                    TextureRole::Roughness,
+                    pbr.roughness_factor(),
+                )?),
+            ),
+            None => (
+                Either::Left(pbr.metallic_factor()),
+                Either::Left(pbr.roughness_factor()),
+            ),
+        };
 
-    //     let reader = std::io::BufReader::new(file);
+        let descriptor = MaterialDescriptor {
+            unlit: material.unlit(),
+            wireframe: None,
+            albedo: (albedo_color, albedo_texture),
+            normal,
+            metallic,
+            roughness,
+        };
 
-    //     let gltf = gltf::Gltf::from_reader(reader)
-    //         .map_err(|e| cobalt_assets::server::AssetLoadError::LoadError(Box::new(e)))?;
+        let file_name = format!("material_{}.asset", index);
 
-    //     // let meshes = gltf.meshes().map(|m| {
+        write_ron::<Material, _>(
+            &descriptor,
+            self.sub_assets.pack(),
+            &self.sub_assets.abs_path(&file_name),
+        )?;
 
-    //     //     Resource::new(mesh)
-    //     // });
+        let name = material
+            .name()
+            .map(|name| name.to_string())
+            .unwrap_or_else(|| format!("material {}", index));
 
-    //     Ok(Self {
-    //         meshes: vec![],
-    //         textures: vec![],
-    //         document: gltf.document,
-    //     })
-    // }
+        Ok(self.sub_assets.add(
+            file_name,
+            name,
+            Material::type_name(),
+            ExtraAssetInfo::new(),
+            descriptor.dependencies(),
+        ))
+    }
+
+    /// Packs a primitive as a mesh, returns `None` for primitives that aren't triangle lists.
+    fn primitive(
+        &mut self,
+        mesh: &gltf::Mesh,
+        primitive: gltf::Primitive,
+    ) -> Result<Option<AssetID>, AssetImportError> {
+        let mesh_name = mesh
+            .name()
+            .map(|name| name.to_string())
+            .unwrap_or_else(|| format!("mesh {}", mesh.index()));
+
+        if primitive.mode() != gltf::mesh::Mode::Triangles {
+            log::warn!(
+                "Primitive {} of \"{}\" is not a triangle list ({:?}), skipping it.",
+                primitive.index(),
+                mesh_name,
+                primitive.mode()
+            );
+            return Ok(None);
+        }
+
+        let reader = primitive.reader(|buffer| self.buffers.get(buffer.index()).map(|data| &data[..]));
+
+        let positions = reader
+            .read_positions()
+            .ok_or_else(|| {
+                AssetImportError::ParseError(
+                    format!("Primitive {} of \"{}\" has no positions", primitive.index(), mesh_name)
+                        .into(),
+                )
+            })?
+            .collect::<Vec<_>>();
+
+        let indices = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect::<Vec<_>>(),
+            None => (0..positions.len() as u32).collect(),
+        };
+
+        let normals = match reader.read_normals() {
+            Some(normals) => normals.collect::<Vec<_>>(),
+            None => generate_normals(&positions.concat(), &indices)
+                .chunks_exact(3)
+                .map(|normal| [normal[0], normal[1], normal[2]])
+                .collect(),
+        };
+
+        let uvs = reader
+            .read_tex_coords(0)
+            .map(|uvs| uvs.into_f32().collect::<Vec<_>>());
+
+        if uvs.is_none() {
+            log::warn!("Primitive {} of \"{}\" does not contain texture coordinates. There will be issues if rendering with textures.", primitive.index(), mesh_name);
+        }
+
+        // Tangents are only valid with the normals they were authored for
+        let tangents = reader
+            .read_normals()
+            .and_then(|_| reader.read_tangents())
+            .map(|tangents| tangents.collect::<Vec<_>>());
+
+        let mut vertex_buffer = positions
+            .iter()
+            .enumerate()
+            .map(|(i, position)| UvNormalTangentVertex {
+                position: *position,
+                uv: uvs.as_ref().map_or([0.0, 0.0], |uvs| uvs[i]),
+                normal: normals[i],
+                tangent: tangents.as_ref().map_or([0.0; 4], |tangents| tangents[i]),
+            })
+            .collect::<Vec<_>>();
+
+        if tangents.is_none() {
+            generate_tangents(&mut vertex_buffer, &indices);
+        }
+
+        let bounds = primitive.bounding_box();
+
+        let mut mesh_buffer = MeshAssetBuffer {
+            vertex_buffer,
+            num_indices: indices.len() as u32,
+            index_buffer: MeshIndices::U32(indices),
+            local_aabb: AABB::from_min_max(bounds.min.into(), bounds.max.into()),
+            has_uv: uvs.is_some(),
+            lods: Vec::new(),
+        };
+
+        let file_name = format!("mesh_{}_{}.asset", mesh.index(), primitive.index());
+
+        let stats = optimize_mesh(&mut mesh_buffer, &self.settings.optimize, &self.settings.lod)?;
+
+        let mut extra = mesh_buffer
+            .write_packed(self.sub_assets.pack(), &self.sub_assets.abs_path(&file_name))?;
+        stats.write_extra(&mut extra);
+
+        Ok(Some(self.sub_assets.add(
+            file_name,
+            format!("{}/{}", mesh_name, primitive.index()),
+            Mesh::type_name(),
+            extra,
+            Vec::new(),
+        )))
+    }
 }
 
-impl AssetImporter<GltfImporter> for GltfImporter {
+impl AssetImporter<Model> for GltfImporter {
+    type Settings = GltfImportSettings;
+
+    fn name() -> String {
+        "GltfImporter".to_string()
+    }
+
     fn unimported_fs_type() -> AssetFileSystemType {
         AssetFileSystemType::File
     }
 
-    fn verify_source(
-        abs_path: &std::path::Path,
-    ) -> Result<(), cobalt_assets::asset::AssetVerifyError> {
-        todo!()
+    fn extensions() -> Vec<String> {
+        vec!["gltf".to_string(), "glb".to_string()]
+    }
+
+    fn note() -> Option<String> {
+        Some("Meshes, materials and textures are packed as sub-assets. Orthographic cameras, skins and animations are not imported. Mesh optimizations are set in [optimize], LOD generation in [lod]. compress_textures = true block compresses textures.".to_string())
+    }
+
+    fn verify_source(abs_path: &Path) -> Result<(), AssetVerifyError> {
+        gltf::Gltf::open(abs_path).map_err(|e| AssetVerifyError::InvalidFile(Box::new(e)))?;
+
+        Ok(())
     }
 
     fn import(
-        abs_input_path: &std::path::Path,
-        asset_info: &cobalt_assets::manifest::AssetInfo,
-        assets_dir: &std::path::Path,
-    ) -> Result<cobalt_assets::manifest::ExtraAssetInfo, cobalt_assets::asset::AssetImportError>
-    {
-        todo!()
+        abs_input_path: &Path,
+        asset_info: &AssetInfo,
+        assets_dir: &Path,
+        settings: &Self::Settings,
+    ) -> Result<ImportedAsset, AssetImportError> {
+        let (document, buffers, images) = gltf::import(abs_input_path)
+            .map_err(|e| AssetImportError::LoadError(Box::new(e)))?;
+
+        let mut import = GltfImport {
+            sub_assets: SubAssets::new(asset_info, assets_dir)?,
+            settings,
+            buffers,
+            images,
+            textures: HashMap::new(),
+        };
+
+        let materials = document
+            .materials()
+            .map(|material| import.material(material))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Primitives of each glTF mesh, skipped ones are left out
+        let mut meshes = Vec::with_capacity(document.meshes().len());
+
+        for mesh in document.meshes() {
+            let mut primitives = Vec::new();
+
+            for primitive in mesh.primitives() {
+                let material = primitive.material().index().map(|index| materials[index]);
+
+                if let Some(mesh_id) = import.primitive(&mesh, primitive)? {
+                    primitives.push(ModelPrimitive {
+                        mesh: mesh_id,
+                        material,
+                    });
+                }
+            }
+
+            meshes.push(primitives);
+        }
+
+        let nodes = document
+            .nodes()
+            .map(|node| {
+                let (translation, rotation, scale) = node.transform().decomposed();
+
+                ModelNode {
+                    name: node.name().map(|name| name.to_string()),
+                    translation,
+                    rotation,
+                    scale,
+                    children: node.children().map(|child| child.index()).collect(),
+                    primitives: node
+                        .mesh()
+                        .map(|mesh| meshes[mesh.index()].clone())
+                        .unwrap_or_default(),
+                    light: node.light().map(convert_light),
+                    camera: node.camera().and_then(convert_camera),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let roots = match document.default_scene().or_else(|| document.scenes().next()) {
+            Some(scene) => scene.nodes().map(|node| node.index()).collect(),
+            // Without scenes, every node that isn't a child is a root
+            None => (0..nodes.len())
+                .filter(|index| !nodes.iter().any(|node| node.children.contains(index)))
+                .collect(),
+        };
+
+        let scene = ModelScene { nodes, roots };
+
+        write_ron::<Model, _>(
+            &scene,
+            &asset_info.pack,
+            &import.sub_assets.abs_path(Model::SCENE_FILE),
+        )?;
+
+        let mut extra = ExtraAssetInfo::new();
+        extra.0.insert("nodes".to_string(), scene.nodes.len().to_string());
+        extra.0.insert("meshes".to_string(), meshes.iter().flatten().count().to_string());
+        extra.0.insert("materials".to_string(), materials.len().to_string());
+        extra.0.insert("textures".to_string(), import.textures.len().to_string());
+
+        Ok(ImportedAsset {
+            extra,
+            // The asset holds handles to everything its nodes use
+            dependencies: scene.dependencies(),
+            sub_assets: import.sub_assets.into_vec(),
+        })
+    }
+}
+
+fn convert_light(light: gltf::khr_lights_punctual::Light) -> ModelLight {
+    use gltf::khr_lights_punctual::Kind;
+
+    let color = light.color();
+    let intensity = light.intensity();
+
+    match light.kind() {
+        Kind::Directional => ModelLight::Directional { color, intensity },
+        Kind::Point => ModelLight::Point {
+            color,
+            intensity,
+            range: light.range(),
+        },
+        Kind::Spot {
+            inner_cone_angle,
+            outer_cone_angle,
+        } => ModelLight::Spot {
+            color,
+            intensity,
+            range: light.range(),
+            inner_cone_angle,
+            outer_cone_angle,
+        },
+    }
+}
+
+/// Perspective cameras only, infinite projections get `DEFAULT_FAR` as their far plane.
+fn convert_camera(camera: gltf::Camera) -> Option<Projection> {
+    const DEFAULT_FAR: f32 = 1000.0;
+
+    match camera.projection() {
+        gltf::camera::Projection::Perspective(perspective) => Some(Projection::Perspective {
+            fov: perspective.yfov(),
+            aspect: perspective
+                .aspect_ratio()
+                .map_or(AspectRatio::Auto, AspectRatio::Value),
+            near: perspective.znear(),
+            far: perspective.zfar().unwrap_or(DEFAULT_FAR),
+        }),
+        gltf::camera::Projection::Orthographic(_) => {
+            log::warn!(
+                "Orthographic camera {} is not supported, skipping it.",
+                camera.name().unwrap_or("unnamed")
+            );
+            None
+        }
+    }
+}
+
+/// Converts decoded glTF image data. Two channel images are expanded to RGB with an empty blue channel.
+fn to_dynamic_image(data: &gltf::image::Data) -> Result<image::DynamicImage, AssetImportError> {
+    use gltf::image::Format;
+    use image::{DynamicImage, ImageBuffer};
+
+    let (width, height) = (data.width, data.height);
+
+    let u16s = || {
+        data.pixels
+            .chunks_exact(2)
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+            .collect::<Vec<_>>()
+    };
+
+    let f32s = || {
+        data.pixels
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect::<Vec<_>>()
+    };
+
+    let image = match data.format {
+        Format::R8 => ImageBuffer::from_raw(width, height, data.pixels.clone())
+            .map(DynamicImage::ImageLuma8),
+        Format::R8G8 => ImageBuffer::from_raw(
+            width,
+            height,
+            data.pixels
+                .chunks_exact(2)
+                .flat_map(|rg| [rg[0], rg[1], 0])
+                .collect(),
+        )
+        .map(DynamicImage::ImageRgb8),
+        Format::R8G8B8 => ImageBuffer::from_raw(width, height, data.pixels.clone())
+            .map(DynamicImage::ImageRgb8),
+        Format::R8G8B8A8 => ImageBuffer::from_raw(width, height, data.pixels.clone())
+            .map(DynamicImage::ImageRgba8),
+        Format::R16 => ImageBuffer::from_raw(width, height, u16s()).map(DynamicImage::ImageLuma16),
+        Format::R16G16 => ImageBuffer::from_raw(
+            width,
+            height,
+            u16s().chunks_exact(2).flat_map(|rg| [rg[0], rg[1], 0]).collect(),
+        )
+        .map(DynamicImage::ImageRgb16),
+        Format::R16G16B16 => {
+            ImageBuffer::from_raw(width, height, u16s()).map(DynamicImage::ImageRgb16)
+        }
+        Format::R16G16B16A16 => {
+            ImageBuffer::from_raw(width, height, u16s()).map(DynamicImage::ImageRgba16)
+        }
+        Format::R32G32B32FLOAT => {
+            ImageBuffer::from_raw(width, height, f32s()).map(DynamicImage::ImageRgb32F)
+        }
+        Format::R32G32B32A32FLOAT => {
+            ImageBuffer::from_raw(width, height, f32s()).map(DynamicImage::ImageRgba32F)
+        }
+    };
+
+    image.ok_or_else(|| {
+        AssetImportError::ParseError(
+            format!("Image data does not match its size ({}x{}, {:?})", width, height, data.format)
+                .into(),
+        )
+    })
+}
+
+/// A single channel of the image as a grayscale image, multiplied by `factor`.
+fn extract_channel(image: &image::DynamicImage, channel: usize, factor: f32) -> image::DynamicImage {
+    let rgba = image.to_rgba8();
+
+    let gray = image::GrayImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let value = rgba.get_pixel(x, y).0[channel] as f32 * factor;
+
+        image::Luma([value.round().clamp(0.0, 255.0) as u8])
+    });
+
+    image::DynamicImage::ImageLuma8(gray)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extract_scaled_channel() {
+        let image = image::DynamicImage::ImageRgb8(image::RgbImage::from_pixel(
+            2,
+            2,
+            image::Rgb([0, 128, 255]),
+        ));
+
+        let roughness = extract_channel(&image, 1, 1.0).into_luma8();
+        let metallic = extract_channel(&image, 2, 0.5).into_luma8();
+
+        assert_eq!(roughness.get_pixel(1, 1).0, [128]);
+        assert_eq!(metallic.get_pixel(0, 0).0, [128]);
+    }
+
+    #[test]
+    fn shared_metallic_roughness_textures_are_baked_per_factor() {
+        let dir =
+            std::env::temp_dir().join(format!("cobalt_gltf_shared_mr_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        image::RgbImage::from_pixel(4, 4, image::Rgb([0, 128, 255]))
+            .save(dir.join("mr.png"))
+            .unwrap();
+
+        // The third material uses the same factors as the first
+        std::fs::write(
+            dir.join("scene.gltf"),
+            r#"{
+                "asset": { "version": "2.0" },
+                "images": [{ "uri": "mr.png" }],
+                "textures": [{ "source": 0 }],
+                "materials": [
+                    { "pbrMetallicRoughness": { "metallicFactor": 1.0, "roughnessFactor": 1.0, "metallicRoughnessTexture": { "index": 0 } } },
+                    { "pbrMetallicRoughness": { "metallicFactor": 0.5, "roughnessFactor": 0.25, "metallicRoughnessTexture": { "index": 0 } } },
+                    { "pbrMetallicRoughness": { "metallicFactor": 1.0, "roughnessFactor": 1.0, "metallicRoughnessTexture": { "index": 0 } } }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let asset_info = AssetInfo {
+            asset_id: AssetID::generate(),
+            relative_path: "scene".into(),
+            pack: Default::default(),
+            name: "scene".to_string(),
+            timestamp: std::time::SystemTime::now(),
+            type_name: Model::type_name(),
+            extra: ExtraAssetInfo::new(),
+            dependencies: Vec::new(),
+            source: None,
+            tags: Vec::new(),
+        };
+
+        let imported = GltfImporter::import(
+            &dir.join("scene.gltf"),
+            &asset_info,
+            &dir,
+            &GltfImportSettings::default(),
+        )
+        .unwrap();
+
+        let mut textures = imported
+            .sub_assets
+            .iter()
+            .filter(|sub_asset| {
+                sub_asset
+                    .relative_path
+                    .to_string_lossy()
+                    .starts_with("texture_")
+            })
+            .map(|sub_asset| sub_asset.relative_path.clone())
+            .collect::<Vec<_>>();
+
+        // Metallic and roughness for each of the two factor pairs, in separate files
+        assert_eq!(textures.len(), 4);
+        textures.sort();
+        textures.dedup();
+        assert_eq!(textures.len(), 4);
+
+        for texture in &textures {
+            assert!(dir.join("scene").join(texture).is_file());
+        }
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn expand_two_channel_images() {
+        let data = gltf::image::Data {
+            pixels: vec![10, 20, 30, 40],
+            format: gltf::image::Format::R8G8,
+            width: 2,
+            height: 1,
+        };
+
+        let image = to_dynamic_image(&data).unwrap().into_rgb8();
+
+        assert_eq!(image.get_pixel(1, 0).0, [30, 40, 0]);
     }
 }
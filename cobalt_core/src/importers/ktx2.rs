@@ -0,0 +1,456 @@
+use std::io::Read;
+
+use cobalt_graphics::texture::TextureKind;
+
+/// File identifier every KTX2 file starts with.
+const IDENTIFIER: [u8; 12] = [
+    0xAB, b'K', b'T', b'X', b' ', b'2', b'0', 0xBB, b'\r', b'\n', 0x1A, b'\n',
+];
+/// Identifier, header and index.
+const HEADER_SIZE: usize = 80;
+const LEVEL_INDEX_ENTRY_SIZE: usize = 24;
+
+const SUPERCOMPRESSION_NONE: u32 = 0;
+const SUPERCOMPRESSION_ZSTD: u32 = 2;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Ktx2Error {
+    #[error("Not a KTX2 file")]
+    InvalidIdentifier,
+    #[error("Unexpected end of file")]
+    UnexpectedEof,
+    #[error("Unsupported Vulkan format {0}")]
+    UnsupportedFormat(u32),
+    #[error("Unsupported supercompression scheme {0}")]
+    UnsupportedSupercompression(u32),
+    #[error("{0:?} textures can't be stored in KTX2")]
+    UnsupportedTextureFormat(wgpu::TextureFormat),
+    #[error("Cube map arrays, 3D texture arrays and non square cube maps aren't supported")]
+    UnsupportedDimensions,
+    #[error("Level {0} has {1} bytes, expected {2}")]
+    LevelSize(usize, usize, usize),
+    #[error("Failed to supercompress or decompress a level")]
+    Io(#[from] std::io::Error),
+}
+
+/// A texture in a KTX2 container, with its mip levels.
+/// Only the formats `TextureType` and `BlockCompression` can be stored as are supported.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ktx2Texture {
+    pub format: wgpu::TextureFormat,
+    pub kind: TextureKind,
+    pub width: u32,
+    pub height: u32,
+    /// Number of array layers, cube faces or depth slices, 1 for 2D textures.
+    pub layers: u32,
+    /// Data of each mip level, largest first, without supercompression.
+    /// A level holds every layer, face or depth slice one after the other.
+    pub levels: Vec<Vec<u8>>,
+}
+
+impl Ktx2Texture {
+    /// Whether `data` starts with the KTX2 file identifier.
+    pub fn has_identifier(data: &[u8]) -> bool {
+        data.starts_with(&IDENTIFIER)
+    }
+
+    pub fn read(data: &[u8]) -> Result<Self, Ktx2Error> {
+        if data.len() < HEADER_SIZE {
+            return Err(Ktx2Error::UnexpectedEof);
+        }
+        if data[..IDENTIFIER.len()] != IDENTIFIER {
+            return Err(Ktx2Error::InvalidIdentifier);
+        }
+
+        let vk_format = read_u32(data, 12)?;
+        let width = read_u32(data, 20)?;
+        let height = read_u32(data, 24)?;
+        let depth = read_u32(data, 28)?;
+        let layer_count = read_u32(data, 32)?;
+        let face_count = read_u32(data, 36)?;
+        // 0 asks the loader to generate the mips, there is still one level in the file
+        let level_count = read_u32(data, 40)?.max(1) as usize;
+        let supercompression = read_u32(data, 44)?;
+
+        let format = format_from_vk(vk_format).ok_or(Ktx2Error::UnsupportedFormat(vk_format))?;
+
+        let (kind, layers) = match (depth, layer_count, face_count) {
+            (0, 0, 1) => (TextureKind::D2, 1),
+            (0, layers, 1) => (TextureKind::D2Array, layers),
+            (0, 0, 6) if width == height => (TextureKind::Cube, 6),
+            (depth, 0, 1) => (TextureKind::D3, depth),
+            _ => return Err(Ktx2Error::UnsupportedDimensions),
+        };
+
+        if height == 0 {
+            return Err(Ktx2Error::UnsupportedDimensions);
+        }
+        if supercompression != SUPERCOMPRESSION_NONE && supercompression != SUPERCOMPRESSION_ZSTD {
+            return Err(Ktx2Error::UnsupportedSupercompression(supercompression));
+        }
+
+        let mut levels = Vec::with_capacity(level_count);
+
+        for level in 0..level_count {
+            let entry = HEADER_SIZE + level * LEVEL_INDEX_ENTRY_SIZE;
+
+            let offset = read_u64(data, entry)? as usize;
+            let length = read_u64(data, entry + 8)? as usize;
+            let uncompressed_length = read_u64(data, entry + 16)? as usize;
+
+            let level_data = data
+                .get(offset..offset + length)
+                .ok_or(Ktx2Error::UnexpectedEof)?;
+
+            let level_data = match supercompression {
+                SUPERCOMPRESSION_ZSTD => {
+                    let mut decoded = Vec::with_capacity(uncompressed_length);
+                    zstd::Decoder::new(level_data)?.read_to_end(&mut decoded)?;
+                    decoded
+                }
+                _ => level_data.to_vec(),
+            };
+
+            let expected = level_size(format, width, height, level as u32)
+                * level_layers(kind, layers, level as u32) as usize;
+            if level_data.len() != expected {
+                return Err(Ktx2Error::LevelSize(level, level_data.len(), expected));
+            }
+
+            levels.push(level_data);
+        }
+
+        Ok(Self {
+            format,
+            kind,
+            width,
+            height,
+            layers,
+            levels,
+        })
+    }
+
+    /// Writes the texture, each level is zstd supercompressed with `zstd_level` if given.
+    pub fn write(&self, zstd_level: Option<i32>) -> Result<Vec<u8>, Ktx2Error> {
+        let vk_format =
+            format_to_vk(self.format).ok_or(Ktx2Error::UnsupportedTextureFormat(self.format))?;
+
+        let levels = self
+            .levels
+            .iter()
+            .map(|level| match zstd_level {
+                Some(zstd_level) => zstd::encode_all(level.as_slice(), zstd_level),
+                None => Ok(level.clone()),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let dfd = data_format_descriptor(self.format);
+        let dfd_offset = HEADER_SIZE + levels.len() * LEVEL_INDEX_ENTRY_SIZE;
+
+        let mut data = Vec::new();
+        data.extend_from_slice(&IDENTIFIER);
+
+        let (depth, layer_count, face_count) = match self.kind {
+            TextureKind::D2 => (0, 0, 1),
+            TextureKind::D2Array => (0, self.layers, 1),
+            TextureKind::Cube => (0, 0, 6),
+            TextureKind::D3 => (self.layers, 0, 1),
+        };
+
+        for value in [
+            vk_format,
+            type_size(self.format),
+            self.width,
+            self.height,
+            depth,
+            layer_count,
+            face_count,
+            levels.len() as u32,
+            match zstd_level {
+                Some(_) => SUPERCOMPRESSION_ZSTD,
+                None => SUPERCOMPRESSION_NONE,
+            },
+            dfd_offset as u32,
+            dfd.len() as u32,
+            // No key/value data
+            0,
+            0,
+        ] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        // No supercompression global data
+        data.extend_from_slice(&0u64.to_le_bytes());
+        data.extend_from_slice(&0u64.to_le_bytes());
+
+        // Level index, filled in once the offsets are known
+        data.resize(dfd_offset, 0);
+        data.extend_from_slice(&dfd);
+
+        // Smallest level first, as the specification recommends
+        for (level, level_data) in levels.iter().enumerate().rev() {
+            // 16 is a multiple of every supported block size and of 4
+            data.resize(data.len().next_multiple_of(16), 0);
+
+            let entry = HEADER_SIZE + level * LEVEL_INDEX_ENTRY_SIZE;
+            let offset = data.len() as u64;
+
+            data[entry..entry + 8].copy_from_slice(&offset.to_le_bytes());
+            data[entry + 8..entry + 16].copy_from_slice(&(level_data.len() as u64).to_le_bytes());
+            data[entry + 16..entry + 24]
+                .copy_from_slice(&(self.levels[level].len() as u64).to_le_bytes());
+
+            data.extend_from_slice(level_data);
+        }
+
+        Ok(data)
+    }
+}
+
+/// Number of layers of a mip level, depth slices of 3D textures halve with each level.
+pub fn level_layers(kind: TextureKind, layers: u32, level: u32) -> u32 {
+    match kind {
+        TextureKind::D3 => (layers >> level).max(1),
+        _ => layers,
+    }
+}
+
+/// Size in bytes of one layer of a mip level, block compressed levels are rounded up to whole blocks.
+pub fn level_size(format: wgpu::TextureFormat, width: u32, height: u32, level: u32) -> usize {
+    let width = (width >> level).max(1);
+    let height = (height >> level).max(1);
+
+    let (block_width, block_height) = format.block_dimensions();
+    let block_size = format.block_copy_size(None).unwrap_or(0) as usize;
+
+    width.div_ceil(block_width) as usize * height.div_ceil(block_height) as usize * block_size
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, Ktx2Error> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or(Ktx2Error::UnexpectedEof)
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Result<u64, Ktx2Error> {
+    data.get(offset..offset + 8)
+        .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or(Ktx2Error::UnexpectedEof)
+}
+
+fn format_from_vk(vk_format: u32) -> Option<wgpu::TextureFormat> {
+    use wgpu::TextureFormat::*;
+
+    Some(match vk_format {
+        9 => R8Unorm,
+        37 => Rgba8Unorm,
+        43 => Rgba8UnormSrgb,
+        76 => R16Float,
+        97 => Rgba16Float,
+        100 => R32Float,
+        109 => Rgba32Float,
+        // BC1 without alpha decodes the same as with it
+        131 | 133 => Bc1RgbaUnorm,
+        132 | 134 => Bc1RgbaUnormSrgb,
+        137 => Bc3RgbaUnorm,
+        138 => Bc3RgbaUnormSrgb,
+        139 => Bc4RUnorm,
+        141 => Bc5RgUnorm,
+        145 => Bc7RgbaUnorm,
+        146 => Bc7RgbaUnormSrgb,
+        _ => return None,
+    })
+}
+
+fn format_to_vk(format: wgpu::TextureFormat) -> Option<u32> {
+    use wgpu::TextureFormat::*;
+
+    Some(match format {
+        R8Unorm => 9,
+        Rgba8Unorm => 37,
+        Rgba8UnormSrgb => 43,
+        R16Float => 76,
+        Rgba16Float => 97,
+        R32Float => 100,
+        Rgba32Float => 109,
+        Bc1RgbaUnorm => 133,
+        Bc1RgbaUnormSrgb => 134,
+        Bc3RgbaUnorm => 137,
+        Bc3RgbaUnormSrgb => 138,
+        Bc4RUnorm => 139,
+        Bc5RgUnorm => 141,
+        Bc7RgbaUnorm => 145,
+        Bc7RgbaUnormSrgb => 146,
+        _ => return None,
+    })
+}
+
+/// Size of the data type of a texel component, 1 for block compressed formats.
+fn type_size(format: wgpu::TextureFormat) -> u32 {
+    match format {
+        wgpu::TextureFormat::R16Float | wgpu::TextureFormat::Rgba16Float => 2,
+        wgpu::TextureFormat::R32Float | wgpu::TextureFormat::Rgba32Float => 4,
+        _ => 1,
+    }
+}
+
+/// Basic data format descriptor of a supported format.
+fn data_format_descriptor(format: wgpu::TextureFormat) -> Vec<u8> {
+    use wgpu::TextureFormat::*;
+
+    const MODEL_RGBSDA: u8 = 1;
+    const MODEL_BC1A: u8 = 128;
+    const MODEL_BC3: u8 = 130;
+    const MODEL_BC4: u8 = 131;
+    const MODEL_BC5: u8 = 132;
+    const MODEL_BC7: u8 = 134;
+
+    const CHANNEL_ALPHA: u8 = 15;
+    const SAMPLE_LINEAR: u8 = 0x10;
+    const SAMPLE_SIGNED: u8 = 0x40;
+    const SAMPLE_FLOAT: u8 = 0x80;
+
+    // (bit offset, bit length, channel, lower, upper)
+    let unorm = |offset: u16, length: u16, channel: u8| (offset, length, channel, 0, u32::MAX);
+    let float = |offset: u16, length: u16, channel: u8| {
+        (
+            offset,
+            length,
+            channel | SAMPLE_FLOAT | SAMPLE_SIGNED,
+            (-1.0f32).to_bits(),
+            1.0f32.to_bits(),
+        )
+    };
+    let rgba = |bits: u16, sample: &dyn Fn(u16, u16, u8) -> (u16, u16, u8, u32, u32)| {
+        [0, 1, 2, CHANNEL_ALPHA]
+            .iter()
+            .enumerate()
+            .map(|(i, channel)| sample(i as u16 * bits, bits, *channel))
+            .collect::<Vec<_>>()
+    };
+
+    let (model, block_dimension, samples) = match format {
+        R8Unorm => (MODEL_RGBSDA, 0, vec![(0, 8, 0, 0, 255)]),
+        Rgba8Unorm | Rgba8UnormSrgb => (
+            MODEL_RGBSDA,
+            0,
+            rgba(8, &|offset, length, channel| {
+                (offset, length, channel, 0, 255)
+            }),
+        ),
+        R16Float => (MODEL_RGBSDA, 0, vec![float(0, 16, 0)]),
+        Rgba16Float => (MODEL_RGBSDA, 0, rgba(16, &float)),
+        R32Float => (MODEL_RGBSDA, 0, vec![float(0, 32, 0)]),
+        Rgba32Float => (MODEL_RGBSDA, 0, rgba(32, &float)),
+        // BC1 with alpha uses the "alpha present" channel
+        Bc1RgbaUnorm | Bc1RgbaUnormSrgb => (MODEL_BC1A, 3, vec![unorm(0, 64, 1)]),
+        Bc3RgbaUnorm | Bc3RgbaUnormSrgb => (
+            MODEL_BC3,
+            3,
+            vec![unorm(0, 64, CHANNEL_ALPHA), unorm(64, 64, 0)],
+        ),
+        Bc4RUnorm => (MODEL_BC4, 3, vec![unorm(0, 64, 0)]),
+        Bc5RgUnorm => (MODEL_BC5, 3, vec![unorm(0, 64, 0), unorm(64, 64, 1)]),
+        _ => (MODEL_BC7, 3, vec![unorm(0, 128, 0)]),
+    };
+
+    let srgb = format.is_srgb();
+    let bytes_per_block = format.block_copy_size(None).unwrap_or(0) as u8;
+    let block_size = 24 + 16 * samples.len();
+
+    let mut dfd = Vec::with_capacity(4 + block_size);
+    dfd.extend_from_slice(&(4 + block_size as u32).to_le_bytes());
+    // Khronos vendor, basic descriptor type
+    dfd.extend_from_slice(&0u32.to_le_bytes());
+    // Version 1.3
+    dfd.extend_from_slice(&2u16.to_le_bytes());
+    dfd.extend_from_slice(&(block_size as u16).to_le_bytes());
+    // Model, BT.709 primaries, transfer function, straight alpha
+    dfd.extend_from_slice(&[model, 1, if srgb { 2 } else { 1 }, 0]);
+    dfd.extend_from_slice(&[block_dimension, block_dimension, 0, 0]);
+    dfd.extend_from_slice(&[bytes_per_block, 0, 0, 0, 0, 0, 0, 0]);
+
+    for (offset, length, mut channel, lower, upper) in samples {
+        // Alpha is never sRGB encoded
+        if srgb && channel & 0x0F == CHANNEL_ALPHA {
+            channel |= SAMPLE_LINEAR;
+        }
+
+        dfd.extend_from_slice(&offset.to_le_bytes());
+        dfd.push((length - 1) as u8);
+        dfd.push(channel);
+        dfd.extend_from_slice(&[0, 0, 0, 0]);
+        dfd.extend_from_slice(&lower.to_le_bytes());
+        dfd.extend_from_slice(&upper.to_le_bytes());
+    }
+
+    dfd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texture() -> Ktx2Texture {
+        Ktx2Texture {
+            format: wgpu::TextureFormat::Bc7RgbaUnormSrgb,
+            kind: TextureKind::D2,
+            width: 8,
+            height: 4,
+            layers: 1,
+            levels: vec![vec![7; 32], vec![3; 16], vec![1; 16]],
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        let texture = texture();
+
+        assert_eq!(
+            Ktx2Texture::read(&texture.write(None).unwrap()).unwrap(),
+            texture
+        );
+        assert_eq!(
+            Ktx2Texture::read(&texture.write(Some(3)).unwrap()).unwrap(),
+            texture
+        );
+    }
+
+    #[test]
+    fn layered_roundtrip() {
+        let cube = Ktx2Texture {
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            kind: TextureKind::Cube,
+            width: 2,
+            height: 2,
+            layers: 6,
+            levels: vec![vec![5; 6 * 16], vec![9; 6 * 4]],
+        };
+        assert_eq!(Ktx2Texture::read(&cube.write(None).unwrap()).unwrap(), cube);
+
+        let lut = Ktx2Texture {
+            kind: TextureKind::D3,
+            layers: 2,
+            levels: vec![vec![5; 2 * 16], vec![9; 4]],
+            ..cube
+        };
+        assert_eq!(Ktx2Texture::read(&lut.write(None).unwrap()).unwrap(), lut);
+    }
+
+    #[test]
+    fn level_sizes_round_up_to_blocks() {
+        assert_eq!(level_size(wgpu::TextureFormat::Bc1RgbaUnorm, 6, 6, 0), 32);
+        assert_eq!(level_size(wgpu::TextureFormat::Bc1RgbaUnorm, 6, 6, 2), 8);
+        assert_eq!(level_size(wgpu::TextureFormat::Rgba8Unorm, 6, 6, 1), 36);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let mut data = texture().write(None).unwrap();
+        data[1] = b'X';
+
+        assert!(matches!(
+            Ktx2Texture::read(&data),
+            Err(Ktx2Error::InvalidIdentifier)
+        ));
+    }
+}
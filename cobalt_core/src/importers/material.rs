@@ -0,0 +1,91 @@
+use cobalt_assets::{
+    asset::{AssetFileSystemType, AssetImportError, AssetImporter, AssetVerifyError, NoSettings},
+    manifest::{AssetInfo, ExtraAssetInfo, ImportedAsset, Manifest},
+};
+
+use crate::renderer::deferred::exports::{Material, MaterialDescriptor};
+
+use super::write_ron;
+
+/// Imports material files, `MaterialDescriptor`s written in RON.
+/// Referenced textures must already be in the manifest.
+pub struct MaterialImporter;
+
+fn parse_descriptor(abs_path: &std::path::Path) -> Result<MaterialDescriptor, AssetImportError> {
+    let text = std::fs::read_to_string(abs_path)
+        .map_err(|e| AssetImportError::ReadError(Box::new(e)))?;
+
+    ron::from_str(&text).map_err(|e| AssetImportError::ParseError(Box::new(e)))
+}
+
+impl AssetImporter<Material> for MaterialImporter {
+    type Settings = NoSettings;
+
+    fn name() -> String {
+        "MaterialImporter".to_string()
+    }
+
+    fn unimported_fs_type() -> AssetFileSystemType {
+        AssetFileSystemType::File
+    }
+
+    fn extensions() -> Vec<String> {
+        vec!["ron".to_string()]
+    }
+
+    fn note() -> Option<String> {
+        Some("Textures are referenced by asset ID and must be imported first.".to_string())
+    }
+
+    fn verify_source(abs_path: &std::path::Path) -> Result<(), AssetVerifyError> {
+        let text = std::fs::read_to_string(abs_path)
+            .map_err(|e| AssetVerifyError::InvalidFile(Box::new(e)))?;
+
+        ron::from_str::<MaterialDescriptor>(&text)
+            .map_err(|e| AssetVerifyError::InvalidFile(Box::new(e)))?;
+
+        Ok(())
+    }
+
+    fn import(
+        abs_input_path: &std::path::Path,
+        asset_info: &AssetInfo,
+        assets_dir: &std::path::Path,
+        _settings: &Self::Settings,
+    ) -> Result<ImportedAsset, AssetImportError> {
+        let descriptor = parse_descriptor(abs_input_path)?;
+
+        let manifest =
+            Manifest::load(assets_dir).map_err(|e| AssetImportError::LoadError(Box::new(e)))?;
+
+        for (asset_id, expected_type) in descriptor.expected_texture_types() {
+            let texture_info = manifest.find_asset(asset_id).ok_or_else(|| {
+                AssetImportError::ProcessError(
+                    format!("Texture {} is not in the manifest", asset_id.uuid()).into(),
+                )
+            })?;
+
+            if texture_info.type_name != expected_type {
+                return Err(AssetImportError::ProcessError(
+                    format!(
+                        "Texture \"{}\" is a {}, expected a {}",
+                        texture_info.name, texture_info.type_name, expected_type
+                    )
+                    .into(),
+                ));
+            }
+        }
+
+        write_ron::<Material, _>(
+            &descriptor,
+            &asset_info.pack,
+            &assets_dir.join(&asset_info.relative_path),
+        )?;
+
+        Ok(ImportedAsset {
+            extra: ExtraAssetInfo::new(),
+            dependencies: descriptor.dependencies(),
+            sub_assets: Vec::new(),
+        })
+    }
+}
@@ -0,0 +1,337 @@
+use cobalt_assets::{asset::AssetImportError, manifest::ExtraAssetInfo};
+use cobalt_graphics::vertex::UvNormalTangentVertex;
+use hashbrown::HashMap;
+
+use crate::{
+    asset_types::mesh::{MeshAssetBuffer, MeshIndices},
+    renderer::mesh::MeshLod,
+};
+
+/// Import-time mesh processing, available on every mesh importer.
+/// Triangle reordering uses meshoptimizer.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct MeshOptimizeSettings {
+    /// Merge vertices with identical attributes.
+    pub weld: bool,
+    /// Reorder triangles for the post-transform vertex cache.
+    pub vertex_cache: bool,
+    /// Reorder triangles to reduce overdraw, after the vertex cache pass.
+    pub overdraw: bool,
+    /// How much worse the vertex cache efficiency may get for less overdraw, 1.05 allows 5%.
+    pub overdraw_threshold: f32,
+    /// Reorder vertices in the order the triangles use them.
+    pub vertex_fetch: bool,
+    /// Use 16 bit indices when there are at most 65536 vertices.
+    pub u16_indices: bool,
+}
+
+impl Default for MeshOptimizeSettings {
+    fn default() -> Self {
+        Self {
+            weld: true,
+            vertex_cache: true,
+            overdraw: true,
+            overdraw_threshold: 1.05,
+            vertex_fetch: true,
+            u16_indices: true,
+        }
+    }
+}
+
+/// Simplified LOD levels generated at import time, with quadric edge collapse.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct MeshLodSettings {
+    pub generate: bool,
+    /// Triangle count of each level relative to the base mesh, from most to least detailed.
+    pub ratios: Vec<f32>,
+    /// Each level is used once the mesh covers at most this fraction of the screen height.
+    /// Pairs with `ratios`.
+    pub screen_sizes: Vec<f32>,
+    /// Largest simplification error allowed, relative to the mesh extents.
+    pub max_error: f32,
+}
+
+impl Default for MeshLodSettings {
+    fn default() -> Self {
+        Self {
+            generate: false,
+            ratios: vec![0.5, 0.25, 0.125],
+            screen_sizes: vec![0.5, 0.25, 0.1],
+            max_error: 0.05,
+        }
+    }
+}
+
+/// Vertex and triangle counts before and after optimizing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MeshOptimizeStats {
+    pub vertices_before: usize,
+    pub vertices_after: usize,
+    pub triangles_before: usize,
+    pub triangles_after: usize,
+    /// Triangle count of each LOD level.
+    pub lod_triangles: Vec<usize>,
+}
+
+impl MeshOptimizeStats {
+    pub fn write_extra(&self, extra: &mut ExtraAssetInfo) {
+        extra.0.insert(
+            "vertices_before".to_string(),
+            self.vertices_before.to_string(),
+        );
+        extra.0.insert(
+            "vertices_after".to_string(),
+            self.vertices_after.to_string(),
+        );
+        extra.0.insert(
+            "triangles_before".to_string(),
+            self.triangles_before.to_string(),
+        );
+        extra.0.insert(
+            "triangles_after".to_string(),
+            self.triangles_after.to_string(),
+        );
+
+        if !self.lod_triangles.is_empty() {
+            let lod_triangles = self
+                .lod_triangles
+                .iter()
+                .map(|triangles| triangles.to_string())
+                .collect::<Vec<_>>();
+
+            extra
+                .0
+                .insert("lod_triangles".to_string(), lod_triangles.join(", "));
+        }
+    }
+}
+
+/// Runs the enabled optimizations on `mesh` in place and generates its LOD levels.
+pub(crate) fn optimize_mesh(
+    mesh: &mut MeshAssetBuffer,
+    settings: &MeshOptimizeSettings,
+    lod_settings: &MeshLodSettings,
+) -> Result<MeshOptimizeStats, AssetImportError> {
+    let mut vertices = std::mem::take(&mut mesh.vertex_buffer);
+    let mut indices = mesh.index_buffer.to_u32();
+
+    let vertices_before = vertices.len();
+    let triangles_before = indices.len() / 3;
+
+    if settings.weld {
+        (vertices, indices) = weld_vertices(&vertices, &indices);
+    }
+
+    if settings.vertex_cache {
+        indices = meshopt::optimize_vertex_cache(&indices, vertices.len());
+    }
+
+    let lod_levels = {
+        let adapter = meshopt::VertexDataAdapter::new(
+            bytemuck::cast_slice(&vertices),
+            std::mem::size_of::<UvNormalTangentVertex>(),
+            0,
+        )
+        .map_err(|e| {
+            AssetImportError::ProcessError(format!("Invalid vertex data: {:?}", e).into())
+        })?;
+
+        if settings.overdraw {
+            indices = optimize_overdraw(&indices, &adapter, settings.overdraw_threshold);
+        }
+
+        if lod_settings.generate {
+            generate_lods(&indices, &adapter, vertices.len(), lod_settings)
+        } else {
+            Vec::new()
+        }
+    };
+
+    let num_indices = indices.len() as u32;
+    let mut lods = Vec::with_capacity(lod_levels.len());
+
+    for (lod_indices, screen_size) in lod_levels {
+        lods.push(MeshLod {
+            first_index: indices.len() as u32,
+            num_indices: lod_indices.len() as u32,
+            screen_size,
+        });
+
+        indices.extend(lod_indices);
+    }
+
+    // Base mesh first, its vertices stay at the start of the buffer
+    if settings.vertex_fetch {
+        optimize_vertex_fetch(&mut vertices, &mut indices);
+    }
+
+    let stats = MeshOptimizeStats {
+        vertices_before,
+        vertices_after: vertices.len(),
+        triangles_before,
+        triangles_after: num_indices as usize / 3,
+        lod_triangles: lods
+            .iter()
+            .map(|lod| lod.num_indices as usize / 3)
+            .collect(),
+    };
+
+    mesh.vertex_buffer = vertices;
+    mesh.num_indices = num_indices;
+    mesh.lods = lods;
+    mesh.index_buffer = if settings.u16_indices {
+        MeshIndices::smallest(indices)
+    } else {
+        MeshIndices::U32(indices)
+    };
+
+    Ok(stats)
+}
+
+/// Reorders the triangles to reduce overdraw, `indices` must already be optimized for the vertex cache.
+/// meshopt 0.1 only has an in place version, which writes through the slice it borrows, so it gets
+/// a copy nothing else reads.
+fn optimize_overdraw(
+    indices: &[u32],
+    adapter: &meshopt::VertexDataAdapter,
+    threshold: f32,
+) -> Vec<u32> {
+    let reordered = indices.to_vec();
+    meshopt::optimize_overdraw_in_place(&reordered, adapter, threshold);
+
+    reordered
+}
+
+/// Simplifies the base mesh to each level's ratio. Stops early once a level can't get any simpler
+/// within the error limit. Returns the indices and screen size of each level.
+fn generate_lods(
+    indices: &[u32],
+    adapter: &meshopt::VertexDataAdapter,
+    vertex_count: usize,
+    settings: &MeshLodSettings,
+) -> Vec<(Vec<u32>, f32)> {
+    let mut levels: Vec<(Vec<u32>, f32)> = Vec::new();
+
+    for (ratio, screen_size) in settings.ratios.iter().zip(settings.screen_sizes.iter()) {
+        let target_count = ((indices.len() / 3) as f32 * ratio) as usize * 3;
+
+        let level = meshopt::simplify(indices, adapter, target_count, settings.max_error);
+
+        let previous_count = levels
+            .last()
+            .map_or(indices.len(), |(previous, _)| previous.len());
+
+        if level.is_empty() || level.len() >= previous_count {
+            break;
+        }
+
+        levels.push((
+            meshopt::optimize_vertex_cache(&level, vertex_count),
+            *screen_size,
+        ));
+    }
+
+    levels
+}
+
+/// Merges vertices that are bit for bit identical, unused vertices are dropped.
+fn weld_vertices(
+    vertices: &[UvNormalTangentVertex],
+    indices: &[u32],
+) -> (Vec<UvNormalTangentVertex>, Vec<u32>) {
+    let mut welded = Vec::new();
+    let mut lookup = HashMap::<[u32; 12], u32>::new();
+
+    let indices = indices
+        .iter()
+        .map(|index| {
+            let vertex = vertices[*index as usize];
+
+            *lookup.entry(bytemuck::cast(vertex)).or_insert_with(|| {
+                welded.push(vertex);
+                welded.len() as u32 - 1
+            })
+        })
+        .collect();
+
+    (welded, indices)
+}
+
+/// Orders vertices by first use so the vertex fetch reads memory sequentially.
+/// Unused vertices are dropped.
+fn optimize_vertex_fetch(vertices: &mut Vec<UvNormalTangentVertex>, indices: &mut [u32]) {
+    let mut remap = vec![u32::MAX; vertices.len()];
+    let mut fetch_order = Vec::with_capacity(vertices.len());
+
+    for index in indices.iter_mut() {
+        let new_index = &mut remap[*index as usize];
+
+        if *new_index == u32::MAX {
+            *new_index = fetch_order.len() as u32;
+            fetch_order.push(vertices[*index as usize]);
+        }
+
+        *index = *new_index;
+    }
+
+    *vertices = fetch_order;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vertex(x: f32) -> UvNormalTangentVertex {
+        UvNormalTangentVertex {
+            position: [x, 0.0, 0.0],
+            uv: [0.0, 0.0],
+            normal: [0.0, 0.0, 1.0],
+            tangent: [1.0, 0.0, 0.0, 1.0],
+        }
+    }
+
+    #[test]
+    fn welding_merges_identical_vertices() {
+        let vertices = [
+            vertex(0.0),
+            vertex(1.0),
+            vertex(2.0),
+            vertex(0.0),
+            vertex(2.0),
+            vertex(3.0),
+        ];
+
+        let (welded, indices) = weld_vertices(&vertices, &[0, 1, 2, 3, 4, 5]);
+
+        assert_eq!(welded.len(), 4);
+        assert_eq!(indices, vec![0, 1, 2, 0, 2, 3]);
+    }
+
+    #[test]
+    fn vertex_fetch_follows_first_use() {
+        let mut vertices = vec![vertex(0.0), vertex(1.0), vertex(2.0), vertex(3.0)];
+        let mut indices = [2, 0, 3, 3, 0, 2];
+
+        optimize_vertex_fetch(&mut vertices, &mut indices);
+
+        // Vertex 1 is unused
+        assert_eq!(vertices.len(), 3);
+        assert_eq!(indices, [0, 1, 2, 2, 1, 0]);
+        assert_eq!(vertices[0].position[0], 2.0);
+        assert_eq!(vertices[2].position[0], 3.0);
+    }
+
+    #[test]
+    fn small_meshes_use_u16_indices() {
+        assert_eq!(
+            MeshIndices::smallest(vec![0, 1, 2]),
+            MeshIndices::U16(vec![0, 1, 2])
+        );
+        assert_eq!(
+            MeshIndices::smallest(vec![0, 1, 70_000]),
+            MeshIndices::U32(vec![0, 1, 70_000])
+        );
+    }
+}
@@ -1,9 +1,155 @@
 pub mod texture;
 pub mod gltf;
 pub mod obj;
+pub mod material;
+pub mod mesh_optimizer;
+pub mod ktx2;
+pub mod cubemap;
+mod block_compression;
+
+use std::path::{Path, PathBuf};
+
+use cobalt_assets::{
+    asset::{AssetID, AssetImportError, AssetTrait},
+    manifest::{previous_sub_assets, AssetInfo, ExtraAssetInfo, PackInfo},
+    packed::write_packed_asset,
+    server::AssetServer,
+};
+use cobalt_graphics::texture::TextureType;
+
+use crate::{
+    asset_types::{model::Model, texture::TextureAsset},
+    renderer::{deferred::exports::Material, mesh::Mesh},
+};
+
+use self::{
+    gltf::GltfImporter,
+    material::MaterialImporter,
+    obj::{ObjImporter, ObjModelImporter},
+    texture::{TextureImporter, TextureLayersImporter},
+};
 
 pub mod exports {
-    pub use super::texture::TextureImporter;
-    pub use super::gltf::GltfImporter;
-    pub use super::obj::ObjImporter;
+    pub use super::texture::{
+        MipFilter, TextureImportSettings, TextureImporter, TextureLayersImportSettings,
+        TextureLayersImporter,
+    };
+    pub use super::gltf::{GltfImportSettings, GltfImporter};
+    pub use super::obj::{ObjImportSettings, ObjImporter, ObjModelImporter, ObjNormals};
+    pub use super::material::MaterialImporter;
+    pub use super::mesh_optimizer::{MeshLodSettings, MeshOptimizeSettings, MeshOptimizeStats};
+    pub use super::ktx2::{Ktx2Error, Ktx2Texture};
+}
+
+macro_rules! register_texture_importers {
+    ($server:expr, $($variant:ident),*) => {
+        $(
+            $server.register_importer::<TextureAsset<{ TextureType::$variant }>, TextureImporter<{ TextureType::$variant }>>();
+            $server.register_importer::<TextureAsset<{ TextureType::$variant }>, TextureLayersImporter<{ TextureType::$variant }>>();
+        )*
+    };
+}
+
+/// Registers the importers defined by the engine, along with their asset types.
+pub fn register_importers(server: &mut AssetServer) {
+    register_texture_importers!(
+        server,
+        RGBA32Float,
+        RGBA16Float,
+        RGBA8Unorm,
+        RGBA8UnormSrgb,
+        R32Float,
+        R16Float,
+        R8Unorm,
+        R8Uint,
+        R8Snorm
+    );
+
+    server.register_importer::<Mesh, ObjImporter>();
+    server.register_importer::<Material, MaterialImporter>();
+    server.register_importer::<Model, GltfImporter>();
+    server.register_importer::<Model, ObjModelImporter>();
+}
+
+/// Writes a value as pretty RON in the packed asset container of an `A`.
+/// Counterpart of `asset_types::read_ron`.
+pub(crate) fn write_ron<A: AssetTrait, T: serde::Serialize>(
+    value: &T,
+    pack: &PackInfo,
+    abs_path: &Path,
+) -> Result<(), AssetImportError> {
+    let text = ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())
+        .map_err(|e| AssetImportError::ProcessError(Box::new(e)))?;
+
+    write_packed_asset::<A>(abs_path, pack, text.as_bytes()).map_err(AssetImportError::WriteError)
+}
+
+/// Collects the sub-assets written by an importer of a directory asset.
+pub(crate) struct SubAssets<'a> {
+    asset_info: &'a AssetInfo,
+    output_dir: PathBuf,
+    assets: Vec<AssetInfo>,
+    /// Sub-assets of the previous import when reimporting, by path.
+    previous: hashbrown::HashMap<PathBuf, (AssetID, String)>,
+}
+
+impl<'a> SubAssets<'a> {
+    /// Creates the asset's directory.
+    pub fn new(asset_info: &'a AssetInfo, assets_dir: &Path) -> Result<Self, AssetImportError> {
+        let output_dir = assets_dir.join(&asset_info.relative_path);
+
+        std::fs::create_dir_all(&output_dir).map_err(AssetImportError::WriteError)?;
+
+        Ok(Self {
+            asset_info,
+            output_dir,
+            assets: Vec::new(),
+            previous: previous_sub_assets(assets_dir, asset_info),
+        })
+    }
+
+    /// Sub-assets are packed the same way as their parent.
+    pub fn pack(&self) -> &PackInfo {
+        &self.asset_info.pack
+    }
+
+    /// Absolute path of a file in the asset's directory.
+    pub fn abs_path(&self, file_name: &str) -> PathBuf {
+        self.output_dir.join(file_name)
+    }
+
+    /// Adds a sub-asset that has been written to `file_name`. Its name is prefixed with the parent's.
+    /// When reimporting, a sub-asset of the same type previously written to `file_name` keeps its ID.
+    pub fn add(
+        &mut self,
+        file_name: String,
+        name: String,
+        type_name: String,
+        extra: ExtraAssetInfo,
+        dependencies: Vec<AssetID>,
+    ) -> AssetID {
+        let asset_id = match self.previous.get(Path::new(&file_name)) {
+            Some((asset_id, previous_type)) if *previous_type == type_name => *asset_id,
+            _ => AssetID::generate(),
+        };
+
+        self.assets.push(AssetInfo {
+            asset_id,
+            relative_path: PathBuf::from(file_name),
+            pack: self.asset_info.pack.clone(),
+            name: format!("{}/{}", self.asset_info.name, name),
+            timestamp: std::time::SystemTime::now(),
+            type_name,
+            extra,
+            dependencies,
+            source: None,
+            tags: Vec::new(),
+        });
+
+        asset_id
+    }
+
+    pub fn into_vec(self) -> Vec<AssetInfo> {
+        self.assets
+    }
 }
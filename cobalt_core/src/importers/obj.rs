@@ -1,273 +1,711 @@
-use std::io::Write;
-
-use cobalt_assets::asset::{AssetImportError, AssetImporter, AssetVerifyError};
-use cobalt_graphics::vertex::UvNormalVertex;
+use std::path::{Path, PathBuf};
+
+use cobalt_assets::{
+    asset::{
+        AssetFileSystemType, AssetID, AssetImportError, AssetImporter, AssetTrait,
+        AssetVerifyError,
+    },
+    manifest::{AssetInfo, ExtraAssetInfo, ImportedAsset},
+};
+use cobalt_graphics::{
+    texture::{SamplerSettings, TextureType},
+    vertex::UvNormalTangentVertex,
+};
+use hashbrown::HashMap;
+
+use crate::{
+    asset_types::{
+        mesh::{generate_tangents, MeshAssetBuffer, MeshIndices},
+        model::{Model, ModelNode, ModelPrimitive, ModelScene},
+        texture::TextureAsset,
+    },
+    renderer::{
+        deferred::exports::{Material, MaterialDescriptor},
+        mesh::Mesh,
+    },
+    types::{aabb::AABB, either::Either},
+};
+
+use super::{
+    mesh_optimizer::{optimize_mesh, MeshLodSettings, MeshOptimizeSettings},
+    texture::{material_block_compression, MipFilter, TextureAssetBuffer},
+    write_ron, SubAssets,
+};
+
+/// How vertex normals are produced.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ObjNormals {
+    /// Use the normals in the file, models without any are smoothed.
+    Keep,
+    /// Recompute smooth normals, vertices at the same position share a normal.
+    #[default]
+    Smooth,
+    /// One normal per face.
+    Flat,
+}
 
-use crate::{asset_types::mesh::MeshAssetBuffer, renderer::mesh::Mesh, types::aabb::AABB};
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct ObjImportSettings {
+    pub normals: ObjNormals,
+    pub optimize: MeshOptimizeSettings,
+    pub lod: MeshLodSettings,
+    /// Block compress `.mtl` textures: BC7 for albedo, BC5 for normal maps, BC4 for metallic and roughness.
+    pub compress_textures: bool,
+}
 
+/// Imports all models of an OBJ file as a single mesh. Materials are ignored, use `ObjModelImporter` for those.
 pub struct ObjImporter;
 
-impl AssetImporter<Mesh> for ObjImporter {
-    fn unimported_fs_type() -> cobalt_assets::asset::AssetFileSystemType {
-        cobalt_assets::asset::AssetFileSystemType::File
+/// Imports an OBJ file as a `Model`, with a mesh per model and the `.mtl` materials and their textures
+/// as sub-assets.
+pub struct ObjModelImporter;
+
+fn load_options() -> tobj::LoadOptions {
+    tobj::LoadOptions {
+        triangulate: true,
+        single_index: true,
+        ignore_points: true,
+        ignore_lines: true,
     }
+}
 
-    fn verify_source(abs_path: &std::path::Path) -> Result<(), AssetVerifyError> {
-        if abs_path.extension().unwrap() != "obj" {
-            return Err(AssetVerifyError::InvalidFileType);
-        }
+fn verify_obj(abs_path: &Path) -> Result<(), AssetVerifyError> {
+    if abs_path.extension().unwrap() != "obj" {
+        return Err(AssetVerifyError::InvalidFileType);
+    }
 
-        let _res = tobj::load_obj(
-            abs_path,
-            &tobj::LoadOptions {
-                triangulate: true,
-                single_index: true,
-                ignore_points: true,
-                ignore_lines: true,
-                ..Default::default()
-            },
-        )
+    let _res = tobj::load_obj(abs_path, &load_options())
         .map_err(|e| AssetVerifyError::InvalidFile(Box::new(e)))?;
 
-        Ok(())
+    Ok(())
+}
+
+/// Loads the models and materials of an OBJ file.
+/// If the materials fail to load, the models are still returned without them.
+fn load_obj(
+    abs_input_path: &Path,
+) -> Result<(Vec<tobj::Model>, Vec<tobj::Material>), AssetImportError> {
+    let obj_parent_dir = obj_parent_dir(abs_input_path)?;
+
+    let file = std::fs::File::open(abs_input_path)?;
+
+    let mut reader = std::io::BufReader::new(file);
+
+    let result = tobj::load_obj_buf(
+        &mut reader,
+        &load_options(),
+        // TODO: Fix bug that happens when loading materials
+        // with spaces in their names. This is an issue with
+        // the tobj crate.
+        |p| {
+            let p = obj_parent_dir.join(p);
+
+            let mat_string = std::fs::read_to_string(&p);
+
+            if let Err(e) = &mat_string {
+                log::error!("Could not read material file {:?}: {}", p, e);
+                return Err(tobj::LoadError::ReadError);
+            }
+
+            let mat_string = mat_string.unwrap();
+
+            let mat = tobj::load_mtl_buf(&mut std::io::BufReader::new(std::io::Cursor::new(
+                mat_string,
+            )));
+
+            if let Err(e) = &mat {
+                log::error!("Could not load material file {:?}: {}", p, e);
+                return Err(tobj::LoadError::ReadError);
+            }
+
+            let mat = mat.unwrap();
+
+            Ok(mat)
+        },
+    );
+
+    match result {
+        Ok((models, mats)) => {
+            let mats = match mats {
+                Ok(m) => m,
+                Err(e) => {
+                    log::warn!(
+                        "Failed to load the materials of {:?}, importing without them: {}",
+                        abs_input_path,
+                        e
+                    );
+                    Vec::new()
+                }
+            };
+
+            Ok((models, mats))
+        }
+        Err(e) => Err(AssetImportError::LoadError(Box::new(e))),
+    }
+}
+
+fn obj_parent_dir(abs_input_path: &Path) -> Result<&Path, AssetImportError> {
+    abs_input_path
+        .parent()
+        .ok_or(AssetImportError::LoadError(Box::new(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "Could not get the .obj file's parent directory.".to_string(),
+        ))))
+}
+
+impl AssetImporter<Mesh> for ObjImporter {
+    type Settings = ObjImportSettings;
+
+    fn name() -> String {
+        "ObjImporter".to_string()
+    }
+
+    fn unimported_fs_type() -> AssetFileSystemType {
+        AssetFileSystemType::File
+    }
+
+    fn extensions() -> Vec<String> {
+        vec!["obj".to_string()]
+    }
+
+    fn note() -> Option<String> {
+        Some("All models are merged into one mesh. Settings: normals = \"keep\" | \"smooth\" | \"flat\", mesh optimizations in [optimize], LOD generation in [lod].".to_string())
+    }
+
+    fn verify_source(abs_path: &Path) -> Result<(), AssetVerifyError> {
+        verify_obj(abs_path)
     }
 
     fn import(
-        abs_input_path: &std::path::Path,
-        asset_info: &cobalt_assets::manifest::AssetInfo,
-        assets_dir: &std::path::Path,
-    ) -> Result<cobalt_assets::manifest::ExtraAssetInfo, AssetImportError> {
-        let obj_parent_dir =
-            abs_input_path
-                .parent()
-                .ok_or(AssetImportError::LoadError(Box::new(std::io::Error::new(
-                    std::io::ErrorKind::InvalidInput,
-                    format!("Could not get the .obj file's parent directory."),
-                ))))?;
-
-        let file = std::fs::File::open(abs_input_path)?;
-
-        let mut reader = std::io::BufReader::new(file);
-
-        let result = tobj::load_obj_buf(
-            &mut reader,
-            &tobj::LoadOptions {
-                triangulate: true,
-                single_index: true,
-                ignore_points: true,
-                ignore_lines: true,
-                ..Default::default()
-            },
-            // TODO: Fix bug that happens when loading materials
-            // with spaces in their names. This is an issue with
-            // the tobj crate.
-            |p| {
-                let p = obj_parent_dir.join(p);
+        abs_input_path: &Path,
+        asset_info: &AssetInfo,
+        assets_dir: &Path,
+        settings: &Self::Settings,
+    ) -> Result<ImportedAsset, AssetImportError> {
+        let (models, _mats) = load_obj(abs_input_path)?;
+
+        let mesh_buffers = models
+            .iter()
+            .map(|model| model_mesh_buffer(model, settings.normals, &asset_info.name))
+            .collect::<Vec<_>>();
 
-                let mat_string = std::fs::read_to_string(&p);
+        let mut mesh_buffer = merge_mesh_buffers(mesh_buffers);
 
-                if let Err(e) = &mat_string {
-                    log::error!("Could not read material file {:?}: {}", p, e);
-                    return Err(tobj::LoadError::ReadError);
-                }
+        let stats = optimize_mesh(&mut mesh_buffer, &settings.optimize, &settings.lod)?;
 
-                let mat_string = mat_string.unwrap();
+        let mut extra = mesh_buffer.write_packed(
+            &asset_info.pack,
+            &assets_dir.join(&asset_info.relative_path),
+        )?;
+        stats.write_extra(&mut extra);
 
-                let mat = tobj::load_mtl_buf(&mut std::io::BufReader::new(std::io::Cursor::new(
-                    mat_string,
-                )));
+        extra.0.insert("models".to_string(), models.len().to_string());
 
-                if let Err(e) = &mat {
-                    log::error!("Could not load material file {:?}: {}", p, e);
-                    return Err(tobj::LoadError::ReadError);
-                }
+        Ok(extra.into())
+    }
+}
 
-                let mat = mat.unwrap();
+impl AssetImporter<Model> for ObjModelImporter {
+    type Settings = ObjImportSettings;
 
-                Ok(mat)
-            },
-        );
+    fn name() -> String {
+        "ObjModelImporter".to_string()
+    }
 
-        let (mut models, _mats) = match result {
-            Ok((models, mats)) => {
-                let mats = match mats {
-                    Ok(m) => m,
-                    Err(e) => return Err(AssetImportError::LoadError(Box::new(e))),
-                };
+    fn unimported_fs_type() -> AssetFileSystemType {
+        AssetFileSystemType::File
+    }
 
-                (models, mats)
-            }
-            Err(e) => return Err(AssetImportError::LoadError(Box::new(e))),
+    fn extensions() -> Vec<String> {
+        vec!["obj".to_string()]
+    }
+
+    fn note() -> Option<String> {
+        Some("Each model becomes a mesh, .mtl materials and their textures are imported too. Settings: normals = \"keep\" | \"smooth\" | \"flat\", mesh optimizations in [optimize], LOD generation in [lod], compress_textures = true block compresses textures.".to_string())
+    }
+
+    fn verify_source(abs_path: &Path) -> Result<(), AssetVerifyError> {
+        verify_obj(abs_path)
+    }
+
+    fn import(
+        abs_input_path: &Path,
+        asset_info: &AssetInfo,
+        assets_dir: &Path,
+        settings: &Self::Settings,
+    ) -> Result<ImportedAsset, AssetImportError> {
+        let (models, mats) = load_obj(abs_input_path)?;
+
+        let mut sub_assets = SubAssets::new(asset_info, assets_dir)?;
+
+        let mut textures = MtlTextures {
+            source_dir: obj_parent_dir(abs_input_path)?.to_path_buf(),
+            imported: HashMap::new(),
+            compress: settings.compress_textures,
         };
 
-        if models.len() > 1 {
-            log::warn!(
-                "Mesh \"{}\" contains more than one model. Only the first model will be loaded.",
-                asset_info.name
+        let mut materials = Vec::with_capacity(mats.len());
+
+        for (i, mat) in mats.iter().enumerate() {
+            let descriptor = mtl_material_descriptor(mat, &mut textures, &mut sub_assets)?;
+
+            let file_name = format!("material_{}.asset", i);
+
+            write_ron::<Material, _>(
+                &descriptor,
+                sub_assets.pack(),
+                &sub_assets.abs_path(&file_name),
+            )?;
+
+            materials.push(sub_assets.add(
+                file_name,
+                mat.name.clone(),
+                Material::type_name(),
+                ExtraAssetInfo::new(),
+                descriptor.dependencies(),
+            ));
+        }
+
+        let mut nodes = Vec::with_capacity(models.len());
+
+        for (i, model) in models.iter().enumerate() {
+            let mut mesh_buffer = model_mesh_buffer(model, settings.normals, &asset_info.name);
+
+            let stats = optimize_mesh(&mut mesh_buffer, &settings.optimize, &settings.lod)?;
+
+            let file_name = format!("mesh_{}.asset", i);
+
+            let mut mesh_extra =
+                mesh_buffer.write_packed(sub_assets.pack(), &sub_assets.abs_path(&file_name))?;
+            stats.write_extra(&mut mesh_extra);
+
+            let mesh = sub_assets.add(
+                file_name,
+                model.name.clone(),
+                Mesh::type_name(),
+                mesh_extra,
+                Vec::new(),
             );
+
+            nodes.push(ModelNode {
+                name: Some(model.name.clone()),
+                translation: [0.0, 0.0, 0.0],
+                rotation: [0.0, 0.0, 0.0, 1.0],
+                scale: [1.0, 1.0, 1.0],
+                children: Vec::new(),
+                primitives: vec![ModelPrimitive {
+                    mesh,
+                    material: model
+                        .mesh
+                        .material_id
+                        .and_then(|material_id| materials.get(material_id).copied()),
+                }],
+                light: None,
+                camera: None,
+            });
         }
 
-        let model = &mut models[0];
+        let scene = ModelScene {
+            roots: (0..nodes.len()).collect(),
+            nodes,
+        };
 
-        // Always recompute normals, as the normals in the .obj file
-        // may not be correct.
-        let normals = generate_normals(&model.mesh.positions, &model.mesh.indices);
+        write_ron::<Model, _>(
+            &scene,
+            &asset_info.pack,
+            &sub_assets.abs_path(Model::SCENE_FILE),
+        )?;
+
+        let mut extra = ExtraAssetInfo::new();
+        extra.0.insert("nodes".to_string(), scene.nodes.len().to_string());
+        extra.0.insert("materials".to_string(), materials.len().to_string());
+        extra.0.insert("textures".to_string(), textures.imported.len().to_string());
+
+        Ok(ImportedAsset {
+            extra,
+            dependencies: scene.dependencies(),
+            sub_assets: sub_assets.into_vec(),
+        })
+    }
+}
 
-        model.mesh.normals = normals;
+/// Textures referenced by `.mtl` files, each path and type is only imported once.
+struct MtlTextures {
+    source_dir: PathBuf,
+    imported: HashMap<(PathBuf, TextureType), AssetID>,
+    compress: bool,
+}
 
-        // Check if texcoords are present
-        if model.mesh.texcoords.len() == 0 {
-            log::warn!("Mesh \"{}\" does not contain texture coordinates. There will be issues if rendering with textures.", asset_info.name);
+impl MtlTextures {
+    fn import<const T: TextureType>(
+        &mut self,
+        map: &str,
+        sub_assets: &mut SubAssets,
+    ) -> Result<AssetID, AssetImportError> {
+        // Texture options like `-bm 0.5` come before the file name
+        let file = map.split_whitespace().last().unwrap_or(map);
+        let path = self.source_dir.join(file);
+
+        if let Some(asset_id) = self.imported.get(&(path.clone(), T)) {
+            return Ok(*asset_id);
         }
 
-        let includes_texcoords = model.mesh.texcoords.len() > 0;
+        let file_name = format!("texture_{}.asset", self.imported.len());
+
+        let mut texture_buffer = TextureAssetBuffer::read_from_source(&path, T)?;
+        texture_buffer.generate_mips(MipFilter::Box);
+
+        let extra = texture_buffer.write_packed(
+            sub_assets.pack(),
+            &sub_assets.abs_path(&file_name),
+            &SamplerSettings::default(),
+            self.compress
+                .then(|| material_block_compression(T))
+                .flatten(),
+        )?;
+
+        let asset_id = sub_assets.add(
+            file_name,
+            file.to_string(),
+            TextureAsset::<T>::type_name(),
+            extra,
+            Vec::new(),
+        );
 
-        let mut min = (0.0, 0.0, 0.0);
-        let mut max = (0.0, 0.0, 0.0);
+        self.imported.insert((path, T), asset_id);
 
-        let vertex_buffer = (0..model.mesh.positions.len() / 3)
-            .map(|i| {
-                // Min and max checks
-                let mut is_min = false;
-                if model.mesh.positions[i * 3] < min.0 {
-                    min.0 = model.mesh.positions[i * 3];
-                    is_min = true;
-                }
+        Ok(asset_id)
+    }
+}
 
-                if model.mesh.positions[i * 3 + 1] < min.1 {
-                    min.1 = model.mesh.positions[i * 3 + 1];
-                    is_min = true;
-                }
+/// Maps an `.mtl` material onto the deferred material.
+/// Roughness and metallic come from the PBR extension (`Pr`, `Pm`, `map_Pr`, `map_Pm`) if present,
+/// otherwise roughness is derived from the specular exponent.
+fn mtl_material_descriptor(
+    mat: &tobj::Material,
+    textures: &mut MtlTextures,
+    sub_assets: &mut SubAssets,
+) -> Result<MaterialDescriptor, AssetImportError> {
+    let albedo_texture = mat
+        .diffuse_texture
+        .as_ref()
+        .map(|map| textures.import::<{ Material::ALBEDO_TEXTURE_TYPE }>(map, sub_assets))
+        .transpose()?;
+
+    let diffuse = mat.diffuse.unwrap_or([1.0, 1.0, 1.0]);
+    let color = [diffuse[0], diffuse[1], diffuse[2], mat.dissolve.unwrap_or(1.0)];
+
+    // The texture is multiplied by the color, which is redundant if it's white
+    let albedo_color = if albedo_texture.is_some() && color == [1.0; 4] {
+        None
+    } else {
+        Some(color)
+    };
+
+    let normal = mat
+        .normal_texture
+        .as_ref()
+        .map(|map| textures.import::<{ Material::NORMAL_TEXTURE_TYPE }>(map, sub_assets))
+        .transpose()?;
+
+    let param = |key: &str| mat.unknown_param.get(key);
+
+    let metallic = match param("map_Pm") {
+        Some(map) => Either::Right(
+            textures.import::<{ Material::METALLIC_TEXTURE_TYPE }>(map, sub_assets)?,
+        ),
+        None => Either::Left(
+            param("Pm")
+                .and_then(|value| value.trim().parse().ok())
+                .unwrap_or(0.0),
+        ),
+    };
+
+    let roughness = match param("map_Pr") {
+        Some(map) => Either::Right(
+            textures.import::<{ Material::ROUGHNESS_TEXTURE_TYPE }>(map, sub_assets)?,
+        ),
+        None => Either::Left(
+            param("Pr")
+                .and_then(|value| value.trim().parse().ok())
+                .or(mat.shininess.map(shininess_to_roughness))
+                .unwrap_or(0.5),
+        ),
+    };
+
+    Ok(MaterialDescriptor {
+        // Illumination model 0 is a constant color
+        unlit: mat.illumination_model == Some(0),
+        wireframe: None,
+        albedo: (albedo_color, albedo_texture),
+        normal,
+        metallic,
+        roughness,
+    })
+}
 
-                if model.mesh.positions[i * 3 + 2] < min.2 {
-                    min.2 = model.mesh.positions[i * 3 + 2];
-                    is_min = true;
-                }
+/// Blinn-Phong exponent to roughness, `sqrt(2 / (Ns + 2))`.
+fn shininess_to_roughness(shininess: f32) -> f32 {
+    (2.0 / (shininess.max(0.0) + 2.0)).sqrt()
+}
 
-                if !is_min {
-                    if model.mesh.positions[i * 3] > max.0 {
-                        max.0 = model.mesh.positions[i * 3];
-                    }
+fn model_mesh_buffer(model: &tobj::Model, normals: ObjNormals, asset_name: &str) -> MeshAssetBuffer {
+    let mesh = &model.mesh;
 
-                    if model.mesh.positions[i * 3 + 1] > max.1 {
-                        max.1 = model.mesh.positions[i * 3 + 1];
-                    }
+    // Check if texcoords are present
+    if mesh.texcoords.is_empty() {
+        log::warn!("Model \"{}\" of \"{}\" does not contain texture coordinates. There will be issues if rendering with textures.", model.name, asset_name);
+    }
 
-                    if model.mesh.positions[i * 3 + 2] > max.2 {
-                        max.2 = model.mesh.positions[i * 3 + 2];
-                    }
-                }
+    let includes_texcoords = !mesh.texcoords.is_empty();
 
-                UvNormalVertex {
-                    position: [
-                        model.mesh.positions[i * 3],
-                        model.mesh.positions[i * 3 + 1],
-                        model.mesh.positions[i * 3 + 2],
-                    ],
-                    uv: [
-                        if includes_texcoords {
-                            model.mesh.texcoords[i * 2]
-                        } else {
-                            0.0
-                        },
-                        if includes_texcoords {
-                            1.0 - model.mesh.texcoords[i * 2 + 1]
-                        } else {
-                            0.0
-                        },
-                    ],
-                    normal: [
-                        model.mesh.normals[i * 3],
-                        model.mesh.normals[i * 3 + 1],
-                        model.mesh.normals[i * 3 + 2],
-                    ],
-                }
-            })
-            .collect::<Vec<_>>();
+    let normals = match normals {
+        ObjNormals::Keep if mesh.normals.len() == mesh.positions.len() => normals,
+        ObjNormals::Keep => {
+            log::info!(
+                "Model \"{}\" of \"{}\" has no normals, generating smooth normals.",
+                model.name,
+                asset_name
+            );
+            ObjNormals::Smooth
+        }
+        normals => normals,
+    };
+
+    let vertex_normals = match normals {
+        ObjNormals::Keep => mesh.normals.clone(),
+        ObjNormals::Smooth => generate_normals(&mesh.positions, &mesh.indices),
+        // Filled in after the vertices are split
+        ObjNormals::Flat => vec![0.0; mesh.positions.len()],
+    };
+
+    let vertex_buffer = (0..mesh.positions.len() / 3)
+        .map(|i| UvNormalTangentVertex {
+            position: [
+                mesh.positions[i * 3],
+                mesh.positions[i * 3 + 1],
+                mesh.positions[i * 3 + 2],
+            ],
+            uv: if includes_texcoords {
+                [mesh.texcoords[i * 2], 1.0 - mesh.texcoords[i * 2 + 1]]
+            } else {
+                [0.0, 0.0]
+            },
+            normal: [
+                vertex_normals[i * 3],
+                vertex_normals[i * 3 + 1],
+                vertex_normals[i * 3 + 2],
+            ],
+            // Generated once the final normals are known
+            tangent: [0.0; 4],
+        })
+        .collect::<Vec<_>>();
+
+    let (mut vertex_buffer, index_buffer) = if normals == ObjNormals::Flat {
+        flat_shade(&vertex_buffer, &mesh.indices)
+    } else {
+        (vertex_buffer, mesh.indices.clone())
+    };
+
+    generate_tangents(&mut vertex_buffer, &index_buffer);
+
+    MeshAssetBuffer {
+        local_aabb: vertices_aabb(&vertex_buffer),
+        num_indices: index_buffer.len() as u32,
+        vertex_buffer,
+        index_buffer: MeshIndices::U32(index_buffer),
+        has_uv: includes_texcoords,
+        lods: Vec::new(),
+    }
+}
 
-        let index_buffer = &model.mesh.indices;
+/// Concatenates meshes into one.
+fn merge_mesh_buffers(mesh_buffers: Vec<MeshAssetBuffer>) -> MeshAssetBuffer {
+    let mut index_buffer = Vec::new();
+    let mut vertex_buffer = Vec::new();
+    let mut has_uv = !mesh_buffers.is_empty();
+
+    for mesh_buffer in mesh_buffers {
+        let offset = vertex_buffer.len() as u32;
+
+        index_buffer.extend(
+            mesh_buffer
+                .index_buffer
+                .to_u32()
+                .into_iter()
+                .map(|index| index + offset),
+        );
+        vertex_buffer.extend(mesh_buffer.vertex_buffer);
+        has_uv &= mesh_buffer.has_uv;
+    }
 
-        let mesh_buffer = MeshAssetBuffer {
-            vertex_buffer,
-            index_buffer: index_buffer.clone(),
-            num_indices: model.mesh.indices.len() as u32,
-            local_aabb: AABB::from_min_max(min.into(), max.into()),
-            has_uv: model.mesh.texcoords.len() > 0,
-        };
+    MeshAssetBuffer {
+        num_indices: index_buffer.len() as u32,
+        local_aabb: vertices_aabb(&vertex_buffer),
+        index_buffer: MeshIndices::U32(index_buffer),
+        vertex_buffer,
+        has_uv,
+        lods: Vec::new(),
+    }
+}
 
-        let ser_bytes = if let Some(compression) = asset_info.pack.compression {
-            let mut encoder = zstd::Encoder::new(Vec::new(), compression as i32)?;
+fn vertices_aabb(vertices: &[UvNormalTangentVertex]) -> AABB {
+    if vertices.is_empty() {
+        return AABB::zero();
+    }
 
-            encoder.write_all(&bincode::serialize(&mesh_buffer)?)?;
+    let mut min = [f32::MAX; 3];
+    let mut max = [f32::MIN; 3];
 
-            encoder.finish()?
-        } else {
-            bincode::serialize(&mesh_buffer)?
-        };
+    for vertex in vertices {
+        for axis in 0..3 {
+            min[axis] = min[axis].min(vertex.position[axis]);
+            max[axis] = max[axis].max(vertex.position[axis]);
+        }
+    }
 
-        // Write the mesh buffer to the target file
-        let target_path = assets_dir.join(&asset_info.relative_path);
+    AABB::from_min_max(min.into(), max.into())
+}
 
-        let mut file =
-            std::fs::File::create(&target_path).map_err(|e| AssetImportError::WriteError(e))?;
+/// Gives every triangle its own vertices, with the face normal.
+fn flat_shade(vertices: &[UvNormalTangentVertex], indices: &[u32]) -> (Vec<UvNormalTangentVertex>, Vec<u32>) {
+    let mut flat_vertices = Vec::with_capacity(indices.len());
 
-        file.write_all(&ser_bytes)
-            .map_err(|e| AssetImportError::WriteError(e))?;
+    for triangle in indices.chunks_exact(3) {
+        let [v0, v1, v2] = [
+            vertices[triangle[0] as usize],
+            vertices[triangle[1] as usize],
+            vertices[triangle[2] as usize],
+        ];
+
+        let normal = normalize(face_normal(v0.position, v1.position, v2.position));
 
-        Ok(cobalt_assets::manifest::ExtraAssetInfo::new())
+        for mut vertex in [v0, v1, v2] {
+            vertex.normal = normal;
+            flat_vertices.push(vertex);
+        }
     }
+
+    let flat_indices = (0..flat_vertices.len() as u32).collect();
+
+    (flat_vertices, flat_indices)
 }
 
-fn generate_normals(positions: &[f32], indices: &[u32]) -> Vec<f32> {
-    let mut normals = vec![0.0; positions.len()];
+/// Not normalized, the length is twice the triangle's area.
+fn face_normal(v0: [f32; 3], v1: [f32; 3], v2: [f32; 3]) -> [f32; 3] {
+    let e1 = [v1[0] - v0[0], v1[1] - v0[1], v1[2] - v0[2]];
+    let e2 = [v2[0] - v0[0], v2[1] - v0[1], v2[2] - v0[2]];
 
-    for i in (0..indices.len()).step_by(3) {
-        let i0 = indices[i] as usize;
-        let i1 = indices[i + 1] as usize;
-        let i2 = indices[i + 2] as usize;
+    [
+        e1[1] * e2[2] - e1[2] * e2[1],
+        e1[2] * e2[0] - e1[0] * e2[2],
+        e1[0] * e2[1] - e1[1] * e2[0],
+    ]
+}
 
-        let v0 = [
-            positions[i0 * 3],
-            positions[i0 * 3 + 1],
-            positions[i0 * 3 + 2],
-        ];
-        let v1 = [
-            positions[i1 * 3],
-            positions[i1 * 3 + 1],
-            positions[i1 * 3 + 2],
-        ];
-        let v2 = [
-            positions[i2 * 3],
-            positions[i2 * 3 + 1],
-            positions[i2 * 3 + 2],
-        ];
+/// Degenerate normals point up.
+fn normalize(v: [f32; 3]) -> [f32; 3] {
+    let len = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
 
-        let e1 = [v1[0] - v0[0], v1[1] - v0[1], v1[2] - v0[2]];
-        let e2 = [v2[0] - v0[0], v2[1] - v0[1], v2[2] - v0[2]];
+    if len > f32::EPSILON {
+        [v[0] / len, v[1] / len, v[2] / len]
+    } else {
+        [0.0, 1.0, 0.0]
+    }
+}
+
+/// Smooth normals weighted by face area. Vertices at the same position share a normal,
+/// so vertices split for UV seams don't show up as seams in the shading.
+pub(crate) fn generate_normals(positions: &[f32], indices: &[u32]) -> Vec<f32> {
+    let mut welded = HashMap::new();
+
+    let groups = positions
+        .chunks_exact(3)
+        .map(|position| {
+            let key = [position[0].to_bits(), position[1].to_bits(), position[2].to_bits()];
+            let next = welded.len();
+
+            *welded.entry(key).or_insert(next)
+        })
+        .collect::<Vec<usize>>();
+
+    let mut group_normals = vec![[0.0f32; 3]; welded.len()];
+
+    for triangle in indices.chunks_exact(3) {
+        let vertex = |i: u32| {
+            let i = i as usize * 3;
+            [positions[i], positions[i + 1], positions[i + 2]]
+        };
 
-        let normal = [
-            e1[1] * e2[2] - e1[2] * e2[1],
-            e1[2] * e2[0] - e1[0] * e2[2],
-            e1[0] * e2[1] - e1[1] * e2[0],
+        let normal = face_normal(vertex(triangle[0]), vertex(triangle[1]), vertex(triangle[2]));
+
+        for index in triangle {
+            let group_normal = &mut group_normals[groups[*index as usize]];
+
+            for axis in 0..3 {
+                group_normal[axis] += normal[axis];
+            }
+        }
+    }
+
+    groups
+        .iter()
+        .flat_map(|group| normalize(group_normals[*group]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smooth_normals_are_shared_across_seams() {
+        // Two triangles in the XY plane, the shared edge's vertices are duplicated
+        let positions = [
+            0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, //
+            1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0,
         ];
+        let indices = [0, 1, 2, 3, 4, 5];
+
+        let normals = generate_normals(&positions, &indices);
 
-        for j in 0..3 {
-            normals[i0 * 3 + j] += normal[j];
-            normals[i1 * 3 + j] += normal[j];
-            normals[i2 * 3 + j] += normal[j];
+        for normal in normals.chunks_exact(3) {
+            assert_eq!(normal, [0.0, 0.0, 1.0]);
         }
     }
 
-    for i in (0..normals.len()).step_by(3) {
-        let len = (normals[i] * normals[i]
-            + normals[i + 1] * normals[i + 1]
-            + normals[i + 2] * normals[i + 2])
-            .sqrt();
+    #[test]
+    fn flat_shading_splits_vertices() {
+        let vertex = |position| UvNormalTangentVertex {
+            position,
+            uv: [0.0, 0.0],
+            normal: [0.0, 0.0, 0.0],
+            tangent: [0.0; 4],
+        };
+
+        let vertices = [
+            vertex([0.0, 0.0, 0.0]),
+            vertex([1.0, 0.0, 0.0]),
+            vertex([0.0, 1.0, 0.0]),
+            vertex([0.0, 0.0, 1.0]),
+        ];
+
+        let (flat_vertices, flat_indices) = flat_shade(&vertices, &[0, 1, 2, 0, 3, 1]);
 
-        normals[i] /= len;
-        normals[i + 1] /= len;
-        normals[i + 2] /= len;
+        assert_eq!(flat_vertices.len(), 6);
+        assert_eq!(flat_indices, vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(flat_vertices[0].normal, [0.0, 0.0, 1.0]);
+        assert_eq!(flat_vertices[3].normal, [0.0, 1.0, 0.0]);
     }
 
-    normals
+    #[test]
+    fn normals_setting_from_toml() {
+        let settings: ObjImportSettings = toml::from_str("normals = \"flat\"").unwrap();
+        assert_eq!(settings.normals, ObjNormals::Flat);
+
+        let settings: ObjImportSettings = toml::from_str("").unwrap();
+        assert_eq!(settings.normals, ObjNormals::Smooth);
+    }
 }
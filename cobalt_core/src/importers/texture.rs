@@ -1,48 +1,501 @@
-use std::io::Cursor;
-
 use cobalt_assets::{
-    asset::{AssetFileSystemType, AssetImportError, AssetImporter, AssetReadError, AssetVerifyError},
-    manifest::ExtraAssetInfo,
+    asset::{
+        AssetFileSystemType, AssetImportError, AssetImporter, AssetReadError, AssetVerifyError,
+    },
+    manifest::{AssetInfo, ExtraAssetInfo, ImportedAsset, PackInfo},
+    packed::write_packed,
 };
 use cobalt_graphics::{
     context::Graphics,
-    texture::{Texture, TextureType},
-    HasBindGroupLayout,
+    texture::{BlockCompression, SamplerSettings, Texture, TextureKind, TextureType},
 };
-use image::{GenericImageView, ImageFormat};
-use serde::ser::SerializeSeq;
+use image::{DynamicImage, GenericImageView};
+
+use crate::asset_types::texture::{texture_type_name, TextureAsset};
 
-use crate::asset_types::texture::TextureAsset;
+use super::{
+    block_compression,
+    cubemap::{equirect_to_cube, is_equirect, sort_cube_faces, split_strip},
+    ktx2::{level_size, Ktx2Texture},
+};
 
 /// Texture asset buffer, used when serialising into a packed asset.
+/// Textures packed before mip levels were added were a bincode sequence of
+/// the size, type and texel data, which is what `Deserialize` reads.
 pub(crate) struct TextureAssetBuffer {
     pub ty: TextureType,
-    pub image: image::DynamicImage,
+    pub kind: TextureKind,
+    /// Array layers, cube faces or depth slices, all the same size. 2D textures have one.
+    pub layers: Vec<image::DynamicImage>,
+    /// `depth_or_array_layers` is the number of layers.
     pub size: wgpu::Extent3d,
+    /// Mip levels of each layer after the full size image, each half the size of the previous one.
+    /// Empty when there are no mip levels.
+    pub mips: Vec<Vec<image::DynamicImage>>,
+    /// Filter the mip levels were generated with.
+    pub mip_filter: MipFilter,
 }
 
-impl serde::Serialize for TextureAssetBuffer {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
+/// Filter used to downsample mip levels at import time.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MipFilter {
+    /// Only the full size image.
+    None,
+    /// Average of 2x2 texels.
+    #[default]
+    Box,
+    /// Kaiser windowed sinc, sharper than box.
+    Kaiser,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct TextureImportSettings {
+    /// Cube maps are read from a 2:1 equirectangular image or a 6:1 strip of faces,
+    /// arrays and 3D textures from a horizontal strip of square layers.
+    pub kind: TextureKind,
+    /// Face size of cube maps made from an equirectangular image, 0 for a quarter of its width.
+    pub cube_face_size: u32,
+    pub mipmaps: MipFilter,
+    pub sampler: SamplerSettings,
+    /// GPU block compression, stored as KTX2. The image is resized to a multiple of 4 texels.
+    pub compression: Option<BlockCompression>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct TextureLayersImportSettings {
+    /// Cube faces are found by file name, array layers and depth slices are in file name order.
+    pub kind: TextureKind,
+    pub mipmaps: MipFilter,
+    pub sampler: SamplerSettings,
+    pub compression: Option<BlockCompression>,
+}
+
+impl Default for TextureLayersImportSettings {
+    fn default() -> Self {
+        Self {
+            kind: TextureKind::Cube,
+            mipmaps: MipFilter::default(),
+            sampler: SamplerSettings::default(),
+            compression: None,
+        }
+    }
+}
+
+/// Encoded data of one mip level.
+#[derive(serde::Serialize, serde::Deserialize)]
+enum PackedLevel {
+    /// Texel data in the format of the texture type.
+    /// `Bytes` is read in one go where `Vec<u8>` is read byte by byte, both encode the same.
+    Raw(bytes::Bytes),
+    /// Only written by textures packed before the packed asset container compressed every asset.
+    Png(Vec<u8>),
+}
+
+/// Packed texture with its mip chain, the payload of the packed asset container.
+#[derive(serde::Serialize, serde::Deserialize)]
+pub(crate) struct PackedTexture {
+    ty: TextureType,
+    size: wgpu::Extent3d,
+    /// Each layer of each mip level, level by level.
+    levels: Vec<PackedLevel>,
+}
+
+impl PackedTexture {
+    /// Format version written in the header of packed textures, see `AssetTrait::format_version`.
+    /// Both `PackedTexture` and KTX2 payloads are of this version.
+    pub const VERSION: u32 = 1;
+    /// Extra asset info key holding the format of the packed texture.
+    /// Only files packed before the header held a format version need it,
+    /// textures without either are read as the legacy single level format.
+    pub const FORMAT_KEY: &'static str = "texture_format";
+    const FORMAT: u32 = 2;
+    /// Value of `FORMAT_KEY` for textures packed as KTX2.
+    pub const KTX2_FORMAT: &'static str = "ktx2";
+    /// Extra asset info key holding the `SamplerSettings` of the texture, in RON.
+    pub const SAMPLER_KEY: &'static str = "sampler";
+    /// Extra asset info key holding the `TextureKind` of the texture, in RON.
+    /// Textures without it are 2D.
+    pub const KIND_KEY: &'static str = "texture_kind";
+
+    /// Converts the payload of a texture packed before the header held a format version.
+    /// `FORMAT_KEY` says what it holds, textures without it are the legacy single level format:
+    /// an image file when the pack is compressed, a bincode `TextureAssetBuffer` otherwise.
+    pub fn migrate_unversioned(
+        ty: TextureType,
+        payload: Vec<u8>,
+        asset_info: &AssetInfo,
+    ) -> Result<Vec<u8>, AssetReadError> {
+        match asset_info.extra.0.get(Self::FORMAT_KEY).map(String::as_str) {
+            Some(Self::KTX2_FORMAT) => return Ok(payload),
+            Some(format) if format.parse::<u32>() == Ok(Self::FORMAT) => return Ok(payload),
+            Some(format) => {
+                return Err(AssetReadError::ParseError(
+                    format!(
+                        "Unsupported texture format \"{}\", reimport the texture",
+                        format
+                    )
+                    .into(),
+                ))
+            }
+            None => {}
+        }
+
+        let tab = if asset_info.pack.compression.is_some() {
+            let mime_type = asset_info
+                .extra
+                .0
+                .get("mime")
+                .ok_or(AssetReadError::MissingExtraAssetInfo("mime".to_string()))?;
+
+            let image = image::load_from_memory_with_format(
+                &payload,
+                image::ImageFormat::from_mime_type(mime_type).ok_or(AssetReadError::ParseError(
+                    "Unsupported image format".to_string().into(),
+                ))?,
+            )
+            .map_err(|e| AssetReadError::ParseError(Box::new(e)))?;
+
+            TextureAssetBuffer::from_image(image, ty)
+        } else {
+            bincode::deserialize::<TextureAssetBuffer>(&payload)?
+        };
+
+        let packed = tab.to_packed().map_err(AssetReadError::CreateError)?;
+
+        Ok(bincode::serialize(&packed)?)
+    }
+
+    /// The sampler settings stored with the texture.
+    /// Textures imported before they existed keep the old nearest, repeating sampler.
+    pub fn sampler_settings(asset_info: &AssetInfo) -> Result<SamplerSettings, AssetReadError> {
+        match asset_info.extra.0.get(Self::SAMPLER_KEY) {
+            Some(sampler) => {
+                ron::from_str(sampler).map_err(|e| AssetReadError::ParseError(Box::new(e)))
+            }
+            None => Ok(SamplerSettings {
+                mag_filter: cobalt_graphics::texture::TextureFilter::Nearest,
+                min_filter: cobalt_graphics::texture::TextureFilter::Nearest,
+                mipmap_filter: cobalt_graphics::texture::TextureFilter::Nearest,
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// The kind of texture stored, 2D for textures imported before other kinds existed.
+    pub fn kind(asset_info: &AssetInfo) -> Result<TextureKind, AssetReadError> {
+        match asset_info.extra.0.get(Self::KIND_KEY) {
+            Some(kind) => ron::from_str(kind).map_err(|e| AssetReadError::ParseError(Box::new(e))),
+            None => Ok(TextureKind::D2),
+        }
+    }
+
+    pub fn create_texture<const T: TextureType>(
+        &self,
+        graphics: &Graphics,
+        kind: TextureKind,
+        sampler_settings: &SamplerSettings,
+    ) -> Result<Texture<T>, AssetReadError> {
+        if self.ty != T {
+            return Err(AssetReadError::ParseError(
+                format!("Texture is a {}, expected a {}", self.ty, T).into(),
+            ));
+        }
+
+        let layers = self.size.depth_or_array_layers.max(1) as usize;
+
+        let levels = self
+            .levels
+            .chunks(layers)
+            .map(|packed_layers| {
+                let mut level = Vec::new();
+
+                for layer in packed_layers {
+                    match layer {
+                        PackedLevel::Raw(data) => level.extend_from_slice(data),
+                        PackedLevel::Png(data) => {
+                            let image =
+                                image::load_from_memory_with_format(data, image::ImageFormat::Png)
+                                    .map_err(|e| AssetReadError::ParseError(Box::new(e)))?;
+
+                            level.extend_from_slice(
+                                &T.get_image_data(image)
+                                    .map_err(AssetReadError::CreateError)?,
+                            );
+                        }
+                    }
+                }
+
+                Ok(level)
+            })
+            .collect::<Result<Vec<_>, AssetReadError>>()?;
+
+        check_layers(kind, self.size)?;
+
+        Ok(create_texture_from_levels(
+            graphics,
+            T.into(),
+            kind,
+            self.size,
+            &levels,
+            sampler_settings,
+        ))
+    }
+}
+
+/// Checks the layer count and size suit `kind`, wgpu would panic creating the texture otherwise.
+fn check_layers(kind: TextureKind, size: wgpu::Extent3d) -> Result<(), AssetReadError> {
+    let valid = match kind {
+        TextureKind::D2 => size.depth_or_array_layers == 1,
+        TextureKind::Cube => size.depth_or_array_layers == 6 && size.width == size.height,
+        TextureKind::D2Array | TextureKind::D3 => size.depth_or_array_layers >= 1,
+    };
+
+    if valid {
+        Ok(())
+    } else {
+        Err(AssetReadError::ParseError(
+            format!(
+                "A {}x{}x{} texture can't be a {:?} texture",
+                size.width, size.height, size.depth_or_array_layers, kind
+            )
+            .into(),
+        ))
+    }
+}
+
+/// Creates a texture with a mip level for each entry of `levels`, texel data in `format`.
+/// Each level holds all of its layers one after the other.
+/// `format` is either the format of `T` or a block compressed format suiting it.
+fn create_texture_from_levels<const T: TextureType>(
+    graphics: &Graphics,
+    format: wgpu::TextureFormat,
+    kind: TextureKind,
+    size: wgpu::Extent3d,
+    levels: &[Vec<u8>],
+    sampler_settings: &SamplerSettings,
+) -> Texture<T> {
+    let texture = graphics.device.create_texture(&wgpu::TextureDescriptor {
+        label: None,
+        size,
+        mip_level_count: levels.len().max(1) as u32,
+        sample_count: 1,
+        dimension: kind.dimension(),
+        format,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[format],
+    });
+
+    let (block_width, block_height) = format.block_dimensions();
+    let bytes_per_block = format.block_copy_size(None).unwrap_or(0);
+
+    for (mip_level, data) in levels.iter().enumerate() {
+        // Block compressed levels smaller than a block still cover the whole block
+        let level_size = size
+            .mip_level_size(mip_level as u32, kind.dimension())
+            .physical_size(format);
+
+        graphics.queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: mip_level as u32,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            data,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(level_size.width / block_width * bytes_per_block),
+                rows_per_image: Some(level_size.height / block_height),
+            },
+            level_size,
+        );
+    }
+
+    Texture::new(graphics, texture, kind, size, sampler_settings)
+}
+
+/// Creates a texture from a KTX2 container, in the format of `T` or a block compressed format suiting it.
+/// Block compressed textures are decompressed when the device doesn't support them,
+/// or when they aren't made of whole blocks, which wgpu can't create.
+pub(crate) fn create_texture_from_ktx2<const T: TextureType>(
+    graphics: &Graphics,
+    ktx2: &Ktx2Texture,
+    sampler_settings: &SamplerSettings,
+) -> Result<Texture<T>, AssetReadError> {
+    let size = wgpu::Extent3d {
+        width: ktx2.width,
+        height: ktx2.height,
+        depth_or_array_layers: ktx2.layers,
+    };
+
+    check_layers(ktx2.kind, size)?;
+
+    if ktx2.format == T.into() {
+        return Ok(create_texture_from_levels(
+            graphics,
+            ktx2.format,
+            ktx2.kind,
+            size,
+            &ktx2.levels,
+            sampler_settings,
+        ));
+    }
+
+    let compression = BlockCompression::from_format(ktx2.format)
+        .filter(|compression| T.block_compressed_format(*compression) == Some(ktx2.format))
+        .ok_or_else(|| {
+            AssetReadError::ParseError(
+                format!("A {:?} texture can't be used as a {}", ktx2.format, T).into(),
+            )
+        })?;
+
+    let bc_supported = graphics
+        .device
+        .features()
+        .contains(wgpu::Features::TEXTURE_COMPRESSION_BC);
+
+    // wgpu only allows block compressed 2D textures made of whole blocks, others are decompressed
+    if ktx2.kind != TextureKind::D3
+        && bc_supported
+        && size.width.is_multiple_of(BlockCompression::BLOCK_DIMENSION)
+        && size.height.is_multiple_of(BlockCompression::BLOCK_DIMENSION)
     {
-        let data = self.ty.get_image_data(self.image.clone()).unwrap();
+        return Ok(create_texture_from_levels(
+            graphics,
+            ktx2.format,
+            ktx2.kind,
+            size,
+            &ktx2.levels,
+            sampler_settings,
+        ));
+    }
 
-        // Serialise the size
-        let mut seq = serializer.serialize_seq(Some(2 + data.len()))?;
+    if ktx2.kind != TextureKind::D3 && !bc_supported {
+        log_once::warn_once!(
+            "The graphics adapter doesn't support BC texture compression, textures are decompressed on load"
+        );
+    }
 
-        seq.serialize_element(&self.size)?;
+    let levels = ktx2
+        .levels
+        .iter()
+        .enumerate()
+        .map(|(level, data)| {
+            let extent = size.mip_level_size(level as u32, ktx2.kind.dimension());
+            let layer_size = level_size(ktx2.format, ktx2.width, ktx2.height, level as u32);
+            let mut decoded = Vec::new();
 
-        // Serialise type
-        seq.serialize_element(&self.ty)?;
+            for layer in data.chunks(layer_size) {
+                let image = decode_blocks(layer, compression, extent.width, extent.height)?;
 
-        // Serialise the data
-        for element in data {
-            seq.serialize_element(&element)?;
+                decoded.extend_from_slice(
+                    &T.get_image_data(image)
+                        .map_err(AssetReadError::CreateError)?,
+                );
+            }
+
+            Ok(decoded)
+        })
+        .collect::<Result<Vec<_>, AssetReadError>>()?;
+
+    Ok(create_texture_from_levels(
+        graphics,
+        T.into(),
+        ktx2.kind,
+        size,
+        &levels,
+        sampler_settings,
+    ))
+}
+
+/// The block compression materials use for textures of type `ty`:
+/// BC7 for colour, BC5 for normal maps and BC4 for single channel textures.
+pub(crate) fn material_block_compression(ty: TextureType) -> Option<BlockCompression> {
+    match ty {
+        TextureType::RGBA8Unorm | TextureType::RGBA8UnormSrgb => Some(BlockCompression::Bc7),
+        TextureType::RGBA16Float => Some(BlockCompression::Bc5),
+        TextureType::R8Unorm => Some(BlockCompression::Bc4),
+        _ => None,
+    }
+}
+
+/// Encodes `image` into blocks, padded to whole blocks with its edge texels.
+fn encode_blocks(image: &DynamicImage, compression: BlockCompression) -> Vec<u8> {
+    let (width, height) = image.dimensions();
+    let rgba = image.to_rgba8();
+
+    let mut data = Vec::with_capacity(compression.data_size(width, height));
+
+    for block_y in (0..height).step_by(BlockCompression::BLOCK_DIMENSION as usize) {
+        for block_x in (0..width).step_by(BlockCompression::BLOCK_DIMENSION as usize) {
+            let texels = std::array::from_fn(|i| {
+                let x = block_x + i as u32 % BlockCompression::BLOCK_DIMENSION;
+                let y = block_y + i as u32 / BlockCompression::BLOCK_DIMENSION;
+
+                rgba.get_pixel(x.min(width - 1), y.min(height - 1)).0
+            });
+
+            block_compression::encode_block(compression, &texels, &mut data);
         }
+    }
+
+    data
+}
+
+/// Decodes block compressed data of a `width` by `height` image.
+/// BC4 decodes to a single channel image, the others to RGBA.
+fn decode_blocks(
+    data: &[u8],
+    compression: BlockCompression,
+    width: u32,
+    height: u32,
+) -> Result<DynamicImage, AssetReadError> {
+    if data.len() < compression.data_size(width, height) {
+        return Err(AssetReadError::ParseError(
+            format!(
+                "Expected {} bytes of block compressed data, found {}",
+                compression.data_size(width, height),
+                data.len()
+            )
+            .into(),
+        ));
+    }
+
+    let blocks_wide = width.div_ceil(BlockCompression::BLOCK_DIMENSION);
+    let mut rgba = image::RgbaImage::new(width, height);
+
+    for (i, block) in data
+        .chunks_exact(compression.bytes_per_block())
+        .take(compression.data_size(width, height) / compression.bytes_per_block())
+        .enumerate()
+    {
+        let block_x = i as u32 % blocks_wide * BlockCompression::BLOCK_DIMENSION;
+        let block_y = i as u32 / blocks_wide * BlockCompression::BLOCK_DIMENSION;
 
-        seq.end()
+        for (j, texel) in block_compression::decode_block(compression, block)
+            .into_iter()
+            .enumerate()
+        {
+            let x = block_x + j as u32 % BlockCompression::BLOCK_DIMENSION;
+            let y = block_y + j as u32 / BlockCompression::BLOCK_DIMENSION;
+
+            if x < width && y < height {
+                rgba.put_pixel(x, y, image::Rgba(texel));
+            }
+        }
     }
+
+    Ok(match compression {
+        BlockCompression::Bc4 => {
+            DynamicImage::ImageLuma8(image::GrayImage::from_fn(width, height, |x, y| {
+                image::Luma([rgba.get_pixel(x, y).0[0]])
+            }))
+        }
+        _ => DynamicImage::ImageRgba8(rgba),
+    })
 }
 
 impl<'de> serde::Deserialize<'de> for TextureAssetBuffer {
@@ -79,11 +532,7 @@ impl<'de> serde::Deserialize<'de> for TextureAssetBuffer {
 
                 let texture = ty.buffer_to_dyn_image(data, size.width as u32, size.height as u32);
 
-                Ok(TextureAssetBuffer {
-                    ty,
-                    image: texture,
-                    size,
-                })
+                Ok(TextureAssetBuffer::from_image(texture, ty))
             }
         }
 
@@ -131,6 +580,76 @@ impl TextureAssetBuffer {
         let image = image::load(reader, image_format)
             .map_err(|e| AssetImportError::ParseError(Box::new(e)))?;
 
+        Ok(Self::from_image(image, texture_type))
+    }
+
+    /// Reads a source image as a texture of `kind`, see `TextureImportSettings::kind`.
+    pub fn read_from_source_as(
+        abs_path: &std::path::Path,
+        texture_type: TextureType,
+        kind: TextureKind,
+        cube_face_size: u32,
+    ) -> Result<Self, AssetImportError> {
+        let image = Self::read_from_source(abs_path, texture_type)?
+            .layers
+            .remove(0);
+
+        let layers = match kind {
+            TextureKind::D2 => vec![image],
+            TextureKind::Cube if is_equirect(&image) => {
+                let face_size = match cube_face_size {
+                    0 => (image.width() / 4).max(1),
+                    face_size => face_size,
+                };
+
+                let faces = equirect_to_cube(&image, face_size);
+
+                // Back to the channel layout of the source, unless it is HDR
+                if is_png_compatible(&image) {
+                    faces
+                        .iter()
+                        .map(|face| convert_like(face, &image))
+                        .collect()
+                } else {
+                    faces
+                }
+            }
+            _ => split_strip(&image)?,
+        };
+
+        Self::from_layers(layers, kind, texture_type)
+    }
+
+    /// Reads the layers of a texture of `kind` from the images in a directory.
+    pub fn read_from_directory(
+        abs_path: &std::path::Path,
+        texture_type: TextureType,
+        kind: TextureKind,
+    ) -> Result<Self, AssetImportError> {
+        let mut paths = std::fs::read_dir(abs_path)
+            .map_err(AssetImportError::Io)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(AssetImportError::Io)?;
+
+        paths.retain(|path| path.is_file() && image::ImageFormat::from_path(path).is_ok());
+        paths.sort();
+
+        if kind == TextureKind::Cube {
+            paths = sort_cube_faces(paths)?;
+        }
+
+        let layers = paths
+            .iter()
+            .map(|path| {
+                Self::read_from_source(path, texture_type).map(|mut buffer| buffer.layers.remove(0))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Self::from_layers(layers, kind, texture_type)
+    }
+
+    pub fn from_image(image: image::DynamicImage, texture_type: TextureType) -> Self {
         let (width, height) = image.dimensions();
 
         let size = wgpu::Extent3d {
@@ -139,82 +658,519 @@ impl TextureAssetBuffer {
             depth_or_array_layers: 1,
         };
 
-        Ok(TextureAssetBuffer {
+        TextureAssetBuffer {
             ty: texture_type,
-            image,
+            kind: TextureKind::D2,
+            layers: vec![image],
             size,
-        })
+            mips: Vec::new(),
+            mip_filter: MipFilter::None,
+        }
     }
 
-    pub fn create_texture<const T: TextureType>(
-        &self,
-        graphics: &Graphics,
-    ) -> Result<Texture<T>, AssetReadError> {
-        let texture = graphics.device.create_texture(&wgpu::TextureDescriptor {
-            label: None,
-            size: self.size,
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: T.into(),
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-            view_formats: &[T.into()],
-        });
+    /// Creates a texture of `kind` from its layers, checking they can make one.
+    pub fn from_layers(
+        layers: Vec<image::DynamicImage>,
+        kind: TextureKind,
+        texture_type: TextureType,
+    ) -> Result<Self, AssetImportError> {
+        let (width, height) = layers
+            .first()
+            .ok_or_else(|| {
+                AssetImportError::LoadError("No texture layers found".to_string().into())
+            })?
+            .dimensions();
 
-        graphics.queue.write_texture(
-            wgpu::ImageCopyTexture {
-                texture: &texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All,
-            },
-            &T.get_image_data(self.image.clone())
-                .map_err(|e| AssetReadError::CreateError(e))?,
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: Some(T.bytes_per_pixel() as u32 * self.size.width),
-                rows_per_image: Some(self.size.height),
+        if layers
+            .iter()
+            .any(|layer| layer.dimensions() != (width, height))
+        {
+            return Err(AssetImportError::ProcessError(
+                "Texture layers must all be the same size"
+                    .to_string()
+                    .into(),
+            ));
+        }
+
+        let valid = match kind {
+            TextureKind::D2 => layers.len() == 1,
+            TextureKind::Cube => layers.len() == 6 && width == height,
+            TextureKind::D2Array | TextureKind::D3 => true,
+        };
+
+        if !valid {
+            return Err(AssetImportError::ProcessError(
+                format!(
+                    "{} layers of {}x{} can't make a {:?} texture",
+                    layers.len(),
+                    width,
+                    height,
+                    kind
+                )
+                .into(),
+            ));
+        }
+
+        Ok(TextureAssetBuffer {
+            ty: texture_type,
+            kind,
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: layers.len() as u32,
             },
-            self.size,
-        );
+            layers,
+            mips: Vec::new(),
+            mip_filter: MipFilter::None,
+        })
+    }
 
-        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-
-        let sampler = graphics.device.create_sampler(&wgpu::SamplerDescriptor {
-            label: None,
-            address_mode_u: wgpu::AddressMode::Repeat,
-            address_mode_v: wgpu::AddressMode::Repeat,
-            address_mode_w: wgpu::AddressMode::Repeat,
-            mag_filter: wgpu::FilterMode::Nearest,
-            min_filter: wgpu::FilterMode::Nearest,
-            ..Default::default()
-        });
-
-        let bind_group = graphics
-            .device
-            .create_bind_group(&wgpu::BindGroupDescriptor {
-                label: None,
-                layout: &Texture::<T>::bind_group_layout(graphics, ()),
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: wgpu::BindingResource::TextureView(&view),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: wgpu::BindingResource::Sampler(&sampler),
-                    },
-                ],
-            });
+    /// Replaces the mip levels with a full chain down to 1x1, filtered with `filter`.
+    /// 3D textures, which are mostly lookup tables, don't get mip levels.
+    pub fn generate_mips(&mut self, filter: MipFilter) {
+        let filter = match self.kind {
+            TextureKind::D3 => MipFilter::None,
+            _ => filter,
+        };
 
-        Ok(Texture {
-            bind_group,
-            texture,
-            view,
-            sampler,
+        self.mips = match filter {
+            MipFilter::None => Vec::new(),
+            _ => self
+                .layers
+                .iter()
+                .map(|layer| generate_mips(layer, self.ty == TextureType::RGBA8UnormSrgb, filter))
+                .collect(),
+        };
+        self.mip_filter = filter;
+    }
+
+    /// Number of mip levels, including the full size one.
+    fn mip_level_count(&self) -> usize {
+        1 + self.mips.first().map_or(0, Vec::len)
+    }
+
+    /// The images of mip level `level`, one for each layer.
+    fn level(&self, level: usize) -> impl Iterator<Item = &image::DynamicImage> {
+        self.layers
+            .iter()
+            .enumerate()
+            .map(move |(layer, image)| match level {
+                0 => image,
+                _ => &self.mips[layer][level - 1],
+            })
+    }
+
+    /// Resizes the layers to a multiple of the block size, needed for block compression.
+    /// Mip levels are generated again for the new size.
+    pub fn round_to_blocks(&mut self) {
+        let (width, height) = (self.size.width, self.size.height);
+        let rounded_width = width.next_multiple_of(BlockCompression::BLOCK_DIMENSION);
+        let rounded_height = height.next_multiple_of(BlockCompression::BLOCK_DIMENSION);
+
+        if (width, height) != (rounded_width, rounded_height) {
+            for layer in &mut self.layers {
+                *layer = layer.resize_exact(
+                    rounded_width,
+                    rounded_height,
+                    image::imageops::FilterType::Triangle,
+                );
+            }
+            self.size.width = rounded_width;
+            self.size.height = rounded_height;
+
+            self.generate_mips(self.mip_filter);
+        }
+    }
+
+    /// Texel data of every layer of every mip level, level by level.
+    pub fn to_packed(&self) -> Result<PackedTexture, Box<dyn std::error::Error>> {
+        let levels = (0..self.mip_level_count())
+            .flat_map(|level| self.level(level))
+            .map(|layer| self.ty.get_image_data(layer.clone()).map(PackedLevel::Raw))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(PackedTexture {
+            ty: self.ty,
             size: self.size,
+            levels,
         })
     }
+
+    /// Writes the packed texture and its mip levels to `output_path`.
+    /// Block compressed textures are written as KTX2, others as a `PackedTexture`.
+    /// Returns the extra asset info needed to read it back.
+    pub fn write_packed(
+        mut self,
+        pack: &PackInfo,
+        output_path: &std::path::Path,
+        sampler_settings: &SamplerSettings,
+        compression: Option<BlockCompression>,
+    ) -> Result<ExtraAssetInfo, AssetImportError> {
+        if let Some(compression) = compression {
+            // wgpu only allows block compressed 2D textures
+            if self.kind == TextureKind::D3 {
+                return Err(AssetImportError::ProcessError(
+                    "3D textures can't be block compressed".to_string().into(),
+                ));
+            }
+
+            let format = self
+                .ty
+                .block_compressed_format(compression)
+                .ok_or_else(|| {
+                    AssetImportError::ProcessError(
+                        format!("{:?} can't compress a {} texture", compression, self.ty).into(),
+                    )
+                })?;
+
+            self.round_to_blocks();
+
+            let ktx2 = Ktx2Texture {
+                format,
+                kind: self.kind,
+                width: self.size.width,
+                height: self.size.height,
+                layers: self.size.depth_or_array_layers,
+                levels: (0..self.mip_level_count())
+                    .map(|level| {
+                        self.level(level)
+                            .flat_map(|layer| encode_blocks(layer, compression))
+                            .collect()
+                    })
+                    .collect(),
+            };
+
+            return write_ktx2(&ktx2, self.ty, pack, output_path, sampler_settings);
+        }
+
+        let packed = self
+            .to_packed()
+            .map_err(|e| AssetImportError::ProcessError(e.to_string().into()))?;
+
+        write_packed(
+            output_path,
+            pack,
+            &texture_type_name(self.ty),
+            PackedTexture::VERSION,
+            &bincode::serialize(&packed)?,
+        )
+        .map_err(AssetImportError::WriteError)?;
+
+        texture_extra_info(
+            &PackedTexture::FORMAT.to_string(),
+            self.mip_level_count(),
+            self.kind,
+            sampler_settings,
+        )
+    }
+}
+
+/// Writes `ktx2` as the payload of the packed texture, compressed like any other asset.
+fn write_ktx2(
+    ktx2: &Ktx2Texture,
+    ty: TextureType,
+    pack: &PackInfo,
+    output_path: &std::path::Path,
+    sampler_settings: &SamplerSettings,
+) -> Result<ExtraAssetInfo, AssetImportError> {
+    let data = ktx2
+        .write(None)
+        .map_err(|e| AssetImportError::ProcessError(Box::new(e)))?;
+
+    write_packed(
+        output_path,
+        pack,
+        &texture_type_name(ty),
+        PackedTexture::VERSION,
+        &data,
+    )
+    .map_err(AssetImportError::WriteError)?;
+
+    texture_extra_info(
+        PackedTexture::KTX2_FORMAT,
+        ktx2.levels.len(),
+        ktx2.kind,
+        sampler_settings,
+    )
+}
+
+fn texture_extra_info(
+    format: &str,
+    mip_levels: usize,
+    kind: TextureKind,
+    sampler_settings: &SamplerSettings,
+) -> Result<ExtraAssetInfo, AssetImportError> {
+    let mut extra_info = ExtraAssetInfo::new();
+
+    extra_info
+        .0
+        .insert(PackedTexture::FORMAT_KEY.to_string(), format.to_string());
+    extra_info
+        .0
+        .insert("mip_levels".to_string(), mip_levels.to_string());
+    extra_info.0.insert(
+        PackedTexture::SAMPLER_KEY.to_string(),
+        ron::to_string(sampler_settings)
+            .map_err(|e| AssetImportError::ProcessError(Box::new(e)))?,
+    );
+    extra_info.0.insert(
+        PackedTexture::KIND_KEY.to_string(),
+        ron::to_string(&kind).map_err(|e| AssetImportError::ProcessError(Box::new(e)))?,
+    );
+
+    Ok(extra_info)
+}
+
+/// Reads a KTX2 source file, checking it can be used as a texture of type `T`.
+fn read_ktx2_source<const T: TextureType>(
+    abs_path: &std::path::Path,
+) -> Result<Ktx2Texture, AssetImportError> {
+    let data = std::fs::read(abs_path).map_err(AssetImportError::Io)?;
+    let ktx2 = Ktx2Texture::read(&data).map_err(|e| AssetImportError::ParseError(Box::new(e)))?;
+
+    let usable = ktx2.format == T.into()
+        || BlockCompression::from_format(ktx2.format)
+            .is_some_and(|compression| T.block_compressed_format(compression) == Some(ktx2.format));
+
+    if !usable {
+        return Err(AssetImportError::ParseError(
+            format!("A {:?} texture can't be imported as a {}", ktx2.format, T).into(),
+        ));
+    }
+
+    Ok(ktx2)
+}
+
+fn is_ktx2(abs_path: &std::path::Path) -> bool {
+    abs_path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("ktx2"))
+}
+
+/// PNG can only hold 8 and 16 bit channels.
+fn is_png_compatible(image: &DynamicImage) -> bool {
+    !matches!(
+        image,
+        DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_)
+    )
+}
+
+/// Converts `image` to the channel layout of `like`.
+fn convert_like(image: &DynamicImage, like: &DynamicImage) -> DynamicImage {
+    match like {
+        DynamicImage::ImageLuma8(_) => DynamicImage::ImageLuma8(image.to_luma8()),
+        DynamicImage::ImageLumaA8(_) => DynamicImage::ImageLumaA8(image.to_luma_alpha8()),
+        DynamicImage::ImageRgb8(_) => DynamicImage::ImageRgb8(image.to_rgb8()),
+        DynamicImage::ImageLuma16(_) => DynamicImage::ImageLuma16(image.to_luma16()),
+        DynamicImage::ImageLumaA16(_) => DynamicImage::ImageLumaA16(image.to_luma_alpha16()),
+        DynamicImage::ImageRgb16(_) => DynamicImage::ImageRgb16(image.to_rgb16()),
+        DynamicImage::ImageRgba16(_) => DynamicImage::ImageRgba16(image.to_rgba16()),
+        _ => DynamicImage::ImageRgba8(image.to_rgba8()),
+    }
+}
+
+/// Generates the mip chain of `image` down to 1x1, not including the image itself.
+/// sRGB images are filtered in linear space.
+pub(crate) fn generate_mips(
+    image: &DynamicImage,
+    srgb: bool,
+    filter: MipFilter,
+) -> Vec<DynamicImage> {
+    let kernel = match filter {
+        MipFilter::None => return Vec::new(),
+        MipFilter::Box => MipKernel::box_filter(),
+        MipFilter::Kaiser => MipKernel::kaiser(),
+    };
+
+    // HDR images can go past 1, don't clamp them
+    let clamp = is_png_compatible(image);
+
+    let (mut width, mut height) = image.dimensions();
+    let mut texels = image
+        .to_rgba32f()
+        .pixels()
+        .map(|pixel| {
+            let mut texel = pixel.0;
+            if srgb {
+                for channel in &mut texel[..3] {
+                    *channel = srgb_to_linear(*channel);
+                }
+            }
+            texel
+        })
+        .collect::<Vec<_>>();
+
+    let mut mips = Vec::new();
+
+    while width > 1 || height > 1 {
+        (texels, width) = kernel.downsample_rows(&texels, width, height);
+        (texels, height) = kernel.downsample_columns(&texels, width, height);
+
+        let data = texels
+            .iter()
+            .flat_map(|texel| {
+                let mut texel = *texel;
+                if clamp {
+                    for channel in &mut texel {
+                        *channel = channel.clamp(0.0, 1.0);
+                    }
+                }
+                if srgb {
+                    for channel in &mut texel[..3] {
+                        *channel = linear_to_srgb(*channel);
+                    }
+                }
+                texel
+            })
+            .collect::<Vec<f32>>();
+
+        mips.push(DynamicImage::ImageRgba32F(
+            image::Rgba32FImage::from_raw(width, height, data)
+                .expect("mip level has width * height texels"),
+        ));
+    }
+
+    mips
+}
+
+/// 1D kernel applied at every second texel, used on rows then columns.
+struct MipKernel {
+    /// Offset of the first tap from `2 * i`.
+    first_offset: i64,
+    weights: Vec<f32>,
+}
+
+impl MipKernel {
+    fn box_filter() -> Self {
+        Self {
+            first_offset: 0,
+            weights: vec![0.5, 0.5],
+        }
+    }
+
+    /// Kaiser windowed sinc with 3 taps on each side, alpha of 4.
+    fn kaiser() -> Self {
+        const RADIUS: f32 = 3.0;
+        const ALPHA: f32 = 4.0;
+
+        let weights = (-2..=3)
+            .map(|tap| {
+                // Distance from the output texel center, in source texels
+                let x = tap as f32 - 0.5;
+                let window = 1.0 - (x / RADIUS).powi(2);
+
+                sinc(x / 2.0) * bessel_i0(ALPHA * window.max(0.0).sqrt()) / bessel_i0(ALPHA)
+            })
+            .collect::<Vec<_>>();
+
+        let sum = weights.iter().sum::<f32>();
+
+        Self {
+            first_offset: -2,
+            weights: weights.iter().map(|weight| weight / sum).collect(),
+        }
+    }
+
+    fn sample(&self, count: u32, i: u32, texel: impl Fn(u32) -> [f32; 4]) -> [f32; 4] {
+        let mut result = [0.0; 4];
+
+        for (k, weight) in self.weights.iter().enumerate() {
+            let source = (2 * i as i64 + self.first_offset + k as i64).clamp(0, count as i64 - 1);
+            let value = texel(source as u32);
+
+            for channel in 0..4 {
+                result[channel] += value[channel] * weight;
+            }
+        }
+
+        result
+    }
+
+    /// Halves the width, unless it is already 1.
+    fn downsample_rows(
+        &self,
+        texels: &[[f32; 4]],
+        width: u32,
+        height: u32,
+    ) -> (Vec<[f32; 4]>, u32) {
+        if width == 1 {
+            return (texels.to_vec(), width);
+        }
+
+        let new_width = width / 2;
+        let mut result = Vec::with_capacity((new_width * height) as usize);
+
+        for y in 0..height {
+            let row = &texels[(y * width) as usize..((y + 1) * width) as usize];
+
+            for x in 0..new_width {
+                result.push(self.sample(width, x, |source| row[source as usize]));
+            }
+        }
+
+        (result, new_width)
+    }
+
+    /// Halves the height, unless it is already 1.
+    fn downsample_columns(
+        &self,
+        texels: &[[f32; 4]],
+        width: u32,
+        height: u32,
+    ) -> (Vec<[f32; 4]>, u32) {
+        if height == 1 {
+            return (texels.to_vec(), height);
+        }
+
+        let new_height = height / 2;
+        let mut result = Vec::with_capacity((width * new_height) as usize);
+
+        for y in 0..new_height {
+            for x in 0..width {
+                result.push(self.sample(height, y, |source| texels[(source * width + x) as usize]));
+            }
+        }
+
+        (result, new_height)
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    if x.abs() < 1e-6 {
+        1.0
+    } else {
+        let x = std::f32::consts::PI * x;
+        x.sin() / x
+    }
+}
+
+/// Modified Bessel function of the first kind, order 0.
+fn bessel_i0(x: f32) -> f32 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+
+    for k in 1..20 {
+        term *= (x / (2.0 * k as f32)).powi(2);
+        sum += term;
+    }
+
+    sum
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
 }
 
 pub struct TextureImporter<const T: TextureType> {}
@@ -222,60 +1178,183 @@ pub struct TextureImporter<const T: TextureType> {}
 impl<const T: TextureType> TextureImporter<T> {}
 
 impl<const T: TextureType> AssetImporter<TextureAsset<T>> for TextureImporter<T> {
+    type Settings = TextureImportSettings;
+
+    fn name() -> String {
+        format!("TextureImporter<{}>", T.to_string())
+    }
+
     fn unimported_fs_type() -> AssetFileSystemType {
         AssetFileSystemType::File
     }
 
+    fn extensions() -> Vec<String> {
+        [
+            "png", "jpg", "jpeg", "bmp", "gif", "ico", "tif", "tiff", "webp", "hdr", "ktx2",
+        ]
+        .iter()
+        .map(|extension| extension.to_string())
+        .collect()
+    }
+
     fn verify_source(abs_path: &std::path::Path) -> Result<(), AssetVerifyError> {
-        TextureAssetBuffer::read_from_source(abs_path, T).map_err(
-            |e| AssetVerifyError::InvalidFile(Box::new(e)),
-        )?;
+        if is_ktx2(abs_path) {
+            read_ktx2_source::<T>(abs_path)
+                .map_err(|e| AssetVerifyError::InvalidFile(Box::new(e)))?;
+        } else {
+            TextureAssetBuffer::read_from_source(abs_path, T)
+                .map_err(|e| AssetVerifyError::InvalidFile(Box::new(e)))?;
+        }
         Ok(())
     }
 
     fn note() -> Option<String> {
-        Some("Supported formats: PNG, JPEG, BMP, GIF, ICO, TIFF, WebP, HDR. Does not use the default compression algorithm and level, but rather PNG compression.".to_string())
+        Some("Supported formats: PNG, JPEG, BMP, GIF, ICO, TIFF, WebP, HDR, KTX2. Settings: kind = \"d2\" | \"d2_array\" | \"cube\" | \"d3\", cube_face_size, mipmaps = \"none\" | \"box\" | \"kaiser\", compression = \"bc1\" | \"bc3\" | \"bc4\" | \"bc5\" | \"bc7\", sampler in [sampler]. Cube maps come from a 2:1 equirectangular image or a 6:1 strip of faces, arrays and 3D textures from a strip of square layers. KTX2 files are packed as they are.".to_string())
     }
 
     fn import(
         abs_input_path: &std::path::Path,
         asset_info: &cobalt_assets::manifest::AssetInfo,
         assets_dir: &std::path::Path,
-    ) -> Result<ExtraAssetInfo, AssetImportError> {
-        let texture_asset_buffer = TextureAssetBuffer::read_from_source(abs_input_path, T)?;
-
-        let ser_data = if let Some(_) = asset_info.pack.compression {
-            // Use PNG for compression
-            // Create image from raw data
-            let image = texture_asset_buffer.image;
-
-            // Create a buffer to hold the PNG data
-            let mut png_buffer = Vec::new();
-            image
-                .write_to(&mut Cursor::new(&mut png_buffer), image::ImageFormat::Png)
-                .map_err(|e| {
-                    log::error!("{}", e);
-                    AssetImportError::ProcessError("Failed to format image as PNG".to_string().into())
-                })?;
+        settings: &Self::Settings,
+    ) -> Result<ImportedAsset, AssetImportError> {
+        let output_path = assets_dir.join(&asset_info.relative_path);
 
-            png_buffer
-        } else {
-            bincode::serialize(&texture_asset_buffer)?
-        };
+        if is_ktx2(abs_input_path) {
+            let ktx2 = read_ktx2_source::<T>(abs_input_path)?;
 
-        let output_path = assets_dir.join(&asset_info.relative_path);
+            return Ok(
+                write_ktx2(&ktx2, T, &asset_info.pack, &output_path, &settings.sampler)?.into(),
+            );
+        }
 
-        std::fs::write(&output_path, ser_data).map_err(|e| AssetImportError::WriteError(e))?;
+        let mut texture_asset_buffer = TextureAssetBuffer::read_from_source_as(
+            abs_input_path,
+            T,
+            settings.kind,
+            settings.cube_face_size,
+        )?;
 
-        let mut extra_info = ExtraAssetInfo::new();
+        texture_asset_buffer.generate_mips(settings.mipmaps);
 
-        if let Some(_) = asset_info.pack.compression {
-            extra_info.0.insert(
-                "mime".to_string(),
-                ImageFormat::Png.to_mime_type().to_string(),
-            );
+        Ok(texture_asset_buffer
+            .write_packed(
+                &asset_info.pack,
+                &output_path,
+                &settings.sampler,
+                settings.compression,
+            )?
+            .into())
+    }
+}
+
+/// Imports a cube map, texture array or 3D texture from a directory with an image per layer.
+pub struct TextureLayersImporter<const T: TextureType> {}
+
+impl<const T: TextureType> AssetImporter<TextureAsset<T>> for TextureLayersImporter<T> {
+    type Settings = TextureLayersImportSettings;
+
+    fn name() -> String {
+        format!("TextureLayersImporter<{}>", T.to_string())
+    }
+
+    fn unimported_fs_type() -> AssetFileSystemType {
+        AssetFileSystemType::Directory
+    }
+
+    fn verify_source(abs_path: &std::path::Path) -> Result<(), AssetVerifyError> {
+        if !abs_path.is_dir() {
+            return Err(AssetVerifyError::InvalidFile(
+                "Expected a directory of images".to_string().into(),
+            ));
         }
+        Ok(())
+    }
+
+    fn note() -> Option<String> {
+        Some("A directory with an image per layer. Cube map faces are named px, nx, py, ny, pz, nz (or posx.., or right, left, top, bottom, front, back), array layers and 3D texture slices are taken in file name order. Settings: kind = \"cube\" | \"d2_array\" | \"d3\", mipmaps, compression and sampler as for TextureImporter.".to_string())
+    }
+
+    fn import(
+        abs_input_path: &std::path::Path,
+        asset_info: &cobalt_assets::manifest::AssetInfo,
+        assets_dir: &std::path::Path,
+        settings: &Self::Settings,
+    ) -> Result<ImportedAsset, AssetImportError> {
+        let output_path = assets_dir.join(&asset_info.relative_path);
+
+        let mut texture_asset_buffer =
+            TextureAssetBuffer::read_from_directory(abs_input_path, T, settings.kind)?;
+
+        texture_asset_buffer.generate_mips(settings.mipmaps);
+
+        Ok(texture_asset_buffer
+            .write_packed(
+                &asset_info.pack,
+                &output_path,
+                &settings.sampler,
+                settings.compression,
+            )?
+            .into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn box_mips_average_texels() {
+        let image = DynamicImage::ImageRgba32F(
+            image::Rgba32FImage::from_raw(
+                2,
+                2,
+                [0.0, 0.2, 0.4, 0.6]
+                    .iter()
+                    .flat_map(|value| [*value; 4])
+                    .collect(),
+            )
+            .unwrap(),
+        );
+
+        let mips = generate_mips(&image, false, MipFilter::Box);
+
+        assert_eq!(mips.len(), 1);
+        assert_eq!(mips[0].dimensions(), (1, 1));
+        assert!((mips[0].to_rgba32f().get_pixel(0, 0).0[0] - 0.3).abs() < 1e-6);
+    }
+
+    #[test]
+    fn mip_chain_goes_down_to_one_texel() {
+        let image = DynamicImage::ImageRgba8(image::RgbaImage::new(8, 2));
+
+        let sizes = generate_mips(&image, true, MipFilter::Kaiser)
+            .iter()
+            .map(|mip| mip.dimensions())
+            .collect::<Vec<_>>();
+
+        assert_eq!(sizes, vec![(4, 1), (2, 1), (1, 1)]);
+    }
+
+    #[test]
+    fn block_compression_roundtrip() {
+        // Solid colours survive block compression exactly
+        let image =
+            DynamicImage::ImageLuma8(image::GrayImage::from_pixel(6, 5, image::Luma([200])));
+
+        let data = encode_blocks(&image, BlockCompression::Bc4);
+        assert_eq!(data.len(), BlockCompression::Bc4.data_size(6, 5));
+
+        let decoded = decode_blocks(&data, BlockCompression::Bc4, 6, 5).unwrap();
+        assert_eq!(decoded.dimensions(), (6, 5));
+        assert!(decoded.to_luma8().pixels().all(|pixel| pixel.0[0] == 200));
+    }
+
+    #[test]
+    fn kaiser_weights_are_normalized() {
+        let kernel = MipKernel::kaiser();
 
-        Ok(extra_info)
+        assert!((kernel.weights.iter().sum::<f32>() - 1.0).abs() < 1e-6);
+        assert_eq!(kernel.weights.len(), 6);
     }
 }
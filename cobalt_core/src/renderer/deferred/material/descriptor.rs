@@ -0,0 +1,249 @@
+use std::sync::Arc;
+
+use cobalt_assets::{
+    asset::{Asset, AssetDeContext, AssetID, AssetReadContext, AssetReadError, AssetTrait},
+    server::AssetLoadError,
+};
+use cobalt_graphics::{context::Graphics, texture::TextureType};
+use parking_lot::RwLock;
+
+use crate::{asset_types::texture::TextureAsset, types::either::Either};
+
+use super::Material;
+
+/// Serialisable form of a `Material`, textures are referenced by `AssetID`.
+/// Used for material files and for materials in scenes.
+///
+/// Material files are written in RON:
+/// ```ron
+/// (
+///     unlit: false,
+///     wireframe: None,
+///     albedo: (Some((1.0, 1.0, 1.0, 1.0)), Some("8c6e5a3f-...")),
+///     normal: None,
+///     metallic: Left(0.0),
+///     roughness: Right("1f0b2d9e-..."),
+/// )
+/// ```
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct MaterialDescriptor {
+    #[serde(default)]
+    pub unlit: bool,
+    #[serde(default)]
+    pub wireframe: Option<[f32; 4]>,
+    #[serde(default = "default_albedo")]
+    pub albedo: (Option<[f32; 4]>, Option<AssetID>),
+    #[serde(default)]
+    pub normal: Option<AssetID>,
+    #[serde(default = "default_metallic")]
+    pub metallic: Either<f32, AssetID>,
+    #[serde(default = "default_roughness")]
+    pub roughness: Either<f32, AssetID>,
+}
+
+// Same values as `Material::default`
+fn default_albedo() -> (Option<[f32; 4]>, Option<AssetID>) {
+    (Some([1.0, 1.0, 1.0, 1.0]), None)
+}
+
+fn default_metallic() -> Either<f32, AssetID> {
+    Either::Left(0.0)
+}
+
+fn default_roughness() -> Either<f32, AssetID> {
+    Either::Left(0.5)
+}
+
+impl Default for MaterialDescriptor {
+    fn default() -> Self {
+        Self {
+            unlit: false,
+            wireframe: None,
+            albedo: default_albedo(),
+            normal: None,
+            metallic: default_metallic(),
+            roughness: default_roughness(),
+        }
+    }
+}
+
+/// Resolves the texture IDs of a descriptor to handles.
+pub(crate) trait TextureResolver {
+    type Error;
+
+    fn texture<const T: TextureType>(
+        &mut self,
+        asset_id: AssetID,
+    ) -> Result<Asset<TextureAsset<T>>, Self::Error>;
+}
+
+/// Textures of a material asset are its dependencies, loaded before it is read.
+impl TextureResolver for &AssetReadContext<'_> {
+    type Error = AssetReadError;
+
+    fn texture<const T: TextureType>(
+        &mut self,
+        asset_id: AssetID,
+    ) -> Result<Asset<TextureAsset<T>>, Self::Error> {
+        self.dependency::<TextureAsset<T>>(asset_id)
+    }
+}
+
+/// Textures of a deserialised material are loaded on demand.
+impl TextureResolver for AssetDeContext<'_> {
+    type Error = AssetLoadError;
+
+    fn texture<const T: TextureType>(
+        &mut self,
+        asset_id: AssetID,
+    ) -> Result<Asset<TextureAsset<T>>, Self::Error> {
+        self.asset_server.write().load::<TextureAsset<T>>(
+            Arc::downgrade(self.asset_server),
+            self.graphics,
+            asset_id,
+        )
+    }
+}
+
+impl MaterialDescriptor {
+    /// Texture assets the material refers to, without duplicates.
+    pub fn dependencies(&self) -> Vec<AssetID> {
+        let mut dependencies = Vec::new();
+
+        for asset_id in self
+            .albedo
+            .1
+            .iter()
+            .chain(self.normal.iter())
+            .chain(self.metallic.right())
+            .chain(self.roughness.right())
+        {
+            if !dependencies.contains(asset_id) {
+                dependencies.push(*asset_id);
+            }
+        }
+
+        dependencies
+    }
+
+    /// Pairs of texture ID and the texture type name the material expects it to be.
+    pub fn expected_texture_types(&self) -> Vec<(AssetID, String)> {
+        let mut textures = Vec::new();
+
+        if let Some(albedo) = self.albedo.1 {
+            textures.push((albedo, TextureAsset::<{ Material::ALBEDO_TEXTURE_TYPE }>::type_name()));
+        }
+        if let Some(normal) = self.normal {
+            textures.push((normal, TextureAsset::<{ Material::NORMAL_TEXTURE_TYPE }>::type_name()));
+        }
+        if let Some(metallic) = self.metallic.right() {
+            textures.push((
+                *metallic,
+                TextureAsset::<{ Material::METALLIC_TEXTURE_TYPE }>::type_name(),
+            ));
+        }
+        if let Some(roughness) = self.roughness.right() {
+            textures.push((
+                *roughness,
+                TextureAsset::<{ Material::ROUGHNESS_TEXTURE_TYPE }>::type_name(),
+            ));
+        }
+
+        textures
+    }
+
+    /// Creates the material, resolving its textures with `resolver`.
+    pub(crate) fn build<R: TextureResolver>(
+        &self,
+        graphics: &Arc<RwLock<Graphics>>,
+        mut resolver: R,
+    ) -> Result<Material, R::Error> {
+        let albedo_texture = self
+            .albedo
+            .1
+            .map(|asset_id| resolver.texture::<{ Material::ALBEDO_TEXTURE_TYPE }>(asset_id))
+            .transpose()?;
+
+        let normal = self
+            .normal
+            .map(|asset_id| resolver.texture::<{ Material::NORMAL_TEXTURE_TYPE }>(asset_id))
+            .transpose()?;
+
+        let metallic = match self.metallic {
+            Either::Left(value) => Either::Left(value),
+            Either::Right(asset_id) => {
+                Either::Right(resolver.texture::<{ Material::METALLIC_TEXTURE_TYPE }>(asset_id)?)
+            }
+        };
+
+        let roughness = match self.roughness {
+            Either::Left(value) => Either::Left(value),
+            Either::Right(asset_id) => {
+                Either::Right(resolver.texture::<{ Material::ROUGHNESS_TEXTURE_TYPE }>(asset_id)?)
+            }
+        };
+
+        Ok(Material::new(
+            graphics,
+            self.unlit,
+            self.wireframe,
+            (self.albedo.0, albedo_texture),
+            normal,
+            metallic,
+            roughness,
+        ))
+    }
+}
+
+impl From<&Material> for MaterialDescriptor {
+    fn from(material: &Material) -> Self {
+        Self {
+            unlit: material.unlit,
+            wireframe: material.wireframe,
+            albedo: (
+                material.albedo.0,
+                material.albedo.1.as_ref().map(|texture| texture.id()),
+            ),
+            normal: material.normal.as_ref().map(|texture| texture.id()),
+            metallic: match &material.metallic {
+                Either::Left(value) => Either::Left(*value),
+                Either::Right(texture) => Either::Right(texture.id()),
+            },
+            roughness: match &material.roughness {
+                Either::Left(value) => Either::Left(*value),
+                Either::Right(texture) => Either::Right(texture.id()),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn descriptor_ron_roundtrip() {
+        let texture = AssetID::generate();
+
+        let descriptor = MaterialDescriptor {
+            albedo: (None, Some(texture)),
+            roughness: Either::Right(texture),
+            ..Default::default()
+        };
+
+        let ron = ron::to_string(&descriptor).unwrap();
+        let parsed: MaterialDescriptor = ron::from_str(&ron).unwrap();
+
+        assert_eq!(parsed, descriptor);
+        assert_eq!(parsed.dependencies(), vec![texture]);
+    }
+
+    #[test]
+    fn descriptor_defaults() {
+        let parsed: MaterialDescriptor = ron::from_str("(unlit: true)").unwrap();
+
+        assert!(parsed.unlit);
+        assert_eq!(parsed.albedo, default_albedo());
+        assert_eq!(parsed.roughness, Either::Left(0.5));
+    }
+}
@@ -5,19 +5,24 @@ use parking_lot::RwLock;
 use wgpu::util::DeviceExt;
 
 use crate::{
-    asset_types::texture::TextureAsset,
+    asset_types::{read_ron, texture::TextureAsset},
     exports::types::Either
 };
 use cobalt_assets::{
-    asset::AssetFileSystemType,
-    exports::{Asset, AssetTrait}, server::AssetServer,
+    asset::{AssetDeContext, AssetFileSystemType, AssetID, AssetReadContext, AssetReadError},
+    exports::{Asset, AssetTrait},
 };
+use serde::Deserialize;
+
+use self::descriptor::MaterialDescriptor;
 use cobalt_graphics::{
     context::Graphics,
     texture::{TextureInternal, TextureType},
     HasBindGroupLayout,
 };
 
+pub mod descriptor;
+
 static MATERIAL_ID: AtomicUsize = AtomicUsize::new(0);
 
 /// Deferred renderer material.
@@ -52,20 +57,25 @@ pub struct Material {
     graphics_weak_ref: Weak<RwLock<Graphics>>,
 }
 
+/// Materials serialize as a `MaterialDescriptor`, their textures are loaded through the asset server when deserialized.
 impl Component for Material {
-    type DeContext<'a> = (&'a Graphics, &'a mut AssetServer);
+    type DeContext<'a> = AssetDeContext<'a>;
     type SerContext<'a> = ();
 
     fn deserialise<'de, D>(context: Self::DeContext<'de>, deserializer: D) -> Result<Self, D::Error>
     where
         D: serde::Deserializer<'de> {
-        todo!()
+        let descriptor = MaterialDescriptor::deserialize(deserializer)?;
+
+        descriptor
+            .build(context.graphics, context)
+            .map_err(|e| serde::de::Error::custom(format!("Failed to load material texture: {}", e)))
     }
 
-    fn serialize<'se, S>(&self, context: Self::SerContext<'se>, serializer: S) -> Result<S::Ok, S::Error>
+    fn serialize<'se, S>(&self, _context: Self::SerContext<'se>, serializer: S) -> Result<S::Ok, S::Error>
         where
             S: serde::Serializer {
-        todo!()
+        serde::Serialize::serialize(&MaterialDescriptor::from(self), serializer)
     }
 }
 
@@ -84,10 +94,10 @@ impl std::fmt::Debug for Material {
 
 impl Material {
     // NOTE: Make sure this type matches the EMPTY_? in the generate_bind_group function when changing.
-    const ALBEDO_TEXTURE_TYPE: TextureType = TextureType::RGBA8UnormSrgb;
-    const NORMAL_TEXTURE_TYPE: TextureType = TextureType::RGBA16Float;
-    const METALLIC_TEXTURE_TYPE: TextureType = TextureType::R8Unorm;
-    const ROUGHNESS_TEXTURE_TYPE: TextureType = TextureType::R8Unorm;
+    pub const ALBEDO_TEXTURE_TYPE: TextureType = TextureType::RGBA8UnormSrgb;
+    pub const NORMAL_TEXTURE_TYPE: TextureType = TextureType::RGBA16Float;
+    pub const METALLIC_TEXTURE_TYPE: TextureType = TextureType::R8Unorm;
+    pub const ROUGHNESS_TEXTURE_TYPE: TextureType = TextureType::R8Unorm;
 
     fn generate_bind_group(&mut self) {
         let graphics_arc = self
@@ -544,6 +554,20 @@ impl Material {
     pub fn roughness(&self) -> &Either<f32, Asset<TextureAsset<{ Self::ROUGHNESS_TEXTURE_TYPE }>>> {
         &self.roughness
     }
+
+    /// Whether any of the material's textures are the given asset.
+    pub fn uses_asset(&self, asset_id: AssetID) -> bool {
+        self.albedo.1.as_ref().is_some_and(|x| x.id() == asset_id)
+            || self.normal.as_ref().is_some_and(|x| x.id() == asset_id)
+            || self.metallic.right().is_some_and(|x| x.id() == asset_id)
+            || self.roughness.right().is_some_and(|x| x.id() == asset_id)
+    }
+
+    /// Rebuilds the bind group. The bind group holds on to the texture views it was created with,
+    /// so this has to be called after one of the material's textures is reloaded.
+    pub fn refresh(&mut self) {
+        self.generate_bind_group();
+    }
 }
 
 // Material bind group layout.
@@ -825,9 +849,28 @@ impl AssetTrait for Material {
     fn imported_fs_type() -> AssetFileSystemType {
         AssetFileSystemType::File
     }
+
+    /// Unversioned materials are the same RON.
+    fn migrate(
+        version: u32,
+        payload: Vec<u8>,
+        _asset_info: &cobalt_assets::manifest::AssetInfo,
+    ) -> Result<Vec<u8>, AssetReadError> {
+        match version {
+            0 => Ok(payload),
+            _ => Err(AssetReadError::UnsupportedVersion(<Self as AssetTrait>::type_name(), version)),
+        }
+    }
     
-    #[allow(unused_variables)]
-    fn read(asset_info: &cobalt_assets::manifest::AssetInfo, assets_dir: &std::path::Path, graphics: &Graphics) -> Result<Self, cobalt_assets::asset::AssetReadError> {
-        todo!()
+    /// Packed materials are `MaterialDescriptor`s in RON, their textures are dependencies.
+    fn read(
+        asset_info: &cobalt_assets::manifest::AssetInfo,
+        source: &dyn cobalt_assets::source::AssetSource,
+        context: &AssetReadContext,
+    ) -> Result<Self, AssetReadError> {
+        let descriptor: MaterialDescriptor =
+            read_ron::<Self, _>(source, &asset_info.relative_path, asset_info)?;
+
+        descriptor.build(context.graphics, context)
     }
 }
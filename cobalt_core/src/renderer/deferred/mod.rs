@@ -30,6 +30,7 @@ use super::{
 
 pub mod exports {
     pub use super::material::Material;
+    pub use super::material::descriptor::MaterialDescriptor;
     pub use super::passes::geometry_debug::GeometryPassDebugMode;
     pub use super::DeferredRenderer as Renderer;
 }
@@ -8,7 +8,7 @@ use crate::{
     },
 };
 use cobalt_graphics::{
-    context::Graphics, vertex::UvNormalVertex, CreateBindGroup, HasBindGroup,
+    context::Graphics, vertex::UvNormalTangentVertex, CreateBindGroup, HasBindGroup,
     HasBindGroupLayout, HasVertexBufferLayout,
 };
 
@@ -48,7 +48,7 @@ impl GeometryPass {
                 vertex: wgpu::VertexState {
                     module: &shader,
                     entry_point: "vs_main",
-                    buffers: &[UvNormalVertex::vertex_buffer_layout()],
+                    buffers: &[UvNormalTangentVertex::vertex_buffer_layout()],
                     compilation_options: Default::default(),
                 },
                 fragment: Some(wgpu::FragmentState {
@@ -70,6 +70,8 @@ struct VertexInput {
     @location(0) position: vec3f,
     @location(1) tex_coords: vec2f,
     @location(2) normal: vec3f,
+    /// w is the handedness of the bitangent
+    @location(3) tangent: vec4f,
 }
 
 struct VertexOutput {
@@ -78,6 +80,8 @@ struct VertexOutput {
     /// Normalised vertex normal
     @location(1) normal: vec3f,
     @location(2) world_position: vec3f,
+    /// World space tangent, w is the handedness of the bitangent
+    @location(3) tangent: vec4f,
 }
 
 @vertex
@@ -91,6 +95,7 @@ fn vs_main(input: VertexInput) -> VertexOutput {
     // Truncated model matrix to 3x3 matrix
     let truncated_model: mat3x3<f32> = mat3x3<f32>(u_model[0].xyz, u_model[1].xyz, u_model[2].xyz);
     output.normal = normalize(truncated_model * input.normal);
+    output.tangent = vec4f(truncated_model * input.tangent.xyz, input.tangent.w);
 
     return output;
 }
@@ -117,17 +122,23 @@ fn fs_main(input: VertexOutput) -> FragmentOutput {
 
     let normal_texture = textureSample(u_normal_texture, u_normal_sampler, input.tex_coords);
 
-    // If the normal texture is not supplied, the normal will be white so vec3(1, 1, 1).
-    let tangent_space_fragment_normal = normalize(normal_texture * 2.0 - 1.0);
+    let vertex_normal = normalize(input.normal);
 
-    // Convert the tangent space normal to world space.
-    // TODO: After implementing tangents and bitangents, we can use them to calculate the world space normal.
-    // Then comes normal mapping 
-    
-    output.normal = vec4(normalize(input.normal), 1.0);
-    
-    // + textureSample(u_normal_texture, u_normal_sampler, input.tex_coords);
+    // Gram-Schmidt, the interpolated tangent is no longer perpendicular to the normal.
+    // Meshes without UVs can have a zero tangent, they never have a normal texture though.
+    let tangent = normalize(input.tangent.xyz - vertex_normal * dot(vertex_normal, input.tangent.xyz));
+    let bitangent = cross(vertex_normal, tangent) * input.tangent.w;
+    let tbn = mat3x3<f32>(tangent, bitangent, vertex_normal);
 
+    // Convert the tangent space normal to world space.
+    // Z is rebuilt from X and Y, BC5 normal maps only store two channels.
+    let normal_xy = normal_texture.xy * 2.0 - 1.0;
+    let normal_z = sqrt(max(1.0 - dot(normal_xy, normal_xy), 0.0));
+    let tangent_space_fragment_normal = vec3(normal_xy, normal_z);
+    let mapped_normal = normalize(tbn * tangent_space_fragment_normal);
+
+    // Sampling has to stay outside of branches, so pick the normal with select.
+    output.normal = vec4(select(vertex_normal, mapped_normal, u_normal_supplied != 0u), 1.0);
 
     // If the texture is not supplied, the texture will be white so this is fine.
     // If the value is not supplied, the value will be 1 by default so this is fine.
@@ -54,6 +54,8 @@ impl<'a, M: ResourceTrait + AssetTrait + Ord + Component> FrameData<'a, M> {
             // TODO: Normal matrix being calculated every frame, is this necessary?
             transform.calculate_normal_matrix(proj_view.view());
 
+            renderable.select_lod(transform, proj_view.proj(), camera_pos);
+
             let render_data = RenderData {
                 renderable,
                 transform,
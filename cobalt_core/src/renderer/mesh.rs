@@ -7,16 +7,30 @@ pub struct Mesh{
     /// Buffer of NormalUvVertex
     pub(crate) vertex_buffer: wgpu::Buffer,
     pub(crate) index_buffer: wgpu::Buffer,
+    pub(crate) index_format: wgpu::IndexFormat,
     pub(crate) num_indices: u32,
     pub(crate) local_aabb: AABB,
     pub(crate) has_uv: bool,
+    /// Simplified levels after the base mesh, in the same index buffer.
+    pub(crate) lods: Vec<MeshLod>,
+}
+
+/// A simplified level of a mesh, a range of the mesh's index buffer.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct MeshLod {
+    pub first_index: u32,
+    pub num_indices: u32,
+    /// The level is used once the mesh covers at most this fraction of the screen height.
+    pub screen_size: f32,
 }
 
 impl Debug for Mesh {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Mesh")
+            .field("index_format", &self.index_format)
             .field("num_indices", &self.num_indices)
             .field("has_uv", &self.has_uv)
+            .field("lods", &self.lods)
             .finish()
     }
 }
@@ -25,16 +39,33 @@ impl Mesh {
     pub fn new(
         vertex_buffer: wgpu::Buffer,
         index_buffer: wgpu::Buffer,
+        index_format: wgpu::IndexFormat,
         num_indices: u32,
         local_aabb: AABB,
         has_uv: bool,
+        lods: Vec<MeshLod>,
     ) -> Self {
         Self {
             vertex_buffer,
             index_buffer,
+            index_format,
             num_indices,
             local_aabb,
             has_uv,
+            lods,
+        }
+    }
+
+    pub fn lods(&self) -> &[MeshLod] {
+        &self.lods
+    }
+
+    /// Range of the index buffer to draw for `lod`, 0 being the base mesh.
+    /// Levels past the last one use the last one.
+    pub fn lod_indices(&self, lod: usize) -> std::ops::Range<u32> {
+        match lod.checked_sub(1).and_then(|i| self.lods.get(i).or(self.lods.last())) {
+            Some(level) => level.first_index..level.first_index + level.num_indices,
+            None => 0..self.num_indices,
         }
     }
 }
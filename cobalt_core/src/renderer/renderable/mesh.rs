@@ -7,6 +7,10 @@ use super::RenderableTrait;
 pub struct MeshRenderable {
     pub mesh: Asset<Mesh>,
     pub(crate) local_space_aabb: AABB,
+    /// Overrides the screen sizes of the mesh's LOD levels for this renderable.
+    pub lod_screen_sizes: Option<Vec<f32>>,
+    /// LOD level picked for the current frame, 0 is the base mesh.
+    pub(crate) lod: usize,
 }
 
 impl MeshRenderable {
@@ -16,8 +20,40 @@ impl MeshRenderable {
         Self {
             mesh,
             local_space_aabb,
+            lod_screen_sizes: None,
+            lod: 0,
         }
     }
+
+    /// LOD level used for the current frame, 0 is the base mesh.
+    pub fn lod(&self) -> usize {
+        self.lod
+    }
+
+    /// Picks the least detailed level whose screen size is at least `screen_size`,
+    /// the fraction of the screen height the mesh covers.
+    pub(crate) fn select_lod(&mut self, screen_size: f32) {
+        let mesh = self.mesh.borrow();
+
+        self.lod = 0;
+
+        for (i, level) in mesh.lods().iter().enumerate() {
+            let threshold = self
+                .lod_screen_sizes
+                .as_ref()
+                .and_then(|screen_sizes| screen_sizes.get(i).copied())
+                .unwrap_or(level.screen_size);
+
+            if screen_size <= threshold {
+                self.lod = i + 1;
+            }
+        }
+    }
+
+    /// Re-reads the local AABB from the mesh asset, needed after the mesh is reloaded.
+    pub fn refresh_aabb(&mut self) {
+        self.local_space_aabb = self.mesh.borrow().local_aabb.clone();
+    }
 }
 
 impl RenderableTrait for MeshRenderable {
@@ -25,7 +61,7 @@ impl RenderableTrait for MeshRenderable {
         let mesh_asset = unsafe { self.mesh.borrow_unsafe() };
 
         render_pass.set_vertex_buffer(0, mesh_asset.vertex_buffer.slice(..));
-        render_pass.set_index_buffer(mesh_asset.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-        render_pass.draw_indexed(0..mesh_asset.num_indices, 0, 0..1);
+        render_pass.set_index_buffer(mesh_asset.index_buffer.slice(..), mesh_asset.index_format);
+        render_pass.draw_indexed(mesh_asset.lod_indices(self.lod), 0, 0..1);
     }
 }
@@ -1,8 +1,8 @@
-use cobalt_assets::{asset::Asset, server::AssetServer};
+use cobalt_assets::asset::AssetDeContext;
 use cobalt_ecs::exports::Component;
 use cobalt_graphics::context::Graphics;
-use serde::{Deserialize, Serialize};
-use crate::{components::transform::Transform, types::{aabb::AABB, either::Either, resource::Resource}};
+use serde::{de::{EnumAccess, VariantAccess}, Deserialize};
+use crate::{components::transform::Transform, renderer::mesh::Mesh, types::aabb::AABB};
 
 use super::deferred::exports::Material;
 
@@ -17,20 +17,62 @@ pub enum Renderable {
     Mesh(mesh::MeshRenderable),
 }
 
+/// Variant tags for deserializing `Renderable`.
+#[derive(Deserialize)]
+enum RenderableVariant {
+    Plane,
+    Mesh,
+}
+
+struct RenderableVisitor<'a> {
+    context: AssetDeContext<'a>,
+}
+
+impl<'de, 'a> serde::de::Visitor<'de> for RenderableVisitor<'a> {
+    type Value = Renderable;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a renderable")
+    }
+
+    fn visit_enum<A>(self, data: A) -> Result<Self::Value, A::Error>
+    where
+        A: EnumAccess<'de> {
+        let (variant, access) = data.variant::<RenderableVariant>()?;
+
+        match variant {
+            RenderableVariant::Plane => {
+                access.unit_variant()?;
+
+                Ok(Renderable::Plane(plane::Plane::new()))
+            }
+            RenderableVariant::Mesh => {
+                let mesh = access.newtype_variant_seed(self.context.seed::<Mesh>())?;
+
+                Ok(Renderable::Mesh(mesh::MeshRenderable::new(mesh)))
+            }
+        }
+    }
+}
+
+/// Planes serialize as a unit variant, meshes as their mesh handle.
 impl Component for Renderable {
-    type DeContext<'a> = (&'a Graphics, &'a mut AssetServer);
+    type DeContext<'a> = AssetDeContext<'a>;
     type SerContext<'a> = ();
 
     fn deserialise<'de, D>(context: Self::DeContext<'de>, deserializer: D) -> Result<Self, D::Error>
     where
         D: serde::Deserializer<'de> {
-        todo!()
+        deserializer.deserialize_enum("Renderable", &["Plane", "Mesh"], RenderableVisitor { context })
     }
 
-    fn serialize<'se, S>(&self, context: Self::SerContext<'se>, serializer: S) -> Result<S::Ok, S::Error>
+    fn serialize<'se, S>(&self, _context: Self::SerContext<'se>, serializer: S) -> Result<S::Ok, S::Error>
         where
             S: serde::Serializer {
-        todo!()
+        match self {
+            Self::Plane(_) => serializer.serialize_unit_variant("Renderable", 0, "Plane"),
+            Self::Mesh(mesh) => serializer.serialize_newtype_variant("Renderable", 1, "Mesh", &mesh.mesh),
+        }
     }
 }
 
@@ -66,4 +108,21 @@ impl Renderable {
 
         world_space_aabb
     }
+
+    /// Picks the LOD level of meshes from the projected size of their world space AABB.
+    pub(crate) fn select_lod(
+        &mut self,
+        transform: &mut Transform,
+        proj: &ultraviolet::Mat4,
+        camera_position: ultraviolet::Vec3,
+    ) {
+        if let Self::Mesh(mesh) = self {
+            let screen_size = mesh
+                .local_space_aabb
+                .multiply_by_matrix(transform.model_matrix())
+                .projected_screen_size(proj, camera_position);
+
+            mesh.select_lod(screen_size);
+        }
+    }
 }
@@ -3,7 +3,7 @@ use std::sync::LazyLock;
 use wgpu::util::DeviceExt;
 
 use crate::types::aabb::AABB;
-use cobalt_graphics::{context::Graphics, vertex::UvNormalVertex};
+use cobalt_graphics::{context::Graphics, vertex::UvNormalTangentVertex};
 
 use super::RenderableTrait;
 
@@ -22,26 +22,31 @@ impl Plane {
 fn create_sprite_vertex_buffer(device: &wgpu::Device) -> wgpu::Buffer {
     device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
         label: None,
+        // v increases upwards here, so the bitangent (towards decreasing v) is -y
         contents: bytemuck::cast_slice(&[
-            UvNormalVertex {
+            UvNormalTangentVertex {
                 position: [-0.5, -0.5, 0.0],
                 uv: [0.0, 0.0],
                 normal: [0.0, 0.0, 1.0],
+                tangent: [1.0, 0.0, 0.0, -1.0],
             },
-            UvNormalVertex {
+            UvNormalTangentVertex {
                 position: [0.5, -0.5, 0.0],
                 uv: [1.0, 0.0],
                 normal: [0.0, 0.0, 1.0],
+                tangent: [1.0, 0.0, 0.0, -1.0],
             },
-            UvNormalVertex {
+            UvNormalTangentVertex {
                 position: [0.5, 0.5, 0.0],
                 uv: [1.0, 1.0],
                 normal: [0.0, 0.0, 1.0],
+                tangent: [1.0, 0.0, 0.0, -1.0],
             },
-            UvNormalVertex {
+            UvNormalTangentVertex {
                 position: [-0.5, 0.5, 0.0],
                 uv: [0.0, 1.0],
                 normal: [0.0, 0.0, 1.0],
+                tangent: [1.0, 0.0, 0.0, -1.0],
             },
         ]),
         usage: wgpu::BufferUsages::VERTEX,
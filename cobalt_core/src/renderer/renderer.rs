@@ -1,9 +1,13 @@
 use downcast::{downcast, Any};
 
-use crate::exports::ecs::{Entity, World};
+use crate::{
+    assets::asset::{Asset, AssetID},
+    exports::ecs::{Entity, World},
+    types::resource::Resource,
+};
 use cobalt_graphics::{context::Graphics, frame::Frame};
 
-use super::{deferred::exports::Material, FrameData};
+use super::{deferred::exports::Material, renderable::Renderable, FrameData};
 
 pub type CreateRendererClosure =
     fn(&Graphics, (u32, u32)) -> Result<Box<dyn Renderer>, RendererError>;
@@ -52,6 +56,37 @@ pub trait Renderer: Any {
 
     fn get_current_output_size(&self) -> (u32, u32);
 
+    /// Updates the render state that depends on an asset after it was reloaded.
+    /// The engine calls this for every `AssetEventKind::Modified` event.
+    fn on_asset_modified(&mut self, world: &mut World, asset_id: AssetID) {
+        // Bind groups keep the old texture views alive, they have to be rebuilt.
+        if let Ok(query) = world.query_mut::<Resource<Material>>() {
+            for (_, material) in query {
+                if material.borrow().uses_asset(asset_id) {
+                    material.borrow_mut().refresh();
+                }
+            }
+        }
+
+        if let Ok(query) = world.query_mut::<Asset<Material>>() {
+            for (_, material) in query {
+                if material.borrow().uses_asset(asset_id) {
+                    material.borrow_mut().refresh();
+                }
+            }
+        }
+
+        if let Ok(query) = world.query_mut::<Renderable>() {
+            for (_, renderable) in query {
+                if let Renderable::Mesh(mesh) = renderable {
+                    if mesh.mesh.id() == asset_id {
+                        mesh.refresh_aabb();
+                    }
+                }
+            }
+        }
+    }
+
     fn name(&self) -> &str {
         std::any::type_name::<Self>()
     }
@@ -25,15 +25,16 @@ impl AABB {
         let mut min = Vec4::broadcast(f32::INFINITY);
         let mut max = Vec4::broadcast(f32::NEG_INFINITY);
         
+        // Corners are points, w = 1 so the translation is applied
         let corners = [
-            self.min,
-            Vec4::new(self.min.x, self.min.y, self.max.z, 0.0),
-            Vec4::new(self.min.x, self.max.y, self.min.z, 0.0),
-            Vec4::new(self.min.x, self.max.y, self.max.z, 0.0),
-            Vec4::new(self.max.x, self.min.y, self.min.z, 0.0),
-            Vec4::new(self.max.x, self.min.y, self.max.z, 0.0),
-            Vec4::new(self.max.x, self.max.y, self.min.z, 0.0),
-            self.max,
+            Vec4::new(self.min.x, self.min.y, self.min.z, 1.0),
+            Vec4::new(self.min.x, self.min.y, self.max.z, 1.0),
+            Vec4::new(self.min.x, self.max.y, self.min.z, 1.0),
+            Vec4::new(self.min.x, self.max.y, self.max.z, 1.0),
+            Vec4::new(self.max.x, self.min.y, self.min.z, 1.0),
+            Vec4::new(self.max.x, self.min.y, self.max.z, 1.0),
+            Vec4::new(self.max.x, self.max.y, self.min.z, 1.0),
+            Vec4::new(self.max.x, self.max.y, self.max.z, 1.0),
         ];
 
         for corner in corners.iter() {
@@ -49,4 +50,59 @@ impl AABB {
     pub fn get_center(&self) -> ultraviolet::Vec3 {
         (self.min.xyz() + self.max.xyz()) / 2.0
     }
+
+    /// Fraction of the screen height covered by the bounding sphere of the AABB,
+    /// seen from `camera_position` through `proj`. 1 or more fills the screen.
+    pub fn projected_screen_size(&self, proj: &Mat4, camera_position: ultraviolet::Vec3) -> f32 {
+        let radius = (self.max.xyz() - self.min.xyz()).mag() / 2.0;
+
+        // cot(fov_y / 2) for perspective projections, 2 / height for orthographic ones
+        let scale = proj.cols[1].y;
+
+        // Orthographic projections keep w = 1
+        if proj.cols[3].w == 1.0 {
+            return radius * scale;
+        }
+
+        let distance = (self.get_center() - camera_position).mag();
+
+        if distance <= radius {
+            return f32::INFINITY;
+        }
+
+        radius * scale / distance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transform_applies_translation() {
+        let aabb = AABB::from_min_max([-1.0, -1.0, -1.0].into(), [1.0, 1.0, 1.0].into());
+
+        let moved =
+            aabb.multiply_by_matrix(&Mat4::from_translation([10.0, 0.0, 0.0].into()));
+
+        assert_eq!(moved.get_center(), [10.0, 0.0, 0.0].into());
+    }
+
+    #[test]
+    fn screen_size_shrinks_with_distance() {
+        let proj = ultraviolet::projection::perspective_wgpu_dx(
+            std::f32::consts::FRAC_PI_2,
+            1.0,
+            0.1,
+            100.0,
+        );
+        let aabb = AABB::from_min_max([-1.0, -1.0, -1.0].into(), [1.0, 1.0, 1.0].into());
+
+        let near = aabb.projected_screen_size(&proj, [0.0, 0.0, 10.0].into());
+        let far = aabb.projected_screen_size(&proj, [0.0, 0.0, 20.0].into());
+
+        assert!((near - 3.0f32.sqrt() / 10.0).abs() < 1e-5);
+        assert!((far - near / 2.0).abs() < 1e-5);
+        assert_eq!(aabb.projected_screen_size(&proj, [0.0, 0.0, 0.0].into()), f32::INFINITY);
+    }
 }
\ No newline at end of file
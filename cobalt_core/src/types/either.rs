@@ -25,6 +25,14 @@ impl<'de, A: serde::Deserialize<'de>, B: serde::Deserialize<'de>> serde::Deseria
     where
         D: serde::de::Deserializer<'de>,
     {
+        // Variant names are identifiers, not strings, in formats like RON.
+        #[derive(serde::Deserialize)]
+        #[serde(variant_identifier)]
+        enum Variant {
+            Left,
+            Right,
+        }
+
         struct EitherVisitor<A, B> {
             _marker: std::marker::PhantomData<(A, B)>,
         }
@@ -40,17 +48,16 @@ impl<'de, A: serde::Deserialize<'de>, B: serde::Deserialize<'de>> serde::Deseria
             where
                 C: serde::de::EnumAccess<'de>,
             {
-                let (variant, value) = data.variant::<&str>()?;
+                let (variant, value) = data.variant::<Variant>()?;
                 match variant {
-                    "Left" => {
+                    Variant::Left => {
                         let value = value.newtype_variant::<A>()?;
                         Ok(Either::Left(value))
                     }
-                    "Right" => {
+                    Variant::Right => {
                         let value = value.newtype_variant::<B>()?;
                         Ok(Either::Right(value))
                     }
-                    _ => Err(serde::de::Error::unknown_variant(variant, &["Left", "Right"])),
                 }
             }
         }
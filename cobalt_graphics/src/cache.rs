@@ -1,6 +1,6 @@
 use std::sync::OnceLock;
 
-use super::texture::{Texture, TextureType};
+use super::texture::{Texture, TextureKind, TextureType};
 
 /// Storage for various items that are initialized once, static, and reused.
 pub struct GraphicsCache {
@@ -47,30 +47,31 @@ impl BindGroupLayoutCache {
     }
 }
 
+/// Layouts of each texture type, indexed by `TextureKind::index`.
 pub struct TexturesBindGroupLayoutCache {
-    pub rgba32_float: OnceLock<wgpu::BindGroupLayout>,
-    pub rgba16_float: OnceLock<wgpu::BindGroupLayout>,
-    pub rgba8_unorm: OnceLock<wgpu::BindGroupLayout>,
-    pub rgba8_unorm_srgb: OnceLock<wgpu::BindGroupLayout>,
-    pub r32_float: OnceLock<wgpu::BindGroupLayout>,
-    pub r16_float: OnceLock<wgpu::BindGroupLayout>,
-    pub r8_unorm: OnceLock<wgpu::BindGroupLayout>,
-    pub r8_uint: OnceLock<wgpu::BindGroupLayout>,
-    pub r8_snorm: OnceLock<wgpu::BindGroupLayout>,
+    pub rgba32_float: [OnceLock<wgpu::BindGroupLayout>; TextureKind::COUNT],
+    pub rgba16_float: [OnceLock<wgpu::BindGroupLayout>; TextureKind::COUNT],
+    pub rgba8_unorm: [OnceLock<wgpu::BindGroupLayout>; TextureKind::COUNT],
+    pub rgba8_unorm_srgb: [OnceLock<wgpu::BindGroupLayout>; TextureKind::COUNT],
+    pub r32_float: [OnceLock<wgpu::BindGroupLayout>; TextureKind::COUNT],
+    pub r16_float: [OnceLock<wgpu::BindGroupLayout>; TextureKind::COUNT],
+    pub r8_unorm: [OnceLock<wgpu::BindGroupLayout>; TextureKind::COUNT],
+    pub r8_uint: [OnceLock<wgpu::BindGroupLayout>; TextureKind::COUNT],
+    pub r8_snorm: [OnceLock<wgpu::BindGroupLayout>; TextureKind::COUNT],
 }
 
 impl TexturesBindGroupLayoutCache {
     pub fn new() -> Self {
         Self {
-            rgba32_float: OnceLock::new(),
-            rgba16_float: OnceLock::new(),
-            rgba8_unorm: OnceLock::new(),
-            rgba8_unorm_srgb: OnceLock::new(),
-            r32_float: OnceLock::new(),
-            r16_float: OnceLock::new(),
-            r8_unorm: OnceLock::new(),
-            r8_uint: OnceLock::new(),
-            r8_snorm: OnceLock::new(),
+            rgba32_float: Default::default(),
+            rgba16_float: Default::default(),
+            rgba8_unorm: Default::default(),
+            rgba8_unorm_srgb: Default::default(),
+            r32_float: Default::default(),
+            r16_float: Default::default(),
+            r8_unorm: Default::default(),
+            r8_uint: Default::default(),
+            r8_snorm: Default::default(),
         }
     }
 }
@@ -55,7 +55,9 @@ impl Graphics {
                 &wgpu::DeviceDescriptor {
                     label: None,
                     // TODO: Disable this feature when wireframe is not used, release builds
-                    required_features: wgpu::Features::POLYGON_MODE_LINE,
+                    // Block compressed textures are decompressed on load when BC is unsupported
+                    required_features: wgpu::Features::POLYGON_MODE_LINE
+                        | (adapter.features() & wgpu::Features::TEXTURE_COMPRESSION_BC),
                     // Make sure we use the texture resolution limits from the adapter, so we can support images the size of the swapchain.
                     required_limits: wgpu::Limits::downlevel_defaults()
                         .using_resolution(adapter.limits()),
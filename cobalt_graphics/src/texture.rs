@@ -45,6 +45,21 @@ impl TextureType {
         }
     }
 
+    /// Whether the texture can be sampled with filtering, the bind group layout depends on it.
+    pub fn is_filterable(&self) -> bool {
+        match self {
+            TextureType::RGBA32Float
+            | TextureType::RGBA16Float
+            | TextureType::RGBA8Unorm
+            | TextureType::RGBA8UnormSrgb => true,
+            TextureType::R32Float
+            | TextureType::R16Float
+            | TextureType::R8Unorm
+            | TextureType::R8Uint
+            | TextureType::R8Snorm => false,
+        }
+    }
+
     pub fn variants() -> Vec<Self> {
         vec![
             TextureType::RGBA32Float,
@@ -186,6 +201,244 @@ impl std::fmt::Display for TextureType {
     }
 }
 
+/// Shape of a texture, independent of its `TextureType`.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Default, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum TextureKind {
+    #[default]
+    D2,
+    /// Layers of the same size, sampled with a layer index.
+    D2Array,
+    /// Six square faces in the order +X, -X, +Y, -Y, +Z, -Z, sampled with a direction.
+    Cube,
+    /// Depth slices, sampled with a 3D coordinate. Used for colour grading LUTs.
+    D3,
+}
+
+impl TextureKind {
+    pub const COUNT: usize = 4;
+
+    pub fn index(&self) -> usize {
+        match self {
+            TextureKind::D2 => 0,
+            TextureKind::D2Array => 1,
+            TextureKind::Cube => 2,
+            TextureKind::D3 => 3,
+        }
+    }
+
+    pub fn dimension(&self) -> wgpu::TextureDimension {
+        match self {
+            TextureKind::D3 => wgpu::TextureDimension::D3,
+            _ => wgpu::TextureDimension::D2,
+        }
+    }
+
+    pub fn view_dimension(&self) -> wgpu::TextureViewDimension {
+        match self {
+            TextureKind::D2 => wgpu::TextureViewDimension::D2,
+            TextureKind::D2Array => wgpu::TextureViewDimension::D2Array,
+            TextureKind::Cube => wgpu::TextureViewDimension::Cube,
+            TextureKind::D3 => wgpu::TextureViewDimension::D3,
+        }
+    }
+}
+
+/// GPU block compression formats a texture can be stored in.
+/// The texture keeps its `TextureType` for binding, only the storage format changes.
+/// These aren't `TextureType`s because a `Texture<T>` has to stay usable as `T` when
+/// the adapter can't sample them and they're decompressed on load.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockCompression {
+    /// RGB with 1 bit alpha, 8 bytes per block.
+    Bc1,
+    /// RGBA with interpolated alpha, 16 bytes per block.
+    Bc3,
+    /// Single channel, 8 bytes per block.
+    Bc4,
+    /// Two channels, 16 bytes per block. Used for normal maps.
+    Bc5,
+    /// High quality RGBA, 16 bytes per block.
+    Bc7,
+}
+
+impl BlockCompression {
+    /// Width and height of a block in texels.
+    pub const BLOCK_DIMENSION: u32 = 4;
+
+    pub fn bytes_per_block(&self) -> usize {
+        match self {
+            BlockCompression::Bc1 | BlockCompression::Bc4 => 8,
+            BlockCompression::Bc3 | BlockCompression::Bc5 | BlockCompression::Bc7 => 16,
+        }
+    }
+
+    /// Size of the block data of an image, rounded up to whole blocks.
+    pub fn data_size(&self, width: u32, height: u32) -> usize {
+        let blocks_wide = width.div_ceil(Self::BLOCK_DIMENSION) as usize;
+        let blocks_high = height.div_ceil(Self::BLOCK_DIMENSION) as usize;
+
+        blocks_wide * blocks_high * self.bytes_per_block()
+    }
+
+    pub fn format(&self, srgb: bool) -> wgpu::TextureFormat {
+        match (self, srgb) {
+            (BlockCompression::Bc1, false) => wgpu::TextureFormat::Bc1RgbaUnorm,
+            (BlockCompression::Bc1, true) => wgpu::TextureFormat::Bc1RgbaUnormSrgb,
+            (BlockCompression::Bc3, false) => wgpu::TextureFormat::Bc3RgbaUnorm,
+            (BlockCompression::Bc3, true) => wgpu::TextureFormat::Bc3RgbaUnormSrgb,
+            (BlockCompression::Bc4, _) => wgpu::TextureFormat::Bc4RUnorm,
+            (BlockCompression::Bc5, _) => wgpu::TextureFormat::Bc5RgUnorm,
+            (BlockCompression::Bc7, false) => wgpu::TextureFormat::Bc7RgbaUnorm,
+            (BlockCompression::Bc7, true) => wgpu::TextureFormat::Bc7RgbaUnormSrgb,
+        }
+    }
+
+    pub fn from_format(format: wgpu::TextureFormat) -> Option<Self> {
+        match format {
+            wgpu::TextureFormat::Bc1RgbaUnorm | wgpu::TextureFormat::Bc1RgbaUnormSrgb => {
+                Some(BlockCompression::Bc1)
+            }
+            wgpu::TextureFormat::Bc3RgbaUnorm | wgpu::TextureFormat::Bc3RgbaUnormSrgb => {
+                Some(BlockCompression::Bc3)
+            }
+            wgpu::TextureFormat::Bc4RUnorm => Some(BlockCompression::Bc4),
+            wgpu::TextureFormat::Bc5RgUnorm => Some(BlockCompression::Bc5),
+            wgpu::TextureFormat::Bc7RgbaUnorm | wgpu::TextureFormat::Bc7RgbaUnormSrgb => {
+                Some(BlockCompression::Bc7)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl TextureType {
+    /// The block compressed format for textures of this type, if `compression` suits it.
+    /// Single channel types only take BC4. 8 bit colour types take BC1, BC3 and BC7, and BC5 unless
+    /// they are sRGB, as BC5 has no sRGB format. RGBA16Float only takes BC5, for normal maps, the
+    /// others would clamp it to 0..1 at 8 bits.
+    pub fn block_compressed_format(
+        &self,
+        compression: BlockCompression,
+    ) -> Option<wgpu::TextureFormat> {
+        match (self, compression) {
+            (TextureType::R8Unorm, BlockCompression::Bc4)
+            | (TextureType::RGBA8Unorm | TextureType::RGBA16Float, BlockCompression::Bc5) => {
+                Some(compression.format(false))
+            }
+            (
+                TextureType::RGBA8Unorm | TextureType::RGBA8UnormSrgb,
+                BlockCompression::Bc1 | BlockCompression::Bc3 | BlockCompression::Bc7,
+            ) => Some(compression.format(*self == TextureType::RGBA8UnormSrgb)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TextureFilter {
+    Nearest,
+    #[default]
+    Linear,
+}
+
+impl From<TextureFilter> for wgpu::FilterMode {
+    fn from(filter: TextureFilter) -> Self {
+        match filter {
+            TextureFilter::Nearest => wgpu::FilterMode::Nearest,
+            TextureFilter::Linear => wgpu::FilterMode::Linear,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TextureAddressMode {
+    #[default]
+    Repeat,
+    MirrorRepeat,
+    ClampToEdge,
+}
+
+impl From<TextureAddressMode> for wgpu::AddressMode {
+    fn from(address_mode: TextureAddressMode) -> Self {
+        match address_mode {
+            TextureAddressMode::Repeat => wgpu::AddressMode::Repeat,
+            TextureAddressMode::MirrorRepeat => wgpu::AddressMode::MirrorRepeat,
+            TextureAddressMode::ClampToEdge => wgpu::AddressMode::ClampToEdge,
+        }
+    }
+}
+
+/// How a texture is sampled, stored per texture asset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct SamplerSettings {
+    pub mag_filter: TextureFilter,
+    pub min_filter: TextureFilter,
+    /// Filter between mip levels.
+    pub mipmap_filter: TextureFilter,
+    pub address_mode_u: TextureAddressMode,
+    pub address_mode_v: TextureAddressMode,
+    pub address_mode_w: TextureAddressMode,
+    /// Maximum anisotropy, 1 to 16. Only used when every filter is linear.
+    pub anisotropy: u16,
+}
+
+impl Default for SamplerSettings {
+    fn default() -> Self {
+        Self {
+            mag_filter: TextureFilter::Linear,
+            min_filter: TextureFilter::Linear,
+            mipmap_filter: TextureFilter::Linear,
+            address_mode_u: TextureAddressMode::Repeat,
+            address_mode_v: TextureAddressMode::Repeat,
+            address_mode_w: TextureAddressMode::Repeat,
+            anisotropy: 1,
+        }
+    }
+}
+
+impl SamplerSettings {
+    /// Non filterable textures are always sampled with nearest filtering.
+    pub fn create_sampler(&self, device: &wgpu::Device, filterable: bool) -> wgpu::Sampler {
+        let filter = |filter: TextureFilter| match filterable {
+            true => filter.into(),
+            false => wgpu::FilterMode::Nearest,
+        };
+
+        let mag_filter = filter(self.mag_filter);
+        let min_filter = filter(self.min_filter);
+        let mipmap_filter = filter(self.mipmap_filter);
+
+        // wgpu only allows anisotropy with linear filtering
+        let all_linear = [mag_filter, min_filter, mipmap_filter]
+            .iter()
+            .all(|filter| *filter == wgpu::FilterMode::Linear);
+
+        let anisotropy_clamp = match all_linear {
+            true => self.anisotropy.clamp(1, 16),
+            false => 1,
+        };
+
+        device.create_sampler(&wgpu::SamplerDescriptor {
+            label: None,
+            address_mode_u: self.address_mode_u.into(),
+            address_mode_v: self.address_mode_v.into(),
+            address_mode_w: self.address_mode_w.into(),
+            mag_filter,
+            min_filter,
+            mipmap_filter,
+            anisotropy_clamp,
+            ..Default::default()
+        })
+    }
+}
+
 #[derive(Debug)]
 pub enum GenericTexture {
     RGBA32Float(Texture<{ TextureType::RGBA32Float }>),
@@ -203,6 +456,8 @@ pub struct Texture<const T: TextureType> {
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
     pub sampler: wgpu::Sampler,
+    pub kind: TextureKind,
+    /// `depth_or_array_layers` is the number of layers, faces or depth slices.
     pub size: wgpu::Extent3d,
     // TODO: Bind group dirty after changing texture?
     pub bind_group: wgpu::BindGroup,
@@ -211,6 +466,7 @@ pub struct Texture<const T: TextureType> {
 impl<const T: TextureType> std::fmt::Debug for Texture<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Texture")
+            .field("kind", &self.kind)
             .field("size", &self.size)
             .field("format", &T)
             .finish()
@@ -270,9 +526,78 @@ impl<const T: TextureType> TextureInternal for Texture<T> {
 }
 
 impl<const T: TextureType> Texture<T> {
+    /// Creates the view, sampler and bind group of `texture`, viewed as `kind`.
+    pub fn new(
+        graphics: &Graphics,
+        texture: wgpu::Texture,
+        kind: TextureKind,
+        size: wgpu::Extent3d,
+        sampler_settings: &SamplerSettings,
+    ) -> Self {
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(kind.view_dimension()),
+            ..Default::default()
+        });
+
+        let sampler = sampler_settings.create_sampler(&graphics.device, T.is_filterable());
+
+        let bind_group = graphics
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: None,
+                layout: &Texture::<T>::bind_group_layout(graphics, kind),
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&sampler),
+                    },
+                ],
+            });
+
+        Self {
+            texture,
+            view,
+            sampler,
+            kind,
+            size,
+            bind_group,
+        }
+    }
+
     pub fn size(&self) -> (u32, u32) {
         (self.size.width, self.size.height)
     }
+
+    /// Number of array layers, cube faces or depth slices.
+    pub fn layers(&self) -> u32 {
+        self.size.depth_or_array_layers
+    }
+
+    pub fn mip_level_count(&self) -> u32 {
+        self.texture.mip_level_count()
+    }
+
+    /// Bytes of GPU memory used by the texture's data, every mip level included.
+    pub fn gpu_memory(&self) -> usize {
+        let format = self.texture.format();
+        let (block_width, block_height) = format.block_dimensions();
+        let block_size = format.block_copy_size(None).unwrap_or(4) as usize;
+
+        (0..self.mip_level_count())
+            .map(|mip| {
+                let size = self.size.mip_level_size(mip, self.texture.dimension());
+
+                size.width.div_ceil(block_width) as usize
+                    * size.height.div_ceil(block_height) as usize
+                    * size.depth_or_array_layers as usize
+                    * block_size
+            })
+            .sum()
+    }
 }
 
 // static TEXTURE_BIND_GROUP_LAYOUT_FILTERING_FILTERABLE: LazyLock<wgpu::BindGroupLayout> =
@@ -285,6 +610,7 @@ fn create_bind_group_layout(
     device: &wgpu::Device,
     filterable: bool,
     filtering: bool,
+    view_dimension: wgpu::TextureViewDimension,
 ) -> wgpu::BindGroupLayout {
     device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
         label: None,
@@ -294,7 +620,7 @@ fn create_bind_group_layout(
                 visibility: wgpu::ShaderStages::FRAGMENT,
                 ty: wgpu::BindingType::Texture {
                     multisampled: false,
-                    view_dimension: wgpu::TextureViewDimension::D2,
+                    view_dimension,
                     sample_type: wgpu::TextureSampleType::Float { filterable },
                 },
                 count: None,
@@ -333,9 +659,10 @@ impl<const T: TextureType> HasBindGroup for Texture<T> {
     }
 }
 
-/// Create or retrieve a cached bind group layout for a texture type.
+/// Create or retrieve a cached bind group layout for a texture type and kind.
 fn texture_bind_group_layout<'a, const T: TextureType>(
     graphics: &'a Graphics,
+    kind: TextureKind,
 ) -> &'a wgpu::BindGroupLayout {
     #[rustfmt::skip]
     let layout_cache_ref = match T {
@@ -350,38 +677,29 @@ fn texture_bind_group_layout<'a, const T: TextureType>(
         TextureType::R8Snorm => &graphics.cache.bind_group_layout_cache.textures.r8_snorm,
     };
 
-    layout_cache_ref.get_or_init(|| {
-        let filterable = match T {
-            TextureType::RGBA32Float
-            | TextureType::RGBA16Float
-            | TextureType::RGBA8Unorm
-            | TextureType::RGBA8UnormSrgb => true,
-            TextureType::R32Float
-            | TextureType::R16Float
-            | TextureType::R8Unorm
-            | TextureType::R8Uint
-            | TextureType::R8Snorm => false,
-        };
-
-        let filtering = match T {
-            TextureType::RGBA32Float
-            | TextureType::RGBA16Float
-            | TextureType::RGBA8Unorm
-            | TextureType::RGBA8UnormSrgb => true,
-            TextureType::R32Float
-            | TextureType::R16Float
-            | TextureType::R8Unorm
-            | TextureType::R8Uint
-            | TextureType::R8Snorm => false,
-        };
-
-        create_bind_group_layout(&graphics.device, filterable, filtering)
+    layout_cache_ref[kind.index()].get_or_init(|| {
+        create_bind_group_layout(
+            &graphics.device,
+            T.is_filterable(),
+            T.is_filterable(),
+            kind.view_dimension(),
+        )
     })
 }
 
+/// Layout of 2D textures.
 impl<const T: TextureType> HasBindGroupLayout<()> for Texture<T> {
     fn bind_group_layout<'a>(graphics: &'a Graphics, _extra: ()) -> &'a wgpu::BindGroupLayout {
-        texture_bind_group_layout::<T>(graphics)
+        texture_bind_group_layout::<T>(graphics, TextureKind::D2)
+    }
+}
+
+impl<const T: TextureType> HasBindGroupLayout<TextureKind> for Texture<T> {
+    fn bind_group_layout<'a>(
+        graphics: &'a Graphics,
+        kind: TextureKind,
+    ) -> &'a wgpu::BindGroupLayout {
+        texture_bind_group_layout::<T>(graphics, kind)
     }
 }
 
@@ -513,6 +831,7 @@ pub(crate) fn gen_empty_texture<const T: TextureType>(graphics: &Graphics) -> Te
         texture,
         view,
         sampler,
+        kind: TextureKind::D2,
         size,
         bind_group,
     }
@@ -88,4 +88,58 @@ impl HasVertexBufferLayout for UvNormalVertex {
             ],
         }
     }
-}
\ No newline at end of file
+}
+
+/// Vertex with a tangent for normal mapping.
+/// `tangent.w` is the handedness of the bitangent, `bitangent = cross(normal, tangent.xyz) * tangent.w`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable, serde::Serialize, serde::Deserialize)]
+pub struct UvNormalTangentVertex {
+    pub position: [f32; 3],
+    pub uv: [f32; 2],
+    pub normal: [f32; 3],
+    pub tangent: [f32; 4],
+}
+
+impl From<UvNormalVertex> for UvNormalTangentVertex {
+    /// The tangent is left zeroed, it has to be generated afterwards.
+    fn from(vertex: UvNormalVertex) -> Self {
+        Self {
+            position: vertex.position,
+            uv: vertex.uv,
+            normal: vertex.normal,
+            tangent: [0.0; 4],
+        }
+    }
+}
+
+impl HasVertexBufferLayout for UvNormalTangentVertex {
+    fn vertex_buffer_layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<UvNormalTangentVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    format: wgpu::VertexFormat::Float32x3,
+                    shader_location: 0,
+                },
+                wgpu::VertexAttribute {
+                    offset: (std::mem::size_of::<[f32; 3]>()) as wgpu::BufferAddress,
+                    format: wgpu::VertexFormat::Float32x2,
+                    shader_location: 1,
+                },
+                wgpu::VertexAttribute {
+                    offset: (std::mem::size_of::<[f32; 3]>() + std::mem::size_of::<[f32; 2]>()) as wgpu::BufferAddress,
+                    format: wgpu::VertexFormat::Float32x3,
+                    shader_location: 2,
+                },
+                wgpu::VertexAttribute {
+                    offset: (std::mem::size_of::<[f32; 3]>() * 2 + std::mem::size_of::<[f32; 2]>()) as wgpu::BufferAddress,
+                    format: wgpu::VertexFormat::Float32x4,
+                    shader_location: 3,
+                },
+            ],
+        }
+    }
+}
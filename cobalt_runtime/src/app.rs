@@ -2,7 +2,7 @@ use crate::{
     engine::Engine,
     plugins::PluginManager,
 };
-use cobalt_core::input::InputEvent;
+use cobalt_core::{assets::server::UntypedAssetEvent, input::InputEvent};
 use downcast::{downcast, Any};
 
 // TODO: Return Box<dyn Error> from App calls.
@@ -31,6 +31,16 @@ pub trait App: Any {
     ) {
     }
 
+    /// Called for every asset loaded, modified, removed or failing to load, once per frame before
+    /// `on_update`. Use `UntypedAssetEvent::downcast` to handle the events of one asset type.
+    fn on_asset_event(
+        &mut self,
+        _engine: &mut Engine,
+        _plugins: &mut PluginManager,
+        _event: &UntypedAssetEvent,
+    ) {
+    }
+
     /// Called once right before the engine stops.
     fn on_stop(&mut self, _engine: &mut Engine, _plugins: &mut PluginManager) {}
 
@@ -7,7 +7,7 @@ use std::{
 use cobalt_core::{
     assets::{
         asset::{Asset, AssetID, AssetTrait},
-        server::{AssetLoadError, AssetServer},
+        server::{AssetEventKind, AssetLoadError, AssetServer, UntypedAssetEvent},
     },
     graphics::{
         context::Graphics,
@@ -97,7 +97,67 @@ impl Engine {
         let assets_weak = Arc::downgrade(&self.assets);
         self.assets
             .write()
-            .load::<T>(assets_weak, &self.graphics.read(), asset_id)
+            .load::<T>(assets_weak, &self.graphics, asset_id)
+    }
+
+    /// Checks for assets that changed on disk, reloading them.
+    fn poll_asset_changes(&mut self) {
+        let assets_weak = Arc::downgrade(&self.assets);
+
+        // Dropped once the server is unlocked, the old data can hold handles to other assets
+        let replaced = self
+            .assets
+            .write()
+            .poll_changes(assets_weak, &self.graphics);
+        drop(replaced);
+    }
+
+    /// Takes the queued asset events and updates the state that depends on modified assets.
+    /// The events are then passed on to plugins and the app.
+    fn drain_asset_events(&mut self) -> Vec<UntypedAssetEvent> {
+        let events = self.assets.write().drain_events();
+
+        for event in &events {
+            if event.kind == AssetEventKind::Modified {
+                self.renderer
+                    .lock()
+                    .on_asset_modified(&mut self.scene.world, event.asset_id);
+            }
+        }
+
+        events
+    }
+
+    /// Evicts assets over the cache budget and publishes the memory used by each asset type to `Stats`.
+    fn update_asset_cache(&mut self) {
+        const LABEL: &str = "Asset memory: ";
+
+        // Dropped once the server is unlocked, evicted assets can hold handles to other assets
+        let evicted = self.assets.write().trim_cache();
+        drop(evicted);
+
+        let memory_stats = self.assets.read().memory_stats();
+        let mut stats = Stats::global();
+
+        // Types with nothing loaded anymore
+        let stale = stats
+            .iter()
+            .filter_map(|(label, _)| label.strip_prefix(LABEL))
+            .filter(|type_name| !memory_stats.contains_key(*type_name))
+            .map(|type_name| format!("{}{}", LABEL, type_name))
+            .collect::<Vec<_>>();
+
+        for label in stale {
+            stats.remove(&label);
+        }
+
+        for (type_name, memory) in memory_stats {
+            stats.set(
+                &format!("{}{}", LABEL, type_name),
+                Stat::String(memory.to_string()),
+                false,
+            );
+        }
     }
 }
 
@@ -107,7 +167,19 @@ pub struct InitialEngineConfig {
     pub window_config: cobalt_core::graphics::window::WindowConfig,
     /// The directory where assets are stored. If None, the default ./assets/ directory will be used.
     /// This should contain the manifest file.
+    /// A path to a `.cpak` archive can be given instead, in which case the archive is mounted.
     pub assets_dir: String,
+    /// Extra directories or `.cpak` archives mounted on top of `assets_dir`, e.g. mods and patches.
+    /// Later entries take priority and override assets with the same ID.
+    pub asset_mounts: Vec<String>,
+    /// If this is `Some`, assets that change on disk will be reloaded.
+    /// The value is how often the assets directory is checked for changes.
+    /// Enabled by default in debug builds.
+    pub hot_reload: Option<Duration>,
+    /// Bytes of assets without handles to keep loaded, so they don't have to be read again if
+    /// they are used soon after, e.g. by the next level. 0 unloads assets as soon as they are unused.
+    /// See `AssetServer::set_cache_budget`.
+    pub asset_cache_budget: usize,
 }
 
 impl Default for InitialEngineConfig {
@@ -116,6 +188,13 @@ impl Default for InitialEngineConfig {
             scene: cobalt_core::scenes::scene::Scene::new("Main Scene"),
             window_config: cobalt_core::graphics::window::WindowConfig::default(),
             assets_dir: String::from("./assets/"),
+            asset_mounts: Vec::new(),
+            hot_reload: if cfg!(debug_assertions) {
+                Some(Duration::from_millis(500))
+            } else {
+                None
+            },
+            asset_cache_budget: 0,
         }
     }
 }
@@ -202,8 +281,23 @@ impl<A: App> EngineRunner<A> {
 
         let assets = Arc::new(RwLock::new(AssetServer::new()));
 
+        cobalt_core::asset_types::register_asset_types(&mut assets.write());
+
         assets.write().set_assets_dir(config.assets_dir.as_str())?;
 
+        for mount in &config.asset_mounts {
+            assets.write().mount_path(std::path::Path::new(mount))?;
+        }
+
+        if let Some(poll_interval) = config.hot_reload {
+            assets.write().set_hot_reload(true, poll_interval);
+
+            log::info!("Asset hot reloading enabled.");
+        }
+
+        // Nothing is loaded yet, so nothing can be evicted
+        let _ = assets.write().set_cache_budget(config.asset_cache_budget);
+
         log::info!(
             "Asset server initialized successfully with asset directory: {:?}",
             config.assets_dir
@@ -436,6 +530,46 @@ impl<A: App> ApplicationHandler for EngineRunner<A> {
                     }
                 }
 
+                self.engine.as_mut().unwrap().poll_asset_changes();
+                self.engine.as_mut().unwrap().update_asset_cache();
+
+                for asset_event in self.engine.as_mut().unwrap().drain_asset_events() {
+                    for (plugin, _, _) in self.plugin_manager.get_plugins_in_order() {
+                        let res = plugin.asset_event(
+                            self.engine.as_mut().unwrap(),
+                            &asset_event,
+                            self.app.as_mut().unwrap().dyn_trait_mut(),
+                        );
+
+                        if let Err(e) = res {
+                            match e {
+                                PluginError::Fatal(e) => {
+                                    log::error!(
+                                        "Plugin '{}' failed in asset_event: {:?}. Fatal error, stopping...",
+                                        plugin.name(),
+                                        e
+                                    );
+                                    event_loop.exit();
+                                }
+                                PluginError::NonFatal(e) => {
+                                    log::error!(
+                                        "Plugin '{}' failed in asset_event: {:?}. Non-fatal error, continuing...",
+                                        plugin.name(),
+                                        e
+                                    );
+                                    continue;
+                                }
+                            }
+                        }
+                    }
+
+                    self.app.as_mut().unwrap().on_asset_event(
+                        self.engine.as_mut().unwrap(),
+                        &mut self.plugin_manager,
+                        &asset_event,
+                    );
+                }
+
                 {
                     let delta_time = self.timing.last_update.elapsed().as_secs_f32();
                     self.app.as_mut().unwrap().on_update(
@@ -1,6 +1,9 @@
 use std::error::Error;
 
-use cobalt_core::graphics::{frame::Frame, winit::{self, event::WindowEvent}};
+use cobalt_core::{
+    assets::server::UntypedAssetEvent,
+    graphics::{frame::Frame, winit::{self, event::WindowEvent}},
+};
 
 use crate::{app::App, engine::Engine};
 
@@ -48,6 +51,16 @@ pub trait Plugin: Any {
         Ok(())
     }
 
+    /// Called for every asset event, before the app receives it.
+    fn asset_event(
+        &mut self,
+        _engine: &mut Engine,
+        _event: &UntypedAssetEvent,
+        _app: &mut dyn App,
+    ) -> Result<(), PluginError> {
+        Ok(())
+    }
+
     /// Called in the main event loop. Should be very fast, ideally this should not be
     /// used.
     fn update(&mut self, _engine: &mut Engine, _app: &mut dyn App) -> Result<(), PluginError> {
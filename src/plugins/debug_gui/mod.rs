@@ -1,4 +1,7 @@
-use cobalt_core::graphics::{wgpu, window::WindowInternal};
+use cobalt_core::{
+    assets::server::UntypedAssetEvent,
+    graphics::{wgpu, window::WindowInternal},
+};
 use cobalt_runtime::{app::App, plugins::Plugin};
 use egui_winit::{
     winit::{event::WindowEvent, window::WindowId},
@@ -143,6 +146,17 @@ impl Plugin for DebugGUIPlugin {
         Ok(event_consumed)
     }
 
+    fn asset_event(
+        &mut self,
+        _engine: &mut cobalt_runtime::engine::Engine,
+        event: &UntypedAssetEvent,
+        _app: &mut dyn App,
+    ) -> Result<(), cobalt_runtime::plugins::plugin::PluginError> {
+        self.debug_menu.record_asset_event(event);
+
+        Ok(())
+    }
+
     fn post_render(
         &mut self,
         engine: &mut cobalt_runtime::engine::Engine,
@@ -1,11 +1,31 @@
+use std::{collections::VecDeque, time::SystemTime};
+
+use cobalt_core::assets::server::{AssetEventKind, UntypedAssetEvent};
 use cobalt_runtime::engine::Engine;
 use egui_extras::Column;
 
-pub struct AssetsPanel {}
+/// Number of asset events kept for the activity log.
+const ACTIVITY_LOG_LEN: usize = 200;
+
+pub struct AssetsPanel {
+    /// Most recent last.
+    activity: VecDeque<(SystemTime, UntypedAssetEvent)>,
+}
 
 impl AssetsPanel {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            activity: VecDeque::new(),
+        }
+    }
+
+    /// Adds an event to the activity log, recorded even while the window is closed.
+    pub fn record(&mut self, event: &UntypedAssetEvent) {
+        if self.activity.len() == ACTIVITY_LOG_LEN {
+            self.activity.pop_front();
+        }
+
+        self.activity.push_back((SystemTime::now(), event.clone()));
     }
 
     pub fn show(&mut self, egui_ctx: &egui::Context, engine: &mut Engine) {
@@ -14,6 +34,54 @@ impl AssetsPanel {
 
             ui.label(format!("Assets directory: {:?}", asset_server.assets_dir()));
 
+            egui::CollapsingHeader::new("Sources").show(ui, |ui| {
+                // Highest priority first
+                for source in asset_server.sources().rev() {
+                    ui.label(source.name());
+                }
+            });
+
+            egui::CollapsingHeader::new("Activity").show(ui, |ui| {
+                if ui.button("Clear").clicked() {
+                    self.activity.clear();
+                }
+
+                let manifest = asset_server.get_manifest().ok();
+
+                egui::ScrollArea::vertical()
+                    .id_salt("asset_activity")
+                    .max_height(200.0)
+                    .show(ui, |ui| {
+                        // Newest first
+                        for (time, event) in self.activity.iter().rev() {
+                            let name = manifest
+                                .and_then(|manifest| manifest.asset(event.asset_id))
+                                .map_or_else(
+                                    || format!("{:?}", event.asset_id.uuid()),
+                                    |asset_info| asset_info.name.clone(),
+                                );
+
+                            let timestamp = humantime::format_rfc3339_seconds(*time);
+
+                            let text = match &event.kind {
+                                AssetEventKind::Loaded => egui::RichText::new("Loaded"),
+                                AssetEventKind::Modified => egui::RichText::new("Modified"),
+                                AssetEventKind::Removed => egui::RichText::new("Removed"),
+                                AssetEventKind::Failed(error) => {
+                                    egui::RichText::new(format!("Failed: {}", error))
+                                        .color(egui::Color32::RED)
+                                }
+                            };
+
+                            ui.horizontal(|ui| {
+                                ui.label(timestamp.to_string());
+                                ui.label(text);
+                                ui.label(format!("{} ({})", name, event.type_name));
+                            });
+                        }
+                    });
+            });
+
             egui::CollapsingHeader::new("Asset Manifest").show(ui, |ui| {
                 egui_extras::TableBuilder::new(ui)
                     .striped(true)
@@ -1,4 +1,4 @@
-use cobalt_core::stats::Stats;
+use cobalt_core::{assets::server::UntypedAssetEvent, stats::Stats};
 
 mod assets;
 mod heirarchy;
@@ -31,6 +31,10 @@ impl DebugMenu {
         }
     }
 
+    pub fn record_asset_event(&mut self, event: &UntypedAssetEvent) {
+        self.assets.record(event);
+    }
+
     pub fn show(
         &mut self,
         egui_ctx: &egui::Context,